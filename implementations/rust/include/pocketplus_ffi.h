@@ -0,0 +1,91 @@
+/**
+ * @file pocketplus_ffi.h
+ * @brief C interface to the Rust POCKET+ implementation (CCSDS 124.0-B-1).
+ *
+ * Build the library with the `ffi` feature:
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type staticlib
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * Functions return POCKETPLUS_OK or a negative error code. Codes -1 to -3
+ * match POCKET_ERROR_* of the C reference. Packet sizes are in bits and
+ * need not be a multiple of 8: raw packets occupy ceil(packet_size / 8)
+ * bytes each, a partial last byte holding the final bits in its most
+ * significant bits (zero padding on output).
+ * When an output buffer is too small, POCKETPLUS_ERROR_OVERFLOW is
+ * returned and the required size is stored in *output_len.
+ */
+
+#ifndef POCKETPLUS_FFI_H
+#define POCKETPLUS_FFI_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define POCKETPLUS_OK                 0     /**< Success */
+#define POCKETPLUS_ERROR_INVALID_ARG  -1    /**< Invalid argument or null pointer */
+#define POCKETPLUS_ERROR_OVERFLOW     -2    /**< Output buffer too small */
+#define POCKETPLUS_ERROR_UNDERFLOW    -3    /**< Compressed input ended early */
+#define POCKETPLUS_ERROR_FORMAT       -4    /**< Malformed compressed data */
+#define POCKETPLUS_ERROR_IO           -5    /**< I/O error */
+#define POCKETPLUS_ERROR_CANCELLED    -6    /**< Operation cancelled */
+#define POCKETPLUS_ERROR_INTERNAL     -100  /**< Internal error */
+
+typedef struct PocketplusCompressor PocketplusCompressor;     /**< Opaque compressor */
+typedef struct PocketplusDecompressor PocketplusDecompressor; /**< Opaque decompressor */
+
+/** Compress whole packets (input_len a multiple of ceil(packet_size / 8)). */
+int pocketplus_compress(const uint8_t *input, size_t input_len, size_t packet_size,
+                        size_t robustness, size_t pt_limit, size_t ft_limit,
+                        size_t rt_limit, uint8_t *output, size_t output_capacity,
+                        size_t *output_len);
+
+/** Decompress a byte-aligned stream. */
+int pocketplus_decompress(const uint8_t *input, size_t input_len, size_t packet_size,
+                          size_t robustness, uint8_t *output, size_t output_capacity,
+                          size_t *output_len);
+
+/** Create a streaming compressor; NULL if the parameters are invalid. */
+PocketplusCompressor *pocketplus_compressor_new(size_t packet_size, size_t robustness,
+                                                size_t pt_limit, size_t ft_limit,
+                                                size_t rt_limit);
+
+/** Compress one packet with the automatic pt/ft/rt schedule. */
+int pocketplus_compressor_compress_packet(PocketplusCompressor *handle,
+                                          const uint8_t *packet, size_t packet_len,
+                                          uint8_t *output, size_t output_capacity,
+                                          size_t *output_len);
+
+/** Release a compressor (NULL is ignored). */
+void pocketplus_compressor_free(PocketplusCompressor *handle);
+
+/** Create a streaming decompressor; NULL if the parameters are invalid. */
+PocketplusDecompressor *pocketplus_decompressor_new(size_t packet_size, size_t robustness);
+
+/** Decompress the packet at the start of input; *consumed receives its length. */
+int pocketplus_decompressor_decompress_packet(PocketplusDecompressor *handle,
+                                              const uint8_t *input, size_t input_len,
+                                              size_t *consumed, uint8_t *output,
+                                              size_t output_capacity, size_t *output_len);
+
+/** Report count (at most R) lost packets before the next one. */
+int pocketplus_decompressor_notify_packet_loss(PocketplusDecompressor *handle, size_t count);
+
+/** Release a decompressor (NULL is ignored). */
+void pocketplus_decompressor_free(PocketplusDecompressor *handle);
+
+/** Library version, e.g. "1.0.0". */
+const char *pocketplus_version(void);
+
+/** Description of an error code. */
+const char *pocketplus_error_string(int code);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* POCKETPLUS_FFI_H */
@@ -0,0 +1,285 @@
+//! Input data analysis helpers.
+//!
+//! Tools for inspecting raw housekeeping dumps before compression:
+//! - Packet size detection for dumps with missing metadata
+//! - Per-bit change frequencies ([`bit_change_profile`]), showing which
+//!   fields dominate the mask
+//!
+//! POCKET+ needs the packet length F up front. Fixed-length housekeeping
+//! packets repeat the same layout, so most bytes are equal to the byte one
+//! packet earlier. Scoring candidate lags by this autocorrelation of the
+//! byte-change pattern recovers likely packet sizes.
+
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_precision_loss)]
+
+use crate::error::PocketError;
+use std::fmt::Write;
+
+/// Maximum number of input bytes examined when scoring packet sizes.
+///
+/// Bounds the cost of analysis on large dumps; a few hundred packets are
+/// enough to separate the true packet size from noise.
+const ANALYSIS_WINDOW: usize = 1 << 16;
+
+/// Tolerance used when suppressing multiples of a better candidate.
+const HARMONIC_TOLERANCE: f64 = 0.05;
+
+/// A candidate packet size with its autocorrelation score.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PacketSizeCandidate {
+    /// Packet size in bytes.
+    pub packet_bytes: usize,
+    /// Packet size in bits (F).
+    pub packet_bits: usize,
+    /// Fraction of bytes unchanged from one packet to the next (0.0-1.0).
+    pub score: f64,
+}
+
+/// Score a candidate packet size.
+///
+/// Computes the fraction of bytes equal to the byte `packet_bytes`
+/// positions earlier, i.e. the fraction of bytes that did not change
+/// between consecutive packets if the dump is split at that size.
+///
+/// # Arguments
+/// * `data` - Raw input dump
+/// * `packet_bytes` - Candidate packet size in bytes
+///
+/// # Returns
+/// Score between 0.0 and 1.0, or 0.0 if fewer than two packets fit.
+pub fn score_packet_size(data: &[u8], packet_bytes: usize) -> f64 {
+    let window = &data[..data.len().min(ANALYSIS_WINDOW)];
+    if packet_bytes == 0 || window.len() < packet_bytes * 2 {
+        return 0.0;
+    }
+
+    let matches = window[packet_bytes..]
+        .iter()
+        .zip(window)
+        .filter(|(current, previous)| current == previous)
+        .count();
+
+    matches as f64 / (window.len() - packet_bytes) as f64
+}
+
+/// Suggest likely packet sizes for an unannotated binary dump.
+///
+/// Every size in `min_bytes..=max_bytes` is scored with
+/// [`score_packet_size`]. Multiples of a size that scores at least as well
+/// are dropped, since a dump with period P also repeats at 2P, 3P, ...
+///
+/// # Arguments
+/// * `data` - Raw input dump
+/// * `min_bytes` - Smallest packet size to consider (bytes)
+/// * `max_bytes` - Largest packet size to consider (bytes)
+///
+/// # Returns
+/// Candidates sorted by descending score (smaller sizes first on ties).
+pub fn suggest_packet_sizes(
+    data: &[u8],
+    min_bytes: usize,
+    max_bytes: usize,
+) -> Vec<PacketSizeCandidate> {
+    let min_bytes = min_bytes.max(1);
+    let max_bytes = max_bytes.min(data.len().min(ANALYSIS_WINDOW) / 2);
+    if min_bytes > max_bytes {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<PacketSizeCandidate> = Vec::new();
+
+    // Scan in increasing size so fundamentals are seen before their multiples
+    for packet_bytes in min_bytes..=max_bytes {
+        let score = score_packet_size(data, packet_bytes);
+        let is_harmonic = candidates
+            .iter()
+            .any(|c| packet_bytes % c.packet_bytes == 0 && c.score + HARMONIC_TOLERANCE >= score);
+        if !is_harmonic {
+            candidates.push(PacketSizeCandidate {
+                packet_bytes,
+                packet_bits: packet_bytes * 8,
+                score,
+            });
+        }
+    }
+
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.packet_bytes.cmp(&b.packet_bytes))
+    });
+    candidates
+}
+
+/// How often each bit position changes between consecutive packets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BitChangeProfile {
+    /// Number of packets analyzed.
+    pub packets: usize,
+    /// Change count per bit position, in transmission order (index 0 is
+    /// the MSB of the first byte).
+    pub changes: Vec<usize>,
+}
+
+impl BitChangeProfile {
+    /// Fraction of packet transitions in which a bit changed (0.0-1.0).
+    ///
+    /// # Arguments
+    /// * `bit` - Bit position, in transmission order
+    ///
+    /// # Returns
+    /// The change frequency, or 0.0 with fewer than two packets.
+    pub fn frequency(&self, bit: usize) -> f64 {
+        if self.packets < 2 {
+            return 0.0;
+        }
+        self.changes[bit] as f64 / (self.packets - 1) as f64
+    }
+
+    /// Render the profile as CSV with one row per bit position.
+    ///
+    /// Columns are `bit,byte,changes,frequency`, where `byte` is the byte
+    /// holding the bit.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("bit,byte,changes,frequency\n");
+        for (bit, changes) in self.changes.iter().enumerate() {
+            let _ = writeln!(
+                csv,
+                "{bit},{},{changes},{:.6}",
+                bit / 8,
+                self.frequency(bit)
+            );
+        }
+        csv
+    }
+}
+
+/// Count how often each bit position changes between consecutive packets.
+///
+/// # Arguments
+/// * `data` - Raw input dump of whole packets
+/// * `packet_bytes` - Packet size in bytes
+///
+/// # Returns
+/// The per-bit change counts, or an error if the dump does not hold whole
+/// packets.
+pub fn bit_change_profile(
+    data: &[u8],
+    packet_bytes: usize,
+) -> Result<BitChangeProfile, PocketError> {
+    if packet_bytes == 0 {
+        return Err(PocketError::InvalidPacketSize(0));
+    }
+    if data.len() % packet_bytes != 0 {
+        return Err(PocketError::InvalidInputLength {
+            expected: (data.len() / packet_bytes + 1) * packet_bytes,
+            actual: data.len(),
+        });
+    }
+
+    let mut changes = vec![0; packet_bytes * 8];
+    let mut packets = data.chunks_exact(packet_bytes);
+    let mut previous = packets.next().unwrap_or(&[]);
+    for packet in packets {
+        for (i, (a, b)) in packet.iter().zip(previous).enumerate() {
+            let diff = a ^ b;
+            for (j, count) in changes[i * 8..i * 8 + 8].iter_mut().enumerate() {
+                *count += usize::from(diff & (0x80 >> j) != 0);
+            }
+        }
+        previous = packet;
+    }
+
+    Ok(BitChangeProfile {
+        packets: data.len() / packet_bytes,
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Generate pseudo-random bytes with a simple LCG.
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    /// Build packets with a static layout and a few changing fields.
+    fn housekeeping(packet_bytes: usize, num_packets: usize) -> Vec<u8> {
+        let layout = noise(packet_bytes, 7);
+        let mut data = Vec::with_capacity(packet_bytes * num_packets);
+        for n in 0..num_packets {
+            let mut packet = layout.clone();
+            packet[0] = n as u8;
+            packet[packet_bytes / 2] ^= (n % 3) as u8;
+            data.extend_from_slice(&packet);
+        }
+        data
+    }
+
+    #[test]
+    fn test_suggest_packet_size_detects_period() {
+        let data = housekeeping(90, 100);
+        let candidates = suggest_packet_sizes(&data, 1, 256);
+        assert_eq!(candidates[0].packet_bytes, 90);
+        assert_eq!(candidates[0].packet_bits, 720);
+        assert!(candidates[0].score > 0.9);
+    }
+
+    #[test]
+    fn test_suggest_packet_size_drops_harmonics() {
+        let data = housekeeping(30, 200);
+        let candidates = suggest_packet_sizes(&data, 1, 200);
+        assert_eq!(candidates[0].packet_bytes, 30);
+        assert!(candidates
+            .iter()
+            .all(|c| c.packet_bytes % 30 != 0 || c.packet_bytes == 30));
+    }
+
+    #[test]
+    fn test_score_noise_is_low() {
+        let data = noise(4096, 42);
+        assert!(score_packet_size(&data, 90) < 0.05);
+    }
+
+    #[test]
+    fn test_score_too_short() {
+        let data = vec![0u8; 100];
+        assert!(score_packet_size(&data, 90).abs() < f64::EPSILON);
+        assert!(score_packet_size(&data, 0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_suggest_empty_input() {
+        assert!(suggest_packet_sizes(&[], 1, 100).is_empty());
+    }
+
+    #[test]
+    fn test_bit_change_profile() {
+        // Byte 0 counts, bit 0 of byte 1 toggles, the rest is constant
+        let data: Vec<u8> = (0..5u8).flat_map(|n| [n, (n % 2) << 7, 0x5A]).collect();
+        let profile = bit_change_profile(&data, 3).unwrap();
+        assert_eq!(profile.packets, 5);
+        assert_eq!(&profile.changes[..8], &[0, 0, 0, 0, 0, 1, 2, 4]);
+        assert_eq!(profile.changes[8], 4);
+        assert!(profile.changes[9..].iter().all(|&c| c == 0));
+        assert!((profile.frequency(7) - 1.0).abs() < f64::EPSILON);
+
+        let csv = profile.to_csv();
+        assert_eq!(csv.lines().count(), 25);
+        assert!(csv.starts_with("bit,byte,changes,frequency\n0,0,0,0.000000\n"));
+        assert!(csv.contains("\n6,0,2,0.500000\n7,0,4,1.000000\n8,1,4,1.000000\n"));
+
+        assert!(bit_change_profile(&data[..4], 3).is_err());
+        assert!(bit_change_profile(&data, 0).is_err());
+        assert_eq!(bit_change_profile(&[], 3).unwrap().packets, 0);
+    }
+}
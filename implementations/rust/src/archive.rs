@@ -0,0 +1,488 @@
+//! Chunked archive format with restart points.
+//!
+//! File-transfer protocols such as CFDP may deliver a compressed file with
+//! segments missing (zero-filled or cut short). A plain POCKET+ stream
+//! cannot be decoded past the first damaged byte, since every packet
+//! depends on the decoder state built from all previous packets.
+//!
+//! An archive splits the stream into *chunks* of roughly N bytes. The
+//! compressor state is reset at the start of every chunk, and each chunk
+//! starts with a restart marker: a header carrying the parameters, the
+//! index of its first packet and checksums of itself and its payload.
+//! [`decompress_archive`] scans for valid markers, so after a damaged
+//! region decoding resumes at the next intact chunk, and the packet
+//! indices tell which packets were lost.
+//!
+//! ## Chunk Layout (big-endian)
+//!
+//! | Offset | Size | Field                                 |
+//! |--------|------|---------------------------------------|
+//! | 0      | 4    | Magic `"PKPA"`                        |
+//! | 4      | 1    | Format version                        |
+//! | 5      | 1    | Robustness R                          |
+//! | 6      | 2    | Reserved (0)                          |
+//! | 8      | 4    | Packet size F in bits                 |
+//! | 12     | 4    | Index of the first packet in the file |
+//! | 16     | 4    | Number of packets                     |
+//! | 20     | 4    | Payload length in bytes               |
+//! | 24     | 4    | CRC-32 of the payload                 |
+//! | 28     | 4    | CRC-32 of header bytes 0-27           |
+//!
+//! The payload, a byte-aligned POCKET+ stream decodable with a fresh
+//! decompressor, follows the header immediately.
+//!
+//! ```rust
+//! # #[cfg(all(feature = "encoder", feature = "decoder"))]
+//! # {
+//! use pocketplus::archive::{compress_archive, decompress_archive};
+//! use pocketplus::Compressor;
+//!
+//! let data: Vec<u8> = (0..90 * 500).map(|i| (i % 90 / 9 + i / 9000) as u8).collect();
+//! let comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+//! let mut archive = compress_archive(&data, comp, 1024).unwrap();
+//!
+//! // A segment lost in transfer
+//! archive[600..900].fill(0);
+//!
+//! // Decoding resumes at the next restart point
+//! let report = decompress_archive(&archive).unwrap();
+//! assert_eq!(report.missing().len(), 1);
+//! let chunk = &report.chunks[0];
+//! let start = chunk.first_packet() as usize * 90;
+//! assert_eq!(chunk.data, data[start..start + chunk.data.len()]);
+//! # }
+//! ```
+
+#![allow(clippy::cast_possible_truncation)]
+
+#[cfg(feature = "encoder")]
+use crate::bitvector::BitVector;
+#[cfg(feature = "encoder")]
+use crate::compress::Compressor;
+use crate::crc::crc32;
+use crate::error::PocketError;
+#[cfg(feature = "decoder")]
+use std::ops::Range;
+
+/// Magic bytes starting every chunk.
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"PKPA";
+
+/// Current archive format version.
+pub const ARCHIVE_VERSION: u8 = 1;
+
+/// Length of a chunk header.
+pub const CHUNK_HEADER_LEN: usize = 32;
+
+/// Restart marker preceding every chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkHeader {
+    /// Robustness level R.
+    pub robustness: u8,
+    /// Packet size F in bits.
+    pub packet_size: u32,
+    /// Index of the chunk's first packet in the archive.
+    pub first_packet: u32,
+    /// Number of packets in the chunk.
+    pub num_packets: u32,
+    /// Payload length in bytes.
+    pub payload_len: u32,
+    /// CRC-32 of the payload.
+    pub payload_crc: u32,
+}
+
+impl ChunkHeader {
+    /// Serialize the header, including its checksum.
+    pub fn to_bytes(&self) -> [u8; CHUNK_HEADER_LEN] {
+        let mut out = [0u8; CHUNK_HEADER_LEN];
+        out[0..4].copy_from_slice(&ARCHIVE_MAGIC);
+        out[4] = ARCHIVE_VERSION;
+        out[5] = self.robustness;
+        out[8..12].copy_from_slice(&self.packet_size.to_be_bytes());
+        out[12..16].copy_from_slice(&self.first_packet.to_be_bytes());
+        out[16..20].copy_from_slice(&self.num_packets.to_be_bytes());
+        out[20..24].copy_from_slice(&self.payload_len.to_be_bytes());
+        out[24..28].copy_from_slice(&self.payload_crc.to_be_bytes());
+        let crc = crc32(&out[..28]);
+        out[28..32].copy_from_slice(&crc.to_be_bytes());
+        out
+    }
+
+    /// Parse and verify a header at the start of a byte slice.
+    ///
+    /// # Arguments
+    /// * `data` - Bytes starting at a restart marker
+    ///
+    /// # Returns
+    /// The header. Fails if the magic or checksum does not match or the
+    /// version is unknown.
+    pub fn parse(data: &[u8]) -> Result<Self, PocketError> {
+        if data.len() < CHUNK_HEADER_LEN {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        if data[0..4] != ARCHIVE_MAGIC {
+            return Err(PocketError::InvalidFormat("not an archive chunk".into()));
+        }
+        let field =
+            |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        if crc32(&data[..28]) != field(28) {
+            return Err(PocketError::InvalidFormat(
+                "archive chunk header checksum mismatch".into(),
+            ));
+        }
+        if data[4] != ARCHIVE_VERSION {
+            return Err(PocketError::InvalidFormat(format!(
+                "unsupported archive version {}",
+                data[4]
+            )));
+        }
+        Ok(Self {
+            robustness: data[5],
+            packet_size: field(8),
+            first_packet: field(12),
+            num_packets: field(16),
+            payload_len: field(20),
+            payload_crc: field(24),
+        })
+    }
+}
+
+/// Returns true if `data` starts with an archive chunk.
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(&ARCHIVE_MAGIC)
+}
+
+/// Streaming archive encoder.
+///
+/// Packets are compressed into the current chunk; once its payload
+/// reaches the restart interval the chunk is written out and the
+/// compressor is reset.
+#[cfg(feature = "encoder")]
+pub struct ArchiveWriter {
+    comp: Compressor,
+    restart_interval: usize,
+    input: BitVector,
+    payload: Vec<u8>,
+    first_packet: u32,
+    num_packets: u32,
+}
+
+#[cfg(feature = "encoder")]
+impl ArchiveWriter {
+    /// Create an archive encoder.
+    ///
+    /// # Arguments
+    /// * `comp` - Compressor in its default starting state (zero initial
+    ///   mask, no initial reference, no epochs or packet CRCs), since
+    ///   chunks are decoded with a decompressor built from the header alone
+    /// * `restart_interval` - Chunk payload size in bytes after which the
+    ///   next packet starts a new chunk
+    pub fn new(comp: Compressor, restart_interval: usize) -> Result<Self, PocketError> {
+        if restart_interval == 0 {
+            return Err(PocketError::InvalidParameter(
+                "restart interval must be positive".into(),
+            ));
+        }
+        if !comp.initial_mask().is_zero()
+            || comp.initial_reference().is_some()
+            || comp.epoch().is_some()
+            || comp.packet_crc()
+        {
+            return Err(PocketError::InvalidParameter(
+                "archive chunks need a compressor in its default configuration".into(),
+            ));
+        }
+        let input = BitVector::new(comp.packet_size());
+        Ok(Self {
+            comp,
+            restart_interval,
+            input,
+            payload: Vec::new(),
+            first_packet: 0,
+            num_packets: 0,
+        })
+    }
+
+    /// Number of packets written so far, including the open chunk.
+    pub fn packets(&self) -> u64 {
+        u64::from(self.first_packet) + u64::from(self.num_packets)
+    }
+
+    /// Compress one packet.
+    ///
+    /// # Arguments
+    /// * `packet` - One uncompressed packet of ⌈F/8⌉ bytes
+    /// * `out` - Archive output; receives the chunk this packet completes
+    ///
+    /// # Returns
+    /// The number of bytes appended (0 while the chunk is open).
+    pub fn push(&mut self, packet: &[u8], out: &mut Vec<u8>) -> Result<usize, PocketError> {
+        if packet.len() != self.input.num_bytes() {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.input.num_bytes(),
+                actual: packet.len(),
+            });
+        }
+        self.input.load_bytes(packet);
+        let params = self.comp.next_params();
+        self.comp
+            .compress_packet_into(&self.input, &params, &mut self.payload)?;
+        self.num_packets += 1;
+
+        if self.payload.len() >= self.restart_interval {
+            Ok(self.flush(out))
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Write the open chunk, if any.
+    ///
+    /// # Returns
+    /// The number of bytes appended.
+    pub fn finish(mut self, out: &mut Vec<u8>) -> usize {
+        self.flush(out)
+    }
+
+    /// Close the current chunk and restart the compressor.
+    fn flush(&mut self, out: &mut Vec<u8>) -> usize {
+        if self.num_packets == 0 {
+            return 0;
+        }
+        let header = ChunkHeader {
+            robustness: self.comp.robustness(),
+            packet_size: self.comp.packet_size() as u32,
+            first_packet: self.first_packet,
+            num_packets: self.num_packets,
+            payload_len: self.payload.len() as u32,
+            payload_crc: crc32(&self.payload),
+        };
+        out.extend_from_slice(&header.to_bytes());
+        out.extend_from_slice(&self.payload);
+
+        self.first_packet += self.num_packets;
+        self.num_packets = 0;
+        self.payload.clear();
+        self.comp.reset();
+        CHUNK_HEADER_LEN + header.payload_len as usize
+    }
+}
+
+/// Compress whole packets into an archive.
+///
+/// # Arguments
+/// * `data` - Input data (whole packets of ⌈F/8⌉ bytes)
+/// * `comp` - Compressor, see [`ArchiveWriter::new`]
+/// * `restart_interval` - Approximate chunk payload size in bytes
+///
+/// # Returns
+/// The archive.
+#[cfg(feature = "encoder")]
+pub fn compress_archive(
+    data: &[u8],
+    comp: Compressor,
+    restart_interval: usize,
+) -> Result<Vec<u8>, PocketError> {
+    let packet_bytes = (comp.packet_size() + 7) / 8;
+    if data.len() % packet_bytes != 0 {
+        return Err(PocketError::InvalidInputLength {
+            expected: (data.len() / packet_bytes + 1) * packet_bytes,
+            actual: data.len(),
+        });
+    }
+    let mut writer = ArchiveWriter::new(comp, restart_interval)?;
+    let mut out = Vec::new();
+    for packet in data.chunks_exact(packet_bytes) {
+        writer.push(packet, &mut out)?;
+    }
+    writer.finish(&mut out);
+    Ok(out)
+}
+
+/// One chunk recovered from an archive.
+#[cfg(feature = "decoder")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveChunk {
+    /// Byte offset of the chunk in the archive.
+    pub offset: usize,
+    /// The chunk header.
+    pub header: ChunkHeader,
+    /// Decompressed packets, concatenated.
+    pub data: Vec<u8>,
+}
+
+#[cfg(feature = "decoder")]
+impl ArchiveChunk {
+    /// Index of the chunk's first packet.
+    pub fn first_packet(&self) -> u32 {
+        self.header.first_packet
+    }
+
+    /// Packet indices covered by the chunk.
+    pub fn packets(&self) -> Range<u32> {
+        self.header.first_packet..self.header.first_packet + self.header.num_packets
+    }
+}
+
+/// Result of [`decompress_archive`].
+#[cfg(feature = "decoder")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveReport {
+    /// Intact chunks, in archive order.
+    pub chunks: Vec<ArchiveChunk>,
+    /// Bytes not part of an intact chunk.
+    pub discarded_bytes: usize,
+}
+
+#[cfg(feature = "decoder")]
+impl ArchiveReport {
+    /// Packet index ranges missing before or between the recovered chunks.
+    ///
+    /// Packets lost after the last intact chunk cannot be detected.
+    pub fn missing(&self) -> Vec<Range<u32>> {
+        let mut missing = Vec::new();
+        let mut next = 0;
+        for chunk in &self.chunks {
+            let packets = chunk.packets();
+            if packets.start > next {
+                missing.push(next..packets.start);
+            }
+            next = next.max(packets.end);
+        }
+        missing
+    }
+
+    /// True if every byte belonged to an intact chunk and no packets are
+    /// missing between them.
+    pub fn is_complete(&self) -> bool {
+        self.discarded_bytes == 0 && self.missing().is_empty()
+    }
+
+    /// Recovered packets of all chunks, concatenated.
+    pub fn data(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .flat_map(|c| c.data.iter().copied())
+            .collect()
+    }
+}
+
+/// Decode an intact chunk at the start of `data`.
+///
+/// # Returns
+/// The chunk header and payload, or `None` if no intact chunk starts here.
+#[cfg(feature = "decoder")]
+fn intact_chunk(data: &[u8]) -> Option<(ChunkHeader, &[u8])> {
+    let header = ChunkHeader::parse(data).ok()?;
+    let end = CHUNK_HEADER_LEN.checked_add(header.payload_len as usize)?;
+    let payload = data.get(CHUNK_HEADER_LEN..end)?;
+    (crc32(payload) == header.payload_crc).then_some((header, payload))
+}
+
+/// Decompress an archive, skipping damaged regions.
+///
+/// Chunks whose header or payload checksum does not match, or that are
+/// cut short, are skipped; the scan resumes at the next restart marker.
+///
+/// # Arguments
+/// * `data` - Archive, possibly with missing or corrupted regions
+///
+/// # Returns
+/// The intact chunks, decoded. Fails only if a chunk passes its checksums
+/// but does not decode to the number of packets in its header.
+#[cfg(feature = "decoder")]
+pub fn decompress_archive(data: &[u8]) -> Result<ArchiveReport, PocketError> {
+    let mut report = ArchiveReport::default();
+    let mut pos = 0;
+    while pos < data.len() {
+        let Some((header, payload)) = intact_chunk(&data[pos..]) else {
+            let next = data[pos + 1..]
+                .windows(ARCHIVE_MAGIC.len())
+                .position(|w| w == ARCHIVE_MAGIC)
+                .map_or(data.len(), |i| pos + 1 + i);
+            report.discarded_bytes += next - pos;
+            pos = next;
+            continue;
+        };
+
+        let packet_size = header.packet_size as usize;
+        let decoded = crate::decompress(payload, packet_size, usize::from(header.robustness))?;
+        if decoded.len() != header.num_packets as usize * ((packet_size + 7) / 8) {
+            return Err(PocketError::InvalidFormat(format!(
+                "archive chunk at offset {pos} holds a different number of packets than its header"
+            )));
+        }
+        let len = CHUNK_HEADER_LEN + payload.len();
+        report.chunks.push(ArchiveChunk {
+            offset: pos,
+            header,
+            data: decoded,
+        });
+        pos += len;
+    }
+    Ok(report)
+}
+
+#[cfg(all(test, feature = "encoder", feature = "decoder"))]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        (0..90 * 800)
+            .map(|i| u8::try_from((i % 90 / 6 + i / 4500) % 256).unwrap())
+            .collect()
+    }
+
+    fn archive(data: &[u8]) -> Vec<u8> {
+        let comp = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        compress_archive(data, comp, 2048).unwrap()
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let data = sample();
+        let archive = archive(&data);
+        assert!(is_archive(&archive));
+
+        let report = decompress_archive(&archive).unwrap();
+        assert!(report.chunks.len() > 2);
+        assert!(report.is_complete());
+        assert_eq!(report.data(), data);
+        for pair in report.chunks.windows(2) {
+            assert_eq!(pair[0].packets().end, pair[1].first_packet());
+        }
+
+        let comp = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        assert!(ArchiveWriter::new(comp, 0).is_err());
+        let mut comp = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        comp.set_packet_crc(true);
+        assert!(ArchiveWriter::new(comp, 1024).is_err());
+    }
+
+    #[test]
+    fn test_archive_partial_delivery() {
+        let data = sample();
+        let mut archive = archive(&data);
+        let intact = decompress_archive(&archive).unwrap();
+        let second = intact.chunks[1].offset;
+
+        // A missing segment zero-filled inside the second chunk, and the
+        // last bytes never delivered
+        archive[second + 100..second + 400].fill(0);
+        archive.truncate(archive.len() - 10);
+
+        let report = decompress_archive(&archive).unwrap();
+        assert!(!report.is_complete());
+        assert_eq!(report.missing(), [intact.chunks[1].packets()]);
+        assert_eq!(report.chunks.len(), intact.chunks.len() - 2);
+        for chunk in &report.chunks {
+            let start = chunk.first_packet() as usize * 90;
+            assert_eq!(chunk.data, data[start..start + chunk.data.len()]);
+        }
+        let kept: usize = report
+            .chunks
+            .iter()
+            .map(|c| CHUNK_HEADER_LEN + c.header.payload_len as usize)
+            .sum();
+        assert_eq!(report.discarded_bytes, archive.len() - kept);
+    }
+}
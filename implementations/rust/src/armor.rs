@@ -0,0 +1,218 @@
+//! ASCII armor for compressed streams.
+//!
+//! Some transfer paths (e-mail, ticketing systems, pasting into a
+//! console) mangle binary data. Armoring turns a stream into hex or
+//! Base64 (RFC 4648, standard alphabet with `=` padding) text, wrapped
+//! into lines of [`LINE_LEN`] characters. Decoding ignores all ASCII
+//! whitespace, so re-wrapped lines and CRLF line endings are harmless.
+//!
+//! ```rust
+//! use pocketplus::armor::Armor;
+//!
+//! let stream = [0x8A, 0x00, 0xFF, 0x10];
+//! assert_eq!(Armor::Hex.encode(&stream), "8a00ff10\n");
+//! assert_eq!(Armor::Base64.encode(&stream), "igD/EA==\n");
+//! assert_eq!(Armor::Base64.decode("igD/\r\nEA==").unwrap(), stream);
+//! ```
+
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::error::PocketError;
+
+/// Characters per line of armored text.
+pub const LINE_LEN: usize = 76;
+
+/// Base64 alphabet (RFC 4648 Section 4).
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Hex digits, lowercase.
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// Text encoding of armored data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Armor {
+    /// Two hex digits per byte.
+    Hex,
+    /// Base64 with padding, four characters per three bytes.
+    Base64,
+}
+
+impl Armor {
+    /// Look up an encoding by name (`hex` or `base64`, any case).
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("hex") {
+            Some(Self::Hex)
+        } else if name.eq_ignore_ascii_case("base64") {
+            Some(Self::Base64)
+        } else {
+            None
+        }
+    }
+
+    /// Encode data as text.
+    ///
+    /// # Returns
+    /// Lines of at most [`LINE_LEN`] characters, each ending in `\n`.
+    pub fn encode(self, data: &[u8]) -> String {
+        let mut chars = Vec::with_capacity(data.len() * 2);
+        match self {
+            Self::Hex => {
+                for &byte in data {
+                    chars.push(HEX[usize::from(byte >> 4)]);
+                    chars.push(HEX[usize::from(byte & 0x0F)]);
+                }
+            }
+            Self::Base64 => {
+                for group in data.chunks(3) {
+                    let bits = group
+                        .iter()
+                        .enumerate()
+                        .fold(0u32, |acc, (i, &b)| acc | u32::from(b) << (16 - 8 * i));
+                    for i in 0..4 {
+                        if i <= group.len() {
+                            chars.push(BASE64[(bits >> (18 - 6 * i)) as usize & 0x3F]);
+                        } else {
+                            chars.push(b'=');
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut text = String::with_capacity(chars.len() + chars.len() / LINE_LEN + 1);
+        for line in chars.chunks(LINE_LEN) {
+            // Only ASCII characters were pushed
+            text.extend(line.iter().map(|&c| char::from(c)));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Decode armored text.
+    ///
+    /// # Arguments
+    /// * `text` - Text written by [`encode`](Self::encode), possibly
+    ///   re-wrapped; whitespace is ignored, hex digits may be upper case
+    ///   and Base64 padding may be missing
+    ///
+    /// # Returns
+    /// The data, or [`PocketError::InvalidFormat`] naming the offending
+    /// character.
+    pub fn decode(self, text: &str) -> Result<Vec<u8>, PocketError> {
+        let mut values = Vec::with_capacity(text.len());
+        let mut padding = 0;
+        for (pos, c) in text.char_indices() {
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            let value = match (self, c) {
+                (Self::Hex, _) => c.to_digit(16),
+                (Self::Base64, '=') => {
+                    padding += 1;
+                    continue;
+                }
+                (Self::Base64, _) if padding == 0 => BASE64
+                    .iter()
+                    .position(|&b| u32::from(b) == u32::from(c))
+                    .and_then(|v| u32::try_from(v).ok()),
+                (Self::Base64, _) => None,
+            };
+            let value = value.ok_or_else(|| {
+                PocketError::InvalidFormat(format!("invalid {self} character {c:?} at {pos}"))
+            })?;
+            values.push(value);
+        }
+
+        match self {
+            Self::Hex => {
+                if values.len() % 2 != 0 {
+                    return Err(PocketError::InvalidFormat(
+                        "odd number of hex digits".into(),
+                    ));
+                }
+                Ok(values
+                    .chunks_exact(2)
+                    .map(|pair| (pair[0] << 4 | pair[1]) as u8)
+                    .collect())
+            }
+            Self::Base64 => {
+                let tail = values.len() % 4;
+                if tail == 1 || padding > 2 || (padding > 0 && (tail + padding) % 4 != 0) {
+                    return Err(PocketError::InvalidFormat("truncated Base64 text".into()));
+                }
+                let mut data = Vec::with_capacity(values.len() / 4 * 3 + 2);
+                for group in values.chunks(4) {
+                    let bits = group
+                        .iter()
+                        .enumerate()
+                        .fold(0u32, |acc, (i, &v)| acc | v << (18 - 6 * i));
+                    for i in 0..group.len() - 1 {
+                        data.push((bits >> (16 - 8 * i)) as u8);
+                    }
+                }
+                Ok(data)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Armor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Hex => "hex",
+            Self::Base64 => "base64",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_armor_round_trip() {
+        // RFC 4648 Section 10 test vectors
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (data, base64) in vectors {
+            let encoded = Armor::Base64.encode(data.as_bytes());
+            assert_eq!(encoded.trim_end(), base64);
+            assert_eq!(Armor::Base64.decode(&encoded).unwrap(), data.as_bytes());
+            assert_eq!(
+                Armor::Base64.decode(base64.trim_end_matches('=')).unwrap(),
+                data.as_bytes()
+            );
+        }
+
+        let data: Vec<u8> = (0..=255).collect();
+        for armor in [Armor::Hex, Armor::Base64] {
+            let text = armor.encode(&data);
+            assert!(text.lines().all(|line| line.len() <= LINE_LEN));
+            assert_eq!(armor.decode(&text).unwrap(), data);
+            assert_eq!(armor.decode(&text.replace('\n', "\r\n ")).unwrap(), data);
+            assert_eq!(
+                Armor::from_name(&armor.to_string().to_uppercase()),
+                Some(armor)
+            );
+        }
+        assert_eq!(Armor::Hex.decode("8A00fF").unwrap(), [0x8A, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn test_armor_rejects_invalid_text() {
+        assert!(Armor::Hex.decode("8a0").is_err());
+        assert!(Armor::Hex.decode("8g").is_err());
+        assert!(Armor::Base64.decode("Zm9vY").is_err());
+        assert!(Armor::Base64.decode("Zg=a").is_err());
+        assert!(Armor::Base64.decode("Zm8==").is_err());
+        assert!(Armor::Base64.decode("Zm-v").is_err());
+        assert_eq!(Armor::from_name("base32"), None);
+    }
+}
@@ -0,0 +1,469 @@
+//! Async IO adapters (`async` feature).
+//!
+//! [`AsyncPocketWriter`] and [`AsyncPocketReader`] are the non-blocking
+//! counterparts of [`io::PocketWriter`](crate::io::PocketWriter): they
+//! implement the `futures-io` [`AsyncWrite`] and [`AsyncRead`] traits, so
+//! the codec can sit inside async ground-data pipelines without a blocking
+//! thread per stream. Tokio types convert with `tokio_util::compat`
+//! (`.compat()` / `.compat_write()`).
+//!
+//! ```rust,ignore
+//! use futures::io::{AsyncReadExt, AsyncWriteExt};
+//! use pocketplus::async_io::{AsyncPocketReader, AsyncPocketWriter};
+//! use pocketplus::{Compressor, Decompressor};
+//!
+//! // Compress raw packets from a socket into a file
+//! let comp = Compressor::new(720, None, 1, 10, 20, 50)?;
+//! let mut writer = AsyncPocketWriter::new(file, comp);
+//! futures::io::copy(socket, &mut writer).await?;
+//! writer.close().await?;
+//!
+//! // Read the raw packets back
+//! let decomp = Decompressor::new(720, None, 1)?;
+//! let mut reader = AsyncPocketReader::new(compressed, decomp);
+//! let mut packets = Vec::new();
+//! reader.read_to_end(&mut packets).await?;
+//! ```
+//!
+//! Both adapters handle byte-aligned streams without a stream checksum
+//! trailer.
+
+use crate::bitvector::BitVector;
+use crate::compress::Compressor;
+use crate::decompress::Decompressor;
+use crate::error::PocketError;
+use futures_io::{AsyncRead, AsyncWrite};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Bytes requested from the inner reader at a time.
+const READ_CHUNK: usize = 8192;
+
+/// Async writer that compresses raw packets into an inner writer.
+///
+/// Bytes are buffered until a whole packet is available; each packet is
+/// then compressed and written downstream, giving exactly the stream
+/// [`compress`](crate::compress) produces for the same input. Compressed
+/// bytes the inner writer has not accepted yet are written before the next
+/// input is taken, and on flush and close.
+pub struct AsyncPocketWriter<W> {
+    inner: W,
+    comp: Compressor,
+    packet_bytes: usize,
+    /// Reused input vector.
+    input: BitVector,
+    /// Partial packet.
+    buffer: Vec<u8>,
+    /// Compressed bytes not yet written downstream.
+    output: Vec<u8>,
+    /// Bytes of `output` already written.
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncPocketWriter<W> {
+    /// Create a writer.
+    ///
+    /// # Arguments
+    /// * `inner` - Destination of the compressed stream
+    /// * `comp` - Compressor; each packet is read as ⌈F/8⌉ bytes
+    pub fn new(inner: W, comp: Compressor) -> Self {
+        let packet_bytes = (comp.packet_size() + 7) / 8;
+        Self {
+            inner,
+            input: BitVector::new(comp.packet_size()),
+            comp,
+            packet_bytes,
+            buffer: Vec::with_capacity(packet_bytes),
+            output: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// The inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// The inner writer, mutably. Writing to it directly corrupts the
+    /// compressed stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// The compressor.
+    pub fn compressor(&self) -> &Compressor {
+        &self.comp
+    }
+
+    /// Number of buffered bytes of the next, incomplete packet.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Return the inner writer. Compressed bytes not yet written are
+    /// lost; close the writer first.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Write the compressed bytes held back so far.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.output.len() {
+            match Pin::new(&mut self.inner).poll_write(cx, &self.output[self.written..]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => self.written += n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        self.output.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Compress one whole packet into the output buffer.
+    fn compress_packet(&mut self, packet: &[u8]) -> Result<(), PocketError> {
+        self.input.load_bytes(packet);
+        let params = self.comp.next_params();
+        self.comp
+            .compress_packet_into(&self.input, &params, &mut self.output)?;
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncPocketWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => {}
+            other => return other.map(|r| r.map(|()| 0)),
+        }
+
+        let mut rest = buf;
+
+        // Complete the buffered packet first
+        if !this.buffer.is_empty() {
+            let take = (this.packet_bytes - this.buffer.len()).min(rest.len());
+            this.buffer.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+            if this.buffer.len() < this.packet_bytes {
+                return Poll::Ready(Ok(buf.len()));
+            }
+            let packet = std::mem::take(&mut this.buffer);
+            let result = this.compress_packet(&packet);
+            this.buffer = packet;
+            this.buffer.clear();
+            result?;
+        }
+
+        // Whole packets straight from the caller's buffer
+        let mut packets = rest.chunks_exact(this.packet_bytes);
+        for packet in &mut packets {
+            this.compress_packet(packet)?;
+        }
+        this.buffer.extend_from_slice(packets.remainder());
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    /// Write the compressed bytes and flush the inner writer. A buffered
+    /// partial packet stays buffered.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    /// Write the compressed bytes and close the inner writer.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if a partial packet is
+    /// buffered.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.buffer.is_empty() {
+            return Poll::Ready(Err(PocketError::InvalidInputLength {
+                expected: this.packet_bytes,
+                actual: this.buffer.len(),
+            }
+            .into()));
+        }
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_close(cx),
+            other => other,
+        }
+    }
+}
+
+/// Async reader that decompresses a byte-aligned stream from an inner
+/// reader, yielding the raw packets.
+///
+/// Compressed bytes are read in chunks and every complete packet is
+/// decoded as soon as it has arrived. The stream must end on a packet
+/// boundary; otherwise reading fails with
+/// [`io::ErrorKind::UnexpectedEof`].
+pub struct AsyncPocketReader<R> {
+    inner: R,
+    decomp: Decompressor,
+    /// Received compressed bytes not yet decoded.
+    input: Vec<u8>,
+    /// Decoded bytes not yet returned.
+    output: Vec<u8>,
+    /// Bytes of `output` already returned.
+    returned: usize,
+    /// The inner reader reached its end.
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncPocketReader<R> {
+    /// Create a reader.
+    ///
+    /// # Arguments
+    /// * `inner` - Source of the compressed stream
+    /// * `decomp` - Decompressor matching the stream's F and R
+    pub fn new(inner: R, decomp: Decompressor) -> Self {
+        Self {
+            inner,
+            decomp,
+            input: Vec::new(),
+            output: Vec::new(),
+            returned: 0,
+            eof: false,
+        }
+    }
+
+    /// The inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// The decompressor.
+    pub fn decompressor(&self) -> &Decompressor {
+        &self.decomp
+    }
+
+    /// Return the inner reader, dropping buffered data.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncPocketReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.returned < this.output.len() {
+                let n = (this.output.len() - this.returned).min(buf.len());
+                buf[..n].copy_from_slice(&this.output[this.returned..this.returned + n]);
+                this.returned += n;
+                if this.returned == this.output.len() {
+                    this.output.clear();
+                    this.returned = 0;
+                }
+                return Poll::Ready(Ok(n));
+            }
+            if buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            if this.eof {
+                if this.input.is_empty() {
+                    return Poll::Ready(Ok(0));
+                }
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "compressed stream ends inside a packet",
+                )));
+            }
+
+            let start = this.input.len();
+            this.input.resize(start + READ_CHUNK, 0);
+            let read = Pin::new(&mut this.inner).poll_read(cx, &mut this.input[start..]);
+            let n = match read {
+                Poll::Ready(Ok(n)) => n,
+                other => {
+                    this.input.truncate(start);
+                    return other;
+                }
+            };
+            this.input.truncate(start + n);
+            if n == 0 {
+                this.eof = true;
+                continue;
+            }
+
+            let consumed = this
+                .decomp
+                .decompress_available(&this.input, &mut this.output)?;
+            this.input.drain(..consumed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{poll_fn, Future};
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    /// Run a future to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Noop;
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// Inner stream that is pending every other call and moves at most
+    /// `step` bytes per call.
+    struct Trickle {
+        data: Vec<u8>,
+        pos: usize,
+        step: usize,
+        ready: bool,
+    }
+
+    impl Trickle {
+        fn new(data: Vec<u8>, step: usize) -> Self {
+            Self {
+                data,
+                pos: 0,
+                step,
+                ready: false,
+            }
+        }
+
+        fn ready(&mut self, cx: &Context<'_>) -> bool {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+            }
+            self.ready
+        }
+    }
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if !self.ready(cx) {
+                return Poll::Pending;
+            }
+            let n = buf.len().min(self.step);
+            self.data.extend_from_slice(&buf[..n]);
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncRead for Trickle {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            if !self.ready(cx) {
+                return Poll::Pending;
+            }
+            let n = buf.len().min(self.step).min(self.data.len() - self.pos);
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    fn sample(packets: usize) -> Vec<u8> {
+        (0..packets * 90)
+            .map(|i| u8::try_from((i / 90 % 4) ^ (i % 7)).unwrap())
+            .collect()
+    }
+
+    async fn write_all<W: AsyncWrite + Unpin>(writer: &mut W, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let n =
+                poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, &data[..data.len().min(37)]))
+                    .await?;
+            data = &data[n..];
+        }
+        Ok(())
+    }
+
+    async fn read_to_end<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 64];
+        loop {
+            let n = poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf)).await?;
+            if n == 0 {
+                return Ok(out);
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[test]
+    fn test_async_round_trip() {
+        let data = sample(40);
+        let expected = crate::compress(&data, 720, 2, 10, 20, 50).unwrap();
+
+        let comp = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        let mut writer = AsyncPocketWriter::new(Trickle::new(Vec::new(), 5), comp);
+        block_on(async {
+            write_all(&mut writer, &data).await?;
+            poll_fn(|cx| Pin::new(&mut writer).poll_close(cx)).await
+        })
+        .unwrap();
+        assert_eq!(writer.pending(), 0);
+        assert_eq!(writer.into_inner().data, expected);
+
+        let decomp = Decompressor::new(720, None, 2).unwrap();
+        let mut reader = AsyncPocketReader::new(Trickle::new(expected.clone(), 11), decomp);
+        assert_eq!(block_on(read_to_end(&mut reader)).unwrap(), data);
+
+        // Truncated stream
+        let decomp = Decompressor::new(720, None, 2).unwrap();
+        let truncated = expected[..expected.len() - 1].to_vec();
+        let mut reader = AsyncPocketReader::new(Trickle::new(truncated, 64), decomp);
+        let err = block_on(read_to_end(&mut reader)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_async_writer_partial_packet() {
+        let comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+        let mut writer = AsyncPocketWriter::new(Trickle::new(Vec::new(), 90), comp);
+        let err = block_on(async {
+            write_all(&mut writer, &sample(1)[..50]).await?;
+            poll_fn(|cx| Pin::new(&mut writer).poll_close(cx)).await
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(writer.pending(), 50);
+    }
+}
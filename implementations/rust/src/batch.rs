@@ -0,0 +1,284 @@
+//! Parallel batch processing.
+//!
+//! Reprocessing a mission archive is embarrassingly parallel, but a
+//! compressor or decompressor works through one stream strictly in order.
+//! The work is split where the codec state does not carry over and spread
+//! over `std::thread` workers, so throughput scales with the cores:
+//! - [`compress_many`] compresses independent streams, such as the
+//!   per-APID streams of a telemetry dump or separate files
+//! - [`decompress_parallel`] decodes one indexed stream from its
+//!   independently decodable packets (see [`PacketIndex::keyframes`])
+
+#[cfg(feature = "encoder")]
+use crate::compress::{compress_with, CompressorBuilder};
+#[cfg(feature = "decoder")]
+use crate::decompress::DecompressorBuilder;
+use crate::error::PocketError;
+#[cfg(feature = "decoder")]
+use crate::index::PacketIndex;
+#[cfg(feature = "decoder")]
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// One stream for [`compress_many`].
+#[cfg(feature = "encoder")]
+#[derive(Clone, Debug)]
+pub struct StreamInput<'a> {
+    /// Input data (whole packets).
+    pub data: &'a [u8],
+    /// Packet size and compression parameters.
+    pub options: CompressorBuilder,
+}
+
+#[cfg(feature = "encoder")]
+impl<'a> StreamInput<'a> {
+    /// Create a stream input.
+    ///
+    /// # Arguments
+    /// * `data` - Input data (whole packets)
+    /// * `options` - Packet size and compression parameters
+    pub fn new(data: &'a [u8], options: CompressorBuilder) -> Self {
+        Self { data, options }
+    }
+}
+
+/// Compress independent streams concurrently.
+///
+/// Each stream is compressed with [`compress_with`] on one of up to
+/// [`available_parallelism`](std::thread::available_parallelism) worker
+/// threads; idle workers take the next waiting stream, so a few long
+/// streams do not hold up the rest. Streams fail independently.
+///
+/// # Arguments
+/// * `streams` - Streams to compress
+///
+/// # Returns
+/// One result per stream, in the order of `streams`, each identical to
+/// [`compress_with`] on that stream.
+#[cfg(feature = "encoder")]
+pub fn compress_many(streams: &[StreamInput<'_>]) -> Vec<Result<Vec<u8>, PocketError>> {
+    let workers = thread::available_parallelism().map_or(1, usize::from);
+    compress_on(streams, workers)
+}
+
+/// [`compress_many`] on at most `workers` threads.
+#[cfg(feature = "encoder")]
+fn compress_on(streams: &[StreamInput<'_>], workers: usize) -> Vec<Result<Vec<u8>, PocketError>> {
+    run_tasks(streams.len(), workers, |i| {
+        compress_with(streams[i].data, &streams[i].options)
+    })
+}
+
+/// Decompress an indexed stream on several threads.
+///
+/// Splits the packets of `index` into runs starting at independently
+/// decodable packets ([`PacketIndex::keyframes`]), a few runs per worker
+/// thread, and decodes each run with its own decompressor using
+/// [`Decompressor::decompress_range`](crate::Decompressor::decompress_range).
+/// The first run starts at packet 0 with the decompressor's initial state.
+/// Streams with keyframes only at the start (no ft/rt coincidence and no
+/// epochs) decode on one thread.
+///
+/// # Arguments
+/// * `data` - Compressed stream the index was built from
+/// * `index` - Index of `data`
+/// * `options` - Settings the index was built with
+///
+/// # Returns
+/// The indexed packets, concatenated as [`decompress`](crate::decompress)
+/// returns them, or the error of the earliest failing run.
+#[cfg(feature = "decoder")]
+pub fn decompress_parallel(
+    data: &[u8],
+    index: &PacketIndex,
+    options: &DecompressorBuilder,
+) -> Result<Vec<u8>, PocketError> {
+    let workers = thread::available_parallelism().map_or(1, usize::from);
+    decompress_on(data, index, options, workers)
+}
+
+/// [`decompress_parallel`] on at most `workers` threads.
+#[cfg(feature = "decoder")]
+fn decompress_on(
+    data: &[u8],
+    index: &PacketIndex,
+    options: &DecompressorBuilder,
+    workers: usize,
+) -> Result<Vec<u8>, PocketError> {
+    let decomp = options.build()?;
+    if index.packet_size != decomp.packet_size() {
+        return Err(PocketError::InvalidPacketSize(index.packet_size));
+    }
+
+    let runs = split_runs(index, 4 * workers);
+    let decoded = run_tasks(runs.len(), workers, |i| {
+        let run = runs[i].clone();
+        let mut decomp = decomp.clone();
+        if run.start > 0 {
+            return decomp.decompress_range(data, index, run);
+        }
+        let mut output = Vec::new();
+        let mut packets = decomp.iter_packets(data);
+        for _ in run {
+            let packet = packets.next().ok_or(PocketError::UnexpectedEndOfInput)??;
+            output.extend(packet);
+        }
+        Ok(output)
+    });
+
+    let mut output = Vec::with_capacity(index.len() * ((index.packet_size + 7) / 8));
+    for run in decoded {
+        output.extend(run?);
+    }
+    Ok(output)
+}
+
+/// Split the packets of `index` into at most about `count` runs of similar
+/// length, each but the first starting at a keyframe.
+#[cfg(feature = "decoder")]
+fn split_runs(index: &PacketIndex, count: usize) -> Vec<Range<usize>> {
+    let target = (index.len() + count - 1) / count.max(1);
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for keyframe in index.keyframes() {
+        if keyframe - start >= target.max(1) {
+            runs.push(start..keyframe);
+            start = keyframe;
+        }
+    }
+    if start < index.len() {
+        runs.push(start..index.len());
+    }
+    runs
+}
+
+/// Run tasks `0..count` on at most `workers` threads.
+///
+/// Idle workers take the next waiting task, so a few long tasks do not
+/// hold up the rest. A panicking task is propagated to the caller.
+///
+/// # Returns
+/// The task results, in task order.
+fn run_tasks<T: Send>(count: usize, workers: usize, task: impl Fn(usize) -> T + Sync) -> Vec<T> {
+    let workers = workers.min(count);
+    if workers <= 1 {
+        return (0..count).map(task).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<T>> = (0..count).map(|_| None).collect();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= count {
+                            return done;
+                        }
+                        done.push((i, task(i)));
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let done = handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (i, result) in done {
+                results[i] = Some(result);
+            }
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| result.expect("every task is taken by a worker"))
+        .collect()
+}
+
+#[cfg(all(test, feature = "encoder"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_many() {
+        let streams: Vec<Vec<u8>> = (0..9u8)
+            .map(|s| {
+                (0..30 * 64u32)
+                    .map(|i| (i % 64 / 8) as u8 ^ s.wrapping_mul(u8::from(i % 64 == 0)))
+                    .collect()
+            })
+            .collect();
+        let mut inputs: Vec<StreamInput> = streams
+            .iter()
+            .enumerate()
+            .map(|(s, data)| StreamInput::new(data, CompressorBuilder::new(512).robustness(s % 8)))
+            .collect();
+
+        // A stream with a partial packet fails on its own
+        inputs.insert(
+            4,
+            StreamInput::new(&streams[0][..100], CompressorBuilder::new(512)),
+        );
+
+        let expected: Vec<_> = inputs
+            .iter()
+            .map(|input| compress_with(input.data, &input.options))
+            .collect();
+        assert!(expected[4].is_err());
+
+        // Results keep the stream order whatever the number of workers
+        assert_eq!(compress_many(&inputs), expected);
+        for workers in [1, 3, 16] {
+            assert_eq!(compress_on(&inputs, workers), expected, "{workers} workers");
+        }
+        assert!(compress_many(&[]).is_empty());
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_decompress_parallel() {
+        let data: Vec<u8> = (0..90 * 500u32)
+            .map(|i| u8::try_from(i % 90 + i / 2000).unwrap())
+            .collect();
+        let compressed = crate::compress(&data, 720, 2, 10, 10, 10).unwrap();
+        let builder = DecompressorBuilder::new(720).robustness(2);
+        let index = PacketIndex::build(&compressed, &builder).unwrap();
+
+        // Runs start at keyframes and cover every packet once
+        let runs = split_runs(&index, 8);
+        assert!(runs.len() > 1 && runs.len() <= 9);
+        assert_eq!(runs[0].start, 0);
+        assert_eq!(runs.last().unwrap().end, 500);
+        for pair in runs.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert!(index.entries[pair[1].start].is_independent());
+        }
+
+        assert_eq!(
+            decompress_parallel(&compressed, &index, &builder).unwrap(),
+            data
+        );
+        for workers in [1, 3, 64] {
+            let output = decompress_on(&compressed, &index, &builder, workers).unwrap();
+            assert_eq!(output, data, "{workers} workers");
+        }
+
+        // Keyframes only at the start: one run
+        let compressed = crate::compress(&data, 720, 2, 10, 0, 0).unwrap();
+        let index = PacketIndex::build(&compressed, &builder).unwrap();
+        assert_eq!(split_runs(&index, 8), vec![0..500]);
+        assert_eq!(
+            decompress_on(&compressed, &index, &builder, 4).unwrap(),
+            data
+        );
+
+        // Settings that do not match the index are rejected
+        let other = DecompressorBuilder::new(360).robustness(2);
+        assert!(decompress_parallel(&compressed, &index, &other).is_err());
+    }
+}
@@ -10,6 +10,7 @@
 
 #![allow(clippy::cast_precision_loss)]
 
+use pocketplus::synth::{self, Profile};
 use pocketplus::{compress, decompress};
 use std::env;
 use std::fs;
@@ -20,9 +21,29 @@ const DEFAULT_ITERATIONS: usize = 100;
 const PACKET_SIZE_BYTES: usize = 90;
 const PACKET_SIZE_BITS: usize = PACKET_SIZE_BYTES * 8;
 
+/// Benchmark input data.
+enum Source {
+    /// Test vector file.
+    File(&'static str),
+    /// Synthetic housekeeping packets from the default profile.
+    Synthetic { packets: usize, seed: u64 },
+}
+
+impl Source {
+    /// Load the input bytes, or `None` if the file is missing.
+    fn load(&self) -> Option<Vec<u8>> {
+        match *self {
+            Self::File(path) => fs::read(Path::new(path)).ok(),
+            Self::Synthetic { packets, seed } => {
+                Some(synth::generate(&Profile::housekeeping(), packets, seed))
+            }
+        }
+    }
+}
+
 struct BenchConfig {
     name: &'static str,
-    path: &'static str,
+    source: Source,
     robustness: usize,
     pt: usize,
     ft: usize,
@@ -32,7 +53,7 @@ struct BenchConfig {
 const BENCHMARKS: &[BenchConfig] = &[
     BenchConfig {
         name: "simple",
-        path: "../../test-vectors/input/simple.bin",
+        source: Source::File("../../test-vectors/input/simple.bin"),
         robustness: 1,
         pt: 10,
         ft: 20,
@@ -40,7 +61,7 @@ const BENCHMARKS: &[BenchConfig] = &[
     },
     BenchConfig {
         name: "hiro",
-        path: "../../test-vectors/input/hiro.bin",
+        source: Source::File("../../test-vectors/input/hiro.bin"),
         robustness: 7,
         pt: 10,
         ft: 20,
@@ -48,7 +69,7 @@ const BENCHMARKS: &[BenchConfig] = &[
     },
     BenchConfig {
         name: "housekeeping",
-        path: "../../test-vectors/input/housekeeping.bin",
+        source: Source::File("../../test-vectors/input/housekeeping.bin"),
         robustness: 2,
         pt: 20,
         ft: 50,
@@ -56,7 +77,18 @@ const BENCHMARKS: &[BenchConfig] = &[
     },
     BenchConfig {
         name: "venus-express",
-        path: "../../test-vectors/input/venus-express.ccsds",
+        source: Source::File("../../test-vectors/input/venus-express.ccsds"),
+        robustness: 2,
+        pt: 20,
+        ft: 50,
+        rt: 100,
+    },
+    BenchConfig {
+        name: "synthetic",
+        source: Source::Synthetic {
+            packets: 1000,
+            seed: 1,
+        },
         robustness: 2,
         pt: 20,
         ft: 50,
@@ -65,8 +97,7 @@ const BENCHMARKS: &[BenchConfig] = &[
 ];
 
 fn bench_compress(config: &BenchConfig, iterations: usize) {
-    let path = Path::new(config.path);
-    let Ok(input) = fs::read(path) else {
+    let Some(input) = config.source.load() else {
         println!("{:<20} SKIP (file not found)", config.name);
         return;
     };
@@ -110,8 +141,7 @@ fn bench_compress(config: &BenchConfig, iterations: usize) {
 }
 
 fn bench_decompress(config: &BenchConfig, iterations: usize) {
-    let path = Path::new(config.path);
-    let Ok(input) = fs::read(path) else {
+    let Some(input) = config.source.load() else {
         println!("{:<20} SKIP (file not found)", config.name);
         return;
     };
@@ -3,8 +3,11 @@
 //! A unified command-line interface for CCSDS 124.0-B-1 compression and decompression.
 //!
 //! Usage:
-//!   pocketplus input packet_size pt ft rt robustness    # compress
-//!   pocketplus -d input.pkt packet_size robustness      # decompress
+//!   pocketplus [options] input packet_size pt ft rt robustness    # compress
+//!   pocketplus [options] -d input.pkt packet_size robustness      # decompress
+//!   pocketplus -g output num_packets seed [profile]                # generate
+//!   pocketplus -m manifest output                                  # manifest
+//!   pocketplus -a input packet_size [output.csv]                   # bit changes
 //!   pocketplus --version
 //!   pocketplus --help
 
@@ -12,12 +15,27 @@
 #![allow(clippy::cast_precision_loss)]
 #![allow(clippy::doc_markdown)]
 
-use pocketplus::{compress, decompress};
+use pocketplus::analyze;
+use pocketplus::archive;
+use pocketplus::armor::Armor;
+use pocketplus::bitorder::BitOrder;
+use pocketplus::container::{self, DuplicatePolicy, PayloadEncoding, SegmentEncoder};
+use pocketplus::index::PacketIndex;
+use pocketplus::json::JsonWriter;
+use pocketplus::manifest::{self, Manifest};
+use pocketplus::passthrough::{self, PassthroughCompressor};
+use pocketplus::synth::{self, Profile};
+use pocketplus::{
+    decompress, decompress_parallel, BitVector, Compressor, DecompressorBuilder, PocketError,
+    RefreshPolicy,
+};
 use std::env;
 use std::fs::{self, File};
 use std::io::{Read, Write};
+use std::ops::Range;
 use std::path::Path;
 use std::process;
+use std::time::Duration;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -54,29 +72,250 @@ fn print_help(prog_name: &str) {
     println!("  Small Satellite Conference, Communications, SSC22-XII-03.");
     println!("  https://digitalcommons.usu.edu/smallsat/2022/all2022/133/\n");
     println!("Usage:");
-    println!("  {prog_name} <input> <packet_size> <pt> <ft> <rt> <robustness>");
-    println!("  {prog_name} -d <input.pkt> <packet_size> <robustness>\n");
+    println!("  {prog_name} [options] <input> <packet_size> <pt> <ft> <rt> <robustness>");
+    println!("  {prog_name} [options] -d <input.pkt> <packet_size> <robustness>");
+    println!("  {prog_name} -g <output> <num_packets> <seed> [profile]");
+    println!("  {prog_name} -m <manifest> <output>");
+    println!("  {prog_name} -a <input> <packet_size> [output.csv]\n");
     println!("Options:");
     println!("  -d             Decompress (default is compress)");
+    println!("  -g             Generate synthetic housekeeping data");
+    println!("  -m             Compress the files listed in a manifest");
+    println!("  -a             Export per-bit change frequencies as CSV");
+    println!("  --container    Compress into a self-delimiting container segment");
+    println!("  --dedupe       Drop repeated identical packets (implies --container)");
+    println!("  --raw          Store packets uncompressed (implies --container)");
+    println!("  --restart=KIB  Compress into an archive with a restart point every KIB KiB");
+    println!("  --lsb-first    Raw data uses LSB-first bit order within bytes");
+    println!("  --crc          Append/verify a CRC-16 after every compressed packet");
+    println!("  --time-bits=K  Carry the first K bits of every packet (time field) verbatim");
+    println!("  --armor=ENC    Write/read compressed data as hex or base64 text");
+    println!("  --index        Write/read a packet index sidecar (<file>.idx)");
+    println!("  --packets=A[-B]");
+    println!("                 Decompress only packets A to B (0-based, inclusive)");
+    println!("  --json         Print the report as JSON");
+    println!("  --refresh-packets=N");
+    println!("                 Force a full mask + uncompressed packet at least every N packets");
+    println!("  --refresh-secs=S");
+    println!("                 Force a full mask + uncompressed packet at least every S seconds");
     println!("  -h, --help     Show this help message");
     println!("  -v, --version  Show version information\n");
     println!("Compress arguments:");
     println!("  input          Input file to compress");
     println!("  packet_size    Packet size in bytes (e.g., 90)");
-    println!("  pt             New mask period (e.g., 10, 20)");
-    println!("  ft             Send mask period (e.g., 20, 50)");
-    println!("  rt             Uncompressed period (e.g., 50, 100)");
+    println!("  pt             New mask period (e.g., 10, 20; 0 = never)");
+    println!("  ft             Send mask period (e.g., 20, 50; 0 = never)");
+    println!("  rt             Uncompressed period (e.g., 50, 100; 0 = never)");
     println!("  robustness     Robustness level 0-7 (e.g., 1, 2)\n");
     println!("Decompress arguments:");
     println!("  input.pkt      Compressed input file");
     println!("  packet_size    Original packet size in bytes");
     println!("  robustness     Robustness level (must match compression)\n");
+    println!("Generate arguments:");
+    println!("  output         Output file for the raw packets");
+    println!("  num_packets    Number of packets to generate");
+    println!("  seed           Generator seed (same seed, same data)");
+    println!("  profile        Packet layout file (default: 90-byte housekeeping)\n");
+    println!("Manifest arguments:");
+    println!("  manifest       One entry per line: path packet_size [r= pt= ft= rt=");
+    println!("                 offset= length=]; paths relative to the manifest");
+    println!("  output         Container file with one segment per entry\n");
+    println!("Bit change arguments:");
+    println!("  input          Raw input file");
+    println!("  packet_size    Packet size in bytes");
+    println!("  output.csv     CSV file (default: standard output)\n");
+    println!("Containers:");
+    println!("  Decompression detects container input automatically. Concatenated");
+    println!("  segments are decoded independently using the parameters in each");
+    println!("  segment header and reported one by one.\n");
+    println!("Archives:");
+    println!("  Decompression detects archive input automatically. Damaged or missing");
+    println!("  chunks are skipped, decoding resumes at the next restart point and the");
+    println!("  missing packet ranges are reported.\n");
+    println!("Index files:");
+    println!("  --index writes the offset and flags of every packet to <output>.idx");
+    println!("  when compressing. With --index and --packets, decompression reads");
+    println!("  <input>.idx and starts decoding at the nearest packet carrying the");
+    println!("  full state instead of at the start of the stream. With --index alone,");
+    println!("  the stream is decoded on several threads, split at those packets.\n");
     println!("Output:");
     println!("  Compress:   <input>.pkt");
     println!("  Decompress: <input>.depkt (or <base>.depkt if input ends in .pkt)\n");
     println!("Examples:");
     println!("  {prog_name} data.bin 90 10 20 50 1        # compress");
     println!("  {prog_name} -d data.bin.pkt 90 1          # decompress");
+    println!("  {prog_name} --container data.bin 90 10 20 50 1");
+    println!("  {prog_name} --restart=64 data.bin 90 10 20 50 1");
+    println!("  {prog_name} --lsb-first data.bin 90 10 20 50 1");
+    println!("  {prog_name} --refresh-packets=25 data.bin 90 10 20 50 1");
+    println!("  {prog_name} --time-bits=48 data.bin 90 10 20 50 1");
+    println!("  {prog_name} --armor=base64 data.bin 90 10 20 50 1");
+    println!("  {prog_name} --index data.bin 90 10 20 50 1");
+    println!("  {prog_name} --index --packets=500-599 -d data.bin.pkt 90 1");
+    println!("  {prog_name} -g synth.bin 1000 42          # generate");
+    println!("  {prog_name} -m session.manifest session.pkt");
+    println!("  {prog_name} --json -d data.bin.pkt 90 1   # JSON report");
+    println!("  {prog_name} -a data.bin 90 bits.csv");
+}
+
+/// Command-line options that may precede the positional arguments.
+#[derive(Clone, Debug, Default)]
+struct Options {
+    /// Wrap compressed output in a container segment.
+    container: bool,
+    /// Bit order of the raw (uncompressed) data.
+    bit_order: BitOrder,
+    /// Handling of repeated identical packets in container output.
+    duplicates: DuplicatePolicy,
+    /// Payload encoding of container output.
+    encoding: PayloadEncoding,
+    /// Archive output with a restart point every N KiB.
+    restart_kib: Option<usize>,
+    /// Forced refresh policy.
+    refresh: RefreshPolicy,
+    /// Compressed packets are followed by a CRC-16.
+    packet_crc: bool,
+    /// Length of the time field passed through uncompressed, in bits.
+    time_bits: Option<usize>,
+    /// Text encoding of the compressed data.
+    armor: Option<Armor>,
+    /// Write or read a sidecar packet index.
+    index: bool,
+    /// Packets to decompress, if not all.
+    packets: Option<Range<usize>>,
+    /// Print reports as JSON.
+    json: bool,
+}
+
+/// Separate `--option` flags from positional arguments.
+///
+/// The program name stays at index 0 of the returned arguments.
+fn split_options(args: &[String]) -> Result<(Options, Vec<String>), String> {
+    let mut options = Options::default();
+    let mut positional = Vec::with_capacity(args.len());
+
+    for arg in args {
+        match arg.as_str() {
+            "--container" => options.container = true,
+            "--raw" => {
+                options.container = true;
+                options.encoding = PayloadEncoding::Raw;
+            }
+            "--dedupe" => {
+                options.container = true;
+                options.duplicates = DuplicatePolicy::Drop;
+            }
+            "--lsb-first" => options.bit_order = BitOrder::LsbFirst,
+            "--crc" => options.packet_crc = true,
+            "--json" => options.json = true,
+            "--index" => options.index = true,
+            a if a.starts_with("--packets=") => {
+                options.packets = Some(
+                    parse_packet_range(&a["--packets=".len()..])
+                        .ok_or_else(|| format!("Invalid value: {a}"))?,
+                );
+            }
+            a if a.starts_with("--time-bits=") => {
+                options.time_bits = Some(parse_option_value(a)?);
+            }
+            a if a.starts_with("--armor=") => {
+                options.armor = Some(
+                    Armor::from_name(&a["--armor=".len()..])
+                        .ok_or_else(|| format!("Invalid value: {a}"))?,
+                );
+            }
+            a if a.starts_with("--restart=") => {
+                options.restart_kib = Some(parse_option_value(a)?);
+            }
+            a if a.starts_with("--refresh-packets=") => {
+                options.refresh.max_packets = Some(parse_option_value(a)?);
+            }
+            a if a.starts_with("--refresh-secs=") => {
+                options.refresh.max_age = Some(Duration::from_secs(parse_option_value(a)?));
+            }
+            "--help" | "--version" => positional.push(arg.clone()),
+            a if a.starts_with("--") => return Err(format!("Unknown option: {a}")),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    Ok((options, positional))
+}
+
+/// Parse the positive integer value of a `--name=value` option.
+fn parse_option_value<T: std::str::FromStr + Default + PartialOrd>(arg: &str) -> Result<T, String> {
+    arg.split_once('=')
+        .and_then(|(_, value)| value.parse().ok())
+        .filter(|value| *value > T::default())
+        .ok_or_else(|| format!("Invalid value: {arg}"))
+}
+
+/// Parse a `FIRST[-LAST]` packet range (inclusive) into a half-open range.
+fn parse_packet_range(value: &str) -> Option<Range<usize>> {
+    let (first, last) = value.split_once('-').unwrap_or((value, value));
+    let first: usize = first.parse().ok()?;
+    let last: usize = last.parse().ok()?;
+    (first <= last).then(|| first..last + 1)
+}
+
+/// Create a compressor configured from the command-line options.
+fn new_compressor(
+    packet_bits: usize,
+    robustness: usize,
+    pt_period: usize,
+    ft_period: usize,
+    rt_period: usize,
+    options: &Options,
+) -> Result<Compressor, PocketError> {
+    if robustness > 7 {
+        return Err(PocketError::InvalidRobustness(robustness));
+    }
+    let mut comp = Compressor::new(
+        packet_bits,
+        None,
+        robustness as u8,
+        pt_period,
+        ft_period,
+        rt_period,
+    )?;
+    comp.set_refresh_policy(options.refresh);
+    comp.set_packet_crc(options.packet_crc);
+    Ok(comp)
+}
+
+/// Compress whole packets into a POCKET+ stream.
+fn compress_stream(
+    data: &[u8],
+    packet_bits: usize,
+    robustness: usize,
+    pt_period: usize,
+    ft_period: usize,
+    rt_period: usize,
+    options: &Options,
+) -> Result<Vec<u8>, PocketError> {
+    let mut comp = new_compressor(
+        packet_bits - options.time_bits.unwrap_or(0),
+        robustness,
+        pt_period,
+        ft_period,
+        rt_period,
+        options,
+    )?;
+
+    let mut output = Vec::new();
+    if let Some(time_bits) = options.time_bits {
+        let mut passthrough = PassthroughCompressor::new(comp, time_bits)?;
+        for packet in data.chunks_exact(packet_bits / 8) {
+            passthrough.compress_packet_into(packet, &mut output)?;
+        }
+        return Ok(output);
+    }
+    for packet in data.chunks_exact(packet_bits / 8) {
+        let params = comp.next_params();
+        let input = BitVector::from_bytes(packet, packet_bits);
+        comp.compress_packet_into(&input, &params, &mut output)?;
+    }
+    Ok(output)
 }
 
 /// Create output filename for decompression.
@@ -127,10 +366,12 @@ fn do_compress(
     ft_period: usize,
     rt_period: usize,
     robustness: usize,
+    options: &Options,
 ) -> Result<(), String> {
     // Read input file
-    let input_data = read_file(input_path)?;
+    let mut input_data = read_file(input_path)?;
     let input_size = input_data.len();
+    options.bit_order.apply(&mut input_data);
 
     // Validate input size
     if input_size % packet_size != 0 {
@@ -139,20 +380,86 @@ fn do_compress(
         ));
     }
 
+    if options.container && options.packet_crc {
+        return Err("--crc cannot be combined with container output".to_string());
+    }
+    if options.restart_kib.is_some() && (options.container || options.packet_crc) {
+        return Err("--restart cannot be combined with --container or --crc".to_string());
+    }
+    if options.time_bits.is_some()
+        && (options.container || options.packet_crc || options.restart_kib.is_some())
+    {
+        return Err(
+            "--time-bits cannot be combined with --container, --crc or --restart".to_string(),
+        );
+    }
+    if options
+        .time_bits
+        .is_some_and(|bits| bits >= packet_size * 8)
+    {
+        return Err("--time-bits must be smaller than the packet size".to_string());
+    }
+    if options.index
+        && (options.container
+            || options.packet_crc
+            || options.restart_kib.is_some()
+            || options.time_bits.is_some())
+    {
+        return Err(
+            "--index cannot be combined with --container, --crc, --restart or --time-bits"
+                .to_string(),
+        );
+    }
+
     // Create output filename
     let output_path = format!("{input_path}.pkt");
 
     // Compress
     let packet_bits = packet_size * 8;
-    let output_data = compress(
-        &input_data,
-        packet_bits,
-        robustness,
-        pt_period,
-        ft_period,
-        rt_period,
-    )
+    let output_data = if options.container {
+        SegmentEncoder::new(packet_bits, robustness, pt_period, ft_period, rt_period).and_then(
+            |encoder| {
+                encoder
+                    .duplicates(options.duplicates)
+                    .encoding(options.encoding)
+                    .refresh(options.refresh)
+                    .encode(&input_data)
+            },
+        )
+    } else if let Some(kib) = options.restart_kib {
+        new_compressor(
+            packet_bits,
+            robustness,
+            pt_period,
+            ft_period,
+            rt_period,
+            options,
+        )
+        .and_then(|comp| archive::compress_archive(&input_data, comp, kib * 1024))
+    } else {
+        compress_stream(
+            &input_data,
+            packet_bits,
+            robustness,
+            pt_period,
+            ft_period,
+            rt_period,
+            options,
+        )
+    }
     .map_err(|e| format!("Compression failed: {e}"))?;
+    let index_path = format!("{output_path}.idx");
+    if options.index {
+        let builder = DecompressorBuilder::new(packet_bits).robustness(robustness);
+        let sidecar = PacketIndex::build(&output_data, &builder)
+            .and_then(|index| index.to_bytes())
+            .map_err(|e| format!("Indexing failed: {e}"))?;
+        write_file(&index_path, &sidecar)?;
+    }
+    let output_data = match options.armor {
+        Some(armor) => armor.encode(&output_data).into_bytes(),
+        None => output_data,
+    };
 
     let output_size = output_data.len();
 
@@ -162,27 +469,116 @@ fn do_compress(
     // Print summary
     let num_packets = input_size / packet_size;
     let ratio = input_size as f64 / output_size as f64;
+    if options.json {
+        let mut json = JsonWriter::new();
+        json.begin_object();
+        json.key("mode").string("compress");
+        json.key("input").string(input_path);
+        json.key("input_bytes").uint(input_size as u64);
+        json.key("packets").uint(num_packets as u64);
+        json.key("output").string(&output_path);
+        json.key("output_bytes").uint(output_size as u64);
+        json.key("ratio").float(ratio);
+        if options.index {
+            json.key("index").string(&index_path);
+        }
+        json.key("parameters").begin_object();
+        json.key("packet_size").uint(packet_size as u64);
+        json.key("robustness").uint(robustness as u64);
+        json.key("pt").uint(pt_period as u64);
+        json.key("ft").uint(ft_period as u64);
+        json.key("rt").uint(rt_period as u64);
+        json.end_object();
+        json.end_object();
+        println!("{}", json.finish());
+        return Ok(());
+    }
     println!("Input:       {input_path} ({input_size} bytes, {num_packets} packets)");
     println!("Output:      {output_path} ({output_size} bytes)");
     println!("Ratio:       {ratio:.2}x");
+    if options.index {
+        println!("Index:       {index_path}");
+    }
     println!("Parameters:  R={robustness}, pt={pt_period}, ft={ft_period}, rt={rt_period}");
 
     Ok(())
 }
 
 /// Decompress a file.
-fn do_decompress(input_path: &str, packet_size: usize, robustness: usize) -> Result<(), String> {
+fn do_decompress(
+    input_path: &str,
+    packet_size: usize,
+    robustness: usize,
+    options: &Options,
+) -> Result<(), String> {
     // Read input file
-    let input_data = read_file(input_path)?;
+    let mut input_data = read_file(input_path)?;
     let input_size = input_data.len();
+    if let Some(armor) = options.armor {
+        input_data = std::str::from_utf8(&input_data)
+            .map_err(|_| format!("Armored input is not {armor} text"))
+            .and_then(|text| {
+                armor
+                    .decode(text)
+                    .map_err(|e| format!("Cannot decode {armor} input: {e}"))
+            })?;
+    }
 
     // Create output filename
     let output_path = make_decompress_filename(input_path);
 
+    let is_container = container::is_container(&input_data);
+    let is_archive = archive::is_archive(&input_data);
+    if (options.packets.is_some() || options.index)
+        && (is_container || is_archive || options.packet_crc || options.time_bits.is_some())
+    {
+        return Err(
+            "--packets and --index require a plain stream without --crc or --time-bits".to_string(),
+        );
+    }
+
+    // Container input carries its own parameters per segment
+    if is_container {
+        return do_decompress_container(input_path, &input_data, &output_path, options);
+    }
+    if is_archive {
+        return do_decompress_archive(input_path, &input_data, &output_path, options);
+    }
+
     // Decompress
     let packet_bits = packet_size * 8;
-    let output_data = decompress(&input_data, packet_bits, robustness)
-        .map_err(|e| format!("Decompression failed: {e}"))?;
+    let index = if options.index {
+        let sidecar = read_file(&format!("{input_path}.idx"))?;
+        Some(PacketIndex::from_bytes(&sidecar).map_err(|e| format!("Invalid index file: {e}"))?)
+    } else {
+        None
+    };
+    let mut output_data = if let Some(packets) = options.packets.clone() {
+        decompress_packets(
+            &input_data,
+            packet_bits,
+            robustness,
+            packets,
+            index.as_ref(),
+        )
+    } else if let Some(index) = &index {
+        check_index(&input_data, index).and_then(|()| {
+            let builder = DecompressorBuilder::new(packet_bits).robustness(robustness);
+            decompress_parallel(&input_data, index, &builder)
+        })
+    } else if let Some(time_bits) = options.time_bits {
+        passthrough::decompress_passthrough(&input_data, packet_bits, time_bits, robustness)
+    } else if options.packet_crc {
+        DecompressorBuilder::new(packet_bits)
+            .robustness(robustness)
+            .packet_crc(true)
+            .build()
+            .and_then(|mut decomp| decomp.decompress_stream(&input_data))
+    } else {
+        decompress(&input_data, packet_bits, robustness)
+    }
+    .map_err(|e| format!("Decompression failed: {e}"))?;
+    options.bit_order.apply(&mut output_data);
 
     let output_size = output_data.len();
 
@@ -192,6 +588,24 @@ fn do_decompress(input_path: &str, packet_size: usize, robustness: usize) -> Res
     // Print summary
     let num_packets = output_size / packet_size;
     let ratio = output_size as f64 / input_size as f64;
+    if options.json {
+        let mut json = JsonWriter::new();
+        json.begin_object();
+        json.key("mode").string("decompress");
+        json.key("input").string(input_path);
+        json.key("input_bytes").uint(input_size as u64);
+        json.key("output").string(&output_path);
+        json.key("output_bytes").uint(output_size as u64);
+        json.key("packets").uint(num_packets as u64);
+        json.key("expansion").float(ratio);
+        json.key("parameters").begin_object();
+        json.key("packet_size").uint(packet_size as u64);
+        json.key("robustness").uint(robustness as u64);
+        json.end_object();
+        json.end_object();
+        println!("{}", json.finish());
+        return Ok(());
+    }
     println!("Input:       {input_path} ({input_size} bytes)");
     println!("Output:      {output_path} ({output_size} bytes, {num_packets} packets)");
     println!("Expansion:   {ratio:.2}x");
@@ -200,6 +614,297 @@ fn do_decompress(input_path: &str, packet_size: usize, robustness: usize) -> Res
     Ok(())
 }
 
+/// Check that an index ends where the stream does.
+///
+/// A stale index would seek into the middle of a packet.
+fn check_index(data: &[u8], index: &PacketIndex) -> Result<(), PocketError> {
+    let end = index
+        .entries
+        .last()
+        .map_or(0, |entry| (entry.bit_offset + entry.bit_length + 7) / 8);
+    if end != data.len() {
+        return Err(PocketError::InvalidFormat(
+            "index does not match the input".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Decompress a range of packets, seeking with the index if one is given.
+fn decompress_packets(
+    data: &[u8],
+    packet_bits: usize,
+    robustness: usize,
+    packets: Range<usize>,
+    index: Option<&PacketIndex>,
+) -> Result<Vec<u8>, PocketError> {
+    let mut decomp = DecompressorBuilder::new(packet_bits)
+        .robustness(robustness)
+        .build()?;
+    if let Some(index) = index {
+        check_index(data, index)?;
+        return decomp.decompress_range(data, index, packets);
+    }
+
+    let output = decomp.decompress_stream(data)?;
+    let packet_bytes = packet_bits / 8;
+    output
+        .get(packets.start * packet_bytes..packets.end * packet_bytes)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| {
+            PocketError::InvalidParameter(format!(
+                "packet range {}..{} outside the {} packets",
+                packets.start,
+                packets.end,
+                output.len() / packet_bytes
+            ))
+        })
+}
+
+/// Decompress a container file, reporting each segment.
+fn do_decompress_container(
+    input_path: &str,
+    input_data: &[u8],
+    output_path: &str,
+    options: &Options,
+) -> Result<(), String> {
+    let segments = container::decompress_segments(input_data)
+        .map_err(|e| format!("Decompression failed: {e}"))?;
+
+    let mut output_data = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        output_data.extend_from_slice(&segment.data);
+        if options.json {
+            continue;
+        }
+        let header = &segment.header;
+        println!(
+            "Segment {}:   offset {}, {} packets, packet_size={}, R={} ({} bytes)",
+            i + 1,
+            segment.offset,
+            header.num_packets,
+            header.packet_size / 8,
+            header.robustness,
+            segment.data.len()
+        );
+    }
+    options.bit_order.apply(&mut output_data);
+
+    write_file(output_path, &output_data)?;
+
+    let input_size = input_data.len();
+    let output_size = output_data.len();
+    if options.json {
+        let mut json = JsonWriter::new();
+        json.begin_object();
+        json.key("mode").string("decompress");
+        json.key("input").string(input_path);
+        json.key("input_bytes").uint(input_size as u64);
+        json.key("output").string(output_path);
+        json.key("output_bytes").uint(output_size as u64);
+        json.key("segments").begin_array();
+        for segment in &segments {
+            json.begin_object();
+            json.key("offset").uint(segment.offset as u64);
+            json.key("output_bytes").uint(segment.data.len() as u64);
+            json.key("header");
+            segment.header.write_json(&mut json);
+            json.end_object();
+        }
+        json.end_array();
+        json.end_object();
+        println!("{}", json.finish());
+        return Ok(());
+    }
+    println!("Input:       {input_path} ({input_size} bytes)");
+    println!(
+        "Output:      {output_path} ({output_size} bytes, {} segments)",
+        segments.len()
+    );
+
+    Ok(())
+}
+
+/// Decompress an archive file, reporting damaged regions.
+fn do_decompress_archive(
+    input_path: &str,
+    input_data: &[u8],
+    output_path: &str,
+    options: &Options,
+) -> Result<(), String> {
+    let report = archive::decompress_archive(input_data)
+        .map_err(|e| format!("Decompression failed: {e}"))?;
+
+    let mut output_data = report.data();
+    options.bit_order.apply(&mut output_data);
+    write_file(output_path, &output_data)?;
+
+    let input_size = input_data.len();
+    let output_size = output_data.len();
+    let missing = report.missing();
+    if options.json {
+        let mut json = JsonWriter::new();
+        json.begin_object();
+        json.key("mode").string("decompress");
+        json.key("input").string(input_path);
+        json.key("input_bytes").uint(input_size as u64);
+        json.key("output").string(output_path);
+        json.key("output_bytes").uint(output_size as u64);
+        json.key("chunks").uint(report.chunks.len() as u64);
+        json.key("discarded_bytes")
+            .uint(report.discarded_bytes as u64);
+        json.key("missing").begin_array();
+        for range in &missing {
+            json.begin_object();
+            json.key("first").uint(u64::from(range.start));
+            json.key("count").uint(u64::from(range.end - range.start));
+            json.end_object();
+        }
+        json.end_array();
+        json.end_object();
+        println!("{}", json.finish());
+        return Ok(());
+    }
+    println!("Input:       {input_path} ({input_size} bytes)");
+    println!(
+        "Output:      {output_path} ({output_size} bytes, {} chunks)",
+        report.chunks.len()
+    );
+    if report.discarded_bytes > 0 {
+        println!("Discarded:   {} bytes", report.discarded_bytes);
+    }
+    for range in &missing {
+        println!("Missing:     packets {}-{}", range.start, range.end - 1);
+    }
+
+    Ok(())
+}
+
+/// Generate synthetic housekeeping packets into a file.
+fn do_generate(
+    output_path: &str,
+    num_packets: usize,
+    seed: u64,
+    profile_path: Option<&str>,
+) -> Result<(), String> {
+    let profile = match profile_path {
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("Cannot read profile '{path}': {e}"))?;
+            Profile::parse(&text).map_err(|e| format!("Invalid profile '{path}': {e}"))?
+        }
+        None => Profile::housekeeping(),
+    };
+
+    let data = synth::generate(&profile, num_packets, seed);
+    write_file(output_path, &data)?;
+
+    println!(
+        "Generated {num_packets} packets of {} bytes ({} bytes) -> {output_path}",
+        profile.packet_bytes(),
+        data.len()
+    );
+
+    Ok(())
+}
+
+/// Compress every entry of a manifest into one container file.
+fn do_manifest(manifest_path: &str, output_path: &str, options: &Options) -> Result<(), String> {
+    let text = fs::read_to_string(manifest_path)
+        .map_err(|e| format!("Cannot read manifest '{manifest_path}': {e}"))?;
+    let manifest =
+        Manifest::parse(&text).map_err(|e| format!("Invalid manifest '{manifest_path}': {e}"))?;
+    let base_dir = Path::new(manifest_path)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+
+    let (output_data, report) = manifest::compress_manifest(&manifest, base_dir)
+        .map_err(|e| format!("Compression failed: {e}"))?;
+    write_file(output_path, &output_data)?;
+
+    if options.json {
+        let mut json = JsonWriter::new();
+        json.begin_object();
+        json.key("mode").string("manifest");
+        json.key("output").string(output_path);
+        json.key("entries").begin_array();
+        for entry in &report.entries {
+            json.begin_object();
+            json.key("path").string(&entry.path.display().to_string());
+            json.key("offset").uint(entry.offset as u64);
+            json.key("packets").uint(entry.packets as u64);
+            json.key("packet_bytes").uint(entry.packet_bytes as u64);
+            json.key("input_bytes").uint(entry.input_bytes as u64);
+            json.key("compressed_bytes")
+                .uint(entry.compressed_bytes as u64);
+            json.key("ratio").float(entry.ratio());
+            json.end_object();
+        }
+        json.end_array();
+        json.key("input_bytes").uint(report.input_bytes() as u64);
+        json.key("compressed_bytes")
+            .uint(report.compressed_bytes() as u64);
+        json.key("ratio").float(report.ratio());
+        json.end_object();
+        println!("{}", json.finish());
+        return Ok(());
+    }
+
+    for (i, entry) in report.entries.iter().enumerate() {
+        println!(
+            "Entry {}:     {} @{}, {} packets of {} bytes, {} -> {} bytes ({:.2}x)",
+            i + 1,
+            entry.path.display(),
+            entry.offset,
+            entry.packets,
+            entry.packet_bytes,
+            entry.input_bytes,
+            entry.compressed_bytes,
+            entry.ratio()
+        );
+    }
+    println!(
+        "Output:      {output_path} ({} bytes, {} segments)",
+        report.compressed_bytes(),
+        report.entries.len()
+    );
+    println!(
+        "Ratio:       {:.2}x ({} -> {} bytes)",
+        report.ratio(),
+        report.input_bytes(),
+        report.compressed_bytes()
+    );
+
+    Ok(())
+}
+
+/// Write the per-bit change frequencies of a raw file as CSV.
+fn do_bit_changes(
+    input_path: &str,
+    packet_size: usize,
+    output_path: Option<&str>,
+) -> Result<(), String> {
+    let data = read_file(input_path)?;
+    let profile = analyze::bit_change_profile(&data, packet_size)
+        .map_err(|e| format!("Analysis failed: {e}"))?;
+    let csv = profile.to_csv();
+
+    match output_path {
+        Some(path) => {
+            write_file(path, csv.as_bytes())?;
+            let changing = profile.changes.iter().filter(|&&c| c > 0).count();
+            println!(
+                "Analyzed {} packets: {changing} of {} bits change -> {path}",
+                profile.packets,
+                profile.changes.len()
+            );
+        }
+        None => print!("{csv}"),
+    }
+
+    Ok(())
+}
+
 /// Parse a positive integer from a string argument.
 fn parse_positive(s: &str, name: &str) -> Result<usize, String> {
     s.parse::<usize>()
@@ -213,6 +918,12 @@ fn parse_positive(s: &str, name: &str) -> Result<usize, String> {
         })
 }
 
+/// Parse a pt/ft/rt period (0 disables the flag).
+fn parse_period(s: &str, name: &str) -> Result<usize, String> {
+    s.parse::<usize>()
+        .map_err(|_| format!("{name} must be a non-negative integer"))
+}
+
 /// Parse robustness value (0-7).
 fn parse_robustness(s: &str) -> Result<usize, String> {
     let value = s
@@ -227,7 +938,7 @@ fn parse_robustness(s: &str) -> Result<usize, String> {
 }
 
 /// Handle decompress mode.
-fn handle_decompress(args: &[String], prog_name: &str) {
+fn handle_decompress(args: &[String], prog_name: &str, options: &Options) {
     if args.len() != 5 {
         eprintln!("Error: Decompress requires 3 arguments after -d");
         eprintln!("Usage: {prog_name} -d <input.pkt> <packet_size> <robustness>");
@@ -255,14 +966,76 @@ fn handle_decompress(args: &[String], prog_name: &str) {
         process::exit(1);
     }
 
-    if let Err(e) = do_decompress(input_path, packet_size, robustness) {
+    if let Err(e) = do_decompress(input_path, packet_size, robustness, options) {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
+
+/// Handle generate mode.
+fn handle_generate(args: &[String], prog_name: &str) {
+    if args.len() != 5 && args.len() != 6 {
+        eprintln!("Error: Generate requires 3 or 4 arguments after -g");
+        eprintln!("Usage: {prog_name} -g <output> <num_packets> <seed> [profile]");
+        process::exit(1);
+    }
+
+    let num_packets = match parse_positive(&args[3], "num_packets") {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    };
+    let Ok(seed) = args[4].parse::<u64>() else {
+        eprintln!("Error: seed must be a number");
+        process::exit(1);
+    };
+
+    if let Err(e) = do_generate(&args[2], num_packets, seed, args.get(5).map(String::as_str)) {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
+
+/// Handle manifest mode.
+fn handle_manifest(args: &[String], prog_name: &str, options: &Options) {
+    if args.len() != 4 {
+        eprintln!("Error: Manifest requires 2 arguments after -m");
+        eprintln!("Usage: {prog_name} -m <manifest> <output>");
+        process::exit(1);
+    }
+
+    if let Err(e) = do_manifest(&args[2], &args[3], options) {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
+
+/// Handle bit change export mode.
+fn handle_bit_changes(args: &[String], prog_name: &str) {
+    if args.len() != 4 && args.len() != 5 {
+        eprintln!("Error: Bit change export requires 2 or 3 arguments after -a");
+        eprintln!("Usage: {prog_name} -a <input> <packet_size> [output.csv]");
+        process::exit(1);
+    }
+
+    let packet_size = match parse_positive(&args[3], "packet_size") {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = do_bit_changes(&args[2], packet_size, args.get(4).map(String::as_str)) {
         eprintln!("Error: {e}");
         process::exit(1);
     }
 }
 
 /// Handle compress mode.
-fn handle_compress(args: &[String], prog_name: &str) {
+fn handle_compress(args: &[String], prog_name: &str, options: &Options) {
     if args.len() != 7 {
         eprintln!("Error: Compress requires 6 arguments");
         eprintln!("Usage: {prog_name} <input> <packet_size> <pt> <ft> <rt> <robustness>");
@@ -277,21 +1050,21 @@ fn handle_compress(args: &[String], prog_name: &str) {
             process::exit(1);
         }
     };
-    let pt_period = match parse_positive(&args[3], "pt") {
+    let pt_period = match parse_period(&args[3], "pt") {
         Ok(v) => v,
         Err(e) => {
             eprintln!("Error: {e}");
             process::exit(1);
         }
     };
-    let ft_period = match parse_positive(&args[4], "ft") {
+    let ft_period = match parse_period(&args[4], "ft") {
         Ok(v) => v,
         Err(e) => {
             eprintln!("Error: {e}");
             process::exit(1);
         }
     };
-    let rt_period = match parse_positive(&args[5], "rt") {
+    let rt_period = match parse_period(&args[5], "rt") {
         Ok(v) => v,
         Err(e) => {
             eprintln!("Error: {e}");
@@ -318,6 +1091,7 @@ fn handle_compress(args: &[String], prog_name: &str) {
         ft_period,
         rt_period,
         robustness,
+        options,
     ) {
         eprintln!("Error: {e}");
         process::exit(1);
@@ -325,7 +1099,13 @@ fn handle_compress(args: &[String], prog_name: &str) {
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let (options, args) = match split_options(&env::args().collect::<Vec<_>>()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    };
     let prog_name = Path::new(&args[0])
         .file_name()
         .map_or("pocketplus", |s| s.to_str().unwrap_or("pocketplus"));
@@ -338,7 +1118,10 @@ fn main() {
     match args[1].as_str() {
         "-h" | "--help" => print_help(prog_name),
         "-v" | "--version" => print_version(),
-        "-d" => handle_decompress(&args, prog_name),
-        _ => handle_compress(&args, prog_name),
+        "-d" => handle_decompress(&args, prog_name, &options),
+        "-g" => handle_generate(&args, prog_name),
+        "-m" => handle_manifest(&args, prog_name, &options),
+        "-a" => handle_bit_changes(&args, prog_name),
+        _ => handle_compress(&args, prog_name, &options),
     }
 }
@@ -0,0 +1,306 @@
+//! Golden-vector regeneration and drift check.
+//!
+//! Maintainer tool that recompresses every test vector described by a
+//! `*-metadata.json` file in `test-vectors/expected-output/` and compares
+//! the result with the checked-in `.pkt` file. Any byte drift is reported
+//! with the first divergent packet, byte and bit.
+//!
+//! Usage:
+//!   cargo run --release --bin regen-vectors                  # check only
+//!   cargo run --release --bin regen-vectors -- --write       # rewrite .pkt files
+//!   cargo run --release --bin regen-vectors -- [--write] TEST_VECTORS_DIR
+//!
+//! Exits with status 1 if any vector drifted (in check mode) or failed.
+
+#![allow(clippy::doc_markdown)]
+
+use pocketplus::container::{SegmentEncoder, SegmentHeader};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const DEFAULT_VECTORS_DIR: &str = "../../test-vectors";
+
+/// Vector parameters read from a metadata file.
+#[derive(Debug)]
+struct Vector {
+    name: String,
+    input_file: String,
+    expected_file: String,
+    packet_bytes: usize,
+    pt: usize,
+    ft: usize,
+    rt: usize,
+    robustness: usize,
+}
+
+/// First point where regenerated output differs from the reference.
+#[derive(Debug, PartialEq, Eq)]
+struct Drift {
+    /// Index of the compressed packet containing the first difference.
+    packet: usize,
+    /// Byte offset in the compressed stream.
+    byte: usize,
+    /// Bit within that byte (0 = MSB).
+    bit: u32,
+}
+
+/// Find the value following `"key":` at or after `from`.
+///
+/// Just enough JSON for the flat metadata files written by the vector
+/// generator; returns the value and the offset just past it.
+fn json_value<'a>(json: &'a str, key: &str, from: usize) -> Option<(&'a str, usize)> {
+    let pattern = format!("\"{key}\"");
+    let start = from + json[from..].find(&pattern)? + pattern.len();
+    let rest = json[start..].trim_start().strip_prefix(':')?.trim_start();
+    let offset = json.len() - rest.len();
+
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let end = quoted.find('"')?;
+        Some((&quoted[..end], offset + end + 2))
+    } else {
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        Some((&rest[..end], offset + end))
+    }
+}
+
+/// Read a numeric field.
+fn json_number(json: &str, key: &str) -> Result<usize, String> {
+    json_value(json, key, 0)
+        .and_then(|(v, _)| v.parse().ok())
+        .ok_or_else(|| format!("missing or invalid \"{key}\""))
+}
+
+/// Read the `"file"` entry of the given object.
+fn json_file(json: &str, object: &str) -> Result<String, String> {
+    json_value(json, object, 0)
+        .and_then(|(_, end)| json_value(json, "file", end))
+        .map(|(v, _)| v.to_string())
+        .ok_or_else(|| format!("missing \"{object}.file\""))
+}
+
+/// Parse a vector metadata file.
+fn parse_metadata(json: &str) -> Result<Vector, String> {
+    let name = json_value(json, "name", 0)
+        .map(|(v, _)| v.to_string())
+        .ok_or("missing \"name\"")?;
+
+    Ok(Vector {
+        name,
+        input_file: json_file(json, "input")?,
+        expected_file: json_file(json, "compressed")?,
+        packet_bytes: json_number(json, "packet_length")?,
+        pt: json_number(json, "pt")?,
+        ft: json_number(json, "ft")?,
+        rt: json_number(json, "rt")?,
+        robustness: json_number(json, "robustness")?,
+    })
+}
+
+/// Compress a vector, returning the stream and the end offset of each packet.
+fn regenerate(vector: &Vector, input: &[u8]) -> Result<(Vec<u8>, Vec<usize>), String> {
+    let mut encoder = SegmentEncoder::new(
+        vector.packet_bytes * 8,
+        vector.robustness,
+        vector.pt,
+        vector.ft,
+        vector.rt,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut output = Vec::with_capacity(input.len());
+    let mut packet_ends = Vec::with_capacity(input.len() / vector.packet_bytes.max(1));
+
+    // One segment per packet gives the packet boundaries in the raw stream
+    for packet in input.chunks(vector.packet_bytes) {
+        let segment = encoder.encode(packet).map_err(|e| e.to_string())?;
+        let (_, header_len) = SegmentHeader::parse(&segment).map_err(|e| e.to_string())?;
+        output.extend_from_slice(&segment[header_len..]);
+        packet_ends.push(output.len());
+    }
+
+    Ok((output, packet_ends))
+}
+
+/// Locate the first difference between regenerated and reference output.
+fn find_drift(actual: &[u8], expected: &[u8], packet_ends: &[usize]) -> Option<Drift> {
+    let byte = actual
+        .iter()
+        .zip(expected)
+        .position(|(a, e)| a != e)
+        .or_else(|| (actual.len() != expected.len()).then(|| actual.len().min(expected.len())))?;
+
+    let bit = match (actual.get(byte), expected.get(byte)) {
+        (Some(a), Some(e)) => (a ^ e).leading_zeros(),
+        _ => 0,
+    };
+    let packet = packet_ends.partition_point(|&end| end <= byte);
+
+    Some(Drift { packet, byte, bit })
+}
+
+/// Check (and optionally rewrite) one vector.
+///
+/// # Returns
+/// `true` if the vector matched or was rewritten.
+fn process_vector(dir: &Path, vector: &Vector, write: bool) -> Result<bool, String> {
+    // Some metadata records the original capture name; the checked-in
+    // input is named after the expected output minus its .pkt suffix
+    let mut input_path = dir.join("input").join(&vector.input_file);
+    if !input_path.exists() {
+        if let Some(stem) = vector.expected_file.strip_suffix(".pkt") {
+            input_path = dir.join("input").join(stem);
+        }
+    }
+    let expected_path = dir.join("expected-output").join(&vector.expected_file);
+
+    let input = fs::read(&input_path)
+        .map_err(|e| format!("cannot read '{}': {e}", input_path.display()))?;
+    let expected = fs::read(&expected_path).unwrap_or_default();
+
+    let (actual, packet_ends) = regenerate(vector, &input)?;
+
+    match find_drift(&actual, &expected, &packet_ends) {
+        None => {
+            println!("{:<20} OK      ({} bytes)", vector.name, actual.len());
+            Ok(true)
+        }
+        Some(drift) => {
+            println!(
+                "{:<20} DRIFT   packet {} byte {} bit {} ({} -> {} bytes)",
+                vector.name,
+                drift.packet,
+                drift.byte,
+                drift.bit,
+                expected.len(),
+                actual.len()
+            );
+            if write {
+                fs::write(&expected_path, &actual)
+                    .map_err(|e| format!("cannot write '{}': {e}", expected_path.display()))?;
+                println!("{:<20} WRITTEN {}", "", expected_path.display());
+            }
+            Ok(write)
+        }
+    }
+}
+
+/// Metadata files in the expected-output directory, sorted by name.
+fn metadata_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let expected_dir = dir.join("expected-output");
+    let mut files: Vec<PathBuf> = fs::read_dir(&expected_dir)
+        .map_err(|e| format!("cannot read '{}': {e}", expected_dir.display()))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.ends_with("-metadata.json"))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn main() {
+    let mut write = false;
+    let mut dir = PathBuf::from(DEFAULT_VECTORS_DIR);
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--write" => write = true,
+            a if a.starts_with("--") => {
+                eprintln!("Error: Unknown option: {a}");
+                process::exit(1);
+            }
+            a => dir = PathBuf::from(a),
+        }
+    }
+
+    let files = match metadata_files(&dir) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+    };
+
+    println!("POCKET+ Golden Vector Check");
+    println!("===========================");
+    println!("Directory: {}\n", dir.display());
+
+    let mut failures = 0;
+    for path in &files {
+        let result = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| parse_metadata(&json))
+            .and_then(|vector| process_vector(&dir, &vector, write));
+
+        match result {
+            Ok(true) => {}
+            Ok(false) => failures += 1,
+            Err(e) => {
+                println!("{:<20} ERROR   {e}", path.display());
+                failures += 1;
+            }
+        }
+    }
+
+    println!("\n{} vectors, {failures} failed", files.len());
+    if failures > 0 {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{
+  "name": "simple",
+  "input": { "file": "simple.bin", "size": 9000 },
+  "compression": {
+    "packet_length": 90,
+    "parameters": { "pt": 10, "ft": 20, "rt": 50, "robustness": 1 }
+  },
+  "output": { "compressed": { "file": "simple.bin.pkt", "size": 641 } }
+}"#;
+
+    #[test]
+    fn test_parse_metadata() {
+        let v = parse_metadata(METADATA).unwrap();
+        assert_eq!(v.name, "simple");
+        assert_eq!(v.input_file, "simple.bin");
+        assert_eq!(v.expected_file, "simple.bin.pkt");
+        assert_eq!(
+            (v.packet_bytes, v.pt, v.ft, v.rt, v.robustness),
+            (90, 10, 20, 50, 1)
+        );
+    }
+
+    #[test]
+    fn test_find_drift() {
+        let ends = [2, 5];
+        assert_eq!(find_drift(&[1, 2, 3], &[1, 2, 3], &ends), None);
+        assert_eq!(
+            find_drift(&[1, 2, 3, 4], &[1, 2, 3, 5], &ends),
+            Some(Drift {
+                packet: 1,
+                byte: 3,
+                bit: 7
+            })
+        );
+        // Length mismatch reports the end of the shorter stream
+        assert_eq!(
+            find_drift(&[1, 2], &[1, 2, 3], &ends),
+            Some(Drift {
+                packet: 1,
+                byte: 2,
+                bit: 0
+            })
+        );
+    }
+}
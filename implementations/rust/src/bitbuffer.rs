@@ -11,10 +11,11 @@
 
 #![allow(clippy::cast_possible_truncation)]
 
-use crate::bitvector::BitVector;
+use crate::bitvector::{BitVector, MAX_EXTENDED_PACKET_LENGTH};
+use crate::buffer::Buffer;
 
 /// Maximum output buffer size in bytes.
-const MAX_OUTPUT_BYTES: usize = 65535 * 6;
+const MAX_OUTPUT_BYTES: usize = MAX_EXTENDED_PACKET_LENGTH * 6;
 
 /// Variable-length bit buffer for building compressed output.
 ///
@@ -22,7 +23,7 @@ const MAX_OUTPUT_BYTES: usize = 65535 * 6;
 #[derive(Clone, Debug, Default)]
 pub struct BitBuffer {
     /// Byte storage for flushed bits.
-    data: Vec<u8>,
+    data: Buffer<u8>,
     /// Total number of bits in the buffer.
     num_bits: usize,
     /// 64-bit accumulator for pending bits.
@@ -35,13 +36,58 @@ impl BitBuffer {
     /// Create a new empty bit buffer.
     pub fn new() -> Self {
         Self {
-            data: Vec::with_capacity(1024),
+            data: Vec::with_capacity(1024).into(),
             num_bits: 0,
             acc: 0,
             acc_len: 0,
         }
     }
 
+    /// Create an empty bit buffer with room for `bytes` bytes.
+    pub(crate) fn with_capacity(bytes: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(bytes).into(),
+            ..Self::default()
+        }
+    }
+
+    /// Create an empty bit buffer in caller-provided bytes.
+    ///
+    /// Output past the end of `bytes` moves to the heap.
+    pub(crate) fn lent(bytes: &'static mut [u8]) -> Self {
+        Self {
+            data: Buffer::lent(bytes, 0),
+            ..Self::default()
+        }
+    }
+
+    /// Continue a byte stream: bits are appended after the bytes already
+    /// in `bytes`.
+    ///
+    /// [`len`](Self::len) counts only the appended bits.
+    /// [`into_bytes`](Self::into_bytes) returns `bytes` followed by them.
+    pub(crate) fn resume(bytes: Vec<u8>) -> Self {
+        Self {
+            data: bytes.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Convert the buffer into bytes, zero-padded to a whole byte.
+    ///
+    /// Same bytes as [`to_bytes`](Self::to_bytes), but hands back the
+    /// internal storage instead of copying it.
+    ///
+    /// # Returns
+    /// The buffer data as a `Vec<u8>`.
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        if self.acc_len > 0 {
+            let last_byte = (self.acc << (8 - self.acc_len)) as u8;
+            self.data.push(last_byte);
+        }
+        self.data.into_vec()
+    }
+
     /// Clear the buffer, resetting to empty state.
     pub fn clear(&mut self) {
         self.data.clear();
@@ -111,6 +157,18 @@ impl BitBuffer {
     /// # Returns
     /// `true` on success, `false` if buffer would overflow.
     pub fn append_value(&mut self, value: u32, num_bits: usize) -> bool {
+        self.append_long(u64::from(value), num_bits)
+    }
+
+    /// Append up to 56 bits from a 64-bit value.
+    ///
+    /// # Arguments
+    /// * `value` - Value containing bits (right-justified)
+    /// * `num_bits` - Number of bits to append (1-56)
+    ///
+    /// # Returns
+    /// `true` on success, `false` if buffer would overflow.
+    pub(crate) fn append_long(&mut self, value: u64, num_bits: usize) -> bool {
         if num_bits == 0 || num_bits > 56 {
             return false;
         }
@@ -123,7 +181,7 @@ impl BitBuffer {
 
         // Mask to ensure only the relevant bits are used
         let mask = (1u64 << num_bits) - 1;
-        let masked_value = u64::from(value) & mask;
+        let masked_value = value & mask;
 
         self.acc = (self.acc << num_bits) | masked_value;
         self.acc_len += num_bits;
@@ -137,6 +195,8 @@ impl BitBuffer {
 
     /// Append bits from a byte slice.
     ///
+    /// Bits are shifted through the accumulator 32 at a time.
+    ///
     /// # Arguments
     /// * `data` - Source byte slice
     /// * `num_bits` - Number of bits to append
@@ -150,18 +210,12 @@ impl BitBuffer {
             return false;
         }
 
-        // Append each bit MSB-first
-        for i in 0..num_bits {
-            let byte_index = i / 8;
-            let bit_index = i % 8;
-
-            // Extract bits MSB-first (bit 7, 6, 5, ..., 0)
-            let shift_amount = 7 - bit_index;
-            let bit = (data[byte_index] >> shift_amount) & 1;
-
-            if !self.append_bit(bit) {
-                return false;
-            }
+        // Append four bytes at a time MSB-first, the last chunk padded
+        let mut remaining = num_bits;
+        for chunk in data[..(num_bits + 7) / 8].chunks(4) {
+            let mut bytes = [0u8; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            remaining -= self.append_word(u32::from_be_bytes(bytes), remaining);
         }
 
         true
@@ -169,59 +223,97 @@ impl BitBuffer {
 
     /// Append all bits from a bit vector.
     ///
+    /// Each 64-bit word of the vector goes through the accumulator in two
+    /// 32-bit halves.
+    ///
     /// # Arguments
     /// * `bv` - Source bit vector
     ///
     /// # Returns
     /// `true` on success, `false` if buffer would overflow.
     pub fn append_bitvector(&mut self, bv: &BitVector) -> bool {
-        let num_bytes = (bv.len() + 7) / 8;
-
-        for byte_idx in 0..num_bytes {
-            let mut bits_in_this_byte = 8;
-
-            // Last byte may have fewer than 8 bits
-            if byte_idx == num_bytes - 1 {
-                let remainder = bv.len() % 8;
-                if remainder != 0 {
-                    bits_in_this_byte = remainder;
-                }
-            }
-
-            // Append bits from this byte position
-            let start_bit = byte_idx * 8;
-            for bit_offset in 0..bits_in_this_byte {
-                let pos = start_bit + bit_offset;
-                let bit = bv.get_bit(pos);
+        // Check for overflow
+        let max_bits = MAX_OUTPUT_BYTES * 8;
+        if self.num_bits + bv.len() > max_bits {
+            return false;
+        }
 
-                if !self.append_bit(bit) {
-                    return false;
-                }
-            }
+        let mut remaining = bv.len();
+        for &word in bv.words() {
+            remaining -= self.append_word((word >> 32) as u32, remaining);
+            remaining -= self.append_word(word as u32, remaining);
         }
 
         true
     }
 
+    /// Append the leading `min(limit, 32)` bits of `word`, MSB-first.
+    ///
+    /// The caller has checked for overflow.
+    ///
+    /// # Returns
+    /// The number of bits appended.
+    #[inline]
+    fn append_word(&mut self, word: u32, limit: usize) -> usize {
+        let n = limit.min(32);
+        if n > 0 {
+            // At most 7 bits are pending after a flush, so 32 more fit
+            self.acc = (self.acc << n) | u64::from(word >> (32 - n));
+            self.acc_len += n;
+            self.num_bits += n;
+            self.flush_acc();
+        }
+        n
+    }
+
     /// Convert buffer to bytes.
     ///
     /// # Returns
     /// A new `Vec<u8>` containing the buffer data.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let num_bytes = (self.num_bits + 7) / 8;
-        let mut result = Vec::with_capacity(num_bytes);
+        let mut result = Vec::with_capacity((self.num_bits + 7) / 8);
+        self.to_bytes_into(&mut result);
+        result
+    }
 
+    /// Append the buffer data to `out`, zero-padded to a whole byte.
+    ///
+    /// Same bytes as [`to_bytes`](Self::to_bytes) without allocating a new
+    /// vector.
+    pub fn to_bytes_into(&self, out: &mut Vec<u8>) {
         // Copy flushed bytes from data buffer
-        result.extend_from_slice(&self.data);
+        out.extend_from_slice(&self.data);
 
         // Handle remaining bits in accumulator
         if self.acc_len > 0 {
             // Shift accumulator bits to MSB position
             let last_byte = (self.acc << (8 - self.acc_len)) as u8;
-            result.push(last_byte);
+            out.push(last_byte);
         }
+    }
 
-        result
+    /// Copy the buffer data to the start of `out`, zero-padded to a whole
+    /// byte.
+    ///
+    /// Includes the bytes a [`resume`](Self::resume)d buffer started with,
+    /// like [`to_bytes`](Self::to_bytes).
+    ///
+    /// # Returns
+    /// The number of bytes written, or `None` if `out` is too short.
+    pub(crate) fn copy_to_slice(&self, out: &mut [u8]) -> Option<usize> {
+        let len = self.data.len() + usize::from(self.acc_len > 0);
+        let out = out.get_mut(..len)?;
+        let (flushed, rest) = out.split_at_mut(self.data.len());
+        flushed.copy_from_slice(&self.data);
+        if self.acc_len > 0 {
+            rest[0] = (self.acc << (8 - self.acc_len)) as u8;
+        }
+        Some(len)
+    }
+
+    /// Heap memory held by the buffer, in bytes.
+    pub(crate) fn heap_size(&self) -> usize {
+        self.data.heap_capacity()
     }
 }
 
@@ -229,6 +321,25 @@ impl BitBuffer {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_resume() {
+        let mut bb = BitBuffer::resume(vec![0xAB, 0xCD]);
+        assert!(bb.is_empty());
+        bb.append_value(0b101, 3);
+        bb.append_value(0xFF, 8);
+        assert_eq!(bb.len(), 11);
+        assert_eq!(bb.into_bytes(), vec![0xAB, 0xCD, 0xBF, 0xE0]);
+        assert_eq!(BitBuffer::resume(vec![1]).into_bytes(), vec![1]);
+
+        // Slices get the resumed bytes too
+        let mut bb = BitBuffer::resume(vec![0xAB, 0xCD]);
+        bb.append_value(0b101, 3);
+        let mut out = [0xFF; 4];
+        assert_eq!(bb.copy_to_slice(&mut out[..2]), None);
+        assert_eq!(bb.copy_to_slice(&mut out), Some(3));
+        assert_eq!(out, [0xAB, 0xCD, 0xA0, 0xFF]);
+    }
+
     #[test]
     fn test_new() {
         let bb = BitBuffer::new();
@@ -287,6 +398,34 @@ mod tests {
         assert_eq!(bytes, data);
     }
 
+    #[test]
+    fn test_append_unaligned() {
+        // Word-sized appends after an odd number of bits match bit-by-bit
+        let bytes: Vec<u8> = (0..23u8).map(|i| i.wrapping_mul(37) ^ 0x5A).collect();
+        let bv = BitVector::from_bytes(&bytes, 179);
+
+        for offset in [0, 1, 5, 7] {
+            let mut expected = BitBuffer::new();
+            let mut words = BitBuffer::new();
+            let mut slice = BitBuffer::new();
+            if offset > 0 {
+                for bb in [&mut expected, &mut words, &mut slice] {
+                    bb.append_value(0b101_1011, offset);
+                }
+            }
+            for i in 0..179 {
+                expected.append_bit(bv.get_bit(i));
+            }
+            words.append_bitvector(&bv);
+            slice.append_bits(&bytes, 179);
+
+            assert_eq!(words.len(), expected.len());
+            assert_eq!(words.to_bytes(), expected.to_bytes(), "offset {offset}");
+            assert_eq!(slice.len(), expected.len());
+            assert_eq!(slice.to_bytes(), expected.to_bytes(), "offset {offset}");
+        }
+    }
+
     #[test]
     fn test_append_bitvector() {
         let mut bb = BitBuffer::new();
@@ -368,4 +507,18 @@ mod tests {
         assert_eq!(bytes[1], 0xAD);
         assert_eq!(bytes[2], 0xBE);
     }
+
+    #[test]
+    fn test_to_bytes_into() {
+        let mut bb = BitBuffer::new();
+        bb.append_value(0b101_1001_1101, 11);
+        let mut out = vec![0xFF];
+        bb.to_bytes_into(&mut out);
+        assert_eq!(out, [0xFF, 0b1011_0011, 0b1010_0000]);
+        assert_eq!(&out[1..], bb.to_bytes());
+        assert_eq!(bb.clone().into_bytes(), bb.to_bytes());
+
+        bb.append_value(0b111, 5);
+        assert_eq!(bb.into_bytes(), [0b1011_0011, 0b1010_0111]);
+    }
 }
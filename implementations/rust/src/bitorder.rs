@@ -0,0 +1,130 @@
+//! Bit-order transform for LSB-first front-ends.
+//!
+//! POCKET+ treats bit 0 of a packet as the MSB of its first byte
+//! (CCSDS 124.0-B-1 Section 1.6.1). Some front-end hardware delivers bytes
+//! with the least significant bit first on the wire. This layer reverses
+//! the bits of every byte on the way in and out, so such data can be
+//! compressed without pre-processing.
+//!
+//! The transform is lossless and its own inverse; the compressed stream
+//! itself is always standard POCKET+ and does not depend on the bit order.
+
+#[cfg(feature = "encoder")]
+use crate::compress::compress;
+#[cfg(feature = "decoder")]
+use crate::decompress::decompress;
+use crate::error::PocketError;
+
+/// Bit order within each input/output byte.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Most significant bit first (POCKET+ native order).
+    #[default]
+    MsbFirst,
+    /// Least significant bit first.
+    LsbFirst,
+}
+
+impl BitOrder {
+    /// Convert bytes between this order and MSB-first, in place.
+    ///
+    /// The conversion is its own inverse, so the same call maps native
+    /// data to MSB-first and MSB-first data back to native.
+    pub fn apply(self, data: &mut [u8]) {
+        if self == Self::LsbFirst {
+            for byte in data {
+                *byte = byte.reverse_bits();
+            }
+        }
+    }
+}
+
+/// Compress data delivered in the given bit order.
+///
+/// # Arguments
+/// * `data` - Input data (whole packets), bits ordered per `order`
+/// * `order` - Bit order of the input bytes
+/// * `packet_size`, `robustness`, `pt_limit`, `ft_limit`, `rt_limit` -
+///   as for [`compress`](crate::compress())
+///
+/// # Returns
+/// A standard POCKET+ compressed stream.
+#[cfg(feature = "encoder")]
+pub fn compress_with_bit_order(
+    data: &[u8],
+    order: BitOrder,
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> Result<Vec<u8>, PocketError> {
+    if order == BitOrder::MsbFirst {
+        return compress(data, packet_size, robustness, pt_limit, ft_limit, rt_limit);
+    }
+
+    let mut native = data.to_vec();
+    order.apply(&mut native);
+    compress(
+        &native,
+        packet_size,
+        robustness,
+        pt_limit,
+        ft_limit,
+        rt_limit,
+    )
+}
+
+/// Decompress a stream, returning data in the given bit order.
+///
+/// # Arguments
+/// * `data` - Compressed POCKET+ stream
+/// * `order` - Bit order wanted for the output bytes
+/// * `packet_size`, `robustness` - as for [`decompress`](crate::decompress())
+#[cfg(feature = "decoder")]
+pub fn decompress_with_bit_order(
+    data: &[u8],
+    order: BitOrder,
+    packet_size: usize,
+    robustness: usize,
+) -> Result<Vec<u8>, PocketError> {
+    let mut output = decompress(data, packet_size, robustness)?;
+    order.apply(&mut output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_is_involution() {
+        let original = vec![0x01, 0x80, 0xF0, 0x5A];
+        let mut data = original.clone();
+        BitOrder::LsbFirst.apply(&mut data);
+        assert_eq!(data, vec![0x80, 0x01, 0x0F, 0x5A]);
+        BitOrder::LsbFirst.apply(&mut data);
+        assert_eq!(data, original);
+
+        BitOrder::MsbFirst.apply(&mut data);
+        assert_eq!(data, original);
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_lsb_first_round_trip() {
+        let msb: Vec<u8> = (0..40u8)
+            .map(|i| if i % 8 == 0 { i } else { 0x0F })
+            .collect();
+        let mut lsb = msb.clone();
+        BitOrder::LsbFirst.apply(&mut lsb);
+
+        let compressed =
+            compress_with_bit_order(&lsb, BitOrder::LsbFirst, 64, 1, 10, 20, 50).unwrap();
+        // Same stream as compressing the MSB-first equivalent
+        assert_eq!(compressed, compress(&msb, 64, 1, 10, 20, 50).unwrap());
+
+        let restored = decompress_with_bit_order(&compressed, BitOrder::LsbFirst, 64, 1).unwrap();
+        assert_eq!(restored, lsb);
+    }
+}
@@ -10,6 +10,7 @@
 
 #![allow(clippy::cast_possible_truncation)]
 
+use crate::bitvector::{BitVector, WORD_BITS};
 use crate::error::PocketError;
 
 /// Sequential bit reader for parsing compressed data.
@@ -45,6 +46,11 @@ impl<'a> BitReader<'a> {
         self.bit_pos
     }
 
+    /// The source bytes.
+    pub(crate) fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
     /// Get number of remaining bits.
     #[inline]
     pub fn remaining(&self) -> usize {
@@ -95,27 +101,64 @@ impl<'a> BitReader<'a> {
             return Err(PocketError::Underflow);
         }
 
-        // Optimized path: read bytes directly when possible
-        let mut value = 0u32;
-        let mut bits_remaining = num_bits;
-
-        while bits_remaining > 0 {
-            let byte_index = self.bit_pos >> 3;
-            let bit_offset = self.bit_pos & 7;
-            let bits_in_byte = 8 - bit_offset;
-            let bits_to_read = bits_remaining.min(bits_in_byte);
+        Ok(self.read_chunk(num_bits))
+    }
 
-            // Extract bits from current byte (MSB-first)
-            let shift = bits_in_byte - bits_to_read;
-            let mask = ((1u32 << bits_to_read) - 1) as u8;
-            let bits = (self.data[byte_index] >> shift) & mask;
+    /// Read bits into the start of a bit vector.
+    ///
+    /// Bits `0..num_bits` of `bv` are overwritten and the rest are kept.
+    /// Reads 32 bits at a time rather than bit by bit, for uncompressed
+    /// packets and other long literal runs.
+    ///
+    /// # Arguments
+    /// * `bv` - Destination bit vector, at least `num_bits` long
+    /// * `num_bits` - Number of bits to read
+    ///
+    /// # Returns
+    /// Ok(()) on success, or error if not enough bits remain or `bv` is
+    /// too short.
+    pub fn read_into_bitvector(
+        &mut self,
+        bv: &mut BitVector,
+        num_bits: usize,
+    ) -> Result<(), PocketError> {
+        if num_bits > bv.len() {
+            return Err(PocketError::InvalidLength);
+        }
+        if self.remaining() < num_bits {
+            return Err(PocketError::Underflow);
+        }
 
-            value = (value << bits_to_read) | u32::from(bits);
-            self.bit_pos += bits_to_read;
-            bits_remaining -= bits_to_read;
+        // Chunks start at bit 0 or 32 of a word and never straddle two
+        let words = bv.words_mut();
+        let mut pos = 0;
+        while pos < num_bits {
+            let n = (num_bits - pos).min(32);
+            let shift = WORD_BITS - pos % WORD_BITS - n;
+            let mask = ((1u64 << n) - 1) << shift;
+            let word = &mut words[pos / WORD_BITS];
+            *word = (*word & !mask) | (u64::from(self.read_chunk(n)) << shift);
+            pos += n;
         }
 
-        Ok(value)
+        Ok(())
+    }
+
+    /// Read 1-32 bits the caller has checked are available.
+    ///
+    /// Loads the (at most five) bytes holding them at once instead of
+    /// assembling the value byte by byte.
+    #[inline]
+    fn read_chunk(&mut self, num_bits: usize) -> u32 {
+        let start = self.bit_pos >> 3;
+        let end = ((self.bit_pos + num_bits + 7) >> 3).min(self.data.len());
+        let mut bytes = [0u8; 8];
+        bytes[..end - start].copy_from_slice(&self.data[start..end]);
+
+        // Drop the bits before the position, then keep num_bits
+        let value = u64::from_be_bytes(bytes) << (self.bit_pos & 7);
+        self.bit_pos += num_bits;
+        (value >> (64 - num_bits)) as u32
     }
 
     /// Align to next byte boundary.
@@ -146,6 +189,18 @@ impl<'a> BitReader<'a> {
         Ok(bit)
     }
 
+    /// Peek at the next 8 bits without consuming them.
+    ///
+    /// Bits past the end of the data read as 0; check
+    /// [`remaining`](Self::remaining) before using them.
+    #[inline]
+    pub(crate) fn peek_byte(&self) -> u8 {
+        let byte_index = self.bit_pos >> 3;
+        let hi = self.data.get(byte_index).copied().unwrap_or(0);
+        let lo = self.data.get(byte_index + 1).copied().unwrap_or(0);
+        (u16::from_be_bytes([hi, lo]) << (self.bit_pos & 7) >> 8) as u8
+    }
+
     /// Skip a number of bits.
     ///
     /// # Arguments
@@ -219,7 +274,7 @@ mod tests {
         assert_eq!(reader.read_bits(4).unwrap(), 0b1101);
 
         // Read 8 bits: 11101010 = 234
-        assert_eq!(reader.read_bits(8).unwrap(), 0b11101010);
+        assert_eq!(reader.read_bits(8).unwrap(), 0b1110_1010);
 
         // Read 4 bits: 1101 = 13
         assert_eq!(reader.read_bits(4).unwrap(), 0b1101);
@@ -227,6 +282,42 @@ mod tests {
         assert_eq!(reader.remaining(), 0);
     }
 
+    #[test]
+    fn test_read_into_bitvector() {
+        let data: Vec<u8> = (0..20u8).map(|i| i.wrapping_mul(73) ^ 0xC3).collect();
+
+        for offset in [0, 3, 8, 13] {
+            let mut reader = BitReader::new(&data, 160);
+            reader.skip(offset).unwrap();
+            let mut expected = reader.clone();
+
+            // Bits past num_bits keep their value
+            let mut bv = BitVector::from_bytes(&[0xFF; 17], 130);
+            reader.read_into_bitvector(&mut bv, 101).unwrap();
+            assert_eq!(reader.position(), offset + 101);
+            for i in 0..130 {
+                let bit = if i < 101 {
+                    expected.read_bit().unwrap()
+                } else {
+                    1
+                };
+                assert_eq!(bv.get_bit(i), bit, "offset {offset}, bit {i}");
+            }
+        }
+
+        let mut reader = BitReader::new(&data, 40);
+        let mut bv = BitVector::new(64);
+        assert!(matches!(
+            reader.read_into_bitvector(&mut bv, 41),
+            Err(PocketError::Underflow)
+        ));
+        assert!(matches!(
+            reader.read_into_bitvector(&mut bv, 65),
+            Err(PocketError::InvalidLength)
+        ));
+        assert_eq!(reader.position(), 0);
+    }
+
     #[test]
     fn test_read_bits_underflow() {
         let data = vec![0xFF];
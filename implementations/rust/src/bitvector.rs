@@ -1,33 +1,71 @@
-//! Fixed-length bit vector implementation using 32-bit words.
+//! Fixed-length bit vector implementation using 64-bit words.
 //!
 //! This module provides fixed-length bit vector operations optimized for
-//! POCKET+ compression. Uses 32-bit words with big-endian byte packing to
-//! match ESA/ESOC reference implementation.
+//! POCKET+ compression. Uses 64-bit words with big-endian byte packing, so
+//! the byte layout matches the 32-bit words of the ESA/ESOC reference
+//! implementation while XOR/OR, popcount and the RLE/BE scans take half as
+//! many iterations on 64-bit hosts.
 //!
 //! ## Bit Numbering Convention (CCSDS 124.0-B-1 Section 1.6.1)
 //! - Bit 0 = LSB (Least Significant Bit)
 //! - Bit N-1 = MSB (Most Significant Bit, transmitted first)
 //!
 //! ## Word Packing (Big-Endian)
-//! Within each 32-bit word:
-//! - Word\[i\] = (Byte\[4i\] << 24) | (Byte\[4i+1\] << 16) | (Byte\[4i+2\] << 8) | Byte\[4i+3\]
-//! - Bit 0 = LSB of word, Bit 31 = MSB of word
+//! Within each 64-bit word:
+//! - Word\[i\] = (Byte\[8i\] << 56) | (Byte\[8i+1\] << 48) | ... | Byte\[8i+7\]
+//! - Bit 0 = LSB of word, Bit 63 = MSB of word
+//!
+//! ## Mask Files (big-endian)
+//! A converged mask can be saved with [`BitVector::save_mask_file`] and
+//! loaded into the next pass's compressor and decompressor as M₀:
+//!
+//! | Size    | Field                                      |
+//! |---------|--------------------------------------------|
+//! | 4       | Magic `"PKM"` followed by format version 1 |
+//! | 2       | Length F in bits                           |
+//! | ⌈F/8⌉   | Mask bits, as [`BitVector::to_bytes`]      |
+//! | 4       | CRC-32 of all preceding bytes              |
 
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_sign_loss)]
 #![allow(clippy::return_self_not_must_use)]
 
+use crate::buffer::Buffer;
+use crate::crc::crc32;
+use crate::error::PocketError;
+use std::fs;
+use std::path::Path;
+
 /// Maximum packet length in bits (CCSDS max).
 pub const MAX_PACKET_LENGTH: usize = 65535;
 
+/// Maximum packet length in bits in the non-standard extended mode
+/// ([`Compressor::new_extended`](crate::Compressor::new_extended)).
+pub const MAX_EXTENDED_PACKET_LENGTH: usize = 1 << 24;
+
+/// Storage word of a [`BitVector`].
+pub type Word = u64;
+
+/// Bits per storage word.
+pub const WORD_BITS: usize = Word::BITS as usize;
+
+/// Bytes per storage word.
+const WORD_BYTES: usize = WORD_BITS / 8;
+
+/// Mask file magic, followed by the format version.
+const MASK_FILE_MAGIC: [u8; 4] = [b'P', b'K', b'M', 1];
+
+/// Length of the mask file header (magic and length) in bytes.
+const MASK_FILE_HEADER: usize = 6;
+
 /// Fixed-length bit vector structure.
 ///
-/// Stores a binary vector of length F bits using 32-bit words.
+/// Stores a binary vector of length F bits using 64-bit words.
 /// Bit 0 is the LSB, bit F-1 is the MSB.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct BitVector {
-    /// 32-bit word storage (big-endian packing).
-    data: Vec<u32>,
+    /// 64-bit word storage (big-endian packing).
+    data: Buffer<Word>,
     /// Number of bits (F).
     length: usize,
 }
@@ -36,26 +74,55 @@ impl BitVector {
     /// Create a new bit vector with specified length, initialized to zero.
     ///
     /// # Arguments
-    /// * `num_bits` - Number of bits (1 to `MAX_PACKET_LENGTH`)
+    /// * `num_bits` - Number of bits (1 to `MAX_EXTENDED_PACKET_LENGTH`)
     ///
     /// # Returns
     /// A new `BitVector` with all bits set to zero.
     ///
     /// # Panics
-    /// Panics if `num_bits` is 0 or exceeds `MAX_PACKET_LENGTH`.
+    /// Panics if `num_bits` is 0 or exceeds `MAX_EXTENDED_PACKET_LENGTH`.
     pub fn new(num_bits: usize) -> Self {
-        assert!(num_bits > 0 && num_bits <= MAX_PACKET_LENGTH);
+        assert!(num_bits > 0 && num_bits <= MAX_EXTENDED_PACKET_LENGTH);
 
-        // Calculate number of 32-bit words needed
-        let num_bytes = (num_bits + 7) / 8;
-        let num_words = (num_bytes + 3) / 4; // Ceiling division
+        Self {
+            data: vec![0; Self::num_words(num_bits)].into(),
+            length: num_bits,
+        }
+    }
 
+    /// Create a zeroed bit vector in caller-provided words.
+    ///
+    /// # Arguments
+    /// * `words` - Storage of exactly [`num_words`](Self::num_words) words
+    /// * `num_bits` - Number of bits (already validated)
+    #[cfg(feature = "encoder")]
+    pub(crate) fn lent(words: &'static mut [Word], num_bits: usize) -> Self {
+        debug_assert_eq!(words.len(), Self::num_words(num_bits));
+        words.fill(0);
+        let len = words.len();
         Self {
-            data: vec![0u32; num_words],
+            data: Buffer::lent(words, len),
             length: num_bits,
         }
     }
 
+    /// Number of words holding `num_bits` bits.
+    #[inline]
+    pub(crate) const fn num_words(num_bits: usize) -> usize {
+        (num_bits + WORD_BITS - 1) / WORD_BITS
+    }
+
+    /// Clear the padding bits after the last valid bit of the last word.
+    #[inline]
+    fn clear_padding(&mut self) {
+        let tail = self.length % WORD_BITS;
+        if tail != 0 {
+            if let Some(last) = self.data.last_mut() {
+                *last &= !(Word::MAX >> tail);
+            }
+        }
+    }
+
     /// Create a bit vector from raw bytes.
     ///
     /// # Arguments
@@ -65,41 +132,35 @@ impl BitVector {
     /// # Panics
     /// Panics if byte slice is too short for the specified bit count.
     pub fn from_bytes(bytes: &[u8], num_bits: usize) -> Self {
-        assert!(num_bits > 0 && num_bits <= MAX_PACKET_LENGTH);
+        let mut bv = Self::new(num_bits);
+        bv.load_bytes(bytes);
+        bv
+    }
 
-        let expected_bytes = (num_bits + 7) / 8;
+    /// Overwrite the vector with raw bytes, keeping its length.
+    ///
+    /// Same result as [`from_bytes`](Self::from_bytes) without allocating,
+    /// for refilling one input vector packet after packet.
+    ///
+    /// # Panics
+    /// Panics if byte slice is too short for the vector length.
+    pub fn load_bytes(&mut self, bytes: &[u8]) {
+        let expected_bytes = (self.length + 7) / 8;
         assert!(bytes.len() >= expected_bytes);
 
-        let num_words = (expected_bytes + 3) / 4;
-        let mut data = vec![0u32; num_words];
-
-        // Pack bytes into 32-bit words (big-endian)
-        let mut j = 4u32; // Counter for bytes within word (4, 3, 2, 1)
-        let mut bytes_to_int = 0u32;
-        let mut current_word = 0usize;
-
-        for &byte in bytes.iter().take(expected_bytes) {
-            j -= 1;
-            bytes_to_int |= u32::from(byte) << (j * 8);
-
-            if j == 0 {
-                // Word complete - store it
-                data[current_word] = bytes_to_int;
-                current_word += 1;
-                bytes_to_int = 0;
-                j = 4;
-            }
+        // Pack bytes into 64-bit words (big-endian)
+        for (word, chunk) in self
+            .data
+            .iter_mut()
+            .zip(bytes[..expected_bytes].chunks(WORD_BYTES))
+        {
+            let mut be = [0u8; WORD_BYTES];
+            be[..chunk.len()].copy_from_slice(chunk);
+            *word = Word::from_be_bytes(be);
         }
 
-        // Handle incomplete final word
-        if j < 4 {
-            data[current_word] = bytes_to_int;
-        }
-
-        Self {
-            data,
-            length: num_bits,
-        }
+        // Padding bits after the last valid bit stay zero
+        self.clear_padding();
     }
 
     /// Convert bit vector to bytes.
@@ -108,28 +169,17 @@ impl BitVector {
     /// A new `Vec<u8>` containing the bit vector data.
     pub fn to_bytes(&self) -> Vec<u8> {
         let expected_bytes = (self.length + 7) / 8;
-        let mut result = Vec::with_capacity(expected_bytes);
-
-        let mut byte_index = 0usize;
-        for word in &self.data {
-            // Extract up to 4 bytes from this word (big-endian)
-            for j in (0u32..4).rev() {
-                if byte_index >= expected_bytes {
-                    break;
-                }
-                result.push((word >> (j * 8)) as u8);
-                byte_index += 1;
-            }
+        let mut result = Vec::with_capacity(self.data.len() * WORD_BYTES);
+        for word in self.data.iter() {
+            result.extend_from_slice(&word.to_be_bytes());
         }
-
+        result.truncate(expected_bytes);
         result
     }
 
     /// Set all bits to zero.
     pub fn zero(&mut self) {
-        for word in &mut self.data {
-            *word = 0;
-        }
+        self.data.fill(0);
     }
 
     /// Get the length in bits.
@@ -144,14 +194,59 @@ impl BitVector {
         self.length == 0
     }
 
-    /// Get raw access to the underlying 32-bit words.
+    /// Heap memory held by the word storage, in bytes.
+    pub fn heap_size(&self) -> usize {
+        self.data.heap_capacity() * WORD_BYTES
+    }
+
+    /// Heap memory a freshly created vector of `num_bits` bits holds.
+    pub(crate) fn heap_size_for(num_bits: usize) -> usize {
+        Self::num_words(num_bits) * WORD_BYTES
+    }
+
+    /// Get raw access to the underlying 64-bit words.
     ///
     /// Used by encoding functions for efficient word-level operations.
+    /// Bit `pos` is bit `WORD_BITS - 1 - pos % WORD_BITS` of word
+    /// `pos / WORD_BITS`; padding bits after the last valid bit are zero.
     #[inline]
-    pub fn words(&self) -> &[u32] {
+    pub fn words(&self) -> &[Word] {
         &self.data
     }
 
+    /// Mutable access to the underlying 64-bit words.
+    ///
+    /// Callers must leave the padding bits after the last valid bit zero.
+    #[cfg(feature = "decoder")]
+    #[inline]
+    pub(crate) fn words_mut(&mut self) -> &mut [Word] {
+        &mut self.data
+    }
+
+    /// Iterate over the positions of the '1' bits, from bit 0 upwards.
+    ///
+    /// Finds set bits a word at a time (`leading_zeros` forwards,
+    /// `trailing_zeros` backwards), so the cost grows with the Hamming
+    /// weight rather than the length. Reverse with `.rev()` for the
+    /// highest position first, the order of BE (Section 5.2.4).
+    ///
+    /// ```rust
+    /// use pocketplus::BitVector;
+    ///
+    /// let mask = BitVector::from_bytes(&[0x81, 0x40], 10);
+    /// assert_eq!(mask.iter_ones().collect::<Vec<_>>(), [0, 7, 9]);
+    /// assert_eq!(mask.iter_ones().rev().next(), Some(9));
+    /// ```
+    pub fn iter_ones(&self) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        Ones {
+            words: &self.data,
+            front: self.data.first().copied().unwrap_or(0),
+            front_idx: 0,
+            back: self.data.last().copied().unwrap_or(0),
+            back_idx: self.data.len().saturating_sub(1),
+        }
+    }
+
     /// Get bit value at position.
     ///
     /// # Arguments
@@ -166,10 +261,10 @@ impl BitVector {
         }
 
         // Direct bit-to-word mapping (optimized):
-        // word_index = pos / 32, bit_in_word = 31 - (pos % 32)
-        // MSB-first: bit 0 is at position 31 in word 0
-        let word_index = pos >> 5;
-        let bit_in_word = 31 - (pos & 31);
+        // word_index = pos / 64, bit_in_word = 63 - (pos % 64)
+        // MSB-first: bit 0 is at position 63 in word 0
+        let word_index = pos / WORD_BITS;
+        let bit_in_word = WORD_BITS - 1 - pos % WORD_BITS;
 
         ((self.data[word_index] >> bit_in_word) & 1) as u8
     }
@@ -186,10 +281,10 @@ impl BitVector {
         }
 
         // Direct bit-to-word mapping (optimized):
-        // word_index = pos / 32, bit_in_word = 31 - (pos % 32)
-        // MSB-first: bit 0 is at position 31 in word 0
-        let word_index = pos >> 5;
-        let bit_in_word = 31 - (pos & 31);
+        // word_index = pos / 64, bit_in_word = 63 - (pos % 64)
+        // MSB-first: bit 0 is at position 63 in word 0
+        let word_index = pos / WORD_BITS;
+        let bit_in_word = WORD_BITS - 1 - pos % WORD_BITS;
 
         if value != 0 {
             self.data[word_index] |= 1 << bit_in_word;
@@ -198,6 +293,57 @@ impl BitVector {
         }
     }
 
+    /// Number of bytes covering the vector (⌈F/8⌉).
+    #[inline]
+    pub fn num_bytes(&self) -> usize {
+        (self.length + 7) / 8
+    }
+
+    /// Get the byte at index `i`, as it would appear in [`to_bytes`](Self::to_bytes).
+    ///
+    /// # Arguments
+    /// * `i` - Byte index (0 = first transmitted byte)
+    ///
+    /// # Returns
+    /// The byte value, or 0 if the index is out of bounds.
+    #[inline]
+    pub fn get_byte(&self, i: usize) -> u8 {
+        if i >= self.num_bytes() {
+            return 0;
+        }
+        (self.data[i / WORD_BYTES] >> ((WORD_BYTES - 1 - i % WORD_BYTES) * 8)) as u8
+    }
+
+    /// Set the byte at index `i`.
+    ///
+    /// Bits beyond the vector length in a partial last byte are kept zero.
+    /// Out-of-bounds indices are ignored, like [`set_bit`](Self::set_bit).
+    ///
+    /// # Arguments
+    /// * `i` - Byte index (0 = first transmitted byte)
+    /// * `value` - Byte value
+    #[inline]
+    pub fn set_byte(&mut self, i: usize, value: u8) {
+        if i >= self.num_bytes() {
+            return;
+        }
+
+        let valid_bits = (self.length - i * 8).min(8);
+        let value = value & (0xFFu8 << (8 - valid_bits));
+        let shift = (WORD_BYTES - 1 - i % WORD_BYTES) * 8;
+        let word = &mut self.data[i / WORD_BYTES];
+        *word = (*word & !(0xFF << shift)) | (Word::from(value) << shift);
+    }
+
+    /// Iterate over the storage as 8-byte chunks, one per 64-bit word.
+    ///
+    /// Chunks are in transmission order without copying the vector; the
+    /// last chunk is zero-padded when ⌈F/8⌉ is not a multiple of 8. Use
+    /// `.flatten().take(bv.num_bytes())` for the exact byte sequence.
+    pub fn as_byte_chunks(&self) -> impl ExactSizeIterator<Item = [u8; WORD_BYTES]> + '_ {
+        self.data.iter().map(|word| word.to_be_bytes())
+    }
+
     /// Bitwise XOR of two bit vectors.
     ///
     /// # Arguments
@@ -208,11 +354,8 @@ impl BitVector {
     pub fn xor(&self, other: &Self) -> Self {
         let num_words = self.data.len().min(other.data.len());
         let mut result = Self::new(self.length);
-
-        for i in 0..num_words {
-            result.data[i] = self.data[i] ^ other.data[i];
-        }
-
+        result.data[..num_words].copy_from_slice(&self.data[..num_words]);
+        zip_words(&mut result.data, &other.data, |a, b| a ^ b);
         result
     }
 
@@ -226,11 +369,8 @@ impl BitVector {
     pub fn or(&self, other: &Self) -> Self {
         let num_words = self.data.len().min(other.data.len());
         let mut result = Self::new(self.length);
-
-        for i in 0..num_words {
-            result.data[i] = self.data[i] | other.data[i];
-        }
-
+        result.data[..num_words].copy_from_slice(&self.data[..num_words]);
+        zip_words(&mut result.data, &other.data, |a, b| a | b);
         result
     }
 
@@ -240,10 +380,28 @@ impl BitVector {
     /// * `other` - Other bit vector (must have same length)
     #[inline]
     pub fn or_assign(&mut self, other: &Self) {
-        let num_words = self.data.len().min(other.data.len());
-        for i in 0..num_words {
-            self.data[i] |= other.data[i];
-        }
+        zip_words(&mut self.data, &other.data, |a, b| a | b);
+    }
+
+    /// In-place bitwise XOR with another bit vector.
+    ///
+    /// # Arguments
+    /// * `other` - Other bit vector (must have same length)
+    #[inline]
+    pub fn xor_assign(&mut self, other: &Self) {
+        zip_words(&mut self.data, &other.data, |a, b| a ^ b);
+    }
+
+    /// In-place OR with the XOR of two bit vectors: `self |= a ^ b`.
+    ///
+    /// The mask and build updates (Equations 6 and 7) without a temporary
+    /// change vector.
+    ///
+    /// # Arguments
+    /// * `a`, `b` - Bit vectors (must have same length)
+    #[inline]
+    pub fn or_xor_assign(&mut self, a: &Self, b: &Self) {
+        or_xor_words(&mut self.data, &a.data, &b.data);
     }
 
     /// Bitwise AND of two bit vectors.
@@ -256,11 +414,8 @@ impl BitVector {
     pub fn and(&self, other: &Self) -> Self {
         let num_words = self.data.len().min(other.data.len());
         let mut result = Self::new(self.length);
-
-        for i in 0..num_words {
-            result.data[i] = self.data[i] & other.data[i];
-        }
-
+        result.data[..num_words].copy_from_slice(&self.data[..num_words]);
+        zip_words(&mut result.data, &other.data, |a, b| a & b);
         result
     }
 
@@ -270,37 +425,25 @@ impl BitVector {
     /// A new `BitVector` containing the NOT result.
     pub fn not(&self) -> Self {
         let mut result = Self::new(self.length);
+        self.not_into(&mut result);
+        result
+    }
 
-        for i in 0..self.data.len() {
-            result.data[i] = !self.data[i];
-        }
+    /// Bitwise NOT into an existing vector of the same length.
+    ///
+    /// Same result as [`not`](Self::not) without allocating.
+    ///
+    /// # Arguments
+    /// * `out` - Destination (overwritten)
+    pub fn not_into(&self, out: &mut Self) {
+        out.copy_from(self);
 
-        // Mask off unused bits in last word with big-endian packing
-        if !self.data.is_empty() {
-            let num_bytes = (self.length + 7) / 8;
-            let bytes_in_last_word = ((num_bytes - 1) % 4) + 1;
-            let bits_in_last_byte = self.length - ((num_bytes - 1) * 8);
-
-            // Create mask for valid bits in big-endian word
-            let mut mask = 0u32;
-            for byte in 0..bytes_in_last_word {
-                let byte_mask: u8 = if byte == bytes_in_last_word - 1 {
-                    // Handle case where bits_in_last_byte is 8 (full byte)
-                    if bits_in_last_byte >= 8 {
-                        0xFF
-                    } else {
-                        ((1u32 << bits_in_last_byte) - 1) as u8
-                    }
-                } else {
-                    0xFF
-                };
-                let shift_amt = (3 - byte as u32) * 8;
-                mask |= u32::from(byte_mask) << shift_amt;
-            }
-            result.data[self.data.len() - 1] &= mask;
+        for word in out.data.iter_mut() {
+            *word = !*word;
         }
 
-        result
+        // Padding bits after the last valid bit stay zero
+        out.clear_padding();
     }
 
     /// Left shift the bit vector by 1 position.
@@ -312,18 +455,28 @@ impl BitVector {
     /// A new `BitVector` containing the shifted result.
     pub fn left_shift(&self) -> Self {
         let mut result = Self::new(self.length);
+        self.left_shift_into(&mut result);
+        result
+    }
+
+    /// Left shift by 1 position into an existing vector of the same length.
+    ///
+    /// Same result as [`left_shift`](Self::left_shift) without allocating.
+    ///
+    /// # Arguments
+    /// * `out` - Destination (overwritten)
+    pub fn left_shift_into(&self, out: &mut Self) {
+        out.copy_from(self);
 
         if !self.data.is_empty() {
             // Process words from first (MSB) to last (LSB)
             for i in 0..self.data.len() - 1 {
                 // Shift current word left by 1, bring in MSB from next word
-                result.data[i] = (self.data[i] << 1) | (self.data[i + 1] >> 31);
+                out.data[i] = (self.data[i] << 1) | (self.data[i + 1] >> (WORD_BITS - 1));
             }
             // Last word: shift left, LSB becomes 0
-            result.data[self.data.len() - 1] = self.data[self.data.len() - 1] << 1;
+            out.data[self.data.len() - 1] = self.data[self.data.len() - 1] << 1;
         }
-
-        result
     }
 
     /// Calculate the Hamming weight (number of 1 bits).
@@ -331,27 +484,47 @@ impl BitVector {
     /// # Returns
     /// Count of bits set to 1.
     pub fn hamming_weight(&self) -> usize {
-        let mut count = 0usize;
-
         // Count '1' bits in each word using popcount
-        for &word in &self.data {
-            count += word.count_ones() as usize;
-        }
+        let mut count = count_ones(&self.data);
 
-        // Adjust for any extra bits in last word
-        let num_bytes = (self.length + 7) / 8;
-        let extra_bits = (num_bytes * 8) - self.length;
-        if extra_bits > 0 && !self.data.is_empty() {
-            // Count bits in the unused portion of the last word and subtract
-            let last_word = self.data[self.data.len() - 1];
-            let mask = (1u32 << extra_bits) - 1; // Mask for the unused LSBs
-            let extra_word = last_word & mask;
-            count -= extra_word.count_ones() as usize;
+        // Adjust for any padding bits in the last word
+        let tail = self.length % WORD_BITS;
+        if tail != 0 {
+            if let Some(&last) = self.data.last() {
+                count -= (last & (Word::MAX >> tail)).count_ones() as usize;
+            }
         }
 
         count
     }
 
+    /// Returns true if no bit is set.
+    ///
+    /// Unlike comparing [`hamming_weight`](Self::hamming_weight) with zero,
+    /// stops at the first non-zero word.
+    pub fn is_zero(&self) -> bool {
+        let Some((&last, rest)) = self.data.split_last() else {
+            return true;
+        };
+        let tail = self.length % WORD_BITS;
+        let last = if tail == 0 {
+            last
+        } else {
+            last & !(Word::MAX >> tail)
+        };
+        last == 0 && rest.iter().all(|&word| word == 0)
+    }
+
+    /// Returns true if the two vectors share a '1' bit.
+    ///
+    /// # Arguments
+    /// * `other` - Other bit vector (must have same length)
+    #[cfg(feature = "encoder")]
+    #[inline]
+    pub(crate) fn intersects(&self, other: &Self) -> bool {
+        intersects(&self.data, &other.data)
+    }
+
     /// Reverse the bit order.
     ///
     /// # Returns
@@ -383,6 +556,170 @@ impl BitVector {
         }
         self.length = other.length;
     }
+
+    /// Serialize the vector in the mask file format.
+    ///
+    /// # Returns
+    /// The mask file contents.
+    pub fn to_mask_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MASK_FILE_HEADER + self.num_bytes() + 4);
+        out.extend_from_slice(&MASK_FILE_MAGIC);
+        out.extend_from_slice(&(self.length as u16).to_be_bytes());
+        out.extend(self.to_bytes());
+        let crc = crc32(&out);
+        out.extend_from_slice(&crc.to_be_bytes());
+        out
+    }
+
+    /// Parse a vector from the mask file format.
+    ///
+    /// # Arguments
+    /// * `data` - Mask file contents
+    ///
+    /// # Returns
+    /// The mask, or [`PocketError::InvalidFormat`] if the contents are not
+    /// a valid mask file.
+    pub fn from_mask_bytes(data: &[u8]) -> Result<Self, PocketError> {
+        if data.len() < MASK_FILE_HEADER + 4 || data[..4] != MASK_FILE_MAGIC {
+            return Err(PocketError::InvalidFormat("not a mask file".into()));
+        }
+        let length = usize::from(u16::from_be_bytes([data[4], data[5]]));
+        if length == 0 || data.len() != MASK_FILE_HEADER + (length + 7) / 8 + 4 {
+            return Err(PocketError::InvalidFormat(format!(
+                "mask file length does not match {length} bits"
+            )));
+        }
+        let (body, crc) = data.split_at(data.len() - 4);
+        if crc32(body).to_be_bytes() != crc {
+            return Err(PocketError::InvalidFormat(
+                "mask file checksum mismatch".into(),
+            ));
+        }
+        let bits = &body[MASK_FILE_HEADER..];
+        if length % 8 != 0 && bits[bits.len() - 1] << (length % 8) != 0 {
+            return Err(PocketError::InvalidFormat(
+                "mask file has bits set past its length".into(),
+            ));
+        }
+        Ok(Self::from_bytes(bits, length))
+    }
+
+    /// Save the vector to a mask file.
+    ///
+    /// # Arguments
+    /// * `path` - File to create or overwrite
+    pub fn save_mask_file(&self, path: impl AsRef<Path>) -> Result<(), PocketError> {
+        fs::write(path, self.to_mask_bytes())?;
+        Ok(())
+    }
+
+    /// Load a vector from a mask file.
+    ///
+    /// # Arguments
+    /// * `path` - File written by [`save_mask_file`](Self::save_mask_file)
+    ///
+    /// # Returns
+    /// The mask, or an error if the file cannot be read or is invalid.
+    pub fn load_mask_file(path: impl AsRef<Path>) -> Result<Self, PocketError> {
+        Self::from_mask_bytes(&fs::read(path)?)
+    }
+}
+
+/// Iterator over the set bit positions of a [`BitVector`].
+///
+/// `front` and `back` hold the bits not yet returned of words `front_idx`
+/// and `back_idx`; once both ends reach the same word they are kept equal.
+struct Ones<'a> {
+    words: &'a [Word],
+    front: Word,
+    front_idx: usize,
+    back: Word,
+    back_idx: usize,
+}
+
+impl Iterator for Ones<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.front == 0 {
+            if self.front_idx >= self.back_idx {
+                return None;
+            }
+            self.front_idx += 1;
+            self.front = if self.front_idx == self.back_idx {
+                self.back
+            } else {
+                self.words[self.front_idx]
+            };
+        }
+        let clz = self.front.leading_zeros() as usize;
+        self.front &= !(1 << (WORD_BITS - 1 - clz));
+        if self.front_idx == self.back_idx {
+            self.back = self.front;
+        }
+        Some(self.front_idx * WORD_BITS + clz)
+    }
+}
+
+impl DoubleEndedIterator for Ones<'_> {
+    fn next_back(&mut self) -> Option<usize> {
+        while self.back == 0 {
+            if self.back_idx <= self.front_idx {
+                return None;
+            }
+            self.back_idx -= 1;
+            self.back = if self.back_idx == self.front_idx {
+                self.front
+            } else {
+                self.words[self.back_idx]
+            };
+        }
+        let ctz = self.back.trailing_zeros() as usize;
+        self.back &= self.back - 1;
+        if self.back_idx == self.front_idx {
+            self.front = self.back;
+        }
+        Some(self.back_idx * WORD_BITS + WORD_BITS - 1 - ctz)
+    }
+}
+
+// Word loops, four words per step with the `simd` feature
+
+#[cfg(all(feature = "simd", feature = "encoder"))]
+use crate::simd::intersects;
+#[cfg(feature = "simd")]
+use crate::simd::{count_ones, or_xor_words, zip_words};
+
+/// `dst[i] = op(dst[i], src[i])` over the common words.
+#[cfg(not(feature = "simd"))]
+#[inline]
+fn zip_words(dst: &mut [Word], src: &[Word], op: impl Fn(Word, Word) -> Word) {
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = op(*d, s);
+    }
+}
+
+/// `dst[i] |= a[i] ^ b[i]` over the common words.
+#[cfg(not(feature = "simd"))]
+#[inline]
+fn or_xor_words(dst: &mut [Word], a: &[Word], b: &[Word]) {
+    for ((d, &a), &b) in dst.iter_mut().zip(a).zip(b) {
+        *d |= a ^ b;
+    }
+}
+
+/// Number of '1' bits in `words`.
+#[cfg(not(feature = "simd"))]
+#[inline]
+fn count_ones(words: &[Word]) -> usize {
+    words.iter().map(|w| w.count_ones() as usize).sum()
+}
+
+/// True if `a[i] & b[i]` is non-zero for any common word.
+#[cfg(all(not(feature = "simd"), feature = "encoder"))]
+#[inline]
+fn intersects(a: &[Word], b: &[Word]) -> bool {
+    a.iter().zip(b).any(|(a, b)| a & b != 0)
 }
 
 #[cfg(test)]
@@ -422,6 +759,17 @@ mod tests {
         assert_eq!(result, original);
     }
 
+    #[test]
+    fn test_load_bytes() {
+        let mut bv = BitVector::from_bytes(&[0xFF; 7], 52);
+        bv.load_bytes(&[0xDE, 0xAD, 0xBE, 0xEF, 0xCA, 0xFE, 0x10]);
+        assert_eq!(
+            bv,
+            BitVector::from_bytes(&[0xDE, 0xAD, 0xBE, 0xEF, 0xCA, 0xFE, 0x10], 52)
+        );
+        assert_eq!(bv.len(), 52);
+    }
+
     #[test]
     fn test_xor() {
         let mut a = BitVector::new(32);
@@ -481,6 +829,16 @@ mod tests {
         assert_eq!(result.get_bit(3), 1);
     }
 
+    #[test]
+    fn test_not_partial_byte() {
+        // 693 bits: the last byte holds 5 valid bits
+        let bv = BitVector::new(693);
+        let result = bv.not();
+        assert_eq!(result.hamming_weight(), 693);
+        assert_eq!(result.get_bit(692), 1);
+        assert_eq!(*result.to_bytes().last().unwrap(), 0xF8);
+    }
+
     #[test]
     fn test_left_shift() {
         let mut bv = BitVector::new(32);
@@ -491,6 +849,26 @@ mod tests {
         assert_eq!(result.get_bit(1), 0);
     }
 
+    #[test]
+    fn test_in_place_ops() {
+        let a = BitVector::from_bytes(&[0xA5, 0x3C, 0xF0], 20);
+        let b = BitVector::from_bytes(&[0x0F, 0x81, 0x30], 20);
+        let mut out = BitVector::from_bytes(&[0xFF, 0xFF, 0xFF], 20);
+
+        a.not_into(&mut out);
+        assert_eq!(out, a.not());
+        a.left_shift_into(&mut out);
+        assert_eq!(out, a.left_shift());
+
+        out.copy_from(&a);
+        out.xor_assign(&b);
+        assert_eq!(out, a.xor(&b));
+
+        let mut acc = b.clone();
+        acc.or_xor_assign(&a, &b.not());
+        assert_eq!(acc, b.or(&a.xor(&b.not())));
+    }
+
     #[test]
     fn test_hamming_weight() {
         let mut bv = BitVector::new(32);
@@ -502,6 +880,20 @@ mod tests {
         assert_eq!(bv.hamming_weight(), 3);
     }
 
+    #[test]
+    fn test_is_zero() {
+        for len in [1, 63, 64, 65, 720] {
+            let mut bv = BitVector::new(len);
+            assert!(bv.is_zero(), "len {len}");
+
+            bv.set_bit(len - 1, 1);
+            assert!(!bv.is_zero(), "len {len}");
+            bv.set_bit(len - 1, 0);
+            bv.set_bit(0, 1);
+            assert!(!bv.is_zero(), "len {len}");
+        }
+    }
+
     #[test]
     fn test_zero() {
         let mut bv = BitVector::new(32);
@@ -567,4 +959,133 @@ mod tests {
         assert_eq!(bv.len(), 0);
         assert!(bv.is_empty());
     }
+
+    #[test]
+    fn test_heap_size() {
+        for bits in [1, 8, 32, 33, 720, 65535] {
+            assert_eq!(
+                BitVector::new(bits).heap_size(),
+                BitVector::heap_size_for(bits)
+            );
+        }
+        assert_eq!(BitVector::heap_size_for(720), 96);
+        assert_eq!(BitVector::heap_size_for(64), 8);
+        assert_eq!(BitVector::heap_size_for(65), 16);
+    }
+
+    #[test]
+    fn test_byte_views() {
+        let bytes = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC];
+        let mut bv = BitVector::from_bytes(&bytes, 48);
+
+        for (i, &b) in bytes.iter().enumerate() {
+            assert_eq!(bv.get_byte(i), b);
+        }
+        assert_eq!(bv.get_byte(6), 0);
+
+        bv.set_byte(4, 0xFF);
+        bv.set_byte(9, 0xFF); // Ignored
+        assert_eq!(bv.to_bytes(), vec![0x12, 0x34, 0x56, 0x78, 0xFF, 0xBC]);
+
+        let chunks: Vec<[u8; 8]> = bv.as_byte_chunks().collect();
+        assert_eq!(chunks, vec![[0x12, 0x34, 0x56, 0x78, 0xFF, 0xBC, 0, 0]]);
+        let flat: Vec<u8> = bv.as_byte_chunks().flatten().take(bv.num_bytes()).collect();
+        assert_eq!(flat, bv.to_bytes());
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        // Lengths around one and two words keep the byte layout and padding
+        for bits in [63, 64, 65, 127, 129] {
+            let bytes: Vec<u8> = (0..(bits + 7) / 8).map(|i| 0xA5 ^ i as u8).collect();
+            let bv = BitVector::from_bytes(&bytes, bits);
+            let mut expected = bytes.clone();
+            if bits % 8 != 0 {
+                *expected.last_mut().unwrap() &= 0xFF << (8 - bits % 8);
+            }
+            assert_eq!(bv.to_bytes(), expected, "{bits} bits");
+
+            let weight: usize = expected.iter().map(|b| b.count_ones() as usize).sum();
+            assert_eq!(bv.hamming_weight(), weight);
+            assert_eq!(bv.not().hamming_weight(), bits - weight);
+
+            // Bit 63 moves across the word boundary into bit 62
+            let shifted = bv.left_shift();
+            for pos in 0..bits - 1 {
+                assert_eq!(shifted.get_bit(pos), bv.get_bit(pos + 1));
+            }
+            assert_eq!(shifted.get_bit(bits - 1), 0);
+        }
+    }
+
+    #[test]
+    fn test_iter_ones() {
+        let mut bv = BitVector::new(200);
+        let positions = [0, 5, 63, 64, 100, 127, 128, 129, 199];
+        for &pos in &positions {
+            bv.set_bit(pos, 1);
+        }
+        assert_eq!(bv.iter_ones().collect::<Vec<_>>(), positions);
+        let mut reversed: Vec<usize> = bv.iter_ones().rev().collect();
+        reversed.reverse();
+        assert_eq!(reversed, positions);
+        assert_eq!(bv.not().iter_ones().count(), 200 - positions.len());
+
+        // Both ends meet without repeating or skipping a position
+        for split in 0..=positions.len() {
+            let mut iter = bv.iter_ones();
+            let mut seen: Vec<usize> = iter.by_ref().take(split).collect();
+            let mut back: Vec<usize> = iter.rev().collect();
+            back.reverse();
+            seen.extend(back);
+            assert_eq!(seen, positions, "split {split}");
+        }
+        let mut iter = bv.iter_ones();
+        assert_eq!(
+            (iter.next(), iter.next_back(), iter.next(), iter.next_back()),
+            (Some(0), Some(199), Some(5), Some(129))
+        );
+
+        assert_eq!(BitVector::new(64).iter_ones().next(), None);
+        assert_eq!(BitVector::default().iter_ones().next_back(), None);
+    }
+
+    #[test]
+    fn test_set_byte_partial() {
+        // 12 bits: the second byte has 4 valid bits
+        let mut bv = BitVector::new(12);
+        bv.set_byte(1, 0xFF);
+        assert_eq!(bv.get_byte(1), 0xF0);
+        assert_eq!(bv.hamming_weight(), 4);
+    }
+
+    #[test]
+    fn test_mask_file() {
+        let mut mask = BitVector::new(12);
+        mask.set_bit(0, 1);
+        mask.set_bit(11, 1);
+        let bytes = mask.to_mask_bytes();
+        assert_eq!(&bytes[..8], &[b'P', b'K', b'M', 1, 0, 12, 0x80, 0x10]);
+        assert_eq!(BitVector::from_mask_bytes(&bytes).unwrap(), mask);
+
+        // Truncation, corruption and stray padding bits are rejected
+        assert!(BitVector::from_mask_bytes(&bytes[..bytes.len() - 1]).is_err());
+        for bit in 0..bytes.len() * 8 {
+            let mut corrupted = bytes.clone();
+            corrupted[bit / 8] ^= 0x80 >> (bit % 8);
+            assert!(BitVector::from_mask_bytes(&corrupted).is_err(), "bit {bit}");
+        }
+        let mut padded = bytes[..8].to_vec();
+        padded[7] |= 0x01;
+        padded.extend_from_slice(&crc32(&padded).to_be_bytes());
+        assert!(BitVector::from_mask_bytes(&padded).is_err());
+
+        let path =
+            std::env::temp_dir().join(format!("pocketplus-mask-{}.mask", std::process::id()));
+        mask.save_mask_file(&path).unwrap();
+        let loaded = BitVector::load_mask_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), mask);
+        assert!(BitVector::load_mask_file(&path).is_err());
+    }
 }
@@ -0,0 +1,182 @@
+//! Backing storage for bit vectors and bit buffers.
+//!
+//! A [`Buffer`] is either an ordinary heap vector or a slice lent by the
+//! caller for the rest of the program, e.g. a `static` array reserved at
+//! initialization (see [`Workspace::from_buffers`](crate::Workspace::from_buffers)).
+//! Both behave like a vector of the used elements; a lent buffer that runs
+//! out of room moves its contents to the heap rather than failing.
+
+// Only the encoder lends storage
+#![cfg_attr(not(feature = "encoder"), allow(dead_code))]
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// Vector-like storage, on the heap or in a lent slice.
+pub(crate) enum Buffer<T: 'static> {
+    /// Heap-allocated vector.
+    Heap(Vec<T>),
+    /// Caller-provided slice and the number of elements in use.
+    Lent(&'static mut [T], usize),
+}
+
+impl<T: Copy> Buffer<T> {
+    /// Wrap a lent slice holding `len` elements in use.
+    pub(crate) fn lent(slice: &'static mut [T], len: usize) -> Self {
+        debug_assert!(len <= slice.len());
+        Self::Lent(slice, len)
+    }
+
+    /// Heap memory held by the buffer, in elements (0 for a lent slice).
+    pub(crate) fn heap_capacity(&self) -> usize {
+        match self {
+            Self::Heap(vec) => vec.capacity(),
+            Self::Lent(..) => 0,
+        }
+    }
+
+    /// Append one element.
+    #[inline]
+    pub(crate) fn push(&mut self, value: T) {
+        match self {
+            Self::Heap(vec) => vec.push(value),
+            Self::Lent(slice, len) if *len < slice.len() => {
+                slice[*len] = value;
+                *len += 1;
+            }
+            Self::Lent(..) => self.spill(1).push(value),
+        }
+    }
+
+    /// Set the number of elements, filling new ones with `value`.
+    pub(crate) fn resize(&mut self, new_len: usize, value: T) {
+        match self {
+            Self::Heap(vec) => vec.resize(new_len, value),
+            Self::Lent(slice, len) if new_len <= slice.len() => {
+                if new_len > *len {
+                    slice[*len..new_len].fill(value);
+                }
+                *len = new_len;
+            }
+            Self::Lent(..) => self.spill(new_len).resize(new_len, value),
+        }
+    }
+
+    /// Remove all elements, keeping the storage.
+    pub(crate) fn clear(&mut self) {
+        match self {
+            Self::Heap(vec) => vec.clear(),
+            Self::Lent(_, len) => *len = 0,
+        }
+    }
+
+    /// The elements in use as a vector (copied from a lent slice).
+    pub(crate) fn into_vec(self) -> Vec<T> {
+        match self {
+            Self::Heap(vec) => vec,
+            Self::Lent(slice, len) => slice[..len].to_vec(),
+        }
+    }
+
+    /// Move the elements to the heap with room for `additional` more.
+    #[cold]
+    fn spill(&mut self, additional: usize) -> &mut Vec<T> {
+        let mut vec = Vec::with_capacity(self.len() + additional);
+        vec.extend_from_slice(self);
+        *self = Self::Heap(vec);
+        match self {
+            Self::Heap(vec) => vec,
+            Self::Lent(..) => unreachable!(),
+        }
+    }
+}
+
+impl<T> Deref for Buffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        match self {
+            Self::Heap(vec) => vec,
+            Self::Lent(slice, len) => &slice[..*len],
+        }
+    }
+}
+
+impl<T> DerefMut for Buffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self {
+            Self::Heap(vec) => vec,
+            Self::Lent(slice, len) => &mut slice[..*len],
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for Buffer<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Self::Heap(vec)
+    }
+}
+
+impl<T> Default for Buffer<T> {
+    fn default() -> Self {
+        Self::Heap(Vec::new())
+    }
+}
+
+/// Cloning copies the elements to the heap; a lent slice is never shared.
+impl<T: Clone> Clone for Buffer<T> {
+    fn clone(&self) -> Self {
+        Self::Heap(self.to_vec())
+    }
+}
+
+impl<T: PartialEq> PartialEq for Buffer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for Buffer<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for Buffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lent_buffer() {
+        // The library forbids unsafe code, so the lent slices are leaked
+        // heap memory rather than statics (see tests/allocations.rs)
+        let slice: &'static mut [u8] = Box::leak(vec![0xFF; 4].into_boxed_slice());
+        let mut buffer = Buffer::lent(slice, 0);
+        for value in 1..=3 {
+            buffer.push(value);
+        }
+        assert_eq!(&*buffer, [1, 2, 3]);
+        assert_eq!(buffer.heap_capacity(), 0);
+
+        buffer.resize(4, 0);
+        assert_eq!(&*buffer, [1, 2, 3, 0]);
+        assert!(matches!(buffer, Buffer::Lent(..)));
+        assert_eq!(buffer.clone(), Buffer::from(vec![1, 2, 3, 0]));
+
+        // Past the lent slice the contents move to the heap
+        buffer.push(5);
+        assert!(matches!(buffer, Buffer::Heap(_)));
+        assert_eq!(buffer.into_vec(), [1, 2, 3, 0, 5]);
+
+        let slice: &'static mut [u8] = Box::leak(vec![7; 2].into_boxed_slice());
+        let mut buffer = Buffer::lent(slice, 2);
+        buffer.clear();
+        assert!(buffer.is_empty());
+        buffer.resize(3, 1);
+        assert_eq!(buffer.into_vec(), [1, 1, 1]);
+    }
+}
@@ -0,0 +1,160 @@
+//! Matched compressor/decompressor pair for self-validation.
+//!
+//! A [`Codec`] runs every packet through a [`Compressor`] and straight
+//! back through a [`Decompressor`] configured to match, so a test bench
+//! can confirm that a parameter set round-trips its packet layouts
+//! without assembling the plumbing itself:
+//!
+//! ```rust
+//! use pocketplus::{Codec, CompressorBuilder};
+//!
+//! let mut codec = Codec::new(&CompressorBuilder::new(64).robustness(2)).unwrap();
+//! for n in 0u8..100 {
+//!     assert!(codec.roundtrip(&[0xCA, 0xFE, 0, 0, n, n / 3, 0, 1]).unwrap());
+//! }
+//! ```
+
+use crate::bitreader::BitReader;
+use crate::bitvector::BitVector;
+use crate::compress::{Compressor, CompressorBuilder};
+use crate::decompress::{Decompressor, DecompressorBuilder};
+use crate::error::PocketError;
+
+/// Compressor and decompressor sharing one stream configuration.
+#[derive(Clone)]
+pub struct Codec {
+    comp: Compressor,
+    decomp: Decompressor,
+    /// Packets round-tripped since creation or the last reset.
+    packets: usize,
+    /// Compressed bytes produced for them.
+    compressed_bytes: usize,
+}
+
+impl Codec {
+    /// Create a matched pair.
+    ///
+    /// # Arguments
+    /// * `options` - Compressor settings; the decompressor gets the same
+    ///   packet size, robustness and initial mask. Packets are checked one
+    ///   at a time, so a stream checksum is rejected.
+    pub fn new(options: &CompressorBuilder) -> Result<Self, PocketError> {
+        options.options.reject_stream_checksum("Codec")?;
+        let comp = options.build()?;
+        let decomp = DecompressorBuilder::new(comp.packet_size())
+            .robustness(comp.robustness() as usize)
+            .initial_mask(comp.initial_mask())
+            .build()?;
+        Ok(Self {
+            comp,
+            decomp,
+            packets: 0,
+            compressed_bytes: 0,
+        })
+    }
+
+    /// Compress one packet, decompress the result and compare.
+    ///
+    /// # Arguments
+    /// * `packet` - Packet bytes (F bits, zero-padded to a whole byte)
+    ///
+    /// # Returns
+    /// `true` if the decompressed packet equals the input and the decoder
+    /// consumed exactly the compressed packet. Errors from either side are
+    /// returned as they are.
+    pub fn roundtrip(&mut self, packet: &[u8]) -> Result<bool, PocketError> {
+        let f = self.comp.packet_size();
+        let packet_bytes = (f + 7) / 8;
+        if packet.len() != packet_bytes {
+            return Err(PocketError::InvalidInputLength {
+                expected: packet_bytes,
+                actual: packet.len(),
+            });
+        }
+
+        let input = BitVector::from_bytes(packet, f);
+        let params = self.comp.next_params();
+        let compressed = self.comp.compress_packet(&input, &params)?.into_bytes();
+
+        let mut reader = BitReader::new(&compressed, compressed.len() * 8);
+        let output = self.decomp.decompress_packet(&mut reader)?;
+        reader.align_byte();
+
+        self.packets += 1;
+        self.compressed_bytes += compressed.len();
+        Ok(output == input && reader.remaining() == 0)
+    }
+
+    /// Number of packets round-tripped.
+    pub fn packets(&self) -> usize {
+        self.packets
+    }
+
+    /// Compressed size of the packets round-tripped, in bytes.
+    pub fn compressed_bytes(&self) -> usize {
+        self.compressed_bytes
+    }
+
+    /// The compressor.
+    pub fn compressor(&self) -> &Compressor {
+        &self.comp
+    }
+
+    /// The decompressor.
+    pub fn decompressor(&self) -> &Decompressor {
+        &self.decomp
+    }
+
+    /// Reset both sides to the start of a stream.
+    pub fn reset(&mut self) {
+        self.comp.reset();
+        self.decomp.reset();
+        self.packets = 0;
+        self.compressed_bytes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synth::{generate, Profile};
+
+    #[test]
+    fn test_codec_roundtrip() {
+        let profile = Profile::housekeeping();
+        let bytes = profile.packet_bytes();
+        let data = generate(&profile, 120, 3);
+
+        for robustness in [0, 1, 4] {
+            let options = CompressorBuilder::new(bytes * 8).robustness(robustness);
+            let mut codec = Codec::new(&options).unwrap();
+            for packet in data.chunks(bytes) {
+                assert!(codec.roundtrip(packet).unwrap());
+            }
+            assert_eq!(codec.packets(), 120);
+            assert!(codec.compressed_bytes() < data.len());
+
+            codec.reset();
+            assert_eq!(codec.packets(), 0);
+        }
+
+        let mut codec = Codec::new(&CompressorBuilder::new(64)).unwrap();
+        assert!(matches!(
+            codec.roundtrip(&[0; 7]),
+            Err(PocketError::InvalidInputLength { .. })
+        ));
+        assert!(Codec::new(&CompressorBuilder::new(64).robustness(9)).is_err());
+        assert!(Codec::new(&CompressorBuilder::new(64).stream_checksum(true)).is_err());
+    }
+
+    #[test]
+    fn test_codec_detects_mismatch() {
+        // Decompressor out of step with the compressor
+        let mut codec = Codec::new(&CompressorBuilder::new(64).robustness(0)).unwrap();
+        for n in 0u8..5 {
+            assert!(codec.roundtrip(&[1, 2, 3, 4, 5, 6, 7, n]).unwrap());
+        }
+        codec.decomp.reset();
+        assert!(!codec.roundtrip(&[1, 2, 3, 4, 5, 6, 7, 9]).unwrap());
+    }
+}
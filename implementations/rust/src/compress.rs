@@ -7,22 +7,42 @@
 
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_possible_wrap)]
+#![allow(clippy::cast_precision_loss)]
 #![allow(clippy::too_many_lines)]
 
 use crate::bitbuffer::BitBuffer;
-use crate::bitvector::BitVector;
-use crate::encode::{bit_extract, bit_extract_forward, count_encode, rle_encode};
+use crate::bitvector::{BitVector, MAX_EXTENDED_PACKET_LENGTH, MAX_PACKET_LENGTH};
+use crate::crc::crc16;
+use crate::encode::{
+    bit_extract, bit_extract_forward, count_encode_extended, rle_encode_counted, OpcodeStats,
+};
 use crate::error::PocketError;
-use crate::mask::{compute_change, update_build, update_mask};
+use crate::json::JsonWriter;
+use crate::mask::{compute_change, compute_change_into, update_build, update_mask};
+use crate::monitor::RatioMonitor;
+use crate::progress::ProgressInfo;
+use crate::state::{StateReader, StateWriter, KIND_COMPRESSOR};
+use crate::trailer::append_trailer;
+use crate::workspace::{InitialMask, Storage, Workspace, STATE_VECTORS};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Maximum history size for robustness.
-const MAX_HISTORY: usize = 16;
+pub(crate) const MAX_HISTORY: usize = 16;
 
 /// Maximum Vt history for ct calculation.
-const MAX_VT_HISTORY: usize = 16;
+pub(crate) const MAX_VT_HISTORY: usize = 16;
 
 /// Compression parameters for a single packet.
-#[derive(Clone, Debug, Default)]
+///
+/// Normally produced by [`Compressor::next_params`] from the pt/ft/rt
+/// schedule. To decide per packet instead, e.g. to send an uncompressed
+/// packet with the full mask right before a ground-station handover, pass
+/// hand-made flags to [`Compressor::compress_packet_with_flags`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CompressionParams {
     /// New mask flag (ṗₜ).
     pub new_mask_flag: bool,
@@ -32,7 +52,197 @@ pub struct CompressionParams {
     pub uncompressed_flag: bool,
 }
 
+/// Forced state refresh, independent of the pt/ft/rt schedule.
+///
+/// When due, the next packet carries the full mask and is sent
+/// uncompressed (ḟₜ = ṙₜ = 1), so a decompressor joining the stream late
+/// needs at most this much history. Any packet with both flags set,
+/// scheduled or forced, restarts the interval.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RefreshPolicy {
+    /// Maximum number of packets between refreshes.
+    pub max_packets: Option<usize>,
+    /// Maximum wall-clock time between refreshes.
+    pub max_age: Option<Duration>,
+}
+
+impl RefreshPolicy {
+    /// Refresh at least every `n` packets.
+    pub fn packets(n: usize) -> Self {
+        Self {
+            max_packets: Some(n),
+            max_age: None,
+        }
+    }
+
+    /// Refresh at least every `age` of wall-clock time.
+    pub fn age(age: Duration) -> Self {
+        Self {
+            max_packets: None,
+            max_age: Some(age),
+        }
+    }
+}
+
+/// Data-driven mask updates, on top of the pt/ft/rt schedule.
+///
+/// Fixed periods waste bits on quiet streams and react slowly to mode
+/// changes. An adaptive policy raises ṗₜ and ḟₜ from what the compressor
+/// observes; the scheduled and forced flags still apply, and the first
+/// R + 1 packets are never changed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdaptivePolicy {
+    /// Raise ṗₜ once more than this many mask bits have not changed since
+    /// the last new mask, i.e. would be dropped by a new mask now. The
+    /// build vector only restarts at a new mask, so this needs a pt
+    /// schedule (or an initial mask) to get going; it then drops fields
+    /// that went quiet well before the next scheduled ṗₜ.
+    pub stale_bits: Option<usize>,
+    /// Minimum packets between a new mask and an adaptive one, so the
+    /// build vector sees enough packets to tell stale bits from slowly
+    /// changing ones.
+    pub min_interval: usize,
+    /// Send the full mask after a packet whose mask changes, RLE(Xₜ), took
+    /// more bits than the full mask would have.
+    pub full_mask_on_change: bool,
+}
+
+impl AdaptivePolicy {
+    /// Returns true if the policy can raise any flag.
+    pub fn is_enabled(&self) -> bool {
+        self.stale_bits.is_some() || self.full_mask_on_change
+    }
+}
+
+/// How a stream starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StartMode {
+    /// The first packet and the following R packets carry the full mask and
+    /// are sent uncompressed (ḟₜ = ṙₜ = 1), so a decompressor can start
+    /// from nothing.
+    #[default]
+    Refresh,
+    /// No forced packets: the pt/ft/rt schedule applies from the first
+    /// packet. The caller guarantees that the decompressor is seeded with
+    /// the same initial mask and reference packet as the compressor
+    /// ([`CompressorBuilder::initial_reference`],
+    /// [`DecompressorBuilder::initial_reference`](crate::DecompressorBuilder::initial_reference)).
+    /// A first packet that differs from the reference outside the initial
+    /// mask cannot be predicted and is rejected unless sent uncompressed.
+    Seeded,
+}
+
+/// Stream-level compression parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressOptions {
+    /// Robustness level (R, 0-7).
+    pub robustness: usize,
+    /// New mask period (0 = never).
+    pub pt_limit: usize,
+    /// Full mask period (0 = never).
+    pub ft_limit: usize,
+    /// Uncompressed packet period (0 = never).
+    pub rt_limit: usize,
+    /// Forced refresh policy (none by default).
+    pub refresh: RefreshPolicy,
+    /// Adaptive mask updates (none by default).
+    pub adaptive: AdaptivePolicy,
+    /// First-packet behavior (forced refresh by default).
+    pub start: StartMode,
+    /// Packets per epoch; the compressor state is fully reset at every
+    /// epoch boundary, so each epoch decodes on its own (none by default).
+    pub epoch: Option<usize>,
+    /// Append a CRC-16 to every compressed packet (off by default).
+    pub packet_crc: bool,
+    /// End the stream with a CRC-32 trailer (off by default, see
+    /// [`crate::trailer`]). Applied by [`compress_with`] and
+    /// [`compress_with_progress`]; entry points that do not produce one
+    /// whole stream, such as [`compress_iter`], reject it.
+    pub stream_checksum: bool,
+    /// Bit ranges (0 = first bit of the packet) excluded from mask
+    /// learning, e.g. sequence counters in a secondary header. Their bits
+    /// stay in the mask and are sent verbatim with the unpredictable bits
+    /// of every packet (none by default, see [`Compressor::set_excluded`]).
+    pub exclude_ranges: Vec<Range<usize>>,
+}
+
+impl CompressOptions {
+    /// Create options from explicit parameters.
+    pub fn new(robustness: usize, pt_limit: usize, ft_limit: usize, rt_limit: usize) -> Self {
+        Self {
+            robustness,
+            pt_limit,
+            ft_limit,
+            rt_limit,
+            refresh: RefreshPolicy::default(),
+            adaptive: AdaptivePolicy::default(),
+            start: StartMode::default(),
+            epoch: None,
+            packet_crc: false,
+            stream_checksum: false,
+            exclude_ranges: Vec::new(),
+        }
+    }
+
+    /// Check the parameters for consistency.
+    ///
+    /// Rejects R > 7, a zero refresh or epoch interval and empty exclude
+    /// ranges. A zero pt, ft or rt
+    /// period disables that flag only (see [`Compressor::next_params`]).
+    pub fn validate(&self) -> Result<(), PocketError> {
+        if self.robustness > 7 {
+            return Err(PocketError::InvalidRobustness(self.robustness));
+        }
+        if self.refresh.max_packets == Some(0) {
+            return Err(PocketError::InvalidParameter(
+                "refresh interval must be at least one packet".into(),
+            ));
+        }
+        if self.epoch == Some(0) {
+            return Err(PocketError::InvalidParameter(
+                "epoch must be at least one packet".into(),
+            ));
+        }
+        if let Some(range) = self.exclude_ranges.iter().find(|r| r.is_empty()) {
+            return Err(PocketError::InvalidParameter(format!(
+                "empty exclude range {range:?}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fail if a stream checksum is requested from `api`, which cannot
+    /// append one.
+    pub(crate) fn reject_stream_checksum(&self, api: &str) -> Result<(), PocketError> {
+        if self.stream_checksum {
+            return Err(PocketError::InvalidParameter(format!(
+                "{api} cannot append a stream checksum, use compress_with"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for CompressOptions {
+    /// R=1, pt=10, ft=20, rt=50.
+    fn default() -> Self {
+        Self::new(1, 10, 20, 50)
+    }
+}
+
 /// POCKET+ compressor state.
+///
+/// Compresses a stream one packet at a time, keeping the mask, build
+/// vector and history between calls, so packets from a live source can be
+/// fed as they arrive. For every packet, call
+/// [`next_params`](Self::next_params) and then
+/// [`compress_packet`](Self::compress_packet); concatenating the returned
+/// packets (each padded to a whole byte) gives exactly the output of
+/// [`compress`].
 #[derive(Clone)]
 pub struct Compressor {
     /// Packet length in bits (F).
@@ -47,14 +257,27 @@ pub struct Compressor {
     build: BitVector,
     /// Previous input vector.
     prev_input: BitVector,
-    /// Initial mask (for reset).
-    initial_mask: BitVector,
+    /// Initial mask (for reset), shared between contexts of the same
+    /// packet type unless it lives in a workspace.
+    initial_mask: InitialMask,
+    /// Reference packet I₋₁ (for reset), all zeros if `None`.
+    initial_reference: Option<BitVector>,
+    /// First-packet behavior.
+    start: StartMode,
+    /// Packets per epoch, if the state is reset at epoch boundaries.
+    epoch: Option<usize>,
+    /// Append a CRC-16 to packets written by `compress_packet_into`.
+    packet_crc: bool,
+    /// Bits excluded from mask learning, kept in the mask at all times.
+    excluded: Option<BitVector>,
     /// Change history (circular buffer).
-    change_history: Vec<BitVector>,
+    change_history: [BitVector; MAX_HISTORY],
+    /// Hamming weight of each change history entry, updated with it.
+    change_weights: [usize; MAX_HISTORY],
     /// Current history index.
     history_index: usize,
     /// New mask flag history.
-    flag_history: Vec<bool>,
+    flag_history: [bool; MAX_VT_HISTORY],
     /// Flag history index.
     flag_history_index: usize,
     /// Current time step.
@@ -71,10 +294,41 @@ pub struct Compressor {
     ft_counter: usize,
     /// Rt counter.
     rt_counter: usize,
+    /// Build vector before the current update (per-packet scratch).
+    prev_build: BitVector,
+    /// Robustness window Xₜ (per-packet scratch).
+    xt: BitVector,
+    /// Inverted, shifted or extraction mask (per-packet scratch).
+    work: BitVector,
+    /// Output buffer reused by `compress_packet_into`.
+    scratch: BitBuffer,
+    /// Opcode usage since the last reset.
+    stats: OpcodeStats,
+    /// Optional compression ratio monitor.
+    monitor: Option<RatioMonitor>,
+    /// Forced refresh policy.
+    refresh: RefreshPolicy,
+    /// Time step of the last packet with ḟₜ = ṙₜ = 1.
+    last_refresh_t: usize,
+    /// Wall-clock time of the last refresh (tracked for `max_age` only).
+    last_refresh_at: Option<Instant>,
+    /// Adaptive mask update policy.
+    adaptive: AdaptivePolicy,
+    /// Time step of the last packet with ṗₜ = 1.
+    last_new_mask_t: usize,
+    /// The last packet's RLE(Xₜ) was longer than the full mask.
+    full_mask_due: bool,
 }
 
 impl Compressor {
     /// Create a new compressor.
+    ///
+    /// # Arguments
+    /// * `f` - Packet length in bits (1-65535)
+    /// * `initial_mask` - Initial mask M₀, `f` bits long (all zeros if `None`)
+    /// * `robustness` - Robustness level R (0-7)
+    /// * `pt_limit`, `ft_limit`, `rt_limit` - New mask, full mask and
+    ///   uncompressed packet periods (0 = never)
     pub fn new(
         f: usize,
         initial_mask: Option<&BitVector>,
@@ -83,34 +337,205 @@ impl Compressor {
         ft_limit: usize,
         rt_limit: usize,
     ) -> Result<Self, PocketError> {
-        if f == 0 || f > 65535 {
+        let initial = initial_mask.cloned().unwrap_or_else(|| BitVector::new(f));
+        Self::with_shared_mask(
+            f,
+            Arc::new(initial),
+            robustness,
+            pt_limit,
+            ft_limit,
+            rt_limit,
+        )
+    }
+
+    /// Create a compressor sharing an immutable initial mask.
+    ///
+    /// The mask is only read on creation and [`reset`](Self::reset), so
+    /// many contexts for the same packet type can hold one copy instead of
+    /// one F-bit vector each. Cloning a compressor also shares it.
+    ///
+    /// # Arguments
+    /// * `f` - Packet length in bits
+    /// * `initial_mask` - Shared initial mask (M₀), `f` bits long
+    /// * `robustness`, `pt_limit`, `ft_limit`, `rt_limit` - as for
+    ///   [`Compressor::new`]
+    pub fn with_shared_mask(
+        f: usize,
+        initial_mask: Arc<BitVector>,
+        robustness: u8,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> Result<Self, PocketError> {
+        if f > MAX_PACKET_LENGTH {
+            return Err(PocketError::InvalidPacketSize(f));
+        }
+        Self::create(f, initial_mask, robustness, pt_limit, ft_limit, rt_limit)
+    }
+
+    /// Create a compressor for packets longer than the standard allows.
+    ///
+    /// **Non-standard extension.** Same as [`new`](Self::new), but F may
+    /// be up to 2²⁴ bits. Above 65535 bits, run
+    /// lengths and COUNT(F) exceed the range of CCSDS 124.0-B-1 Equation 9
+    /// and are coded in the same form with a longer field, so such streams
+    /// can only be read by a decompressor from
+    /// [`Decompressor::new_extended`](crate::Decompressor::new_extended).
+    /// Flag them wherever they are stored, e.g. with
+    /// [`SegmentEncoder::new_extended`](crate::container::SegmentEncoder::new_extended).
+    /// Up to 65535 bits the output is identical to [`new`](Self::new).
+    pub fn new_extended(
+        f: usize,
+        initial_mask: Option<&BitVector>,
+        robustness: u8,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> Result<Self, PocketError> {
+        if f == 0 || f > MAX_EXTENDED_PACKET_LENGTH {
+            return Err(PocketError::InvalidPacketSize(f));
+        }
+        let initial = initial_mask.cloned().unwrap_or_else(|| BitVector::new(f));
+        Self::create(
+            f,
+            Arc::new(initial),
+            robustness,
+            pt_limit,
+            ft_limit,
+            rt_limit,
+        )
+    }
+
+    /// Validate the parameters of a compressor with F already checked
+    /// against its upper limit.
+    fn create(
+        f: usize,
+        initial_mask: Arc<BitVector>,
+        robustness: u8,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> Result<Self, PocketError> {
+        if f == 0 {
             return Err(PocketError::InvalidPacketSize(f));
         }
         if robustness > 7 {
             return Err(PocketError::InvalidRobustness(robustness as usize));
         }
 
-        let mask = initial_mask.cloned().unwrap_or_else(|| BitVector::new(f));
-        let initial = mask.clone();
+        if initial_mask.len() != f {
+            return Err(PocketError::InvalidInputLength {
+                expected: f,
+                actual: initial_mask.len(),
+            });
+        }
+
+        // The reused output buffer holds the largest packet from the start,
+        // so no later packet grows it
+        let output = BitBuffer::with_capacity(packet_capacity(f));
+        let storage = Storage::new(f, InitialMask::Shared(initial_mask), output);
+        Ok(Self::from_storage(
+            f, storage, robustness, pt_limit, ft_limit, rt_limit,
+        ))
+    }
 
-        let mut change_history = Vec::with_capacity(MAX_HISTORY);
-        for _ in 0..MAX_HISTORY {
-            let mut v = BitVector::new(f);
-            v.zero();
-            change_history.push(v);
+    /// Create a compressor in preallocated storage.
+    ///
+    /// Takes the buffers reserved by `workspace` instead of allocating, so
+    /// no heap memory is requested here or, with
+    /// [`compress_packet_to_slice`](Self::compress_packet_to_slice), while
+    /// compressing. The initial mask is the workspace's. Fails if another
+    /// compressor already holds the storage.
+    ///
+    /// # Arguments
+    /// * `workspace` - Storage from [`Workspace::new`] or
+    ///   [`Workspace::from_buffers`]
+    /// * `robustness`, `pt_limit`, `ft_limit`, `rt_limit` - as for
+    ///   [`Compressor::new`]
+    pub fn new_in(
+        workspace: &mut Workspace,
+        robustness: u8,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> Result<Self, PocketError> {
+        if robustness > 7 {
+            return Err(PocketError::InvalidRobustness(robustness as usize));
         }
+        let storage = workspace.storage.take().ok_or_else(|| {
+            PocketError::InvalidParameter("workspace is held by another compressor".into())
+        })?;
+        Ok(Self::from_storage(
+            workspace.packet_size(),
+            storage,
+            robustness,
+            pt_limit,
+            ft_limit,
+            rt_limit,
+        ))
+    }
+
+    /// Return the storage of a compressor created with
+    /// [`new_in`](Self::new_in) to its workspace, ready for the next
+    /// compressor.
+    ///
+    /// # Arguments
+    /// * `workspace` - Workspace to refill (replaced if it holds storage)
+    pub fn release(self, workspace: &mut Workspace) {
+        *workspace = Workspace::from_storage(
+            self.f,
+            Storage {
+                initial_mask: self.initial_mask,
+                vectors: [
+                    self.mask,
+                    self.prev_mask,
+                    self.build,
+                    self.prev_build,
+                    self.prev_input,
+                    self.xt,
+                    self.work,
+                ],
+                change_history: self.change_history,
+                flag_history: self.flag_history,
+                output: self.scratch,
+            },
+        );
+    }
+
+    /// Assemble a compressor from validated parameters and its storage.
+    fn from_storage(
+        f: usize,
+        storage: Storage,
+        robustness: u8,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> Self {
+        let Storage {
+            initial_mask,
+            vectors: [mask, prev_mask, build, prev_build, prev_input, xt, work],
+            change_history,
+            flag_history,
+            output,
+        } = storage;
 
         let mut comp = Self {
             f,
             robustness,
             mask,
-            prev_mask: BitVector::new(f),
-            build: BitVector::new(f),
-            prev_input: BitVector::new(f),
-            initial_mask: initial,
+            prev_mask,
+            build,
+            prev_input,
+            initial_mask,
+            initial_reference: None,
+            start: StartMode::default(),
+            epoch: None,
+            packet_crc: false,
+            excluded: None,
             change_history,
+            change_weights: [0; MAX_HISTORY],
             history_index: 0,
-            flag_history: vec![false; MAX_VT_HISTORY],
+            flag_history,
             flag_history_index: 0,
             t: 0,
             pt_limit,
@@ -119,14 +544,35 @@ impl Compressor {
             pt_counter: pt_limit,
             ft_counter: ft_limit,
             rt_counter: rt_limit,
+            prev_build,
+            xt,
+            work,
+            scratch: output,
+            stats: OpcodeStats::default(),
+            monitor: None,
+            refresh: RefreshPolicy::default(),
+            last_refresh_t: 0,
+            last_refresh_at: None,
+            adaptive: AdaptivePolicy::default(),
+            last_new_mask_t: 0,
+            full_mask_due: false,
         };
 
         comp.reset();
-        Ok(comp)
+        comp
     }
 
     /// Reset compressor to initial state.
     pub fn reset(&mut self) {
+        self.restart();
+        self.stats = OpcodeStats::default();
+        if let Some(monitor) = &mut self.monitor {
+            monitor.clear();
+        }
+    }
+
+    /// Reset the stream state, keeping the statistics and monitor.
+    fn restart(&mut self) {
         self.t = 0;
         self.history_index = 0;
         self.flag_history_index = 0;
@@ -134,11 +580,15 @@ impl Compressor {
         self.mask.copy_from(&self.initial_mask);
         self.prev_mask.zero();
         self.build.zero();
-        self.prev_input.zero();
+        match &self.initial_reference {
+            Some(reference) => self.prev_input.copy_from(reference),
+            None => self.prev_input.zero(),
+        }
 
         for change in &mut self.change_history {
             change.zero();
         }
+        self.change_weights = [0; MAX_HISTORY];
         for flag in &mut self.flag_history {
             *flag = false;
         }
@@ -146,22 +596,548 @@ impl Compressor {
         self.pt_counter = self.pt_limit;
         self.ft_counter = self.ft_limit;
         self.rt_counter = self.rt_limit;
+        self.last_refresh_t = 0;
+        self.last_refresh_at = None;
+        self.last_new_mask_t = 0;
+        self.full_mask_due = false;
+    }
+
+    /// Heap memory currently held by this compressor, in bytes.
+    ///
+    /// Covers the mask, build, input and scratch vectors, the history
+    /// buffers and the reused output buffer; excludes the per-packet
+    /// output accounted for by [`Compressor::peak_heap_size`]. A shared
+    /// initial mask is only counted while this compressor is its sole
+    /// owner.
+    pub fn heap_size(&self) -> usize {
+        let vectors = [
+            &self.mask,
+            &self.prev_mask,
+            &self.build,
+            &self.prev_build,
+            &self.prev_input,
+            &self.xt,
+            &self.work,
+        ];
+        self.initial_mask.heap_size()
+            + vectors.iter().map(|v| v.heap_size()).sum::<usize>()
+            + self
+                .initial_reference
+                .as_ref()
+                .map_or(0, BitVector::heap_size)
+            + self
+                .change_history
+                .iter()
+                .map(BitVector::heap_size)
+                .sum::<usize>()
+            + self.scratch.heap_size()
+    }
+
+    /// Peak heap footprint of a compressor for packets of `f` bits.
+    ///
+    /// Retained state plus the output buffers allocated while compressing
+    /// one packet. History buffers always hold the
+    /// maximum 16 entries, so the footprint depends on F but not on R.
+    ///
+    /// # Arguments
+    /// * `f` - Packet length in bits
+    ///
+    /// # Returns
+    /// Upper bound in bytes, for admission control of many contexts.
+    pub fn peak_heap_size(f: usize) -> usize {
+        let vector = BitVector::heap_size_for(f);
+        // State and scratch vectors, the initial mask and the change history
+        let retained = (STATE_VECTORS + 1 + MAX_HISTORY) * vector;
+
+        // The reused output buffer plus the returned packet
+        let worst = CompressionParams {
+            new_mask_flag: true,
+            send_mask_flag: true,
+            uncompressed_flag: true,
+        };
+        let output = ((packet_upper_bound_bits(f, &worst) + 7) / 8).max(1024);
+
+        retained + 2 * output
+    }
+
+    /// Packet length in bits (F).
+    pub fn packet_size(&self) -> usize {
+        self.f
+    }
+
+    /// Robustness level (R).
+    pub fn robustness(&self) -> u8 {
+        self.robustness
+    }
+
+    /// Initial mask M₀, restored by [`reset`](Self::reset).
+    pub fn initial_mask(&self) -> &BitVector {
+        &self.initial_mask
+    }
+
+    /// Reference packet the first packet is predicted from, if set with
+    /// [`set_initial_reference`](Self::set_initial_reference).
+    pub fn initial_reference(&self) -> Option<&BitVector> {
+        self.initial_reference.as_ref()
+    }
+
+    /// Opcode usage of all packets compressed since the last reset.
+    pub fn stats(&self) -> &OpcodeStats {
+        &self.stats
+    }
+
+    /// Current mask Mₜ₋₁, applied to the next packet.
+    pub fn mask(&self) -> &BitVector {
+        &self.mask
+    }
+
+    /// Current build vector Bₜ₋₁, which replaces the mask at the next
+    /// packet with ṗₜ = 1.
+    pub fn build(&self) -> &BitVector {
+        &self.build
+    }
+
+    /// Time step t of the next packet (packets compressed since the last
+    /// reset).
+    pub fn time_step(&self) -> usize {
+        self.t
+    }
+
+    /// Remaining pt countdown: from t = 1 on, the schedule raises ṗₜ on
+    /// the `pt_counter()`-th next packet. Unused while `pt_limit` is 0.
+    pub fn pt_counter(&self) -> usize {
+        self.pt_counter
+    }
+
+    /// Remaining ft countdown: from t = 1 on, the schedule raises ḟₜ on
+    /// the `ft_counter()`-th next packet. Unused while `ft_limit` is 0.
+    pub fn ft_counter(&self) -> usize {
+        self.ft_counter
+    }
+
+    /// Remaining rt countdown: from t = 1 on, the schedule raises ṙₜ on
+    /// the `rt_counter()`-th next packet. Unused while `rt_limit` is 0.
+    pub fn rt_counter(&self) -> usize {
+        self.rt_counter
+    }
+
+    /// Attach a compression ratio monitor, updated by every
+    /// [`compress_packet`](Self::compress_packet) call.
+    ///
+    /// Packets are counted with their byte-aligned size, as in the stream.
+    pub fn set_monitor(&mut self, monitor: RatioMonitor) {
+        self.monitor = Some(monitor);
+    }
+
+    /// The attached ratio monitor, if any.
+    pub fn monitor(&self) -> Option<&RatioMonitor> {
+        self.monitor.as_ref()
+    }
+
+    /// Set the forced refresh policy.
+    ///
+    /// Applied by [`next_params`](Self::next_params); the interval counts
+    /// from the last refresh, or from now if there was none yet.
+    pub fn set_refresh_policy(&mut self, policy: RefreshPolicy) {
+        self.refresh = policy;
+        self.last_refresh_at = policy.max_age.map(|_| Instant::now());
+    }
+
+    /// The forced refresh policy.
+    pub fn refresh_policy(&self) -> RefreshPolicy {
+        self.refresh
+    }
+
+    /// Set the adaptive mask update policy.
+    ///
+    /// Applied by [`next_params`](Self::next_params), after the pt/ft/rt
+    /// schedule.
+    pub fn set_adaptive_policy(&mut self, policy: AdaptivePolicy) {
+        self.adaptive = policy;
+    }
+
+    /// The adaptive mask update policy.
+    pub fn adaptive_policy(&self) -> AdaptivePolicy {
+        self.adaptive
+    }
+
+    /// Set the first-packet behavior; takes effect at the next
+    /// [`reset`](Self::reset) or on a fresh compressor.
+    pub fn set_start_mode(&mut self, start: StartMode) {
+        self.start = start;
+    }
+
+    /// The first-packet behavior.
+    pub fn start_mode(&self) -> StartMode {
+        self.start
+    }
+
+    /// Reset the stream state every `epoch` packets (`None` = never).
+    ///
+    /// At each epoch boundary the compressor starts over as after
+    /// [`reset`](Self::reset): initial mask, forced first packets and
+    /// schedule counters. Each epoch is then an independent stream that can
+    /// be decoded on its own; decoding the whole stream needs a
+    /// decompressor with the same epoch
+    /// ([`DecompressorBuilder::epoch`](crate::DecompressorBuilder::epoch)).
+    /// Opcode statistics and the ratio monitor carry on across epochs.
+    /// Epochs are counted from the last reset.
+    pub fn set_epoch(&mut self, epoch: Option<usize>) {
+        self.epoch = epoch;
+    }
+
+    /// Packets per epoch, if the state is reset at epoch boundaries.
+    pub fn epoch(&self) -> Option<usize> {
+        self.epoch
+    }
+
+    /// Append a CCSDS CRC-16 (big-endian) after every packet written by
+    /// [`compress_packet_into`](Self::compress_packet_into) and the batch
+    /// functions, covering the packet and its byte padding. Decode such
+    /// streams with
+    /// [`DecompressorBuilder::packet_crc`](crate::DecompressorBuilder::packet_crc).
+    /// [`compress_packet`](Self::compress_packet) returns the bare packet.
+    pub fn set_packet_crc(&mut self, enabled: bool) {
+        self.packet_crc = enabled;
+    }
+
+    /// Returns true if packets are followed by a CRC-16.
+    pub fn packet_crc(&self) -> bool {
+        self.packet_crc
+    }
+
+    /// Exclude bits from mask learning.
+    ///
+    /// The excluded bits are added to the mask before every packet and
+    /// never leave it, so they are sent verbatim with the unpredictable
+    /// bits instead of churning through mask updates. Use it for fields
+    /// known to change all the time (sequence counters, timestamps). The
+    /// stream stays standard POCKET+: the bits enter the mask through the
+    /// regular mask change information of the first packet. With
+    /// [`StartMode::Seeded`], seed the decompressor with the initial mask
+    /// OR the excluded bits.
+    ///
+    /// # Arguments
+    /// * `excluded` - Bits to exclude, F bits long, or `None` to learn all
+    ///   bits
+    pub fn set_excluded(&mut self, excluded: Option<&BitVector>) -> Result<(), PocketError> {
+        if let Some(excluded) = excluded {
+            if excluded.len() != self.f {
+                return Err(PocketError::InvalidInputLength {
+                    expected: self.f,
+                    actual: excluded.len(),
+                });
+            }
+        }
+        self.excluded = excluded.filter(|e| !e.is_zero()).cloned();
+        Ok(())
+    }
+
+    /// Bits excluded from mask learning, if any.
+    pub fn excluded(&self) -> Option<&BitVector> {
+        self.excluded.as_ref()
+    }
+
+    /// Set the reference packet the first packet is predicted from.
+    ///
+    /// Restored by [`reset`](Self::reset) and applied immediately if no
+    /// packet has been compressed yet. The decompressor needs the same
+    /// reference.
+    ///
+    /// # Arguments
+    /// * `reference` - Reference packet I₋₁, F bits long
+    pub fn set_initial_reference(&mut self, reference: &BitVector) -> Result<(), PocketError> {
+        if reference.len() != self.f {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.f,
+                actual: reference.len(),
+            });
+        }
+        self.initial_reference = Some(reference.clone());
+        if self.t == 0 {
+            self.prev_input.copy_from(reference);
+        }
+        Ok(())
+    }
+
+    /// Replace the current mask Mₜ₋₁.
+    ///
+    /// For injecting state recovered from a previous pass or a pre-agreed
+    /// managed parameter set. The change is not signalled in the stream
+    /// until a packet with ḟₜ = 1, so the decompressor must be given the
+    /// same mask with [`Decompressor::set_state`](crate::Decompressor::set_state).
+    /// Unlike the initial mask, it is not restored by [`reset`](Self::reset).
+    ///
+    /// # Arguments
+    /// * `mask` - Mask, F bits long
+    pub fn set_mask(&mut self, mask: &BitVector) -> Result<(), PocketError> {
+        if mask.len() != self.f {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.f,
+                actual: mask.len(),
+            });
+        }
+        self.mask.copy_from(mask);
+        Ok(())
+    }
+
+    /// Replace the previous packet Iₜ₋₁ the next packet is predicted from.
+    ///
+    /// Counterpart of [`set_mask`](Self::set_mask) for the reference
+    /// packet; the decompressor must be given the same packet. Unlike the
+    /// initial reference, it is not restored by [`reset`](Self::reset).
+    ///
+    /// # Arguments
+    /// * `reference` - Previous packet, F bits long
+    pub fn set_reference(&mut self, reference: &BitVector) -> Result<(), PocketError> {
+        if reference.len() != self.f {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.f,
+                actual: reference.len(),
+            });
+        }
+        self.prev_input.copy_from(reference);
+        Ok(())
+    }
+
+    /// Serialize the stream state.
+    ///
+    /// Covers the mask, build and previous-input vectors, the change and
+    /// flag histories, the pt/ft/rt limits and counters and t, so a
+    /// restarted service can continue the stream with
+    /// [`restore_state`](Self::restore_state) instead of starting over
+    /// with full masks and uncompressed packets. The initial mask, opcode
+    /// statistics, monitor, refresh and adaptive policies are
+    /// configuration and are not included.
+    ///
+    /// # Returns
+    /// A self-checking snapshot (see the `state` module layout).
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new(KIND_COMPRESSOR);
+        w.int(self.f);
+        w.int(self.robustness as usize);
+        for value in [
+            self.t,
+            self.pt_limit,
+            self.ft_limit,
+            self.rt_limit,
+            self.pt_counter,
+            self.ft_counter,
+            self.rt_counter,
+            self.history_index,
+            self.flag_history_index,
+            self.last_refresh_t,
+        ] {
+            w.int(value);
+        }
+        for vector in [&self.mask, &self.prev_mask, &self.build, &self.prev_input] {
+            w.vector(vector);
+        }
+        for change in &self.change_history {
+            w.vector(change);
+        }
+        for &flag in &self.flag_history {
+            w.flag(flag);
+        }
+        w.finish()
+    }
+
+    /// Restore the stream state saved by [`save_state`](Self::save_state).
+    ///
+    /// The compressor must have the same packet size and robustness as the
+    /// one that was saved. On error the state is left unchanged. A
+    /// `max_age` refresh interval and the adaptive `min_interval` restart
+    /// at the restore.
+    pub fn restore_state(&mut self, data: &[u8]) -> Result<(), PocketError> {
+        let mut r = StateReader::new(data, KIND_COMPRESSOR)?;
+        let f = r.int()?;
+        if f != self.f {
+            return Err(PocketError::InvalidPacketSize(f));
+        }
+        let robustness = r.int()?;
+        if robustness != self.robustness as usize {
+            return Err(PocketError::RobustnessMismatch {
+                expected: self.robustness as usize,
+                actual: robustness,
+            });
+        }
+
+        let t = r.int()?;
+        let limits = [r.int()?, r.int()?, r.int()?];
+        let counters = [r.int()?, r.int()?, r.int()?];
+        let history_index = r.index(MAX_HISTORY)?;
+        let flag_history_index = r.index(MAX_VT_HISTORY)?;
+        let last_refresh_t = r.int()?;
+        let vectors = [r.vector(f)?, r.vector(f)?, r.vector(f)?, r.vector(f)?];
+        let change_history = (0..MAX_HISTORY)
+            .map(|_| r.vector(f))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut flag_history = [false; MAX_VT_HISTORY];
+        for flag in &mut flag_history {
+            *flag = r.flag()?;
+        }
+        r.finish()?;
+
+        self.t = t;
+        [self.pt_limit, self.ft_limit, self.rt_limit] = limits;
+        [self.pt_counter, self.ft_counter, self.rt_counter] = counters;
+        self.history_index = history_index;
+        self.flag_history_index = flag_history_index;
+        self.last_refresh_t = last_refresh_t;
+        self.last_refresh_at = self.refresh.max_age.map(|_| Instant::now());
+        self.last_new_mask_t = t;
+        self.full_mask_due = false;
+        // Copy into the existing vectors, which may live in a workspace
+        for (vector, saved) in [
+            &mut self.mask,
+            &mut self.prev_mask,
+            &mut self.build,
+            &mut self.prev_input,
+        ]
+        .into_iter()
+        .zip(&vectors)
+        {
+            vector.copy_from(saved);
+        }
+        for ((weight, change), saved) in self
+            .change_weights
+            .iter_mut()
+            .zip(&mut self.change_history)
+            .zip(&change_history)
+        {
+            change.copy_from(saved);
+            *weight = change.hamming_weight();
+        }
+        self.flag_history = flag_history;
+        Ok(())
+    }
+
+    /// Compute the flags for the next packet.
+    ///
+    /// Follows the pt/ft/rt schedule, raises ṗₜ and ḟₜ as the
+    /// [`AdaptivePolicy`] decides and raises ḟₜ and ṙₜ whenever the
+    /// [`RefreshPolicy`] is due. Call once per packet, right before
+    /// [`compress_packet`](Self::compress_packet).
+    pub fn next_params(&mut self) -> CompressionParams {
+        let mut params = self.scheduled_params();
+        if self.t > self.robustness as usize {
+            params.new_mask_flag |= self.stale_mask();
+            params.send_mask_flag |= self.adaptive.full_mask_on_change && self.full_mask_due;
+        }
+        if self.refresh_due() {
+            params.send_mask_flag = true;
+            params.uncompressed_flag = true;
+        }
+        self.note_refresh(&params);
+        params
+    }
+
+    /// Restart the refresh interval if `params` carry a full refresh.
+    fn note_refresh(&mut self, params: &CompressionParams) {
+        if params.send_mask_flag && params.uncompressed_flag {
+            self.last_refresh_t = self.t;
+            if self.refresh.max_age.is_some() {
+                self.last_refresh_at = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Returns true if the adaptive policy calls for a new mask.
+    fn stale_mask(&self) -> bool {
+        let Some(limit) = self.adaptive.stale_bits else {
+            return false;
+        };
+        if self.t - self.last_new_mask_t < self.adaptive.min_interval.max(1) {
+            return false;
+        }
+        // Mask bits a new mask (Bₜ₋₁) would drop
+        let stale: u32 = self
+            .mask
+            .words()
+            .iter()
+            .zip(self.build.words())
+            .map(|(m, b)| (m & !b).count_ones())
+            .sum();
+        stale as usize > limit
+    }
+
+    /// Returns true if the refresh policy forces the next packet.
+    fn refresh_due(&mut self) -> bool {
+        if self.t == 0 {
+            return false;
+        }
+        let by_count = self
+            .refresh
+            .max_packets
+            .is_some_and(|n| self.t - self.last_refresh_t >= n);
+        let by_age = self.refresh.max_age.is_some_and(|age| {
+            self.last_refresh_at
+                .get_or_insert_with(Instant::now)
+                .elapsed()
+                >= age
+        });
+        by_count || by_age
     }
 
-    /// Compute robustness window Xₜ.
-    fn compute_robustness_window(&self, current_change: &BitVector) -> BitVector {
-        if self.robustness == 0 || self.t == 0 {
-            current_change.clone()
+    /// Compute the flags for the next packet from the pt/ft/rt schedule.
+    ///
+    /// The first packet and the following R packets are sent uncompressed
+    /// with a full mask, unless the start is [`StartMode::Seeded`]. After
+    /// that, ḟₜ, ṗₜ and ṙₜ are raised every
+    /// `ft_limit`, `pt_limit` and `rt_limit` packets respectively; a zero
+    /// limit never raises its flag. If all three limits are zero, no flags
+    /// are raised at all, not even on the first packets, for callers that
+    /// set every flag themselves. A due epoch boundary restarts the
+    /// stream first.
+    fn scheduled_params(&mut self) -> CompressionParams {
+        if self.t > 0 && self.epoch == Some(self.t) {
+            self.restart();
+        }
+        if self.pt_limit == 0 && self.ft_limit == 0 && self.rt_limit == 0 {
+            return CompressionParams::default();
+        }
+
+        let seeded = self.start == StartMode::Seeded;
+        if self.t == 0 {
+            return CompressionParams {
+                new_mask_flag: false,
+                send_mask_flag: !seeded,
+                uncompressed_flag: !seeded,
+            };
+        }
+
+        let send_mask_flag = tick(&mut self.ft_counter, self.ft_limit);
+        let new_mask_flag = tick(&mut self.pt_counter, self.pt_limit);
+        let uncompressed_flag = tick(&mut self.rt_counter, self.rt_limit);
+
+        if self.t <= self.robustness as usize && !seeded {
+            CompressionParams {
+                new_mask_flag: false,
+                send_mask_flag: true,
+                uncompressed_flag: true,
+            }
         } else {
-            let mut xt = current_change.clone();
+            CompressionParams {
+                new_mask_flag,
+                send_mask_flag,
+                uncompressed_flag,
+            }
+        }
+    }
+
+    /// Compute robustness window Xₜ from the current change Dₜ and the
+    /// change history.
+    fn compute_robustness_window(&self, change: &BitVector, xt: &mut BitVector) {
+        xt.copy_from(change);
+        if self.robustness > 0 && self.t > 0 {
             let num_changes = self.t.min(self.robustness as usize);
 
             for i in 1..=num_changes {
                 let hist_idx = (self.history_index + MAX_HISTORY - i) % MAX_HISTORY;
-                let new_xt = xt.or(&self.change_history[hist_idx]);
-                xt.copy_from(&new_xt);
+                if self.change_weights[hist_idx] > 0 {
+                    xt.or_assign(&self.change_history[hist_idx]);
+                }
             }
-            xt
         }
     }
 
@@ -174,7 +1150,7 @@ impl Compressor {
             let mut ct = 0u8;
             for i in (rt as usize + 1)..=15.min(self.t) {
                 let hist_idx = (self.history_index + MAX_HISTORY - i) % MAX_HISTORY;
-                if self.change_history[hist_idx].hamming_weight() > 0 {
+                if self.change_weights[hist_idx] > 0 {
                     break;
                 }
                 ct += 1;
@@ -187,13 +1163,6 @@ impl Compressor {
         vt
     }
 
-    /// Check if there are positive updates.
-    fn has_positive_updates(&self, xt: &BitVector) -> bool {
-        let inverted = self.mask.not();
-        let positive = xt.and(&inverted);
-        positive.hamming_weight() > 0
-    }
-
     /// Compute cₜ flag.
     fn compute_ct_flag(&self, vt: u8, current_new_mask_flag: bool) -> bool {
         if vt == 0 {
@@ -214,25 +1183,184 @@ impl Compressor {
     }
 
     /// Compress a single packet.
+    ///
+    /// Produces oₜ = hₜ || qₜ || uₜ for the next time step and advances the
+    /// compressor state. Packets must be passed in stream order; on error
+    /// the state is unspecified and the compressor should be
+    /// [`reset`](Self::reset).
+    ///
+    /// # Arguments
+    /// * `input` - Packet Iₜ, F bits long
+    /// * `params` - Flags for this packet, normally from
+    ///   [`next_params`](Self::next_params)
+    ///
+    /// # Returns
+    /// The compressed packet. Streams place packets on byte boundaries, so
+    /// append [`BitBuffer::to_bytes`] of each packet to the output.
     pub fn compress_packet(
         &mut self,
         input: &BitVector,
         params: &CompressionParams,
     ) -> Result<BitBuffer, PocketError> {
-        if input.len() != self.f {
-            return Err(PocketError::InvalidInputLength {
-                expected: self.f,
-                actual: input.len(),
-            });
-        }
-
         let mut output = BitBuffer::new();
+        self.encode_packet(input, params, &mut output)?;
+        Ok(output)
+    }
 
-        // Step 1: Update mask and build vectors
-        self.prev_mask.copy_from(&self.mask);
-        let prev_build = self.build.clone();
-
-        if self.t > 0 {
+    /// Compress a single packet with caller-chosen flags.
+    ///
+    /// The pt/ft/rt schedule still advances as if
+    /// [`next_params`](Self::next_params) had been called, so later packets
+    /// keep their cadence, and a packet with ḟₜ = ṙₜ = 1 restarts the
+    /// [`RefreshPolicy`] interval. The flags are used as given: the caller
+    /// is responsible for a decodable stream, which needs ḟₜ = ṙₜ = 1 on
+    /// the first R + 1 packets unless the decompressor is seeded with the
+    /// same initial mask.
+    ///
+    /// # Arguments
+    /// * `input` - Packet Iₜ, F bits long
+    /// * `params` - Flags for this packet
+    ///
+    /// # Returns
+    /// The compressed packet.
+    pub fn compress_packet_with_flags(
+        &mut self,
+        input: &BitVector,
+        params: &CompressionParams,
+    ) -> Result<BitBuffer, PocketError> {
+        self.scheduled_params();
+        self.note_refresh(params);
+        self.compress_packet(input, params)
+    }
+
+    /// Compress a single packet, appending it to a byte vector.
+    ///
+    /// Same as [`compress_packet`](Self::compress_packet), but the packet
+    /// is encoded directly at the end of `out` (padded to a whole byte),
+    /// without an intermediate buffer to copy from. The compressor's
+    /// buffers are sized when it is created, so this allocates only if
+    /// `out` has to grow. On error `out` is left as it was.
+    ///
+    /// With [`set_packet_crc`](Self::set_packet_crc) the packet is
+    /// followed by its CRC-16.
+    ///
+    /// # Returns
+    /// The number of bytes appended.
+    pub fn compress_packet_into(
+        &mut self,
+        input: &BitVector,
+        params: &CompressionParams,
+        out: &mut Vec<u8>,
+    ) -> Result<usize, PocketError> {
+        let before = out.len();
+        self.append_packet(input, params, out)?;
+        Ok(out.len() - before)
+    }
+
+    /// Encode a packet at the end of `out`, followed by its CRC-16 if
+    /// enabled.
+    ///
+    /// # Returns
+    /// The length of the packet in bits, excluding padding and CRC.
+    fn append_packet(
+        &mut self,
+        input: &BitVector,
+        params: &CompressionParams,
+        out: &mut Vec<u8>,
+    ) -> Result<usize, PocketError> {
+        let before = out.len();
+        let mut output = BitBuffer::resume(std::mem::take(out));
+        let result = self.encode_packet(input, params, &mut output);
+        let bits = output.len();
+        *out = output.into_bytes();
+        if let Err(e) = result {
+            out.truncate(before);
+            return Err(e);
+        }
+        if self.packet_crc {
+            let crc = crc16(&out[before..]);
+            out.extend_from_slice(&crc.to_be_bytes());
+        }
+        Ok(bits)
+    }
+
+    /// Largest packet [`compress_packet_to_slice`](Self::compress_packet_to_slice)
+    /// can write, in bytes, including the CRC-16 if enabled.
+    pub fn max_packet_bytes(&self) -> usize {
+        packet_capacity(self.f) + if self.packet_crc { 2 } else { 0 }
+    }
+
+    /// Compress a single packet into a caller-provided slice.
+    ///
+    /// The allocation-free counterpart of
+    /// [`compress_packet_into`](Self::compress_packet_into) for compressors
+    /// created with [`new_in`](Self::new_in). `out` must hold at least
+    /// [`max_packet_bytes`](Self::max_packet_bytes), checked before the
+    /// state advances, so a short slice fails with
+    /// [`PocketError::BufferOverflow`] and the packet can be retried.
+    ///
+    /// # Returns
+    /// The number of bytes written to the start of `out`.
+    pub fn compress_packet_to_slice(
+        &mut self,
+        input: &BitVector,
+        params: &CompressionParams,
+        out: &mut [u8],
+    ) -> Result<usize, PocketError> {
+        if out.len() < self.max_packet_bytes() {
+            return Err(PocketError::BufferOverflow);
+        }
+        let mut output = std::mem::take(&mut self.scratch);
+        output.clear();
+        let result = self.encode_packet(input, params, &mut output).map(|()| {
+            let mut len = output
+                .copy_to_slice(out)
+                .expect("slice holds the largest packet");
+            if self.packet_crc {
+                let crc = crc16(&out[..len]);
+                out[len..len + 2].copy_from_slice(&crc.to_be_bytes());
+                len += 2;
+            }
+            len
+        });
+        self.scratch = output;
+        result
+    }
+
+    /// Encode oₜ into `output` and advance the state.
+    fn encode_packet(
+        &mut self,
+        input: &BitVector,
+        params: &CompressionParams,
+        output: &mut BitBuffer,
+    ) -> Result<(), PocketError> {
+        if input.len() != self.f {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.f,
+                actual: input.len(),
+            });
+        }
+
+        // A seeded first packet is predicted from the reference outside M₀
+        if self.t == 0 && self.start == StartMode::Seeded && !params.uncompressed_flag {
+            let unpredictable = input
+                .words()
+                .iter()
+                .zip(self.prev_input.words())
+                .zip(self.mask.words())
+                .any(|((i, r), m)| (i ^ r) & !m != 0);
+            if unpredictable {
+                return Err(PocketError::InvalidParameter(
+                    "first packet differs from the reference outside the initial mask".into(),
+                ));
+            }
+        }
+
+        // Step 1: Update mask and build vectors
+        self.prev_mask.copy_from(&self.mask);
+        self.prev_build.copy_from(&self.build);
+
+        if self.t > 0 {
             update_build(
                 &mut self.build,
                 input,
@@ -244,30 +1372,82 @@ impl Compressor {
                 &mut self.mask,
                 input,
                 &self.prev_input,
-                &prev_build,
+                &self.prev_build,
                 params.new_mask_flag,
             );
         }
+        if let Some(excluded) = &self.excluded {
+            self.mask.or_assign(excluded);
+        }
+
+        compute_change_into(
+            &self.mask,
+            &self.prev_mask,
+            self.t,
+            &mut self.change_history[self.history_index],
+        );
+        self.change_weights[self.history_index] =
+            self.change_history[self.history_index].hamming_weight();
+
+        // Step 2: Encode output packet, with Xₜ and the derived masks in
+        // the retained scratch vectors
+        let mut xt = std::mem::take(&mut self.xt);
+        let mut work = std::mem::take(&mut self.work);
+        let result = self.encode_components(input, params, output, &mut xt, &mut work);
+        self.xt = xt;
+        self.work = work;
+        result?;
+
+        // Step 3: Update state
+        self.prev_input.copy_from(input);
+        self.prev_mask.copy_from(&self.mask);
+        self.flag_history[self.flag_history_index] = params.new_mask_flag;
+        if params.new_mask_flag {
+            self.last_new_mask_t = self.t;
+        }
+        self.flag_history_index = (self.flag_history_index + 1) % MAX_VT_HISTORY;
+        self.t += 1;
+        self.history_index = (self.history_index + 1) % MAX_HISTORY;
+
+        if let Some(monitor) = &mut self.monitor {
+            monitor.record(self.f, (output.len() + 7) / 8 * 8);
+        }
 
-        let change = compute_change(&self.mask, &self.prev_mask, self.t);
-        self.change_history[self.history_index].copy_from(&change);
+        Ok(())
+    }
 
-        // Step 2: Encode output packet
-        let xt = self.compute_robustness_window(&change);
+    /// Encode hₜ, qₜ and uₜ of the current packet into `output`.
+    fn encode_components(
+        &mut self,
+        input: &BitVector,
+        params: &CompressionParams,
+        output: &mut BitBuffer,
+        xt: &mut BitVector,
+        work: &mut BitVector,
+    ) -> Result<(), PocketError> {
+        self.compute_robustness_window(&self.change_history[self.history_index], xt);
         let vt = self.compute_effective_robustness();
         let dt = u8::from(!params.send_mask_flag && !params.uncompressed_flag);
 
         // Component hₜ: RLE(Xₜ) || BIT₄(Vₜ) || eₜ || kₜ || cₜ || ḋₜ
-        rle_encode(&mut output, &xt)?;
+        let before = output.len();
+        rle_encode_counted(output, xt, &mut self.stats)?;
+        if self.adaptive.full_mask_on_change {
+            self.mask.left_shift_into(work);
+            work.xor_assign(&self.mask);
+            self.full_mask_due = output.len() - before > rle_bits(work);
+        }
         output.append_value(u32::from(vt), 4);
 
-        if vt > 0 && xt.hamming_weight() > 0 {
-            let et = self.has_positive_updates(&xt);
+        if vt > 0 && !xt.is_zero() {
+            self.mask.not_into(work);
+            let et = has_positive_updates(xt, work);
             output.append_bit(u8::from(et));
 
             if et {
-                let inverted = self.mask.not();
-                bit_extract_forward(&mut output, &inverted, &xt)?;
+                let before = output.len();
+                bit_extract_forward(output, work, xt)?;
+                self.stats.be_bits += (output.len() - before) as u64;
 
                 let ct = self.compute_ct_flag(vt, params.new_mask_flag);
                 output.append_bit(u8::from(ct));
@@ -280,9 +1460,9 @@ impl Compressor {
         if dt == 0 {
             if params.send_mask_flag {
                 output.append_bit(1);
-                let shifted = self.mask.left_shift();
-                let diff = self.mask.xor(&shifted);
-                rle_encode(&mut output, &diff)?;
+                self.mask.left_shift_into(work);
+                work.xor_assign(&self.mask);
+                rle_encode_counted(output, work, &mut self.stats)?;
             } else {
                 output.append_bit(0);
             }
@@ -291,7 +1471,8 @@ impl Compressor {
         // Component uₜ
         if params.uncompressed_flag {
             output.append_bit(1);
-            count_encode(&mut output, self.f as u32)?;
+            count_encode_extended(output, self.f as u32)?;
+            self.stats.record_count(self.f as u32);
             output.append_bitvector(input);
         } else {
             if dt == 0 {
@@ -299,27 +1480,430 @@ impl Compressor {
             }
 
             let ct = self.compute_ct_flag(vt, params.new_mask_flag);
+            let before = output.len();
             if ct && vt > 0 {
-                let extraction_mask = self.mask.or(&xt);
-                bit_extract(&mut output, input, &extraction_mask)?;
+                work.copy_from(&self.mask);
+                work.or_assign(xt);
+                bit_extract(output, input, work)?;
             } else {
-                bit_extract(&mut output, input, &self.mask)?;
+                bit_extract(output, input, &self.mask)?;
             }
+            self.stats.be_bits += (output.len() - before) as u64;
         }
 
-        // Step 3: Update state
-        self.prev_input.copy_from(input);
-        self.prev_mask.copy_from(&self.mask);
-        self.flag_history[self.flag_history_index] = params.new_mask_flag;
-        self.flag_history_index = (self.flag_history_index + 1) % MAX_VT_HISTORY;
-        self.t += 1;
-        self.history_index = (self.history_index + 1) % MAX_HISTORY;
+        Ok(())
+    }
+}
+
+/// Builder for [`Compressor`].
+///
+/// Replaces the positional arguments of [`Compressor::new`] and
+/// [`compress`] with named, validated settings, starting from
+/// [`CompressOptions::default`] (R=1, pt=10, ft=20, rt=50):
+///
+/// ```rust
+/// use pocketplus::{compress_with, CompressorBuilder};
+///
+/// let options = CompressorBuilder::new(720)
+///     .robustness(2)
+///     .pt_limit(20)
+///     .ft_limit(50)
+///     .rt_limit(100);
+/// let compressed = compress_with(&[0u8; 900], &options).unwrap();
+/// # assert!(!compressed.is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompressorBuilder {
+    packet_size: usize,
+    pub(crate) options: CompressOptions,
+    initial_mask: Option<BitVector>,
+    initial_reference: Option<BitVector>,
+    extended: bool,
+}
+
+impl CompressorBuilder {
+    /// Start a builder for packets of `packet_size` bits (F).
+    pub fn new(packet_size: usize) -> Self {
+        Self {
+            packet_size,
+            options: CompressOptions::default(),
+            initial_mask: None,
+            initial_reference: None,
+            extended: false,
+        }
+    }
+
+    /// Robustness level R (0-7, default 1).
+    #[must_use]
+    pub fn robustness(mut self, robustness: usize) -> Self {
+        self.options.robustness = robustness;
+        self
+    }
+
+    /// New mask period (default 10).
+    #[must_use]
+    pub fn pt_limit(mut self, pt_limit: usize) -> Self {
+        self.options.pt_limit = pt_limit;
+        self
+    }
+
+    /// Full mask period (default 20).
+    #[must_use]
+    pub fn ft_limit(mut self, ft_limit: usize) -> Self {
+        self.options.ft_limit = ft_limit;
+        self
+    }
+
+    /// Uncompressed packet period (default 50).
+    #[must_use]
+    pub fn rt_limit(mut self, rt_limit: usize) -> Self {
+        self.options.rt_limit = rt_limit;
+        self
+    }
+
+    /// Forced refresh policy (default none).
+    #[must_use]
+    pub fn refresh(mut self, refresh: RefreshPolicy) -> Self {
+        self.options.refresh = refresh;
+        self
+    }
+
+    /// Adaptive mask updates (default none).
+    #[must_use]
+    pub fn adaptive(mut self, adaptive: AdaptivePolicy) -> Self {
+        self.options.adaptive = adaptive;
+        self
+    }
+
+    /// Replace all stream-level parameters at once.
+    #[must_use]
+    pub fn options(mut self, options: CompressOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Initial mask M₀ (F bits, default all zeros).
+    #[must_use]
+    pub fn initial_mask(mut self, mask: &BitVector) -> Self {
+        self.initial_mask = Some(mask.clone());
+        self
+    }
+
+    /// First-packet behavior (default [`StartMode::Refresh`]).
+    #[must_use]
+    pub fn start(mut self, start: StartMode) -> Self {
+        self.options.start = start;
+        self
+    }
 
+    /// Reset the state every `packets` packets (default never, see
+    /// [`Compressor::set_epoch`]).
+    #[must_use]
+    pub fn epoch(mut self, packets: usize) -> Self {
+        self.options.epoch = Some(packets);
+        self
+    }
+
+    /// Append a CRC-16 to every compressed packet (default off, see
+    /// [`Compressor::set_packet_crc`]).
+    #[must_use]
+    pub fn packet_crc(mut self, enabled: bool) -> Self {
+        self.options.packet_crc = enabled;
+        self
+    }
+
+    /// End the stream with a CRC-32 trailer, verified and stripped by
+    /// [`decompress`](crate::decompress) (default off). Honoured by
+    /// [`compress_with`] and [`compress_with_progress`].
+    #[must_use]
+    pub fn stream_checksum(mut self, enabled: bool) -> Self {
+        self.options.stream_checksum = enabled;
+        self
+    }
+
+    /// Bit ranges excluded from mask learning (default none, see
+    /// [`Compressor::set_excluded`]).
+    #[must_use]
+    pub fn exclude_ranges(mut self, ranges: &[Range<usize>]) -> Self {
+        self.options.exclude_ranges = ranges.to_vec();
+        self
+    }
+
+    /// Reference packet the first packet is predicted from (F bits,
+    /// default all zeros).
+    #[must_use]
+    pub fn initial_reference(mut self, reference: &BitVector) -> Self {
+        self.initial_reference = Some(reference.clone());
+        self
+    }
+
+    /// Allow packets above 65535 bits (default off). **Non-standard**, see
+    /// [`Compressor::new_extended`].
+    #[must_use]
+    pub fn extended(mut self, enabled: bool) -> Self {
+        self.extended = enabled;
+        self
+    }
+
+    /// Validate the settings and create the compressor.
+    pub fn build(&self) -> Result<Compressor, PocketError> {
+        self.options.validate()?;
+        let options = &self.options;
+        let create = if self.extended {
+            Compressor::new_extended
+        } else {
+            Compressor::new
+        };
+        let mut comp = create(
+            self.packet_size,
+            self.initial_mask.as_ref(),
+            options.robustness as u8,
+            options.pt_limit,
+            options.ft_limit,
+            options.rt_limit,
+        )?;
+        self.configure(&mut comp)?;
+        Ok(comp)
+    }
+
+    /// Validate the settings and create the compressor in preallocated
+    /// storage.
+    ///
+    /// Like [`build`](Self::build), but takes the buffers of `workspace`
+    /// as [`Compressor::new_in`] does, so a service creating a compressor
+    /// per pass reuses one set of buffers. The builder's initial mask, if
+    /// set, replaces the workspace's. Return the storage with
+    /// [`Compressor::release`] at the end of the pass.
+    ///
+    /// # Arguments
+    /// * `workspace` - Storage from [`Workspace::new`] for packets of the
+    ///   builder's size
+    pub fn build_in(&self, workspace: &mut Workspace) -> Result<Compressor, PocketError> {
+        self.options.validate()?;
+        if workspace.packet_size() != self.packet_size {
+            return Err(PocketError::InvalidPacketSize(self.packet_size));
+        }
+        if let (Some(mask), Some(storage)) = (&self.initial_mask, &mut workspace.storage) {
+            if mask.len() != self.packet_size {
+                return Err(PocketError::InvalidInputLength {
+                    expected: self.packet_size,
+                    actual: mask.len(),
+                });
+            }
+            storage.initial_mask.make_mut().copy_from(mask);
+        }
+        let options = &self.options;
+        let mut comp = Compressor::new_in(
+            workspace,
+            options.robustness as u8,
+            options.pt_limit,
+            options.ft_limit,
+            options.rt_limit,
+        )?;
+        self.configure(&mut comp)?;
+        Ok(comp)
+    }
+
+    /// Apply the settings beyond the constructor arguments.
+    fn configure(&self, comp: &mut Compressor) -> Result<(), PocketError> {
+        let options = &self.options;
+        comp.set_refresh_policy(options.refresh);
+        comp.set_adaptive_policy(options.adaptive);
+        comp.set_start_mode(options.start);
+        comp.set_epoch(options.epoch);
+        comp.set_packet_crc(options.packet_crc);
+        if !options.exclude_ranges.is_empty() {
+            let excluded = exclusion_mask(self.packet_size, &options.exclude_ranges)?;
+            comp.set_excluded(Some(&excluded))?;
+        }
+        if let Some(reference) = &self.initial_reference {
+            comp.set_initial_reference(reference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Build the vector of the bits in `ranges`.
+///
+/// # Arguments
+/// * `packet_size` - Packet size F in bits
+/// * `ranges` - Bit ranges, 0 = first bit of the packet
+///
+/// # Returns
+/// An F-bit vector with the bits of all ranges set, e.g. for
+/// [`Compressor::set_excluded`]. Ranges past F are rejected.
+pub fn exclusion_mask(
+    packet_size: usize,
+    ranges: &[Range<usize>],
+) -> Result<BitVector, PocketError> {
+    let mut mask = BitVector::new(packet_size);
+    for range in ranges {
+        if range.is_empty() || range.end > packet_size {
+            return Err(PocketError::InvalidParameter(format!(
+                "exclude range {range:?} outside the {packet_size}-bit packet"
+            )));
+        }
+        for bit in range.clone() {
+            mask.set_bit(bit, 1);
+        }
+    }
+    Ok(mask)
+}
+
+/// Compress multiple packets with named, validated settings.
+///
+/// # Arguments
+/// * `data` - Input data (whole packets)
+/// * `options` - Packet size and compression parameters
+///
+/// # Returns
+/// The compressed stream, identical to [`compress`] for the same
+/// parameters, followed by a trailer if
+/// [`stream_checksum`](CompressorBuilder::stream_checksum) is set.
+pub fn compress_with(data: &[u8], options: &CompressorBuilder) -> Result<Vec<u8>, PocketError> {
+    let packet_size = options.packet_size;
+    if packet_size == 0 {
+        return Err(PocketError::InvalidPacketSize(packet_size));
+    }
+    let mut comp = options.build()?;
+
+    let packet_bytes = (packet_size + 7) / 8;
+    if data.len() % packet_bytes != 0 {
+        return Err(PocketError::InvalidInputLength {
+            expected: (data.len() / packet_bytes + 1) * packet_bytes,
+            actual: data.len(),
+        });
+    }
+
+    let mut output = Vec::new();
+    compress_packets(&mut comp, data, &mut output, |_, _, _| Ok(()))?;
+    if options.options.stream_checksum {
+        append_trailer(&mut output);
+    }
+    Ok(output)
+}
+
+/// Lazy compression over a packet source, created by [`compress_iter`].
+///
+/// Yields the compressed bytes of one packet per input packet. After an
+/// error the iterator is exhausted.
+pub struct CompressIter<I> {
+    packets: I,
+    options: CompressOptions,
+    /// Created from the first packet, which fixes the packet size.
+    comp: Option<Compressor>,
+    failed: bool,
+}
+
+impl<I> CompressIter<I>
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+{
+    /// Compress the remaining packets into one stream.
+    ///
+    /// The result is identical to [`compress`] over the concatenated
+    /// packets, without building that concatenation.
+    pub fn concat(self) -> Result<Vec<u8>, PocketError> {
+        let mut output = Vec::new();
+        for packet in self {
+            output.extend(packet?);
+        }
         Ok(output)
     }
+
+    fn compress_next(&mut self, packet: &[u8]) -> Result<Vec<u8>, PocketError> {
+        let packet_size = packet.len() * 8;
+        if self.comp.is_none() {
+            let comp = CompressorBuilder::new(packet_size)
+                .options(self.options.clone())
+                .build()?;
+            self.comp = Some(comp);
+        }
+        let comp = self.comp.as_mut().expect("compressor created above");
+        if packet_size != comp.packet_size() {
+            return Err(PocketError::InvalidInputLength {
+                expected: comp.packet_size() / 8,
+                actual: packet.len(),
+            });
+        }
+        let input = BitVector::from_bytes(packet, packet_size);
+        let params = comp.next_params();
+        Ok(comp.compress_packet(&input, &params)?.into_bytes())
+    }
+}
+
+impl<I> Iterator for CompressIter<I>
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+{
+    type Item = Result<Vec<u8>, PocketError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let packet = self.packets.next()?;
+        let result = self.compress_next(packet.as_ref());
+        self.failed = result.is_err();
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.failed {
+            (0, Some(0))
+        } else {
+            self.packets.size_hint()
+        }
+    }
+}
+
+/// Compress packets from an iterator, one at a time.
+///
+/// Packets are consumed lazily, so a generator never needs to be collected
+/// into one contiguous buffer. The first packet fixes the packet size
+/// (its length in bytes × 8); all packets must have the same length.
+///
+/// # Arguments
+/// * `packets` - Packet source (`&[u8]`, `Vec<u8>`, ...)
+/// * `options` - Compression parameters
+///
+/// # Returns
+/// An iterator of per-packet compressed chunks; use
+/// [`CompressIter::concat`] for the whole stream. Fails immediately if the
+/// options are inconsistent or ask for a
+/// [`stream_checksum`](CompressOptions::stream_checksum), which needs the
+/// whole stream ([`compress_with`]).
+pub fn compress_iter<I>(
+    packets: I,
+    options: &CompressOptions,
+) -> Result<CompressIter<I::IntoIter>, PocketError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    options.validate()?;
+    options.reject_stream_checksum("compress_iter")?;
+    Ok(CompressIter {
+        packets: packets.into_iter(),
+        options: options.clone(),
+        comp: None,
+        failed: false,
+    })
 }
 
 /// Compress multiple packets of housekeeping data.
+///
+/// See [`compress_with`] for the same operation with named parameters.
+///
+/// # Packet Framing
+///
+/// Each packet occupies ⌈F/8⌉ bytes of `data`. When F is not a multiple
+/// of 8, the last byte of every packet holds the packet's final F mod 8
+/// bits in its most significant bits; the remaining padding bits are
+/// ignored. [`decompress`](crate::decompress) produces the same framing
+/// with zero padding, so 721-bit packets round-trip as 91 bytes each.
 pub fn compress(
     data: &[u8],
     packet_size: usize,
@@ -328,19 +1912,431 @@ pub fn compress(
     ft_limit: usize,
     rt_limit: usize,
 ) -> Result<Vec<u8>, PocketError> {
+    compress_with_stats(data, packet_size, robustness, pt_limit, ft_limit, rt_limit)
+        .map(|(output, _)| output)
+}
+
+/// Compress multiple packets with an initial mask.
+///
+/// Seeding the mask with the fields already known to be volatile skips the
+/// warm-up packets the compressor otherwise spends learning them. The
+/// stream must be decompressed with
+/// [`decompress_with_mask`](crate::decompress_with_mask) and the same mask.
+///
+/// # Arguments
+///
+/// * `data` - Input data (whole packets)
+/// * `packet_size` - Size of each packet in bits
+/// * `robustness` - Robustness parameter R (0-7)
+/// * `pt_limit` - New mask period
+/// * `ft_limit` - Full mask period
+/// * `rt_limit` - Uncompressed packet period
+/// * `initial_mask` - Initial mask vector (F bits), or `None` for all zeros
+///
+/// # Errors
+///
+/// Returns `PocketError` if:
+/// - `packet_size` is 0 or above 65535
+/// - `robustness` is greater than 7
+/// - `initial_mask` length doesn't match `packet_size`
+/// - `data` is not a whole number of packets
+pub fn compress_with_mask(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+    initial_mask: Option<&BitVector>,
+) -> Result<Vec<u8>, PocketError> {
+    let mut output = Vec::new();
+    compress_seeded(
+        data,
+        &mut output,
+        packet_size,
+        robustness,
+        [pt_limit, ft_limit, rt_limit],
+        initial_mask,
+        |_, _, _| Ok(()),
+    )?;
+    Ok(output)
+}
+
+/// Location of one packet in a compressed stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PacketSpan {
+    /// Offset of the packet in bits (always `byte_offset * 8`, since
+    /// packets start on byte boundaries).
+    pub bit_offset: usize,
+    /// Length of the packet in bits, excluding the padding to the next
+    /// byte boundary.
+    pub bit_length: usize,
+    /// Offset of the packet in bytes.
+    pub byte_offset: usize,
+    /// Length of the packet in bytes, including padding.
+    pub byte_length: usize,
+}
+
+/// Compress multiple packets and index the packets in the output.
+///
+/// Takes the same arguments as [`compress`]. Each [`PacketSpan`] gives the
+/// position of one compressed packet, so archive tools can slice packets
+/// out of a stored stream without parsing it. Decoding a slice still
+/// requires the decompressor state of the preceding packets, unless the
+/// packet carries the full mask and the uncompressed packet.
+///
+/// # Returns
+/// The compressed stream and one span per input packet, in order.
+pub fn compress_indexed(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> Result<(Vec<u8>, Vec<PacketSpan>), PocketError> {
+    let mut output = Vec::new();
+    let mut spans = Vec::new();
+    compress_seeded(
+        data,
+        &mut output,
+        packet_size,
+        robustness,
+        [pt_limit, ft_limit, rt_limit],
+        None,
+        |_, span, _| {
+            spans.push(span);
+            Ok(())
+        },
+    )?;
+    Ok((output, spans))
+}
+
+/// Statistics of one compressed packet.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PacketStats {
+    /// Compressed size in bits, excluding byte padding.
+    pub compressed_bits: usize,
+    /// Compressed size in bytes, as stored in the stream.
+    pub compressed_bytes: usize,
+    /// Flags the packet was compressed with (ṗₜ, ḟₜ, ṙₜ).
+    pub params: CompressionParams,
+    /// Hamming weight of the mask Mₜ after the packet.
+    pub mask_weight: usize,
+    /// Compression ratio of the stream up to and including the packet.
+    pub cumulative_ratio: f64,
+}
+
+/// Per-packet statistics of a compressed stream.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressionStats {
+    /// One entry per input packet, in order.
+    pub packets: Vec<PacketStats>,
+    /// Opcode usage of the whole stream.
+    pub opcodes: OpcodeStats,
+}
+
+impl CompressionStats {
+    /// Overall compression ratio, `None` for an empty stream.
+    pub fn ratio(&self) -> Option<f64> {
+        self.packets.last().map(|p| p.cumulative_ratio)
+    }
+
+    /// Number of packets sent with ṙₜ = 1 (uncompressed).
+    pub fn uncompressed_packets(&self) -> usize {
+        self.packets
+            .iter()
+            .filter(|p| p.params.uncompressed_flag)
+            .count()
+    }
+
+    /// Number of packets carrying the full mask (ḟₜ = 1).
+    pub fn full_mask_packets(&self) -> usize {
+        self.packets
+            .iter()
+            .filter(|p| p.params.send_mask_flag)
+            .count()
+    }
+
+    /// Write the statistics as a JSON object.
+    ///
+    /// Totals come first, followed by one object per packet under
+    /// `"per_packet"`.
+    pub fn write_json(&self, json: &mut JsonWriter) {
+        json.begin_object();
+        json.key("packets").uint(self.packets.len() as u64);
+        json.key("ratio");
+        match self.ratio() {
+            Some(ratio) => json.float(ratio),
+            None => json.null(),
+        };
+        json.key("uncompressed_packets")
+            .uint(self.uncompressed_packets() as u64);
+        json.key("full_mask_packets")
+            .uint(self.full_mask_packets() as u64);
+        json.key("opcodes");
+        self.opcodes.write_json(json);
+        json.key("per_packet").begin_array();
+        for packet in &self.packets {
+            json.begin_object();
+            json.key("compressed_bits")
+                .uint(packet.compressed_bits as u64);
+            json.key("compressed_bytes")
+                .uint(packet.compressed_bytes as u64);
+            json.key("new_mask").bool(packet.params.new_mask_flag);
+            json.key("send_mask").bool(packet.params.send_mask_flag);
+            json.key("uncompressed")
+                .bool(packet.params.uncompressed_flag);
+            json.key("mask_weight").uint(packet.mask_weight as u64);
+            json.key("cumulative_ratio").float(packet.cumulative_ratio);
+            json.end_object();
+        }
+        json.end_array();
+        json.end_object();
+    }
+
+    /// Serialize the statistics as a JSON document.
+    pub fn to_json(&self) -> String {
+        let mut json = JsonWriter::new();
+        self.write_json(&mut json);
+        json.finish()
+    }
+}
+
+/// Compress multiple packets and report per-packet statistics.
+///
+/// Takes the same arguments as [`compress`]. The [`CompressionStats`] give
+/// each packet's compressed size, flags, mask weight and the running
+/// compression ratio, for tuning pt/ft/rt without parsing the stream.
+pub fn compress_with_packet_stats(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> Result<(Vec<u8>, CompressionStats), PocketError> {
+    let mut output = Vec::new();
+    let mut packets = Vec::new();
+    let opcodes = compress_seeded(
+        data,
+        &mut output,
+        packet_size,
+        robustness,
+        [pt_limit, ft_limit, rt_limit],
+        None,
+        |comp, span, params| {
+            let input_bits = (packets.len() + 1) * packet_size;
+            let output_bits = (span.byte_offset + span.byte_length) * 8;
+            packets.push(PacketStats {
+                compressed_bits: span.bit_length,
+                compressed_bytes: span.byte_length,
+                params: params.clone(),
+                mask_weight: comp.mask.hamming_weight(),
+                cumulative_ratio: input_bits as f64 / output_bits as f64,
+            });
+            Ok(())
+        },
+    )?;
+    Ok((output, CompressionStats { packets, opcodes }))
+}
+
+/// Compress multiple packets into a caller-provided buffer.
+///
+/// Takes the same parameters as [`compress`] and appends the stream to
+/// `output`. Packets are assembled in one reused input vector and output
+/// buffer, so repeated calls with a cleared `output` allocate nothing for
+/// the stream once it has grown to size. On error `output` is left as it
+/// was.
+///
+/// # Returns
+/// The number of bytes appended.
+pub fn compress_into(
+    data: &[u8],
+    output: &mut Vec<u8>,
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> Result<usize, PocketError> {
+    let before = output.len();
+    match compress_seeded(
+        data,
+        output,
+        packet_size,
+        robustness,
+        [pt_limit, ft_limit, rt_limit],
+        None,
+        |_, _, _| Ok(()),
+    ) {
+        Ok(_) => Ok(output.len() - before),
+        Err(e) => {
+            output.truncate(before);
+            Err(e)
+        }
+    }
+}
+
+/// Compress multiple packets, checking a cancellation flag between packets.
+///
+/// Takes the same arguments as [`compress`]. Long batch jobs can be aborted
+/// from another thread by setting `cancel`; the flag is checked before the
+/// first packet and after each packet.
+///
+/// # Arguments
+/// * `cancel` - Set to `true` to abort the compression
+///
+/// # Errors
+///
+/// Returns [`PocketError::Cancelled`] once `cancel` is set, plus the errors
+/// of [`compress`].
+pub fn compress_cancellable(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+    cancel: &AtomicBool,
+) -> Result<Vec<u8>, PocketError> {
+    let check = || {
+        if cancel.load(Ordering::Relaxed) {
+            Err(PocketError::Cancelled)
+        } else {
+            Ok(())
+        }
+    };
+    check()?;
+
+    let mut output = Vec::new();
+    compress_seeded(
+        data,
+        &mut output,
+        packet_size,
+        robustness,
+        [pt_limit, ft_limit, rt_limit],
+        None,
+        |_, _, _| check(),
+    )?;
+    Ok(output)
+}
+
+/// Compress multiple packets, reporting progress every `every` packets.
+///
+/// `callback` is called after every `every`-th packet and once more after
+/// the last packet, unless that was just reported.
+///
+/// # Arguments
+/// * `data` - Input data (whole packets)
+/// * `options` - Packet size and compression parameters
+/// * `every` - Packets between reports (at least 1)
+/// * `callback` - Receives the packets, bytes read and written and the
+///   ratio so far
+///
+/// # Returns
+/// The compressed stream, identical to [`compress_with`].
+pub fn compress_with_progress(
+    data: &[u8],
+    options: &CompressorBuilder,
+    every: usize,
+    mut callback: impl FnMut(ProgressInfo),
+) -> Result<Vec<u8>, PocketError> {
+    let packet_size = options.packet_size;
     if packet_size == 0 {
         return Err(PocketError::InvalidPacketSize(packet_size));
     }
-    if packet_size % 8 != 0 {
+    if every == 0 {
+        return Err(PocketError::InvalidParameter(
+            "progress interval must be at least 1".into(),
+        ));
+    }
+    let mut comp = options.build()?;
+
+    let packet_bytes = (packet_size + 7) / 8;
+    if data.len() % packet_bytes != 0 {
+        return Err(PocketError::InvalidInputLength {
+            expected: (data.len() / packet_bytes + 1) * packet_bytes,
+            actual: data.len(),
+        });
+    }
+
+    let mut packets = 0;
+    let mut output = Vec::new();
+    compress_packets(&mut comp, data, &mut output, |_, span, _| {
+        packets += 1;
+        if packets % every == 0 {
+            let written = span.byte_offset + span.byte_length;
+            callback(ProgressInfo::new(
+                packets,
+                packets * packet_bytes,
+                written,
+                true,
+            ));
+        }
+        Ok(())
+    })?;
+    if options.options.stream_checksum {
+        append_trailer(&mut output);
+    }
+    if packets % every != 0 {
+        callback(ProgressInfo::new(packets, data.len(), output.len(), true));
+    }
+    Ok(output)
+}
+
+/// Compress multiple packets and report encoder opcode usage.
+///
+/// Takes the same arguments as [`compress`]; the returned [`OpcodeStats`]
+/// count the COUNT forms, RLE terminators and BE bits emitted.
+pub fn compress_with_stats(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> Result<(Vec<u8>, OpcodeStats), PocketError> {
+    let mut output = Vec::new();
+    let stats = compress_seeded(
+        data,
+        &mut output,
+        packet_size,
+        robustness,
+        [pt_limit, ft_limit, rt_limit],
+        None,
+        |_, _, _| Ok(()),
+    )?;
+    Ok((output, stats))
+}
+
+/// Shared implementation of the batch compression functions; appends to
+/// `output`, `limits` holds pt, ft and rt.
+///
+/// `on_packet` is called after every packet with the compressor, the
+/// packet's location in `output` and its flags; an error from it aborts
+/// the batch.
+fn compress_seeded(
+    data: &[u8],
+    output: &mut Vec<u8>,
+    packet_size: usize,
+    robustness: usize,
+    limits: [usize; 3],
+    initial_mask: Option<&BitVector>,
+    on_packet: impl FnMut(&Compressor, PacketSpan, &CompressionParams) -> Result<(), PocketError>,
+) -> Result<OpcodeStats, PocketError> {
+    if packet_size == 0 {
         return Err(PocketError::InvalidPacketSize(packet_size));
     }
     if robustness > 7 {
         return Err(PocketError::InvalidRobustness(robustness));
     }
 
-    let packet_bytes = packet_size / 8;
+    let packet_bytes = (packet_size + 7) / 8;
     if data.is_empty() {
-        return Ok(Vec::new());
+        return Ok(OpcodeStats::default());
     }
     if data.len() % packet_bytes != 0 {
         return Err(PocketError::InvalidInputLength {
@@ -349,85 +2345,332 @@ pub fn compress(
         });
     }
 
-    let num_packets = data.len() / packet_bytes;
+    let [pt_limit, ft_limit, rt_limit] = limits;
     let mut comp = Compressor::new(
         packet_size,
-        None,
+        initial_mask,
         robustness as u8,
         pt_limit,
         ft_limit,
         rt_limit,
     )?;
+    compress_packets(&mut comp, data, output, on_packet)?;
+    Ok(*comp.stats())
+}
 
-    let mut output = Vec::new();
+/// Compress whole packets of `data` with `comp`, appending to `output`.
+///
+/// The length of `data` must be a multiple of the packet size in bytes.
+/// `on_packet` is called as in [`compress_seeded`].
+///
+/// Room for as many bytes as the input is reserved once up front: a
+/// stream only outgrows its input for incompressible data, while the
+/// worst case ([`compressed_stream_upper_bound`]) is several times the
+/// input. Capacity reserved here beyond twice the output is released at
+/// the end; capacity the caller reserved is left alone.
+fn compress_packets(
+    comp: &mut Compressor,
+    data: &[u8],
+    output: &mut Vec<u8>,
+    mut on_packet: impl FnMut(&Compressor, PacketSpan, &CompressionParams) -> Result<(), PocketError>,
+) -> Result<(), PocketError> {
+    let grown = output.capacity() - output.len() < data.len();
+    output.reserve(data.len());
+
+    let mut input = BitVector::new(comp.packet_size());
+    for packet in data.chunks_exact((comp.packet_size() + 7) / 8) {
+        input.load_bytes(packet);
+
+        let params = comp.next_params();
+        let byte_offset = output.len();
+        let bit_length = comp.append_packet(&input, &params, output)?;
+        let span = PacketSpan {
+            bit_offset: byte_offset * 8,
+            bit_length,
+            byte_offset,
+            byte_length: output.len() - byte_offset,
+        };
+        on_packet(comp, span, &params)?;
+    }
 
-    for i in 0..num_packets {
-        let packet_data = &data[i * packet_bytes..(i + 1) * packet_bytes];
-        let input = BitVector::from_bytes(packet_data, packet_size);
+    if grown && output.capacity() > 2 * output.len() {
+        output.shrink_to(output.len());
+    }
+    Ok(())
+}
 
-        let params = if pt_limit > 0 && ft_limit > 0 && rt_limit > 0 {
-            if i == 0 {
-                CompressionParams {
-                    new_mask_flag: false,
-                    send_mask_flag: true,
-                    uncompressed_flag: true,
-                }
-            } else {
-                let send_mask_flag = if comp.ft_counter == 1 {
-                    comp.ft_counter = ft_limit;
-                    true
-                } else {
-                    comp.ft_counter -= 1;
-                    false
-                };
+/// Advance a pt/ft/rt countdown; returns true when the flag is due.
+///
+/// A zero `limit` disables the flag.
+fn tick(counter: &mut usize, limit: usize) -> bool {
+    if limit == 0 {
+        false
+    } else if *counter == 1 {
+        *counter = limit;
+        true
+    } else {
+        *counter -= 1;
+        false
+    }
+}
 
-                let new_mask_flag = if comp.pt_counter == 1 {
-                    comp.pt_counter = pt_limit;
-                    true
-                } else {
-                    comp.pt_counter -= 1;
-                    false
-                };
+/// Length in bits of COUNT(a) (Table 5-1).
+const fn count_bits(a: usize) -> usize {
+    match a {
+        0 | 1 => 1,
+        2..=33 => 8,
+        _ => {
+            let highest_bit = (usize::BITS - 1 - (a - 2).leading_zeros()) as usize;
+            3 + 2 * (highest_bit + 1) - 6
+        }
+    }
+}
 
-                let uncompressed_flag = if comp.rt_counter == 1 {
-                    comp.rt_counter = rt_limit;
-                    true
-                } else {
-                    comp.rt_counter -= 1;
-                    false
-                };
+/// True if Xₜ flags a bit that is predictable in the current mask, i.e.
+/// the update has positive entries (eₜ = 1).
+///
+/// # Arguments
+/// * `xt` - Robustness window Xₜ
+/// * `inverted_mask` - NOT Mₜ
+fn has_positive_updates(xt: &BitVector, inverted_mask: &BitVector) -> bool {
+    xt.intersects(inverted_mask)
+}
 
-                let (send_mask_flag, uncompressed_flag, new_mask_flag) = if i <= robustness {
-                    (true, true, false)
-                } else {
-                    (send_mask_flag, uncompressed_flag, new_mask_flag)
-                };
+/// Largest compressed packet for packets of `f` bits, in bytes, without
+/// a packet CRC.
+pub(crate) const fn packet_capacity(f: usize) -> usize {
+    let worst = CompressionParams {
+        new_mask_flag: true,
+        send_mask_flag: true,
+        uncompressed_flag: true,
+    };
+    (packet_upper_bound_bits(f, &worst) + 7) / 8
+}
 
-                CompressionParams {
-                    new_mask_flag,
-                    send_mask_flag,
-                    uncompressed_flag,
-                }
-            }
-        } else {
-            CompressionParams {
-                new_mask_flag: false,
-                send_mask_flag: false,
-                uncompressed_flag: false,
-            }
-        };
+/// Length in bits of RLE(v) (Equation 10), including the terminator.
+fn rle_bits(v: &BitVector) -> usize {
+    let mut bits = 2;
+    let mut previous = v.len();
+    for i in (0..v.len()).rev() {
+        if v.get_bit(i) == 1 {
+            bits += count_bits(previous - i);
+            previous = i;
+        }
+    }
+    bits
+}
+
+/// Upper bound in bits on one compressed packet with the given flags.
+///
+/// RLE costs at most 4 bits per input bit (COUNT(2) every second bit)
+/// plus the '10' terminator; BE emits at most one bit per input bit.
+const fn packet_upper_bound_bits(packet_bits: usize, params: &CompressionParams) -> usize {
+    let rle = 4 * packet_bits + 2;
+    let send_d = params.send_mask_flag || params.uncompressed_flag;
+
+    // hₜ = RLE(Xₜ) || BIT₄(Vₜ) || eₜ || kₜ || cₜ || ḋₜ
+    let mut bits = rle + 4 + 1 + packet_bits + 1 + 1;
+
+    // qₜ
+    if send_d {
+        bits += 1;
+        if params.send_mask_flag {
+            bits += rle;
+        }
+    }
 
-        let packet_output = comp.compress_packet(&input, &params)?;
-        output.extend(packet_output.to_bytes());
+    // uₜ
+    if params.uncompressed_flag {
+        bits += 1 + count_bits(packet_bits) + packet_bits;
+    } else {
+        if send_d {
+            bits += 1;
+        }
+        bits += packet_bits;
     }
 
-    Ok(output)
+    bits
+}
+
+/// Upper bound on the size of a compressed stream.
+///
+/// Walks the pt/ft/rt schedule of `config` (which does not depend on the
+/// data) and adds the worst-case size of every packet, including full
+/// masks and uncompressed packets where they are scheduled or forced by a
+/// packet-count refresh. A wall-clock refresh can fall on any packet, so
+/// it bounds every packet as a refresh; likewise adaptive full masks
+/// ([`AdaptivePolicy::full_mask_on_change`]) bound every packet with a full
+/// mask. Use it to size fixed output buffers before calling [`compress`].
+///
+/// # Arguments
+/// * `num_packets` - Number of input packets
+/// * `packet_bits` - Packet size in bits (F)
+/// * `config` - Compression parameters
+///
+/// # Returns
+/// Maximum number of output bytes. Invalid parameters are bounded as if
+/// every packet carried a full mask and was sent uncompressed.
+pub fn compressed_stream_upper_bound(
+    num_packets: usize,
+    packet_bits: usize,
+    config: &CompressOptions,
+) -> usize {
+    let crc_bytes = if config.packet_crc { 2 } else { 0 };
+    let per_packet = |params: &CompressionParams| {
+        (packet_upper_bound_bits(packet_bits, params) + 7) / 8 + crc_bytes
+    };
+
+    let worst = CompressionParams {
+        new_mask_flag: true,
+        send_mask_flag: true,
+        uncompressed_flag: true,
+    };
+    let comp = Compressor::new(
+        packet_bits,
+        None,
+        config.robustness.min(usize::from(u8::MAX)) as u8,
+        config.pt_limit,
+        config.ft_limit,
+        config.rt_limit,
+    );
+    let (Ok(mut comp), None) = (comp, config.refresh.max_age) else {
+        return num_packets.saturating_mul(per_packet(&worst));
+    };
+    comp.set_refresh_policy(config.refresh);
+    comp.set_start_mode(config.start);
+    comp.set_epoch(config.epoch);
+
+    // Adaptive full masks can fall on any packet
+    let mut total = 0usize;
+    for _ in 0..num_packets {
+        let mut params = comp.next_params();
+        params.send_mask_flag |= config.adaptive.full_mask_on_change;
+        total = total.saturating_add(per_packet(&params));
+        comp.t += 1;
+    }
+    total
+}
+
+/// Size of the next compressed packet, without compressing it.
+///
+/// Runs the mask update and the encoding step of
+/// [`Compressor::compress_packet`] on copies of the state and adds up the
+/// component lengths instead of emitting bits, so `comp` is left
+/// untouched. Link-budget tools can price each flag combination for a
+/// packet before committing to one.
+///
+/// # Arguments
+/// * `comp` - Compressor about to compress `input`
+/// * `input` - Packet Iₜ, F bits long
+/// * `params` - Flags to price
+///
+/// # Returns
+/// The number of bytes [`Compressor::compress_packet_into`] would append,
+/// including the CRC-16 if enabled.
+///
+/// # Panics
+/// If `input` is not F bits long.
+pub fn estimate_packet_size(
+    comp: &Compressor,
+    input: &BitVector,
+    params: &CompressionParams,
+) -> usize {
+    assert_eq!(input.len(), comp.f, "input must be F bits long");
+
+    // Mₜ and Dₜ as encode_packet computes them; Bₜ is not needed
+    let mut mask = comp.mask.clone();
+    if comp.t > 0 {
+        update_mask(
+            &mut mask,
+            input,
+            &comp.prev_input,
+            &comp.build,
+            params.new_mask_flag,
+        );
+    }
+    if let Some(excluded) = &comp.excluded {
+        mask.or_assign(excluded);
+    }
+    let change = compute_change(&mask, &comp.mask, comp.t);
+
+    let mut xt = BitVector::new(comp.f);
+    comp.compute_robustness_window(&change, &mut xt);
+    let vt = comp.compute_effective_robustness();
+    let ct = comp.compute_ct_flag(vt, params.new_mask_flag);
+    let dt = !params.send_mask_flag && !params.uncompressed_flag;
+    let mut work = BitVector::new(comp.f);
+
+    // hₜ = RLE(Xₜ) || BIT₄(Vₜ) || eₜ || kₜ || cₜ || ḋₜ
+    let mut bits = rle_bits(&xt) + 4 + 1;
+    let changes = xt.hamming_weight();
+    if vt > 0 && changes > 0 {
+        mask.not_into(&mut work);
+        bits += 1;
+        if has_positive_updates(&xt, &work) {
+            bits += changes + 1;
+        }
+    }
+
+    // qₜ
+    if !dt {
+        bits += 1;
+        if params.send_mask_flag {
+            mask.left_shift_into(&mut work);
+            work.xor_assign(&mask);
+            bits += rle_bits(&work);
+        }
+    }
+
+    // uₜ
+    if params.uncompressed_flag {
+        bits += 1 + count_bits(comp.f) + comp.f;
+    } else {
+        bits += usize::from(!dt);
+        if ct && vt > 0 {
+            mask.or_assign(&xt);
+        }
+        bits += mask.hamming_weight();
+    }
+
+    (bits + 7) / 8 + if comp.packet_crc { 2 } else { 0 }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_compress_output_capacity() {
+        let data: Vec<u8> = (0..90 * 400)
+            .map(|i: usize| (i % 90 / 30 + i / 9000) as u8)
+            .collect();
+        let compressed = compress(&data, 720, 1, 10, 20, 50).unwrap();
+        assert!(compressed.len() * 4 < data.len());
+        assert!(compressed.capacity() <= 2 * compressed.len());
+
+        // Capacity reserved by the caller is kept
+        let mut output = Vec::with_capacity(data.len());
+        compress_into(&data, &mut output, 720, 1, 10, 20, 50).unwrap();
+        assert_eq!(output, compressed);
+        assert!(output.capacity() >= data.len());
+
+        // Spans match packets encoded one by one
+        let (indexed, spans) = compress_indexed(&data, 720, 1, 10, 20, 50).unwrap();
+        assert_eq!(indexed, compressed);
+        let mut comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+        for (packet, span) in data.chunks(90).zip(&spans) {
+            let params = comp.next_params();
+            let out = comp
+                .compress_packet(&BitVector::from_bytes(packet, 720), &params)
+                .unwrap();
+            assert_eq!(span.bit_length, out.len());
+            assert_eq!(
+                &compressed[span.byte_offset..span.byte_offset + span.byte_length],
+                out.to_bytes()
+            );
+        }
+    }
+
     #[test]
     fn test_compress_empty_input() {
         let result = compress(&[], 720, 1, 10, 20, 50);
@@ -443,10 +2686,15 @@ mod tests {
     }
 
     #[test]
-    fn test_compress_invalid_packet_size_not_byte_aligned() {
-        let data = vec![0u8; 90];
-        let result = compress(&data, 719, 1, 10, 20, 50);
-        assert!(matches!(result, Err(PocketError::InvalidPacketSize(719))));
+    fn test_compress_packet_size_not_byte_aligned() {
+        // 719-bit packets occupy 90 bytes; the last bit of each is padding
+        let mut data: Vec<u8> = (0..90 * 12u32).map(|i| (i % 7 + i / 90) as u8).collect();
+        let compressed = compress(&data, 719, 1, 10, 20, 50).unwrap();
+        for packet in data.chunks_mut(90) {
+            packet[89] ^= 1;
+        }
+        assert_eq!(compress(&data, 719, 1, 10, 20, 50).unwrap(), compressed);
+        assert!(compress(&data[..100], 719, 1, 10, 20, 50).is_err());
     }
 
     #[test]
@@ -501,4 +2749,960 @@ mod tests {
         let compressed = result.unwrap();
         assert!(!compressed.is_empty());
     }
+
+    #[test]
+    fn test_compress_with_stats() {
+        let data: Vec<u8> = (0..800u32).map(|i| (i % 7 + i / 80) as u8).collect();
+        let (output, stats) = compress_with_stats(&data, 64, 1, 10, 20, 50).unwrap();
+        assert_eq!(output, compress(&data, 64, 1, 10, 20, 50).unwrap());
+
+        // One Xt RLE per packet plus one per full mask
+        assert!(stats.rle_terminators >= 100);
+        // Uncompressed packets encode COUNT(F = 64) in the long form
+        assert!(stats.count_long >= 1);
+        assert!(stats.be_bits > 0);
+
+        let (_, empty) = compress_with_stats(&[], 64, 1, 10, 20, 50).unwrap();
+        assert_eq!(empty, OpcodeStats::default());
+    }
+
+    #[test]
+    fn test_count_bits_matches_encoder() {
+        for a in [1usize, 2, 33, 34, 35, 100, 720, 65535] {
+            let mut output = BitBuffer::new();
+            crate::encode::count_encode(&mut output, a as u32).unwrap();
+            assert_eq!(count_bits(a), output.len(), "COUNT({a})");
+        }
+    }
+
+    #[test]
+    fn test_upper_bound_holds() {
+        // Alternating bit patterns maximise mask churn and RLE cost
+        let mut data = Vec::new();
+        let mut state = 0x1234_5678u32;
+        for i in 0..300u32 {
+            for _ in 0..16 {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                data.push(if i % 2 == 0 {
+                    0x55
+                } else {
+                    (state >> 16) as u8
+                });
+            }
+        }
+
+        for config in [
+            CompressOptions::default(),
+            CompressOptions::new(7, 1, 1, 1),
+            CompressOptions::new(0, 3, 5, 7),
+        ] {
+            let stream = compress(
+                &data,
+                128,
+                config.robustness,
+                config.pt_limit,
+                config.ft_limit,
+                config.rt_limit,
+            )
+            .unwrap();
+            let bound = compressed_stream_upper_bound(300, 128, &config);
+            assert!(stream.len() <= bound, "{} > {bound}", stream.len());
+        }
+    }
+
+    #[test]
+    fn test_upper_bound_schedule() {
+        assert_eq!(
+            compressed_stream_upper_bound(0, 720, &CompressOptions::default()),
+            0
+        );
+
+        // Scheduled full masks and uncompressed packets cost more
+        let sparse = compressed_stream_upper_bound(100, 720, &CompressOptions::new(1, 50, 50, 50));
+        let dense = compressed_stream_upper_bound(100, 720, &CompressOptions::new(1, 1, 1, 1));
+        assert!(sparse < dense);
+
+        // Invalid parameters fall back to the all-flags bound
+        let invalid = compressed_stream_upper_bound(100, 720, &CompressOptions::new(9, 1, 1, 1));
+        assert!(invalid >= dense);
+    }
+
+    #[test]
+    fn test_estimate_packet_size() {
+        let data: Vec<u8> = (0..90 * 120u32)
+            .map(|i| (i % 90 / 30 + i / 2700 + (i * 7 % 13) * u32::from(i % 90 > 80)) as u8)
+            .collect();
+        let flag_sets: Vec<CompressionParams> = (0..8u8)
+            .map(|bits| CompressionParams {
+                new_mask_flag: bits & 1 != 0,
+                send_mask_flag: bits & 2 != 0,
+                uncompressed_flag: bits & 4 != 0,
+            })
+            .collect();
+
+        for robustness in [0, 1, 3] {
+            let mut comp = Compressor::new(720, None, robustness, 10, 20, 50).unwrap();
+            comp.set_packet_crc(robustness == 3);
+            let mut out = Vec::new();
+            for packet in data.chunks(90) {
+                let input = BitVector::from_bytes(packet, 720);
+
+                // Every flag combination prices exactly and leaves the state alone
+                let state = comp.save_state();
+                for flags in &flag_sets {
+                    let estimate = estimate_packet_size(&comp, &input, flags);
+                    let actual = comp.clone().compress_packet_into(&input, flags, &mut out);
+                    assert_eq!(estimate, actual.unwrap(), "R = {robustness}, {flags:?}");
+                }
+                assert_eq!(comp.save_state(), state);
+
+                let params = comp.next_params();
+                comp.compress_packet_into(&input, &params, &mut out)
+                    .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_heap_size() {
+        let comp = Compressor::new(720, None, 7, 10, 20, 50).unwrap();
+        let retained = comp.heap_size();
+        assert!(retained >= 21 * 92);
+        assert!(Compressor::peak_heap_size(720) >= retained);
+        assert!(Compressor::peak_heap_size(8000) > Compressor::peak_heap_size(720));
+    }
+
+    #[test]
+    fn test_shared_initial_mask() {
+        let mut initial = BitVector::new(64);
+        initial.set_bit(3, 1);
+        let shared = Arc::new(initial.clone());
+        let packets: Vec<BitVector> = (0..10u8)
+            .map(|i| BitVector::from_bytes(&[i % 3, 0, i, 0, 0, 0, 0, 1], 64))
+            .collect();
+        let run = |comp: &mut Compressor| -> Vec<u8> {
+            packets
+                .iter()
+                .flat_map(|p| {
+                    let params = comp.next_params();
+                    comp.compress_packet(p, &params).unwrap().to_bytes()
+                })
+                .collect()
+        };
+
+        let mut contexts: Vec<Compressor> = (0..4)
+            .map(|_| Compressor::with_shared_mask(64, Arc::clone(&shared), 1, 10, 20, 50).unwrap())
+            .collect();
+        assert_eq!(Arc::strong_count(&shared), 5);
+
+        // Same output as a compressor owning its own copy
+        let mut owned = Compressor::new(64, Some(&initial), 1, 10, 20, 50).unwrap();
+        let expected = run(&mut owned);
+        for comp in &mut contexts {
+            assert_eq!(run(comp), expected);
+        }
+
+        // Shared copy is not charged to each context
+        assert_eq!(
+            contexts[0].heap_size() + initial.heap_size(),
+            owned.heap_size()
+        );
+
+        let short = Arc::new(BitVector::new(32));
+        assert!(Compressor::with_shared_mask(64, short, 1, 10, 20, 50).is_err());
+    }
+
+    #[test]
+    fn test_ratio_monitor_hook() {
+        use crate::monitor::{RatioMonitor, RatioWindow};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let fired = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&fired);
+        let mut comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+        comp.set_monitor(
+            RatioMonitor::new(RatioWindow::Packets(20), 3.0)
+                .on_degraded(move |_| flag.store(true, Ordering::SeqCst)),
+        );
+
+        // Constant packets compress well
+        let steady = BitVector::from_bytes(&[0x5A; 90], 720);
+        for _ in 0..40 {
+            let params = comp.next_params();
+            comp.compress_packet(&steady, &params).unwrap();
+        }
+        assert!(comp.monitor().unwrap().ratio().unwrap() > 3.0);
+        assert!(!fired.load(Ordering::SeqCst));
+
+        // Layout change: every packet differs
+        for i in 0..40u32 {
+            let bytes: Vec<u8> = (0..90u32)
+                .map(|j| (i.wrapping_mul(2_654_435_761) ^ j.wrapping_mul(40_503)) as u8)
+                .collect();
+            let params = comp.next_params();
+            comp.compress_packet(&BitVector::from_bytes(&bytes, 720), &params)
+                .unwrap();
+        }
+        assert!(fired.load(Ordering::SeqCst));
+        assert!(comp.monitor().unwrap().is_degraded());
+
+        comp.reset();
+        assert_eq!(comp.monitor().unwrap().ratio(), None);
+    }
+
+    #[test]
+    fn test_refresh_policy() {
+        let packet = BitVector::from_bytes(&[7; 8], 64);
+        // Schedule refreshes every 20 packets (ft = rt = 20); force every 7
+        let mut comp = Compressor::new(64, None, 1, 10, 20, 20).unwrap();
+        comp.set_refresh_policy(RefreshPolicy::packets(7));
+        let mut forced = Vec::new();
+        for t in 0..45 {
+            let params = comp.next_params();
+            comp.compress_packet(&packet, &params).unwrap();
+            if params.send_mask_flag && params.uncompressed_flag {
+                forced.push(t);
+            }
+        }
+        assert_eq!(forced, vec![0, 1, 8, 15, 20, 27, 34, 40]);
+
+        // Applies even with the periodic schedule disabled
+        let mut comp = Compressor::new(64, None, 1, 0, 0, 0).unwrap();
+        comp.set_refresh_policy(RefreshPolicy::packets(5));
+        let flags: Vec<bool> = (0..11)
+            .map(|_| {
+                let params = comp.next_params();
+                comp.compress_packet(&packet, &params).unwrap();
+                params.uncompressed_flag
+            })
+            .collect();
+        assert_eq!(flags.iter().filter(|&&f| f).count(), 2);
+        assert!(flags[5] && flags[10]);
+
+        // Elapsed wall-clock time forces the next packet
+        let mut comp = Compressor::new(64, None, 1, 10, 20, 50).unwrap();
+        comp.set_refresh_policy(RefreshPolicy::age(Duration::ZERO));
+        for _ in 0..3 {
+            assert!(comp.next_params().uncompressed_flag);
+            comp.t += 1;
+        }
+
+        // Upper bound accounts for forced refreshes
+        let options = CompressOptions {
+            refresh: RefreshPolicy::packets(3),
+            ..CompressOptions::default()
+        };
+        let data = [0x5Au8; 90 * 30];
+        let mut comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+        comp.set_refresh_policy(options.refresh);
+        let mut size = 0;
+        for packet in data.chunks(90) {
+            let params = comp.next_params();
+            size += comp
+                .compress_packet(&BitVector::from_bytes(packet, 720), &params)
+                .unwrap()
+                .to_bytes()
+                .len();
+        }
+        assert!(size <= compressed_stream_upper_bound(30, 720, &options));
+        assert!(
+            compressed_stream_upper_bound(30, 720, &options)
+                > compressed_stream_upper_bound(30, 720, &CompressOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_rle_bits() {
+        for bits in [[0u8; 8], [0x80, 0, 0, 0, 0, 0, 0, 1], [0x5A; 8], [0xFF; 8]] {
+            let v = BitVector::from_bytes(&bits, 64);
+            let mut buf = BitBuffer::new();
+            crate::rle_encode(&mut buf, &v).unwrap();
+            assert_eq!(rle_bits(&v), buf.len());
+        }
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_adaptive_policy() {
+        // Byte 3 counts for 30 packets, then the stream goes quiet; stale
+        // bits are only counted after a new mask restarts the build vector
+        let data: Vec<u8> = (0u8..80)
+            .flat_map(|n| [1, 2, 3, n.min(30), 5, 6, 7, 8])
+            .collect();
+        let compress_adaptive = |adaptive: AdaptivePolicy| {
+            let mut comp = CompressorBuilder::new(64)
+                .pt_limit(20)
+                .ft_limit(1000)
+                .rt_limit(1000)
+                .adaptive(adaptive)
+                .build()
+                .unwrap();
+            let mut output = Vec::new();
+            let mut flags = Vec::new();
+            for packet in data.chunks(8) {
+                let params = comp.next_params();
+                comp.compress_packet_into(&BitVector::from_bytes(packet, 64), &params, &mut output)
+                    .unwrap();
+                flags.push(params);
+            }
+            (output, flags)
+        };
+
+        // Stale mask bits are dropped by an adaptive new mask
+        let (fixed, _) = compress_adaptive(AdaptivePolicy::default());
+        let (adaptive, flags) = compress_adaptive(AdaptivePolicy {
+            stale_bits: Some(2),
+            min_interval: 5,
+            full_mask_on_change: false,
+        });
+        let new_masks: Vec<usize> = (0..flags.len())
+            .filter(|&t| flags[t].new_mask_flag)
+            .collect();
+        // Scheduled at 20, 40 and 60; the quiet counter is dropped at 45
+        assert_eq!(new_masks, vec![20, 40, 45, 60]);
+        assert!(adaptive.len() < fixed.len());
+        assert_eq!(crate::decompress(&adaptive, 64, 1).unwrap(), data);
+
+        // A burst of mask changes is followed by a full mask
+        let mut comp = Compressor::new(64, None, 0, 1000, 1000, 1000).unwrap();
+        comp.set_adaptive_policy(AdaptivePolicy {
+            full_mask_on_change: true,
+            ..AdaptivePolicy::default()
+        });
+        let mut output = Vec::new();
+        let mut full_masks = Vec::new();
+        for t in 0..10u8 {
+            let packet = if t == 5 { [0x55; 8] } else { [0; 8] };
+            let params = comp.next_params();
+            if params.send_mask_flag {
+                full_masks.push(t);
+            }
+            comp.compress_packet_into(&BitVector::from_bytes(&packet, 64), &params, &mut output)
+                .unwrap();
+        }
+        assert_eq!(full_masks, vec![0, 6]);
+        let mut expected = vec![0u8; 80];
+        expected[40..48].fill(0x55);
+        assert_eq!(crate::decompress(&output, 64, 0).unwrap(), expected);
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_independent_limits() {
+        let data: Vec<u8> = (0..8 * 60u32).map(|i| (i / 8 % 7) as u8).collect();
+        let flags = |pt, ft, rt| {
+            let mut comp = Compressor::new(64, None, 1, pt, ft, rt).unwrap();
+            let packet = BitVector::new(64);
+            (0..60).map(move |_| {
+                let params = comp.next_params();
+                comp.compress_packet(&packet, &params).unwrap();
+                params
+            })
+        };
+
+        // Reliable link: no uncompressed packets after the first R + 1,
+        // but masks are still renewed and sent
+        let params: Vec<_> = flags(10, 20, 0).collect();
+        assert!(params[..2].iter().all(|p| p.uncompressed_flag));
+        assert!(params[2..].iter().all(|p| !p.uncompressed_flag));
+        assert_eq!(params.iter().filter(|p| p.new_mask_flag).count(), 5);
+        assert_eq!(params.iter().filter(|p| p.send_mask_flag).count(), 4);
+
+        assert!(flags(0, 20, 50).all(|p| !p.new_mask_flag));
+        assert_eq!(flags(10, 0, 50).filter(|p| p.send_mask_flag).count(), 2);
+        assert_eq!(flags(0, 0, 0).filter(|p| p.send_mask_flag).count(), 0);
+
+        for limits in [(10, 20, 0), (0, 20, 50), (10, 0, 0)] {
+            let compressed = compress(&data, 64, 1, limits.0, limits.1, limits.2).unwrap();
+            assert_eq!(crate::decompress(&compressed, 64, 1).unwrap(), data);
+        }
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_seeded_start() {
+        // Byte 7 counts; the ground already holds the mask and a reference
+        let data: Vec<u8> = (0u8..40).flat_map(|n| [9, 8, 7, 6, 5, 4, 3, n]).collect();
+        let mut mask = BitVector::new(64);
+        for i in 56..64 {
+            mask.set_bit(i, 1);
+        }
+        let reference = BitVector::from_bytes(&[9, 8, 7, 6, 5, 4, 3, 0], 64);
+        let options = CompressorBuilder::new(64)
+            .robustness(2)
+            .initial_mask(&mask)
+            .initial_reference(&reference);
+
+        let mut comp = options.clone().start(StartMode::Seeded).build().unwrap();
+        let params = comp.next_params();
+        assert!(!params.send_mask_flag && !params.uncompressed_flag);
+        comp.reset();
+
+        let seeded = compress_with(&data, &options.clone().start(StartMode::Seeded)).unwrap();
+        let refreshed = compress_with(&data, &options).unwrap();
+        assert!(seeded.len() < refreshed.len());
+
+        let decomp = crate::DecompressorBuilder::new(64)
+            .robustness(2)
+            .initial_mask(&mask)
+            .initial_reference(&reference);
+        assert_eq!(
+            decomp.build().unwrap().decompress_stream(&seeded).unwrap(),
+            data
+        );
+
+        // The first packet must be predictable from the reference
+        let mut other = data.clone();
+        other[0] = 0xFF;
+        assert!(matches!(
+            compress_with(&other, &options.start(StartMode::Seeded)),
+            Err(PocketError::InvalidParameter(_))
+        ));
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_epoch() {
+        let data: Vec<u8> = (0..8 * 35u32).map(|i| (i % 3 + i / 8 % 5) as u8).collect();
+        let builder = CompressorBuilder::new(64).robustness(1).epoch(10);
+
+        // Byte offset of every packet
+        let mut comp = builder.build().unwrap();
+        let mut output = Vec::new();
+        let mut offsets = Vec::new();
+        for packet in data.chunks(8) {
+            offsets.push(output.len());
+            let params = comp.next_params();
+            if offsets.len() % 10 == 1 {
+                assert!(params.send_mask_flag && params.uncompressed_flag);
+            }
+            let input = BitVector::from_bytes(packet, 64);
+            comp.compress_packet_into(&input, &params, &mut output)
+                .unwrap();
+        }
+        assert_eq!(comp.time_step(), 5);
+        assert_eq!(compress_with(&data, &builder).unwrap(), output);
+
+        // Whole stream, and one epoch on its own
+        let decomp = crate::DecompressorBuilder::new(64).robustness(1);
+        assert_eq!(
+            decomp
+                .clone()
+                .epoch(10)
+                .build()
+                .unwrap()
+                .decompress_stream(&output)
+                .unwrap(),
+            data
+        );
+        let epoch = &output[offsets[20]..offsets[30]];
+        assert_eq!(
+            decomp.build().unwrap().decompress_stream(epoch).unwrap(),
+            &data[8 * 20..8 * 30]
+        );
+
+        assert!(builder.epoch(0).build().is_err());
+    }
+
+    #[test]
+    fn test_state_accessors() {
+        let mut comp = Compressor::new(64, None, 1, 10, 20, 50).unwrap();
+        assert_eq!(comp.time_step(), 0);
+        assert_eq!(
+            (comp.pt_counter(), comp.ft_counter(), comp.rt_counter()),
+            (10, 20, 50)
+        );
+
+        // Only the low nibble of the first byte changes
+        for t in 0..15u8 {
+            let input = BitVector::from_bytes(&[t, 1, 2, 3, 4, 5, 6, 7], 64);
+            let params = comp.next_params();
+            comp.compress_packet(&input, &params).unwrap();
+        }
+        assert_eq!(comp.time_step(), 15);
+        assert_eq!(
+            (comp.pt_counter(), comp.ft_counter(), comp.rt_counter()),
+            (6, 6, 36)
+        );
+        assert_eq!(comp.mask().hamming_weight(), 4);
+        // Rebuilt since the new mask at t = 10: 11 → 14 only flips 3 bits
+        assert_eq!(comp.build().hamming_weight(), 3);
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_compress_packet_with_flags() {
+        let data: Vec<u8> = (0..8 * 40u32).map(|i| (i / 8 % 5) as u8).collect();
+        let mut comp = Compressor::new(64, None, 1, 10, 20, 50).unwrap();
+        let mut scheduled = comp.clone();
+        let mut output = Vec::new();
+        for (t, packet) in data.chunks(8).enumerate() {
+            let input = BitVector::from_bytes(packet, 64);
+            let expected = scheduled.next_params();
+            scheduled.compress_packet(&input, &expected).unwrap();
+
+            // Handover before packet 25: full mask, uncompressed
+            let packet = if t == 25 {
+                let params = CompressionParams {
+                    new_mask_flag: false,
+                    send_mask_flag: true,
+                    uncompressed_flag: true,
+                };
+                comp.compress_packet_with_flags(&input, &params).unwrap()
+            } else {
+                let params = comp.next_params();
+                assert_eq!(params, expected, "schedule kept its cadence");
+                comp.compress_packet(&input, &params).unwrap()
+            };
+            output.extend(packet.to_bytes());
+        }
+        assert_eq!(comp.last_refresh_t, 25);
+        assert_eq!(crate::decompress(&output, 64, 1).unwrap(), data);
+    }
+
+    #[test]
+    fn test_streaming_matches_compress() {
+        let data: Vec<u8> = (0..90 * 25u32)
+            .map(|i| (i / 90 % 7) as u8 ^ (i % 5) as u8)
+            .collect();
+        let mut comp = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        let mut streamed = Vec::new();
+        for packet in data.chunks(90) {
+            let params = comp.next_params();
+            let input = BitVector::from_bytes(packet, 720);
+            streamed.extend(comp.compress_packet(&input, &params).unwrap().to_bytes());
+        }
+        assert_eq!(streamed, compress(&data, 720, 2, 10, 20, 50).unwrap());
+    }
+
+    #[test]
+    fn test_compress_with_builder() {
+        let data: Vec<u8> = (0..90 * 30u32).map(|i| (i / 90 % 3) as u8).collect();
+        let options = CompressorBuilder::new(720)
+            .robustness(2)
+            .pt_limit(20)
+            .ft_limit(50)
+            .rt_limit(100);
+        assert_eq!(
+            compress_with(&data, &options).unwrap(),
+            compress(&data, 720, 2, 20, 50, 100).unwrap()
+        );
+
+        // Defaults match CompressOptions::default()
+        assert_eq!(
+            compress_with(&data, &CompressorBuilder::new(720)).unwrap(),
+            compress(&data, 720, 1, 10, 20, 50).unwrap()
+        );
+
+        // Validation
+        assert!(matches!(
+            compress_with(&data, &options.clone().robustness(8)),
+            Err(PocketError::InvalidRobustness(8))
+        ));
+        assert!(compress_with(&data, &options.clone().ft_limit(0)).is_ok());
+        assert!(compress_with(&data, &options.clone().pt_limit(0).ft_limit(0).rt_limit(0)).is_ok());
+        assert!(matches!(
+            compress_with(&data, &options.clone().refresh(RefreshPolicy::packets(0))),
+            Err(PocketError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            compress_with(&data, &CompressorBuilder::new(0)),
+            Err(PocketError::InvalidPacketSize(0))
+        ));
+        assert!(compress_with(&data[..100], &options).is_err());
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_compress_with_mask() {
+        // Bytes 5-7 of each packet vary
+        let data: Vec<u8> = (0u8..60)
+            .flat_map(|n| [0xAA, 0xBB, 0xCC, 0xDD, 0x11, n, n.wrapping_mul(7), n ^ 0x5A])
+            .collect();
+        let mut mask = BitVector::new(64);
+        for i in 40..64 {
+            mask.set_bit(i, 1);
+        }
+
+        let seeded = compress_with_mask(&data, 64, 1, 10, 20, 50, Some(&mask)).unwrap();
+        assert_eq!(
+            crate::decompress_with_mask(&seeded, 64, 1, Some(&mask)).unwrap(),
+            data
+        );
+        let cold = compress(&data, 64, 1, 10, 20, 50).unwrap();
+        assert_ne!(seeded, cold);
+        assert_eq!(
+            compress_with_mask(&data, 64, 1, 10, 20, 50, None).unwrap(),
+            cold
+        );
+        assert!(matches!(
+            compress_with_mask(&data, 64, 1, 10, 20, 50, Some(&BitVector::new(32))),
+            Err(PocketError::InvalidInputLength { .. })
+        ));
+    }
+
+    #[test]
+    fn test_compress_iter() {
+        let data: Vec<u8> = (0..90 * 30u32).map(|i| (i / 90 % 3) as u8).collect();
+        let options = CompressOptions {
+            robustness: 2,
+            ..CompressOptions::default()
+        };
+        let expected = compress(&data, 720, 2, 10, 20, 50).unwrap();
+
+        // Borrowed slices, one chunk per packet
+        let chunks: Vec<Vec<u8>> = compress_iter(data.chunks(90), &options)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(chunks.len(), 30);
+        assert_eq!(chunks.concat(), expected);
+
+        // Owned packets from a generator
+        let generated = (0..30).map(|i| data[i * 90..(i + 1) * 90].to_vec());
+        assert_eq!(
+            compress_iter(generated, &options)
+                .unwrap()
+                .concat()
+                .unwrap(),
+            expected
+        );
+
+        // Mismatched packet length stops the iterator
+        let mut iter = compress_iter([&data[..90], &data[..45], &data[..90]], &options).unwrap();
+        assert!(iter.next().unwrap().is_ok());
+        assert!(matches!(
+            iter.next(),
+            Some(Err(PocketError::InvalidInputLength {
+                expected: 90,
+                actual: 45
+            }))
+        ));
+        assert!(iter.next().is_none());
+
+        let invalid = CompressOptions {
+            robustness: 8,
+            ..options.clone()
+        };
+        assert!(compress_iter(data.chunks(90), &invalid).is_err());
+        let sealed = CompressOptions {
+            stream_checksum: true,
+            ..options.clone()
+        };
+        assert!(matches!(
+            compress_iter(data.chunks(90), &sealed),
+            Err(PocketError::InvalidParameter(_))
+        ));
+        assert!(compress_iter(std::iter::empty::<&[u8]>(), &options)
+            .unwrap()
+            .concat()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_extended_packet_length() {
+        // 72000-bit packets with changes 71990 bits apart need COUNT values
+        // beyond 65535
+        let data: Vec<u8> = (0..8u8)
+            .flat_map(|t| {
+                let mut packet = vec![0xA5; 9000];
+                packet[0] = t;
+                packet[8999] = t.wrapping_mul(3);
+                packet
+            })
+            .collect();
+        let options = CompressorBuilder::new(72_000).robustness(1).pt_limit(3);
+        assert!(matches!(
+            options.build(),
+            Err(PocketError::InvalidPacketSize(72_000))
+        ));
+        let stream = compress_with(&data, &options.clone().extended(true)).unwrap();
+        let decoded = crate::DecompressorBuilder::new(72_000)
+            .robustness(1)
+            .extended(true)
+            .build()
+            .unwrap()
+            .decompress_stream(&stream)
+            .unwrap();
+        assert_eq!(decoded, data);
+        assert!(crate::DecompressorBuilder::new(72_000)
+            .robustness(1)
+            .build()
+            .is_err());
+        assert!(
+            Compressor::new_extended(MAX_EXTENDED_PACKET_LENGTH + 1, None, 1, 1, 1, 1).is_err()
+        );
+
+        // Standard sizes produce standard streams in extended mode
+        let data = &data[..90 * 8];
+        let standard = CompressorBuilder::new(720).robustness(1);
+        assert_eq!(
+            compress_with(data, &standard.clone().extended(true)).unwrap(),
+            compress_with(data, &standard).unwrap()
+        );
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_exclude_ranges() {
+        // A 16-bit sequence counter at bits 32..48 and a fine time byte at
+        // bits 56..64, the rest slowly changing
+        let data: Vec<u8> = (0..200u16)
+            .flat_map(|t| {
+                let mut packet = vec![0xA5; 90];
+                packet[4..6].copy_from_slice(&(t * 37).to_be_bytes());
+                packet[7] = u8::try_from(t % 256).unwrap();
+                packet[20] = u8::try_from(t / 25).unwrap();
+                packet
+            })
+            .collect();
+        let options = CompressorBuilder::new(720)
+            .robustness(1)
+            .exclude_ranges(&[32..48, 56..64]);
+        let excluded = compress_with(&data, &options).unwrap();
+        let learned = compress_with(&data, &CompressorBuilder::new(720).robustness(1)).unwrap();
+        assert!(excluded.len() < learned.len());
+        assert_eq!(crate::decompress(&excluded, 720, 1).unwrap(), data);
+
+        // The excluded bits never leave the mask, even across new masks
+        let mut comp = options.build().unwrap();
+        for packet in data.chunks(90) {
+            let params = comp.next_params();
+            comp.compress_packet(&BitVector::from_bytes(packet, 720), &params)
+                .unwrap();
+            assert!((32..48)
+                .chain(56..64)
+                .all(|bit| comp.mask().get_bit(bit) == 1));
+        }
+        assert_eq!(comp.excluded().unwrap().hamming_weight(), 24);
+
+        assert!(options
+            .clone()
+            .exclude_ranges(&[0..8, 700..721])
+            .build()
+            .is_err());
+        assert!(options.exclude_ranges(&[0..8, 5..5]).build().is_err());
+        assert!(comp.set_excluded(Some(&BitVector::new(8))).is_err());
+    }
+
+    #[test]
+    fn test_compress_cancellable() {
+        let data: Vec<u8> = (0..90 * 30u32).map(|i| (i / 90 % 3) as u8).collect();
+        let cancel = AtomicBool::new(false);
+        assert_eq!(
+            compress_cancellable(&data, 720, 1, 10, 20, 50, &cancel).unwrap(),
+            compress(&data, 720, 1, 10, 20, 50).unwrap()
+        );
+
+        cancel.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            compress_cancellable(&data, 720, 1, 10, 20, 50, &cancel),
+            Err(PocketError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_compress_with_progress() {
+        let data: Vec<u8> = (0..90 * 25u32).map(|i| (i / 90 % 3) as u8).collect();
+        let expected = compress(&data, 720, 1, 10, 20, 50).unwrap();
+
+        let options = CompressorBuilder::new(720);
+        let mut reports = Vec::new();
+        let output = compress_with_progress(&data, &options, 10, |p| reports.push(p)).unwrap();
+        assert_eq!(output, expected);
+
+        // Every 10 packets, plus the final 5
+        let packets: Vec<usize> = reports.iter().map(|p| p.packets).collect();
+        assert_eq!(packets, vec![10, 20, 25]);
+        let last = reports.last().unwrap();
+        assert_eq!(last.bytes_read, data.len());
+        assert_eq!(last.bytes_written, expected.len());
+        assert!(last.ratio > 1.0);
+
+        // No duplicate final report on a multiple of `every`
+        let mut count = 0;
+        compress_with_progress(&data, &options, 5, |_| count += 1).unwrap();
+        assert_eq!(count, 5);
+        assert!(matches!(
+            compress_with_progress(&data, &options, 0, |_| {}),
+            Err(PocketError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_compress_into() {
+        let data: Vec<u8> = (0..90 * 30u32).map(|i| (i / 90 % 3) as u8).collect();
+        let expected = compress(&data, 720, 1, 10, 20, 50).unwrap();
+
+        let mut output = Vec::new();
+        assert_eq!(
+            compress_into(&data, &mut output, 720, 1, 10, 20, 50).unwrap(),
+            expected.len()
+        );
+        assert_eq!(output, expected);
+
+        // Reused buffer keeps its allocation
+        let capacity = output.capacity();
+        output.clear();
+        compress_into(&data, &mut output, 720, 1, 10, 20, 50).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(output.capacity(), capacity);
+
+        // Appends; errors leave the buffer untouched
+        compress_into(&data[..90], &mut output, 720, 1, 10, 20, 50).unwrap();
+        assert_eq!(
+            output.len(),
+            expected.len() + compress(&data[..90], 720, 1, 10, 20, 50).unwrap().len()
+        );
+        let len = output.len();
+        assert!(compress_into(&data[..100], &mut output, 720, 1, 10, 20, 50).is_err());
+        assert_eq!(output.len(), len);
+
+        // Packet-level variant matches compress_packet
+        let mut a = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+        let mut b = a.clone();
+        let mut out = Vec::new();
+        for packet in data.chunks(90) {
+            let input = BitVector::from_bytes(packet, 720);
+            let params = a.next_params();
+            let expected = a.compress_packet(&input, &params).unwrap().to_bytes();
+            out.clear();
+            let params = b.next_params();
+            assert_eq!(
+                b.compress_packet_into(&input, &params, &mut out).unwrap(),
+                expected.len()
+            );
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn test_compress_indexed() {
+        let data: Vec<u8> = (0..90 * 30u32).map(|i| (i / 90 % 3) as u8).collect();
+        let (output, spans) = compress_indexed(&data, 720, 1, 10, 20, 50).unwrap();
+        assert_eq!(output, compress(&data, 720, 1, 10, 20, 50).unwrap());
+        assert_eq!(spans.len(), 30);
+
+        // Spans tile the stream and match packet-by-packet compression
+        let mut comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+        let mut offset = 0;
+        for (span, packet) in spans.iter().zip(data.chunks(90)) {
+            let params = comp.next_params();
+            let expected = comp
+                .compress_packet(&BitVector::from_bytes(packet, 720), &params)
+                .unwrap();
+            assert_eq!(span.byte_offset, offset);
+            assert_eq!(span.bit_offset, offset * 8);
+            assert_eq!(span.bit_length, expected.len());
+            assert_eq!(span.byte_length, (expected.len() + 7) / 8);
+            assert_eq!(
+                &output[span.byte_offset..span.byte_offset + span.byte_length],
+                expected.to_bytes()
+            );
+            offset += span.byte_length;
+        }
+        assert_eq!(offset, output.len());
+        assert!(compress_indexed(&data, 721, 1, 10, 20, 50).is_err());
+    }
+
+    #[test]
+    fn test_save_restore_state() {
+        let data: Vec<u8> = (0..90 * 40u32)
+            .map(|i| (i / 90 % 3 + i % 11) as u8)
+            .collect();
+        let expected = compress(&data, 720, 2, 10, 20, 50).unwrap();
+
+        // Compress 17 packets, "restart", continue from the snapshot
+        let mut comp = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        let mut output = Vec::new();
+        for packet in data[..17 * 90].chunks(90) {
+            let params = comp.next_params();
+            comp.compress_packet_into(&BitVector::from_bytes(packet, 720), &params, &mut output)
+                .unwrap();
+        }
+        let snapshot = comp.save_state();
+
+        let mut resumed = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        resumed.restore_state(&snapshot).unwrap();
+        for packet in data[17 * 90..].chunks(90) {
+            let params = resumed.next_params();
+            resumed
+                .compress_packet_into(&BitVector::from_bytes(packet, 720), &params, &mut output)
+                .unwrap();
+        }
+        assert_eq!(output, expected);
+        assert_eq!(resumed.save_state(), {
+            let mut full = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+            let _ = compress_all(&mut full, &data);
+            full.save_state()
+        });
+
+        // Mismatched configuration and corrupt snapshots leave the state alone
+        let mut other = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+        let before = other.save_state();
+        assert!(matches!(
+            other.restore_state(&snapshot),
+            Err(PocketError::RobustnessMismatch { .. })
+        ));
+        let mut corrupted = snapshot.clone();
+        corrupted[20] ^= 0x40;
+        assert!(other.restore_state(&corrupted).is_err());
+        assert!(Compressor::new(64, None, 2, 10, 20, 50)
+            .unwrap()
+            .restore_state(&snapshot)
+            .is_err());
+        assert_eq!(other.save_state(), before);
+    }
+
+    fn compress_all(comp: &mut Compressor, data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        for packet in data.chunks(comp.packet_size() / 8) {
+            let params = comp.next_params();
+            let input = BitVector::from_bytes(packet, comp.packet_size());
+            comp.compress_packet_into(&input, &params, &mut output)
+                .unwrap();
+        }
+        output
+    }
+
+    #[test]
+    fn test_compress_with_packet_stats() {
+        let data: Vec<u8> = (0..90 * 60u32)
+            .map(|i| (i / 90 % 3 + i % 13) as u8)
+            .collect();
+        let (output, stats) = compress_with_packet_stats(&data, 720, 1, 10, 20, 50).unwrap();
+        let (expected, opcodes) = compress_with_stats(&data, 720, 1, 10, 20, 50).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(stats.opcodes, opcodes);
+        assert_eq!(stats.packets.len(), 60);
+
+        // Sizes add up, first R + 1 packets and every rt-th are uncompressed
+        let total: usize = stats.packets.iter().map(|p| p.compressed_bytes).sum();
+        assert_eq!(total, output.len());
+        assert!(stats.packets[..2]
+            .iter()
+            .all(|p| p.params.uncompressed_flag));
+        assert!(stats.packets[0].compressed_bits > 720);
+        assert_eq!(stats.uncompressed_packets(), 3);
+        assert!(stats.full_mask_packets() >= 4);
+
+        let ratio = stats.ratio().unwrap();
+        assert!((ratio - (data.len() as f64 / output.len() as f64)).abs() < 1e-9);
+        assert!(stats.packets[0].cumulative_ratio < 1.0);
+        assert!(stats.packets.iter().any(|p| p.mask_weight > 0));
+
+        let (_, empty) = compress_with_packet_stats(&[], 720, 1, 10, 20, 50).unwrap();
+        assert_eq!(empty.ratio(), None);
+        assert_eq!(
+            empty.to_json(),
+            "{\"packets\":0,\"ratio\":null,\"uncompressed_packets\":0,\"full_mask_packets\":0,\
+             \"opcodes\":{\"count_short\":0,\"count_medium\":0,\"count_long\":0,\
+             \"rle_terminators\":0,\"be_bits\":0},\"per_packet\":[]}"
+        );
+        let json = stats.to_json();
+        assert!(json.starts_with("{\"packets\":60,\"ratio\":"));
+        assert_eq!(json.matches("\"cumulative_ratio\"").count(), 60);
+    }
 }
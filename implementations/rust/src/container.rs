@@ -0,0 +1,1144 @@
+//! Self-delimiting container format for compressed streams.
+//!
+//! A raw POCKET+ stream carries no length or parameter information, so
+//! several streams concatenated back-to-back cannot be told apart. The
+//! container prefixes each independently compressed stream (a *segment*)
+//! with a small header so concatenated files can be split again and each
+//! segment decoded with a fresh decompressor.
+//!
+//! ## Segment Layout (big-endian)
+//!
+//! | Offset | Size | Field                                  |
+//! |--------|------|----------------------------------------|
+//! | 0      | 4    | Magic `"PKP+"`                         |
+//! | 4      | 1    | Format version                         |
+//! | 5      | 1    | Header length in bytes                 |
+//! | 6      | 2    | Flags (see below)                      |
+//! | 8      | 4    | Packet size F in bits                  |
+//! | 12     | 1    | Robustness R                           |
+//! | 13     | 1    | Reserved (0)                           |
+//! | 14     | 4    | Number of packets                      |
+//! | 18     | 4    | Payload length in bytes                |
+//! | 22     | 4    | Index of the first packet in the stream|
+//! | 26     | 4    | Seam check: CRC-32 of the starting mask|
+//! | 30     | 4    | Packets per epoch (only with [`FLAG_EPOCH`]) |
+//!
+//! Segments written by a resumed compressor start at a non-zero packet
+//! index and record a checksum of the compressor mask at the seam, so
+//! adjacent segments can be verified and stitched with [`merge_segments`].
+//!
+//! ## Flags
+//!
+//! - [`FLAG_DEDUPE`] - Runs of bit-identical consecutive input packets
+//!   were compressed once. The compressed packets are followed by a run
+//!   table of `(packet, repeats)` u32 pairs, one per run, and a u32 entry
+//!   count: `packet` indexes the compressed packets of the segment and
+//!   `repeats` is the number of identical copies dropped after it.
+//! - [`FLAG_RAW`] - Packets bypassed POCKET+ and are stored verbatim, so
+//!   raw and compressed streams share one file format for A/B comparisons
+//!   and staged rollouts. Combines with [`FLAG_DEDUPE`].
+//! - [`FLAG_EPOCH`] - The compressor state was reset every N packets (see
+//!   [`Compressor::set_epoch`]), recorded in the header, so every epoch of
+//!   the payload can be decoded on its own.
+//! - [`FLAG_EXTENDED`] - **Non-standard.** The packet size exceeds the
+//!   CCSDS limit of 65535 bits; the payload uses extended COUNT fields
+//!   (see [`Compressor::new_extended`]) and needs an extended decompressor.
+//!   Set only when F > 65535, so every other segment stays standard.
+//!
+//! Readers reject segments carrying flags they do not know.
+//!
+//! The payload (a raw POCKET+ stream) follows the header immediately.
+//! Readers skip header bytes beyond the fields they know, so fields can be
+//! appended in later versions without breaking older readers.
+
+#![allow(clippy::cast_possible_truncation)]
+
+#[cfg(feature = "decoder")]
+use crate::bitreader::BitReader;
+use crate::bitvector::{BitVector, MAX_PACKET_LENGTH};
+#[cfg(feature = "encoder")]
+use crate::compress::{Compressor, RefreshPolicy};
+use crate::crc::crc32;
+#[cfg(feature = "decoder")]
+use crate::decompress::{Decompressor, DecompressorBuilder};
+use crate::error::PocketError;
+use crate::json::JsonWriter;
+
+/// Container magic bytes.
+pub const MAGIC: [u8; 4] = *b"PKP+";
+
+/// Container format version written by this implementation.
+pub const VERSION: u8 = 1;
+
+/// Length of the segment header without optional fields.
+pub const HEADER_LEN: usize = 30;
+
+/// Segment flag: duplicate packets were dropped and repeat counts recorded.
+pub const FLAG_DEDUPE: u16 = 0x0001;
+
+/// Segment flag: packets are stored uncompressed.
+pub const FLAG_RAW: u16 = 0x0002;
+
+/// Segment flag: the compressor state was reset at epoch boundaries.
+pub const FLAG_EPOCH: u16 = 0x0004;
+
+/// Segment flag: non-standard packet size above 65535 bits.
+pub const FLAG_EXTENDED: u16 = 0x0008;
+
+/// Flags understood by this implementation.
+const KNOWN_FLAGS: u16 = FLAG_DEDUPE | FLAG_RAW | FLAG_EPOCH | FLAG_EXTENDED;
+
+/// How a [`SegmentEncoder`] stores packets in the payload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    /// POCKET+ compression.
+    #[default]
+    Pocket,
+    /// Packets stored verbatim (sets [`FLAG_RAW`]).
+    Raw,
+}
+
+/// What a [`SegmentEncoder`] does with a packet identical to the previous one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Compress every packet.
+    #[default]
+    Keep,
+    /// Compress the first packet of a run once and record how many
+    /// identical packets followed it (sets [`FLAG_DEDUPE`]).
+    Drop,
+}
+
+/// Segment header describing one independently compressed stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SegmentHeader {
+    /// Packet size in bits (F).
+    pub packet_size: usize,
+    /// Robustness level (R).
+    pub robustness: u8,
+    /// Segment flags.
+    pub flags: u16,
+    /// Number of packets in the payload.
+    pub num_packets: u32,
+    /// Payload length in bytes.
+    pub payload_len: u32,
+    /// Index of the first packet within the overall stream.
+    pub start_packet: u32,
+    /// CRC-32 of the compressor mask before the first packet.
+    pub seam_check: u32,
+    /// Packets per epoch, written only with [`FLAG_EPOCH`].
+    pub epoch: u32,
+}
+
+impl SegmentHeader {
+    /// Serialize the header.
+    ///
+    /// # Returns
+    /// `HEADER_LEN` bytes (4 more with [`FLAG_EPOCH`]) ready to be followed
+    /// by the payload.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let epoch = self.flags & FLAG_EPOCH != 0;
+        let len = HEADER_LEN + if epoch { 4 } else { 0 };
+        let mut out = Vec::with_capacity(len);
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION);
+        out.push(len as u8);
+        out.extend_from_slice(&self.flags.to_be_bytes());
+        out.extend_from_slice(&(self.packet_size as u32).to_be_bytes());
+        out.push(self.robustness);
+        out.push(0);
+        out.extend_from_slice(&self.num_packets.to_be_bytes());
+        out.extend_from_slice(&self.payload_len.to_be_bytes());
+        out.extend_from_slice(&self.start_packet.to_be_bytes());
+        out.extend_from_slice(&self.seam_check.to_be_bytes());
+        if epoch {
+            out.extend_from_slice(&self.epoch.to_be_bytes());
+        }
+        out
+    }
+
+    /// Parse a header from the start of a byte slice.
+    ///
+    /// # Arguments
+    /// * `data` - Bytes starting at a segment header
+    ///
+    /// # Returns
+    /// The parsed header and the number of header bytes consumed.
+    pub fn parse(data: &[u8]) -> Result<(Self, usize), PocketError> {
+        if data.len() < 6 {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        if data[..4] != MAGIC {
+            return Err(PocketError::InvalidFormat("missing container magic".into()));
+        }
+        if data[4] != VERSION {
+            return Err(PocketError::InvalidFormat(format!(
+                "unsupported container version {}",
+                data[4]
+            )));
+        }
+
+        let header_len = data[5] as usize;
+        if header_len < HEADER_LEN {
+            return Err(PocketError::InvalidFormat(format!(
+                "container header too short ({header_len} bytes)"
+            )));
+        }
+        if data.len() < header_len {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+
+        let flags = u16::from_be_bytes([data[6], data[7]]);
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(PocketError::InvalidFormat(format!(
+                "unsupported segment flags {flags:#06x}"
+            )));
+        }
+        let epoch = if flags & FLAG_EPOCH == 0 {
+            0
+        } else if header_len < HEADER_LEN + 4 {
+            return Err(PocketError::InvalidFormat(
+                "container header too short for the epoch field".into(),
+            ));
+        } else {
+            read_u32(data, HEADER_LEN)
+        };
+        if flags & FLAG_EPOCH != 0 && epoch == 0 {
+            return Err(PocketError::InvalidFormat("zero epoch length".into()));
+        }
+        let packet_size = read_u32(data, 8) as usize;
+        if (packet_size > MAX_PACKET_LENGTH) != (flags & FLAG_EXTENDED != 0) {
+            return Err(PocketError::InvalidFormat(format!(
+                "packet size {packet_size} does not match the extended flag"
+            )));
+        }
+
+        let header = Self {
+            flags,
+            packet_size,
+            robustness: data[12],
+            num_packets: read_u32(data, 14),
+            payload_len: read_u32(data, 18),
+            start_packet: read_u32(data, 22),
+            seam_check: read_u32(data, 26),
+            epoch,
+        };
+        Ok((header, header_len))
+    }
+
+    /// Write the header as a JSON object.
+    ///
+    /// The packet size is given in bits; the epoch is omitted unless
+    /// [`FLAG_EPOCH`] is set.
+    pub fn write_json(&self, json: &mut JsonWriter) {
+        json.begin_object();
+        json.key("packet_size").uint(self.packet_size as u64);
+        json.key("robustness").uint(u64::from(self.robustness));
+        json.key("flags").uint(u64::from(self.flags));
+        json.key("dedupe").bool(self.flags & FLAG_DEDUPE != 0);
+        json.key("raw").bool(self.flags & FLAG_RAW != 0);
+        json.key("num_packets").uint(u64::from(self.num_packets));
+        json.key("payload_len").uint(u64::from(self.payload_len));
+        json.key("start_packet").uint(u64::from(self.start_packet));
+        json.key("seam_check").uint(u64::from(self.seam_check));
+        if self.flags & FLAG_EPOCH != 0 {
+            json.key("epoch").uint(u64::from(self.epoch));
+        }
+        json.end_object();
+    }
+
+    /// Serialize the header as a JSON document.
+    pub fn to_json(&self) -> String {
+        let mut json = JsonWriter::new();
+        self.write_json(&mut json);
+        json.finish()
+    }
+}
+
+/// Read a big-endian u32 at a byte offset.
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Check whether data starts with a container header.
+pub fn is_container(data: &[u8]) -> bool {
+    data.len() >= MAGIC.len() && data[..MAGIC.len()] == MAGIC
+}
+
+/// A segment located inside a container, payload not yet decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawSegment<'a> {
+    /// Byte offset of the segment header in the container.
+    pub offset: usize,
+    /// Segment header.
+    pub header: SegmentHeader,
+    /// Compressed payload.
+    pub payload: &'a [u8],
+}
+
+/// Iterator over the segments of a container.
+///
+/// Yields an error and stops if a header is malformed or a payload is
+/// truncated.
+#[derive(Clone, Debug)]
+pub struct Segments<'a> {
+    data: &'a [u8],
+    pos: usize,
+    failed: bool,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Result<RawSegment<'a>, PocketError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.pos >= self.data.len() {
+            return None;
+        }
+
+        let offset = self.pos;
+        let result = SegmentHeader::parse(&self.data[offset..]).and_then(|(header, header_len)| {
+            let start = offset + header_len;
+            let end = start + header.payload_len as usize;
+            if end > self.data.len() {
+                return Err(PocketError::UnexpectedEndOfInput);
+            }
+            self.pos = end;
+            Ok(RawSegment {
+                offset,
+                header,
+                payload: &self.data[start..end],
+            })
+        });
+
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Iterate over the segments of a container.
+///
+/// # Arguments
+/// * `data` - One or more concatenated segments
+pub fn segments(data: &[u8]) -> Segments<'_> {
+    Segments {
+        data,
+        pos: 0,
+        failed: false,
+    }
+}
+
+/// Checksum of a mask as recorded in the seam check field.
+fn mask_check(mask: &BitVector) -> u32 {
+    crc32(&mask.to_bytes())
+}
+
+/// Compressor that emits consecutive container segments.
+///
+/// Each call to [`SegmentEncoder::encode`] produces one segment and keeps
+/// the compressor state, so the next segment resumes where the previous
+/// one stopped. The seam fields in each header allow [`merge_segments`]
+/// to verify that two segments really are adjacent.
+#[cfg(feature = "encoder")]
+#[derive(Clone)]
+pub struct SegmentEncoder {
+    comp: Compressor,
+    packet_size: usize,
+    robustness: u8,
+    duplicates: DuplicatePolicy,
+    encoding: PayloadEncoding,
+    /// Input packets consumed so far (the next segment's start index).
+    next_packet: u32,
+    /// Duplicate packets detected so far.
+    duplicate_count: usize,
+    /// Packets per epoch (0 = none).
+    epoch: u32,
+}
+
+#[cfg(feature = "encoder")]
+impl SegmentEncoder {
+    /// Create a new segment encoder.
+    ///
+    /// # Arguments
+    /// * `packet_size` - Packet size in bits (must be divisible by 8)
+    /// * `robustness` - Robustness level R (0-7)
+    /// * `pt_limit` - New mask period
+    /// * `ft_limit` - Full mask period
+    /// * `rt_limit` - Uncompressed packet period
+    pub fn new(
+        packet_size: usize,
+        robustness: usize,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> Result<Self, PocketError> {
+        if packet_size == 0 || packet_size % 8 != 0 {
+            return Err(PocketError::InvalidPacketSize(packet_size));
+        }
+        if robustness > 7 {
+            return Err(PocketError::InvalidRobustness(robustness));
+        }
+
+        let comp = Compressor::new(
+            packet_size,
+            None,
+            robustness as u8,
+            pt_limit,
+            ft_limit,
+            rt_limit,
+        )?;
+        Ok(Self::with_compressor(comp, packet_size, robustness as u8))
+    }
+
+    /// Create a segment encoder for packets longer than the standard allows.
+    ///
+    /// **Non-standard extension.** Same as [`new`](Self::new), but the
+    /// packet size may be up to 2²⁴ bits (see
+    /// [`Compressor::new_extended`]). Segments with more than
+    /// 65535 bits per packet carry [`FLAG_EXTENDED`], so readers without
+    /// the extension reject them instead of misdecoding.
+    pub fn new_extended(
+        packet_size: usize,
+        robustness: usize,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> Result<Self, PocketError> {
+        if packet_size <= MAX_PACKET_LENGTH {
+            return Self::new(packet_size, robustness, pt_limit, ft_limit, rt_limit);
+        }
+        if packet_size % 8 != 0 {
+            return Err(PocketError::InvalidPacketSize(packet_size));
+        }
+        if robustness > 7 {
+            return Err(PocketError::InvalidRobustness(robustness));
+        }
+        let comp = Compressor::new_extended(
+            packet_size,
+            None,
+            robustness as u8,
+            pt_limit,
+            ft_limit,
+            rt_limit,
+        )?;
+        Ok(Self::with_compressor(comp, packet_size, robustness as u8))
+    }
+
+    /// Wrap a validated compressor with default segment settings.
+    fn with_compressor(comp: Compressor, packet_size: usize, robustness: u8) -> Self {
+        Self {
+            comp,
+            packet_size,
+            robustness,
+            duplicates: DuplicatePolicy::Keep,
+            encoding: PayloadEncoding::Pocket,
+            next_packet: 0,
+            duplicate_count: 0,
+            epoch: 0,
+        }
+    }
+
+    /// Set the duplicate packet policy for the following segments.
+    #[must_use]
+    pub fn duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicates = policy;
+        self
+    }
+
+    /// Set the forced refresh policy (see [`RefreshPolicy`]).
+    #[must_use]
+    pub fn refresh(mut self, policy: RefreshPolicy) -> Self {
+        self.comp.set_refresh_policy(policy);
+        self
+    }
+
+    /// Reset the compressor state every `packets` packets (0 = never),
+    /// recorded in each segment header (see [`Compressor::set_epoch`]).
+    ///
+    /// Epochs count compressed packets, so with
+    /// [`DuplicatePolicy::Drop`] they span more input packets.
+    #[must_use]
+    pub fn epoch(mut self, packets: u32) -> Self {
+        self.epoch = packets;
+        self.comp
+            .set_epoch((packets > 0).then_some(packets as usize));
+        self
+    }
+
+    /// Set how packets are stored in the following segments.
+    ///
+    /// [`PayloadEncoding::Raw`] bypasses the compressor; its state stays
+    /// untouched.
+    #[must_use]
+    pub fn encoding(mut self, encoding: PayloadEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Number of input packets identical to their predecessor within the
+    /// same segment seen so far.
+    ///
+    /// Counted under either policy, so the saving of
+    /// [`DuplicatePolicy::Drop`] can be estimated before enabling it.
+    pub fn duplicate_count(&self) -> usize {
+        self.duplicate_count
+    }
+
+    /// Compress the next run of packets into a segment.
+    ///
+    /// # Arguments
+    /// * `data` - Whole packets to compress
+    ///
+    /// # Returns
+    /// The segment (header and payload).
+    pub fn encode(&mut self, data: &[u8]) -> Result<Vec<u8>, PocketError> {
+        let packet_bytes = self.packet_size / 8;
+        if data.len() % packet_bytes != 0 {
+            return Err(PocketError::InvalidInputLength {
+                expected: (data.len() / packet_bytes + 1) * packet_bytes,
+                actual: data.len(),
+            });
+        }
+
+        let num_packets =
+            u32::try_from(data.len() / packet_bytes).map_err(|_| PocketError::BufferOverflow)?;
+        let start_packet = self.next_packet;
+        let seam_check = mask_check(self.comp.mask());
+        let dedupe = self.duplicates == DuplicatePolicy::Drop;
+        let raw = self.encoding == PayloadEncoding::Raw;
+
+        let mut payload = Vec::new();
+        let mut runs: Vec<Run> = Vec::new();
+        let mut previous: Option<&[u8]> = None;
+        let mut compressed = 0u32;
+        for packet in data.chunks_exact(packet_bytes) {
+            let duplicate = previous == Some(packet);
+            self.duplicate_count += usize::from(duplicate);
+            previous = Some(packet);
+
+            if dedupe && duplicate {
+                match runs.last_mut() {
+                    Some((index, repeats)) if *index == compressed - 1 => *repeats += 1,
+                    _ => runs.push((compressed - 1, 1)),
+                }
+                continue;
+            }
+
+            if raw {
+                payload.extend_from_slice(packet);
+            } else {
+                let input = BitVector::from_bytes(packet, self.packet_size);
+                let params = self.comp.next_params();
+                self.comp
+                    .compress_packet(&input, &params)?
+                    .to_bytes_into(&mut payload);
+            }
+            compressed += 1;
+        }
+        if dedupe {
+            write_runs(&mut payload, &runs)?;
+        }
+        self.next_packet = start_packet
+            .checked_add(num_packets)
+            .ok_or(PocketError::BufferOverflow)?;
+
+        let header = SegmentHeader {
+            packet_size: self.packet_size,
+            robustness: self.robustness,
+            flags: if dedupe { FLAG_DEDUPE } else { 0 }
+                | if raw { FLAG_RAW } else { 0 }
+                | if self.epoch > 0 { FLAG_EPOCH } else { 0 }
+                | if self.packet_size > MAX_PACKET_LENGTH {
+                    FLAG_EXTENDED
+                } else {
+                    0
+                },
+            num_packets,
+            payload_len: u32::try_from(payload.len()).map_err(|_| PocketError::BufferOverflow)?,
+            start_packet,
+            seam_check,
+            epoch: self.epoch,
+        };
+
+        let mut out = header.to_bytes();
+        out.extend_from_slice(&payload);
+        Ok(out)
+    }
+}
+
+/// Compress data into a single container segment.
+///
+/// Takes the same arguments as [`compress`](crate::compress()); the output
+/// can be concatenated with other segments and split again by [`segments`].
+#[cfg(feature = "encoder")]
+pub fn compress_segment(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> Result<Vec<u8>, PocketError> {
+    SegmentEncoder::new(packet_size, robustness, pt_limit, ft_limit, rt_limit)?.encode(data)
+}
+
+/// Parse a byte slice holding exactly one segment.
+#[cfg(feature = "decoder")]
+fn single_segment(data: &[u8]) -> Result<RawSegment<'_>, PocketError> {
+    let mut iter = segments(data);
+    let segment = iter.next().ok_or(PocketError::UnexpectedEndOfInput)??;
+    if iter.next().is_some() {
+        return Err(PocketError::InvalidFormat(
+            "expected a single segment".into(),
+        ));
+    }
+    Ok(segment)
+}
+
+/// Dedupe run: compressed packet index and number of copies dropped after it.
+type Run = (u32, u32);
+
+/// Append a dedupe run table and its entry count.
+fn write_runs(payload: &mut Vec<u8>, runs: &[Run]) -> Result<(), PocketError> {
+    for (index, repeats) in runs {
+        payload.extend_from_slice(&index.to_be_bytes());
+        payload.extend_from_slice(&repeats.to_be_bytes());
+    }
+    let count = u32::try_from(runs.len()).map_err(|_| PocketError::BufferOverflow)?;
+    payload.extend_from_slice(&count.to_be_bytes());
+    Ok(())
+}
+
+/// Split a payload into compressed packets and its dedupe run table.
+///
+/// Payloads without [`FLAG_DEDUPE`] have an empty table.
+#[cfg(feature = "decoder")]
+fn split_runs(flags: u16, payload: &[u8]) -> Result<(&[u8], Vec<Run>), PocketError> {
+    if flags & FLAG_DEDUPE == 0 {
+        return Ok((payload, Vec::new()));
+    }
+    let truncated = || PocketError::InvalidFormat("dedupe run table truncated".into());
+
+    let count_at = payload.len().checked_sub(4).ok_or_else(truncated)?;
+    let count = read_u32(payload, count_at) as usize;
+    let table_at = count
+        .checked_mul(8)
+        .and_then(|len| count_at.checked_sub(len))
+        .ok_or_else(truncated)?;
+
+    let runs = (0..count)
+        .map(|i| {
+            let at = table_at + i * 8;
+            (read_u32(payload, at), read_u32(payload, at + 4))
+        })
+        .collect();
+    Ok((&payload[..table_at], runs))
+}
+
+/// Decode a segment payload with an existing decompressor.
+///
+/// # Arguments
+/// * `header` - Segment header describing the payload layout
+///
+/// # Returns
+/// The decoded packets (duplicates restored), the number of packets and
+/// the number of stored packets they came from.
+#[cfg(feature = "decoder")]
+fn decode_payload(
+    decomp: &mut Decompressor,
+    header: &SegmentHeader,
+    payload: &[u8],
+) -> Result<(Vec<u8>, usize, usize), PocketError> {
+    let (packets, runs) = split_runs(header.flags, payload)?;
+    let mut runs = runs.into_iter().peekable();
+    let raw = header.flags & FLAG_RAW != 0;
+
+    let mut reader = BitReader::new(packets, packets.len() * 8);
+    let mut output = Vec::new();
+    let mut count = 0;
+    let mut compressed = 0;
+    while reader.remaining() > 0 {
+        let packet = if raw {
+            (0..header.packet_size / 8)
+                .map(|_| reader.read_bits(8).map(|byte| byte as u8))
+                .collect::<Result<Vec<u8>, _>>()?
+        } else {
+            decomp.decompress_packet(&mut reader)?.to_bytes()
+        };
+        reader.align_byte();
+
+        let mut copies = 1;
+        if let Some((_, repeats)) = runs.next_if(|&(index, _)| index as usize == compressed) {
+            copies += repeats as usize;
+        }
+        for _ in 0..copies {
+            output.extend_from_slice(&packet);
+        }
+        count += copies;
+        compressed += 1;
+    }
+    if runs.next().is_some() {
+        return Err(PocketError::InvalidFormat(
+            "dedupe run table refers to missing packets".into(),
+        ));
+    }
+    Ok((output, count, compressed))
+}
+
+/// Create a fresh decompressor for the payload described by `header`.
+#[cfg(feature = "decoder")]
+fn segment_decompressor(header: &SegmentHeader) -> Result<Decompressor, PocketError> {
+    let mut builder = DecompressorBuilder::new(header.packet_size)
+        .robustness(header.robustness.into())
+        .extended(header.flags & FLAG_EXTENDED != 0);
+    if header.flags & FLAG_EPOCH != 0 {
+        builder = builder.epoch(header.epoch as usize);
+    }
+    builder.build()
+}
+
+/// Merge two adjacent segments into one decodable segment.
+///
+/// `second` must have been produced by a compressor resumed from the state
+/// at the end of `first` (e.g. consecutive [`SegmentEncoder::encode`]
+/// calls). The merge is validated before anything is written:
+/// - packet size, robustness, flags and epoch match
+/// - `second` starts at the packet index where `first` ends
+/// - the decompressor mask after decoding `first` matches the seam check
+///   recorded in `second`
+/// - `second` decodes cleanly when continued from that state
+///
+/// # Arguments
+/// * `first` - Earlier segment (must start from a fresh state)
+/// * `second` - Segment continuing `first`
+///
+/// # Returns
+/// A single segment covering both time ranges.
+#[cfg(feature = "decoder")]
+pub fn merge_segments(first: &[u8], second: &[u8]) -> Result<Vec<u8>, PocketError> {
+    let a = single_segment(first)?;
+    let b = single_segment(second)?;
+
+    if a.header.packet_size != b.header.packet_size
+        || a.header.robustness != b.header.robustness
+        || a.header.flags != b.header.flags
+        || a.header.epoch != b.header.epoch
+    {
+        return Err(PocketError::InvalidFormat(
+            "segment parameters differ".into(),
+        ));
+    }
+    if a.header.start_packet != 0 {
+        return Err(PocketError::InvalidFormat(
+            "first segment does not start from a fresh state".into(),
+        ));
+    }
+    let seam = a.header.start_packet + a.header.num_packets;
+    if b.header.start_packet != seam {
+        return Err(PocketError::InvalidFormat(format!(
+            "segments are not adjacent: first ends at packet {seam}, second starts at {}",
+            b.header.start_packet
+        )));
+    }
+
+    let mut decomp = segment_decompressor(&a.header)?;
+    let (_, _, a_compressed) = decode_payload(&mut decomp, &a.header, a.payload)?;
+    if mask_check(decomp.mask()) != b.header.seam_check {
+        return Err(PocketError::InvalidFormat(
+            "mask state at seam does not match".into(),
+        ));
+    }
+    let (_, decoded, _) = decode_payload(&mut decomp, &b.header, b.payload)?;
+    if decoded != b.header.num_packets as usize {
+        return Err(PocketError::InvalidInputLength {
+            expected: b.header.num_packets as usize,
+            actual: decoded,
+        });
+    }
+
+    // Packets of both segments, then one run table renumbered to match
+    let (a_packets, mut runs) = split_runs(a.header.flags, a.payload)?;
+    let (b_packets, b_runs) = split_runs(b.header.flags, b.payload)?;
+    let offset = u32::try_from(a_compressed).map_err(|_| PocketError::BufferOverflow)?;
+    runs.extend(
+        b_runs
+            .iter()
+            .map(|&(index, repeats)| (index + offset, repeats)),
+    );
+
+    let mut payload = [a_packets, b_packets].concat();
+    if a.header.flags & FLAG_DEDUPE != 0 {
+        write_runs(&mut payload, &runs)?;
+    }
+
+    let header = SegmentHeader {
+        num_packets: seam + b.header.num_packets,
+        payload_len: u32::try_from(payload.len()).map_err(|_| PocketError::BufferOverflow)?,
+        ..a.header
+    };
+
+    let mut out = header.to_bytes();
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// A decompressed container segment.
+#[cfg(feature = "decoder")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// Byte offset of the segment header in the container.
+    pub offset: usize,
+    /// Segment header.
+    pub header: SegmentHeader,
+    /// Decompressed packet data.
+    pub data: Vec<u8>,
+}
+
+/// Decompress every segment of a container.
+///
+/// Each segment is decoded with a fresh decompressor using the packet size
+/// and robustness from its own header, so segments with different
+/// parameters can be mixed.
+///
+/// # Arguments
+/// * `data` - One or more concatenated segments
+///
+/// # Returns
+/// The decompressed segments in order, or the first error encountered.
+#[cfg(feature = "decoder")]
+pub fn decompress_segments(data: &[u8]) -> Result<Vec<Segment>, PocketError> {
+    let mut result = Vec::new();
+
+    for segment in segments(data) {
+        let segment = segment?;
+        if segment.header.start_packet != 0 {
+            return Err(PocketError::InvalidFormat(format!(
+                "segment at offset {} continues an earlier segment; merge it first",
+                segment.offset
+            )));
+        }
+        let mut decomp = segment_decompressor(&segment.header)?;
+        let (decoded, _, _) = decode_payload(&mut decomp, &segment.header, segment.payload)?;
+
+        let packet_bytes = segment.header.packet_size / 8;
+        let expected = segment.header.num_packets as usize * packet_bytes;
+        if decoded.len() != expected {
+            return Err(PocketError::InvalidInputLength {
+                expected,
+                actual: decoded.len(),
+            });
+        }
+
+        result.push(Segment {
+            offset: segment.offset,
+            header: segment.header,
+            data: decoded,
+        });
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    fn sample(num_packets: usize, seed: u8) -> Vec<u8> {
+        (0..num_packets * 90)
+            .map(|i| {
+                if i % 90 < 4 {
+                    (i / 90) as u8 ^ seed
+                } else {
+                    seed
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = SegmentHeader {
+            packet_size: 720,
+            robustness: 3,
+            flags: 0,
+            num_packets: 42,
+            payload_len: 1234,
+            start_packet: 7,
+            seam_check: 0xDEAD_BEEF,
+            epoch: 0,
+        };
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN);
+        assert!(is_container(&bytes));
+
+        let (parsed, len) = SegmentHeader::parse(&bytes).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(len, HEADER_LEN);
+        assert_eq!(
+            header.to_json(),
+            "{\"packet_size\":720,\"robustness\":3,\"flags\":0,\"dedupe\":false,\
+             \"raw\":false,\"num_packets\":42,\"payload_len\":1234,\"start_packet\":7,\
+             \"seam_check\":3735928559}"
+        );
+    }
+
+    #[test]
+    fn test_header_bad_magic() {
+        let result = SegmentHeader::parse(b"NOPE\x01\x16");
+        assert!(matches!(result, Err(PocketError::InvalidFormat(_))));
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_concatenated_segments() {
+        let first = sample(10, 0x11);
+        let second = sample(7, 0x22);
+
+        let mut container = compress_segment(&first, 720, 1, 10, 20, 50).unwrap();
+        let second_offset = container.len();
+        container.extend(compress_segment(&second, 720, 2, 20, 50, 100).unwrap());
+
+        let decoded = decompress_segments(&container).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].offset, 0);
+        assert_eq!(decoded[0].header.num_packets, 10);
+        assert_eq!(decoded[0].data, first);
+        assert_eq!(decoded[1].offset, second_offset);
+        assert_eq!(decoded[1].header.robustness, 2);
+        assert_eq!(decoded[1].data, second);
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_truncated_payload() {
+        let mut container = compress_segment(&sample(3, 0x33), 720, 1, 10, 20, 50).unwrap();
+        container.pop();
+        assert!(matches!(
+            decompress_segments(&container),
+            Err(PocketError::UnexpectedEndOfInput)
+        ));
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_merge_resumed_segments() {
+        let data = sample(30, 0x44);
+        let mut encoder = SegmentEncoder::new(720, 2, 10, 20, 50).unwrap();
+        let first = encoder.encode(&data[..12 * 90]).unwrap();
+        let second = encoder.encode(&data[12 * 90..]).unwrap();
+
+        // A resumed segment cannot be decoded on its own
+        assert!(decompress_segments(&second).is_err());
+
+        let merged = merge_segments(&first, &second).unwrap();
+        let decoded = decompress_segments(&merged).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].header.num_packets, 30);
+        assert_eq!(decoded[0].data, data);
+
+        // The merged stream is identical to compressing in one go
+        assert_eq!(merged, compress_segment(&data, 720, 2, 10, 20, 50).unwrap());
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_merge_rejects_non_adjacent() {
+        let data = sample(30, 0x55);
+        let mut encoder = SegmentEncoder::new(720, 1, 10, 20, 50).unwrap();
+        let first = encoder.encode(&data[..10 * 90]).unwrap();
+        let _skipped = encoder.encode(&data[10 * 90..20 * 90]).unwrap();
+        let third = encoder.encode(&data[20 * 90..]).unwrap();
+
+        assert!(matches!(
+            merge_segments(&first, &third),
+            Err(PocketError::InvalidFormat(_))
+        ));
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_merge_rejects_state_mismatch() {
+        let mut encoder = SegmentEncoder::new(720, 1, 10, 20, 50).unwrap();
+        let first = encoder.encode(&sample(10, 0x66)).unwrap();
+
+        // Same packet index but a different history (constant packets)
+        let mut other = SegmentEncoder::new(720, 1, 10, 20, 50).unwrap();
+        other.encode(&[0x77; 900]).unwrap();
+        let second = other.encode(&sample(5, 0x77)).unwrap();
+
+        assert!(matches!(
+            merge_segments(&first, &second),
+            Err(PocketError::InvalidFormat(_))
+        ));
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_empty_segment() {
+        let container = compress_segment(&[], 720, 1, 10, 20, 50).unwrap();
+        let decoded = decompress_segments(&container).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert!(decoded[0].data.is_empty());
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_dedupe_segments() {
+        // Runs of identical packets, split across two segments
+        let mut data = Vec::new();
+        for value in [1u8, 1, 1, 2, 3, 3, 1, 1] {
+            for _ in 0..4 {
+                data.extend_from_slice(&sample(1, value));
+            }
+        }
+
+        let mut keep = SegmentEncoder::new(720, 1, 10, 20, 50).unwrap();
+        let plain = keep.encode(&data).unwrap();
+        assert_eq!(keep.duplicate_count(), 28);
+
+        let mut encoder = SegmentEncoder::new(720, 1, 10, 20, 50)
+            .unwrap()
+            .duplicates(DuplicatePolicy::Drop);
+        let first = encoder.encode(&data[..18 * 90]).unwrap();
+        let second = encoder.encode(&data[18 * 90..]).unwrap();
+        // Runs do not span segments: the packet opening the second is kept
+        assert_eq!(encoder.duplicate_count(), 27);
+
+        let (header, _) = SegmentHeader::parse(&first).unwrap();
+        assert_eq!(header.flags, FLAG_DEDUPE);
+        assert_eq!(header.num_packets, 18);
+
+        assert_eq!(
+            decompress_segments(&first).unwrap()[0].data,
+            &data[..18 * 90]
+        );
+        let merged = merge_segments(&first, &second).unwrap();
+        assert!(merged.len() < plain.len());
+        let decoded = decompress_segments(&merged).unwrap();
+        assert_eq!(decoded[0].header.num_packets, 32);
+        assert_eq!(decoded[0].data, data);
+
+        // A run table pointing past the packets is rejected
+        let mut corrupt = first.clone();
+        let at = corrupt.len() - 12;
+        corrupt[at..at + 4].copy_from_slice(&99u32.to_be_bytes());
+        assert!(decompress_segments(&corrupt).is_err());
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_unknown_flags_rejected() {
+        let mut segment = compress_segment(&sample(2, 0x88), 720, 1, 10, 20, 50).unwrap();
+        segment[7] = 0x80;
+        assert!(matches!(
+            decompress_segments(&segment),
+            Err(PocketError::InvalidFormat(_))
+        ));
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_raw_segments() {
+        let data = sample(20, 0x99);
+        let mut encoder = SegmentEncoder::new(720, 1, 10, 20, 50)
+            .unwrap()
+            .encoding(PayloadEncoding::Raw);
+        let first = encoder.encode(&data[..8 * 90]).unwrap();
+        let second = encoder.encode(&data[8 * 90..]).unwrap();
+
+        let (header, header_len) = SegmentHeader::parse(&first).unwrap();
+        assert_eq!(header.flags, FLAG_RAW);
+        assert_eq!(&first[header_len..], &data[..8 * 90]);
+
+        // Same split, merge and decode path as compressed segments
+        let merged = merge_segments(&first, &second).unwrap();
+        assert_eq!(decompress_segments(&merged).unwrap()[0].data, data);
+
+        // Raw and compressed segments cannot be merged
+        let mut pocket = SegmentEncoder::new(720, 1, 10, 20, 50).unwrap();
+        let compressed = pocket.encode(&data[..8 * 90]).unwrap();
+        assert!(merge_segments(&compressed, &second).is_err());
+
+        // Raw with dedupe
+        let repeated = [sample(1, 1), sample(1, 1), sample(1, 2)].concat();
+        let segment = SegmentEncoder::new(720, 1, 10, 20, 50)
+            .unwrap()
+            .encoding(PayloadEncoding::Raw)
+            .duplicates(DuplicatePolicy::Drop)
+            .encode(&repeated)
+            .unwrap();
+        assert_eq!(
+            SegmentHeader::parse(&segment).unwrap().0.flags,
+            FLAG_RAW | FLAG_DEDUPE
+        );
+        assert_eq!(decompress_segments(&segment).unwrap()[0].data, repeated);
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_epoch_segments() {
+        let data = sample(25, 0x42);
+        let mut encoder = SegmentEncoder::new(720, 1, 10, 20, 50).unwrap().epoch(10);
+        let first = encoder.encode(&data[..12 * 90]).unwrap();
+        let second = encoder.encode(&data[12 * 90..]).unwrap();
+
+        let (header, header_len) = SegmentHeader::parse(&first).unwrap();
+        assert_eq!(header.flags, FLAG_EPOCH);
+        assert_eq!(header.epoch, 10);
+        assert_eq!(header_len, HEADER_LEN + 4);
+        assert_eq!(header.to_bytes(), first[..header_len]);
+
+        let merged = merge_segments(&first, &second).unwrap();
+        assert_eq!(decompress_segments(&merged).unwrap()[0].data, data);
+
+        // Epochs must match to merge
+        let mut other = SegmentEncoder::new(720, 1, 10, 20, 50).unwrap().epoch(5);
+        other.encode(&data[..12 * 90]).unwrap();
+        let second = other.encode(&data[12 * 90..]).unwrap();
+        assert!(merge_segments(&first, &second).is_err());
+
+        // The epoch field is mandatory with the flag
+        let mut truncated = first[..HEADER_LEN].to_vec();
+        truncated[5] = HEADER_LEN as u8;
+        assert!(SegmentHeader::parse(&truncated).is_err());
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_extended_segments() {
+        // 9000-byte packets, changing at both ends
+        let data: Vec<u8> = (0..6u8)
+            .flat_map(|t| {
+                let mut packet = vec![0x3C; 9000];
+                packet[0] = t;
+                packet[8999] = t / 2;
+                packet
+            })
+            .collect();
+        assert!(SegmentEncoder::new(72_000, 1, 2, 3, 4).is_err());
+        let segment = SegmentEncoder::new_extended(72_000, 1, 2, 3, 4)
+            .unwrap()
+            .encode(&data)
+            .unwrap();
+        let (header, _) = SegmentHeader::parse(&segment).unwrap();
+        assert_eq!(header.flags, FLAG_EXTENDED);
+        assert_eq!(decompress_segments(&segment).unwrap()[0].data, data);
+
+        // Standard packet sizes stay unflagged
+        let segment = SegmentEncoder::new_extended(720, 1, 10, 20, 50)
+            .unwrap()
+            .encode(&sample(3, 0x11))
+            .unwrap();
+        assert_eq!(SegmentHeader::parse(&segment).unwrap().0.flags, 0);
+
+        // The flag must agree with the packet size
+        let mut unflagged = segment.clone();
+        unflagged[8..12].copy_from_slice(&72_000u32.to_be_bytes());
+        assert!(SegmentHeader::parse(&unflagged).is_err());
+    }
+}
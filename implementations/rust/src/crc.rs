@@ -0,0 +1,104 @@
+//! Checksums used by the container format and packet integrity checks.
+//!
+//! Implemented in-crate to keep the zero-dependency guarantee:
+//! - CRC-32 (IEEE 802.3, reflected, polynomial 0xEDB88320)
+//! - CRC-16 (CCSDS, polynomial 0x1021, initial value 0xFFFF)
+
+#![allow(clippy::cast_possible_truncation)]
+
+/// CRC-32 lookup table, generated at compile time.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the CRC-32 (IEEE) of a byte slice.
+///
+/// # Arguments
+/// * `data` - Input bytes
+///
+/// # Returns
+/// The CRC-32 checksum.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        let index = ((crc ^ u32::from(byte)) & 0xFF) as usize;
+        crc = (crc >> 8) ^ CRC32_TABLE[index];
+    }
+    !crc
+}
+
+/// CRC-16 lookup table, generated at compile time.
+const CRC16_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the CCSDS CRC-16 of a byte slice, as used by space packets and
+/// transfer frames (CRC-16/CCITT-FALSE).
+///
+/// # Arguments
+/// * `data` - Input bytes
+///
+/// # Returns
+/// The CRC-16 checksum.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        let index = ((crc >> 8) ^ u16::from(byte)) as usize;
+        crc = (crc << 8) ^ CRC16_TABLE[index];
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_check_value() {
+        // Standard check value for "123456789"
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn test_crc32_empty() {
+        assert_eq!(crc32(&[]), 0);
+    }
+}
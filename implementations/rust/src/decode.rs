@@ -6,9 +6,36 @@
 //! - Bit Insertion - inverse of BE extraction
 
 use crate::bitreader::BitReader;
-use crate::bitvector::BitVector;
+use crate::bitvector::{BitVector, WORD_BITS};
 use crate::error::PocketError;
 
+/// COUNT codes of at most 8 bits, indexed by the next 8 bits of the
+/// stream: (value, code length), or length 0 for the long '111' form.
+const COUNT_TABLE: [(u8, u8); 256] = count_table();
+
+/// Build [`COUNT_TABLE`].
+#[allow(clippy::cast_possible_truncation)]
+const fn count_table() -> [(u8, u8); 256] {
+    let mut table = [(0, 0); 256];
+    let mut byte = 0;
+    while byte < 256 {
+        table[byte] = if byte & 0x80 == 0 {
+            // '0' → 1
+            (1, 1)
+        } else if byte & 0x40 == 0 {
+            // '10' → 0 (terminator)
+            (0, 2)
+        } else if byte & 0x20 == 0 {
+            // '110' + 5 bits → value + 2
+            ((byte & 0x1F) as u8 + 2, 8)
+        } else {
+            (0, 0)
+        };
+        byte += 1;
+    }
+    table
+}
+
 /// Counter Decoding - inverse of COUNT encoding.
 ///
 /// Decodes COUNT-encoded values:
@@ -17,6 +44,9 @@ use crate::error::PocketError;
 /// - '110' + 5 bits → value + 2
 /// - '111' + variable bits → value + 2
 ///
+/// The first three forms are decoded from a table lookup on the next 8
+/// bits; only the long '111' form is read bit by bit.
+///
 /// # Arguments
 /// * `reader` - Bit reader to read encoded bits from
 ///
@@ -24,6 +54,17 @@ use crate::error::PocketError;
 /// Decoded value, or error if invalid encoding.
 #[inline]
 pub fn count_decode(reader: &mut BitReader) -> Result<u32, PocketError> {
+    let (value, length) = COUNT_TABLE[reader.peek_byte() as usize];
+    if length > 0 && usize::from(length) <= reader.remaining() {
+        reader.skip(usize::from(length))?;
+        return Ok(u32::from(value));
+    }
+    count_decode_bits(reader)
+}
+
+/// [`count_decode`] bit by bit, for the long '111' form and the end of
+/// the stream.
+fn count_decode_bits(reader: &mut BitReader) -> Result<u32, PocketError> {
     // Read first bit
     let bit0 = reader.read_bit()?;
 
@@ -107,6 +148,44 @@ pub fn rle_decode(reader: &mut BitReader, length: usize) -> Result<BitVector, Po
     Ok(result)
 }
 
+/// Run-Length Decoding into the positions of the '1' bits.
+///
+/// Reads the same encoding as [`rle_decode`], but returns where the bits
+/// are set rather than an F-bit vector, so callers that walk the changes
+/// skip a second pass over all F bits. Positions are `u32` so that
+/// extended packets of up to 2²⁴ bits fit.
+///
+/// # Arguments
+/// * `reader` - Bit reader to read encoded bits from
+/// * `length` - Length of the encoded bit vector
+///
+/// # Returns
+/// Positions of the '1' bits in ascending order, or error if invalid
+/// encoding.
+#[inline]
+pub fn rle_decode_positions(
+    reader: &mut BitReader,
+    length: usize,
+) -> Result<Vec<u32>, PocketError> {
+    let mut positions = Vec::new();
+
+    // Runs are counted from the end of the vector, as in rle_decode
+    let mut bit_position =
+        u32::try_from(length).map_err(|_| PocketError::InvalidPacketSize(length))?;
+    let mut delta = count_decode(reader)?;
+
+    while delta != 0 {
+        if delta <= bit_position {
+            bit_position -= delta;
+            positions.push(bit_position);
+        }
+        delta = count_decode(reader)?;
+    }
+
+    positions.reverse();
+    Ok(positions)
+}
+
 /// Bit Insertion - inverse of BE extraction.
 ///
 /// Inserts bits from reader into data at positions where mask has '1' bits.
@@ -132,19 +211,54 @@ pub fn bit_insert(
         });
     }
 
-    // Insert bits in reverse order (matching BE extraction)
-    // Iterate backward through mask positions to avoid Vec allocation
-    let len = mask.len();
-    for i in (0..len).rev() {
-        if mask.get_bit(i) != 0 {
+    // Insert bits in reverse order (matching BE extraction): words from
+    // high to low, set mask bits within a word from its LSB up. Padding
+    // bits after the last valid bit are zero, so every hit is in range.
+    for (word_idx, &word) in mask.words().iter().enumerate().rev() {
+        let mut mask_word = word;
+        while mask_word != 0 {
+            let bit_pos_in_word = WORD_BITS - 1 - mask_word.trailing_zeros() as usize;
             let bit = reader.read_bit()?;
-            data.set_bit(i, bit);
+            data.set_bit(word_idx * WORD_BITS + bit_pos_in_word, bit);
+
+            // Clear the processed bit
+            mask_word &= mask_word - 1;
         }
     }
 
     Ok(())
 }
 
+/// Bit Insertion at precomputed positions.
+///
+/// Same as [`bit_insert`] for a mask whose set positions are listed in
+/// insertion order (highest first), reading the bits 32 at a time.
+///
+/// # Arguments
+/// * `reader` - Bit reader to read bits from
+/// * `data` - Bit vector to insert bits into
+/// * `positions` - Set positions of the mask, highest first
+///
+/// # Returns
+/// `Ok(())` on success, or error if not enough bits.
+pub(crate) fn bit_insert_at(
+    reader: &mut BitReader,
+    data: &mut BitVector,
+    positions: &[u32],
+) -> Result<(), PocketError> {
+    if reader.remaining() < positions.len() {
+        return Err(PocketError::Underflow);
+    }
+    for chunk in positions.chunks(32) {
+        let bits = reader.read_bits(chunk.len())?;
+        for (k, &pos) in chunk.iter().enumerate() {
+            let bit = (bits >> (chunk.len() - 1 - k)) & 1 != 0;
+            data.set_bit(pos as usize, u8::from(bit));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,6 +311,27 @@ mod tests {
         assert_eq!(count_decode(&mut reader).unwrap(), 34);
     }
 
+    #[test]
+    fn test_count_decode_table() {
+        // The table matches the bitwise decoder for every code start,
+        // including codes cut off at the end of the stream
+        for pattern in 0..=u16::MAX {
+            let data = pattern.to_be_bytes();
+            for num_bits in [1, 2, 5, 8, 9, 13, 16] {
+                let mut table = BitReader::new(&data, num_bits);
+                let mut bits = BitReader::new(&data, num_bits);
+                table.skip(num_bits.min(3)).unwrap();
+                bits.skip(num_bits.min(3)).unwrap();
+                assert_eq!(
+                    count_decode(&mut table).ok(),
+                    count_decode_bits(&mut bits).ok(),
+                    "{pattern:016b} in {num_bits} bits"
+                );
+                assert_eq!(table.position(), bits.position());
+            }
+        }
+    }
+
     #[test]
     fn test_rle_decode_empty() {
         // Just terminator '10'
@@ -221,6 +356,25 @@ mod tests {
         assert_eq!(result.get_bit(7), 1); // Last bit should be set
     }
 
+    #[test]
+    fn test_rle_decode_positions() {
+        // Bits 0, 2 and 7 of 8: COUNT(1) || COUNT(5) || COUNT(2) || '10'
+        // = 0 11000011 11000000 10 (19 bits)
+        let data = vec![0b0110_0001, 0b1110_0000, 0b0100_0000];
+
+        let mut reader = BitReader::new(&data, 19);
+        assert_eq!(rle_decode_positions(&mut reader, 8).unwrap(), [0, 2, 7]);
+        assert_eq!(reader.position(), 19);
+
+        let mut reader = BitReader::new(&data, 19);
+        let vector = rle_decode(&mut reader, 8).unwrap();
+        assert_eq!(vector.iter_ones().collect::<Vec<_>>(), [0, 2, 7]);
+
+        // Just the terminator
+        let mut reader = BitReader::new(&[0b1000_0000], 2);
+        assert!(rle_decode_positions(&mut reader, 8).unwrap().is_empty());
+    }
+
     #[test]
     fn test_bit_insert() {
         // Insert bits 0, 1, 1 at mask positions 1, 4, 6
@@ -263,4 +417,57 @@ mod tests {
         // Should succeed with no bits inserted
         bit_insert(&mut reader, &mut data, &mask).unwrap();
     }
+
+    #[test]
+    fn test_bit_insert_across_words() {
+        // Mask bits on both sides of the word boundaries, 130 bits
+        let mut mask = BitVector::new(130);
+        for pos in [0, 1, 62, 63, 64, 65, 100, 127, 128, 129] {
+            mask.set_bit(pos, 1);
+        }
+        let input = [0b1011_0011, 0b0100_0000];
+        let mut reader = BitReader::new(&input, 10);
+        let mut data = BitVector::new(130);
+        bit_insert(&mut reader, &mut data, &mask).unwrap();
+        assert_eq!(reader.remaining(), 0);
+
+        // Bit-by-bit definition: the first read goes to the last position
+        let mut reader = BitReader::new(&input, 10);
+        let mut expected = BitVector::new(130);
+        for i in (0..130).rev() {
+            if mask.get_bit(i) != 0 {
+                expected.set_bit(i, reader.read_bit().unwrap());
+            }
+        }
+        assert_eq!(data, expected);
+        assert_eq!(data.hamming_weight(), 6);
+    }
+
+    #[test]
+    fn test_bit_insert_at() {
+        // 70 positions span three 32-bit reads
+        let mut mask = BitVector::new(200);
+        for pos in (0..200).filter(|i| i % 3 == 0 || i % 7 == 0).take(70) {
+            mask.set_bit(pos, 1);
+        }
+        let positions: Vec<u32> = mask
+            .iter_ones()
+            .rev()
+            .map(|i| u32::try_from(i).unwrap())
+            .collect();
+        let input: Vec<u8> = (0..9u8).map(|i| i.wrapping_mul(91) ^ 0x36).collect();
+
+        let mut reader = BitReader::new(&input, 72);
+        let mut expected = BitVector::from_bytes(&[0xA5; 25], 200);
+        bit_insert(&mut reader, &mut expected, &mask).unwrap();
+
+        let mut reader = BitReader::new(&input, 72);
+        let mut data = BitVector::from_bytes(&[0xA5; 25], 200);
+        bit_insert_at(&mut reader, &mut data, &positions).unwrap();
+        assert_eq!(data, expected);
+        assert_eq!(reader.position(), 70);
+
+        let mut reader = BitReader::new(&input, 69);
+        assert!(bit_insert_at(&mut reader, &mut data, &positions).is_err());
+    }
 }
@@ -10,11 +10,108 @@
 #![allow(dead_code)]
 
 use crate::bitreader::BitReader;
-use crate::bitvector::BitVector;
-use crate::decode::{bit_insert, count_decode, rle_decode};
+use crate::bitvector::{BitVector, MAX_EXTENDED_PACKET_LENGTH, MAX_PACKET_LENGTH};
+use crate::crc::crc16;
+use crate::decode::{bit_insert, bit_insert_at, count_decode, rle_decode_positions};
 use crate::error::PocketError;
+use crate::index::PacketIndex;
+use crate::progress::ProgressInfo;
+use crate::state::{StateReader, StateWriter, KIND_DECOMPRESSOR};
+use crate::trailer::{apply_trailer, strip_trailer};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How strictly the decoder treats inconsistent input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Reject streams whose uncompressed packets declare a length other
+    /// than F or whose Vₜ contradicts the configured robustness, and fail
+    /// on a truncated final packet.
+    #[default]
+    Strict,
+    /// Ignore the declared length of uncompressed packets and stop
+    /// quietly at a truncated final packet, keeping the packets decoded
+    /// so far.
+    Lenient,
+}
+
+/// How compressed packets are laid out in the input stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alignment {
+    /// Each packet starts on a byte boundary (the standard layout).
+    #[default]
+    Byte,
+    /// Packets are bit-packed back to back with no padding.
+    Bit,
+}
+
+/// Resource limits for stream decoding.
+///
+/// Bound the work done on untrusted input; `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum number of packets to decode.
+    pub max_packets: Option<usize>,
+    /// Maximum number of output bytes to produce.
+    pub max_output_bytes: Option<usize>,
+}
+
+/// Metadata of one packet passed over by [`Decompressor::skip_packet`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SkippedPacket {
+    /// Bits read, excluding alignment padding.
+    pub bits: usize,
+    /// The packet carried the full mask (ḟₜ = 1).
+    pub full_mask: bool,
+    /// The packet was sent uncompressed (ṙₜ = 1).
+    pub uncompressed: bool,
+}
+
+/// Counters collected while decoding, when diagnostics are enabled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeDiagnostics {
+    /// Packets decoded.
+    pub packets: usize,
+    /// Packets carrying a full mask (ḟₜ = 1).
+    pub full_masks: usize,
+    /// Packets sent uncompressed (ṙₜ = 1).
+    pub uncompressed: usize,
+    /// Total mask change bits signalled in Xₜ.
+    pub mask_changes: usize,
+    /// Input bits consumed.
+    pub bits_consumed: usize,
+}
+
+/// Extent of the compressed input used by a decode.
+///
+/// Lets callers that embed a POCKET+ payload inside a larger frame find
+/// where the payload ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Consumed {
+    /// Bits read, up to the end of the last decoded packet (including its
+    /// alignment padding in [`Alignment::Byte`] streams).
+    pub bits: usize,
+    /// Bytes touched: `bits` rounded up to a whole byte.
+    pub bytes: usize,
+}
+
+impl Consumed {
+    /// Create from a bit count.
+    fn from_bits(bits: usize) -> Self {
+        Self {
+            bits,
+            bytes: (bits + 7) / 8,
+        }
+    }
+}
 
 /// POCKET+ decompressor state.
+///
+/// Decompresses a stream one packet at a time, keeping the mask and the
+/// previous packet between calls. Read packets in stream order with
+/// [`decompress_packet`](Self::decompress_packet) from a [`BitReader`]
+/// over the received bytes, calling [`BitReader::align_byte`] after each
+/// one; the packets equal the output of [`decompress`].
 #[derive(Clone)]
 pub struct Decompressor {
     /// Packet length in bits (F).
@@ -27,28 +124,82 @@ pub struct Decompressor {
     initial_mask: BitVector,
     /// Previous output vector.
     prev_output: BitVector,
+    /// Reference packet (for reset), all zeros if `None`.
+    initial_reference: Option<BitVector>,
     /// Positive changes tracker (Xt).
     xt: BitVector,
     /// Reusable extraction mask buffer.
     extraction_mask: BitVector,
+    /// Set positions of the mask in BE order, while `positions_valid`.
+    mask_positions: Vec<u32>,
+    /// The mask is unchanged since `mask_positions` was derived.
+    positions_valid: bool,
     /// Current time step.
     t: usize,
+    /// Decoding strictness.
+    mode: DecodeMode,
+    /// Packet alignment in the input stream.
+    alignment: Alignment,
+    /// Resource limits for stream decoding.
+    limits: DecodeLimits,
+    /// Diagnostic counters, if enabled.
+    diagnostics: Option<DecodeDiagnostics>,
+    /// Packets per epoch, if the state is reset at epoch boundaries.
+    epoch: Option<usize>,
+    /// Packets lost right before the next one (see `mark_lost`).
+    lost: usize,
+    /// Stream packets are followed by a CRC-16.
+    packet_crc: bool,
+    /// Whole streams end in a CRC-32 trailer: required, absent, or
+    /// detected by its magic (`None`).
+    stream_checksum: Option<bool>,
 }
 
 impl Decompressor {
     /// Create a new decompressor.
+    ///
+    /// # Arguments
+    /// * `f` - Packet length in bits (1-65535)
+    /// * `initial_mask` - Initial mask, `f` bits long (all zeros if `None`);
+    ///   must match the compressor's
+    /// * `robustness` - Robustness level R (0-7), as used by the compressor
     pub fn new(
         f: usize,
         initial_mask: Option<&BitVector>,
         robustness: u8,
     ) -> Result<Self, PocketError> {
-        if f == 0 || f > 65535 {
+        if f > MAX_PACKET_LENGTH {
+            return Err(PocketError::InvalidPacketSize(f));
+        }
+        Self::new_extended(f, initial_mask, robustness)
+    }
+
+    /// Create a decompressor for packets longer than the standard allows.
+    ///
+    /// **Non-standard extension.** Same as [`new`](Self::new), but F may
+    /// be up to 2²⁴ bits, for streams from
+    /// [`Compressor::new_extended`](crate::Compressor::new_extended).
+    pub fn new_extended(
+        f: usize,
+        initial_mask: Option<&BitVector>,
+        robustness: u8,
+    ) -> Result<Self, PocketError> {
+        if f == 0 || f > MAX_EXTENDED_PACKET_LENGTH {
             return Err(PocketError::InvalidPacketSize(f));
         }
         if robustness > 7 {
             return Err(PocketError::InvalidRobustness(robustness as usize));
         }
 
+        if let Some(m) = initial_mask {
+            if m.len() != f {
+                return Err(PocketError::InvalidInputLength {
+                    expected: f,
+                    actual: m.len(),
+                });
+            }
+        }
+
         let mask = initial_mask.cloned().unwrap_or_else(|| BitVector::new(f));
         let initial = mask.clone();
 
@@ -58,29 +209,471 @@ impl Decompressor {
             mask,
             initial_mask: initial,
             prev_output: BitVector::new(f),
+            initial_reference: None,
             xt: BitVector::new(f),
             extraction_mask: BitVector::new(f),
+            mask_positions: Vec::new(),
+            positions_valid: false,
             t: 0,
+            mode: DecodeMode::default(),
+            alignment: Alignment::default(),
+            limits: DecodeLimits::default(),
+            diagnostics: None,
+            epoch: None,
+            lost: 0,
+            packet_crc: false,
+            stream_checksum: None,
         };
 
         decomp.reset();
         Ok(decomp)
     }
 
+    /// Current mask Mₜ₋₁, applied to the next packet.
+    pub fn mask(&self) -> &BitVector {
+        &self.mask
+    }
+
+    /// Packet length in bits (F).
+    pub fn packet_size(&self) -> usize {
+        self.f
+    }
+
+    /// Robustness level R.
+    pub fn robustness(&self) -> u8 {
+        self.robustness
+    }
+
+    /// Packet alignment in the input stream.
+    pub(crate) fn alignment(&self) -> Alignment {
+        self.alignment
+    }
+
+    /// Returns true if stream packets are followed by a CRC-16.
+    pub(crate) fn packet_crc(&self) -> bool {
+        self.packet_crc
+    }
+
+    /// Time step t of the next packet.
+    pub fn time_step(&self) -> usize {
+        self.t
+    }
+
+    /// Heap memory currently held by this decompressor, in bytes.
+    pub fn heap_size(&self) -> usize {
+        [
+            &self.mask,
+            &self.initial_mask,
+            &self.prev_output,
+            &self.xt,
+            &self.extraction_mask,
+        ]
+        .into_iter()
+        .chain(self.initial_reference.as_ref())
+        .map(BitVector::heap_size)
+        .sum::<usize>()
+            + self.mask_positions.capacity() * 4
+    }
+
+    /// Peak heap footprint of a decompressor for packets of `f` bits.
+    ///
+    /// Retained state plus the output packet and decoded RLE vectors
+    /// allocated per packet. Independent of R.
+    ///
+    /// # Arguments
+    /// * `f` - Packet length in bits
+    pub fn peak_heap_size(f: usize) -> usize {
+        let vector = BitVector::heap_size_for(f);
+        // Retained: mask, initial mask, previous output, Xₜ, extraction mask
+        // and the cached mask positions (at most F).
+        // Scratch: output, the output bytes and the change positions of
+        // RLE(Xₜ) and RLE(HXOR), at most F each.
+        let positions = 4 * f.next_power_of_two().max(4);
+        5 * vector + 4 * f + vector + (f + 7) / 8 + 2 * positions
+    }
+
+    /// Diagnostic counters, if enabled with
+    /// [`DecompressorBuilder::diagnostics`].
+    pub fn diagnostics(&self) -> Option<&DecodeDiagnostics> {
+        self.diagnostics.as_ref()
+    }
+
     /// Reset decompressor to initial state.
     pub fn reset(&mut self) {
+        self.restart();
+        if let Some(diag) = &mut self.diagnostics {
+            *diag = DecodeDiagnostics::default();
+        }
+    }
+
+    /// Reset the stream state, keeping the diagnostics.
+    fn restart(&mut self) {
         self.t = 0;
+        self.lost = 0;
         self.mask.copy_from(&self.initial_mask);
-        self.prev_output.zero();
+        self.positions_valid = false;
+        match &self.initial_reference {
+            Some(reference) => self.prev_output.copy_from(reference),
+            None => self.prev_output.zero(),
+        }
         self.xt.zero();
     }
 
+    /// Serialize the stream state.
+    ///
+    /// Covers the mask, the previous packet and t, so chunked ground
+    /// processing can resume a pass with
+    /// [`restore_state`](Self::restore_state) instead of decoding it from
+    /// the start. The initial mask and decoding policies are configuration
+    /// and are not included.
+    ///
+    /// # Returns
+    /// A self-checking snapshot.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new(KIND_DECOMPRESSOR);
+        w.int(self.f);
+        w.int(self.robustness as usize);
+        w.int(self.t);
+        w.vector(&self.mask);
+        w.vector(&self.prev_output);
+        w.finish()
+    }
+
+    /// Restore the stream state saved by [`save_state`](Self::save_state).
+    ///
+    /// The decompressor must have the same packet size and robustness as
+    /// the one that was saved. On error the state is left unchanged.
+    pub fn restore_state(&mut self, data: &[u8]) -> Result<(), PocketError> {
+        let mut r = StateReader::new(data, KIND_DECOMPRESSOR)?;
+        let f = r.int()?;
+        if f != self.f {
+            return Err(PocketError::InvalidPacketSize(f));
+        }
+        let robustness = r.int()?;
+        if robustness != self.robustness as usize {
+            return Err(PocketError::RobustnessMismatch {
+                expected: self.robustness as usize,
+                actual: robustness,
+            });
+        }
+        let t = r.int()?;
+        let mask = r.vector(f)?;
+        let prev_output = r.vector(f)?;
+        r.finish()?;
+
+        self.t = t;
+        self.mask = mask;
+        self.positions_valid = false;
+        self.prev_output = prev_output;
+        Ok(())
+    }
+
+    /// Replace the current mask and the previous packet.
+    ///
+    /// For handing the decompressor the state recovered from a previous
+    /// pass or a pre-agreed managed parameter set, matching what the
+    /// compressor was given with
+    /// [`Compressor::set_mask`](crate::Compressor::set_mask) and
+    /// [`Compressor::set_reference`](crate::Compressor::set_reference).
+    /// The time step is left unchanged. Unlike the initial mask and
+    /// reference, the state is not restored by [`reset`](Self::reset).
+    ///
+    /// # Arguments
+    /// * `mask` - Mask Mₜ₋₁, F bits long
+    /// * `reference` - Previous packet Iₜ₋₁, F bits long
+    pub fn set_state(
+        &mut self,
+        mask: &BitVector,
+        reference: &BitVector,
+    ) -> Result<(), PocketError> {
+        for vector in [mask, reference] {
+            if vector.len() != self.f {
+                return Err(PocketError::InvalidInputLength {
+                    expected: self.f,
+                    actual: vector.len(),
+                });
+            }
+        }
+        self.mask.copy_from(mask);
+        self.positions_valid = false;
+        self.prev_output.copy_from(reference);
+        Ok(())
+    }
+
+    /// Position the decompressor at packet `n` of an indexed stream.
+    ///
+    /// Restarts from the nearest independently decodable packet at or
+    /// before `n` (see [`PacketIndex::anchor`]) and decodes up to `n`, so
+    /// the next [`decompress_packet`](Self::decompress_packet) call on the
+    /// returned reader yields packet `n`. The decompressor must have the
+    /// settings the index was built with.
+    ///
+    /// # Arguments
+    /// * `data` - Compressed stream the index was built from
+    /// * `index` - Index of `data`
+    /// * `n` - Packet to seek to
+    ///
+    /// # Returns
+    /// A reader positioned at packet `n`.
+    pub fn seek_to<'a>(
+        &mut self,
+        data: &'a [u8],
+        index: &PacketIndex,
+        n: usize,
+    ) -> Result<BitReader<'a>, PocketError> {
+        if index.packet_size != self.f {
+            return Err(PocketError::InvalidPacketSize(index.packet_size));
+        }
+        let anchor = index.anchor(n).ok_or_else(|| {
+            PocketError::InvalidParameter(format!(
+                "no independently decodable packet at or before packet {n}"
+            ))
+        })?;
+
+        let mut reader = BitReader::new(data, data.len() * 8);
+        reader.skip(index.entries[anchor].bit_offset)?;
+        self.restart();
+        self.t = index.entries[anchor].time_step;
+
+        for _ in anchor..n {
+            self.skip_packet(&mut reader)?;
+            if self.alignment == Alignment::Byte {
+                reader.align_byte();
+            }
+        }
+        Ok(reader)
+    }
+
+    /// Decompress a range of packets of an indexed stream.
+    ///
+    /// Seeks to the first packet with [`seek_to`](Self::seek_to), so only
+    /// the packets from the preceding independently decodable one onward
+    /// are decoded.
+    ///
+    /// # Arguments
+    /// * `data` - Compressed stream the index was built from
+    /// * `index` - Index of `data`
+    /// * `packets` - Packet numbers to decode
+    ///
+    /// # Returns
+    /// The decoded packets, concatenated.
+    pub fn decompress_range(
+        &mut self,
+        data: &[u8],
+        index: &PacketIndex,
+        packets: Range<usize>,
+    ) -> Result<Vec<u8>, PocketError> {
+        if packets.is_empty() || packets.end > index.len() {
+            return Err(PocketError::InvalidParameter(format!(
+                "packet range {}..{} outside the {} indexed packets",
+                packets.start,
+                packets.end,
+                index.len()
+            )));
+        }
+        let mut reader = self.seek_to(data, index, packets.start)?;
+        let mut output = Vec::with_capacity(packets.len() * ((self.f + 7) / 8));
+        let mut packet = BitVector::new(self.f);
+        for _ in packets {
+            self.decompress_packet_buf(&mut reader, &mut packet)?;
+            output.extend_from_slice(&packet.to_bytes());
+            if self.alignment == Alignment::Byte {
+                reader.align_byte();
+            }
+        }
+        Ok(output)
+    }
+
+    /// Record that `count` consecutive packets were lost before the next one.
+    ///
+    /// Implements the robustness recovery of CCSDS 124.0-B-1: every packet
+    /// signals the mask changes of the last Vₜ + 1 packets in Xₜ, with
+    /// their new values in kₜ, and Vₜ ≥ R once past the first R + 1
+    /// packets. Up to R lost packets are therefore bridged by the next
+    /// received one, which restores the mask instead of being decoded
+    /// against a stale one. Bits outside the mask did not change while the
+    /// packets were lost and are still predicted from the last packet
+    /// decoded.
+    ///
+    /// The next packet fails with [`PocketError::InvalidFormat`] if its Vₜ
+    /// is below `count` and it does not carry both the full mask and the
+    /// uncompressed packet.
+    ///
+    /// # Arguments
+    /// * `count` - Number of lost packets K (at most R)
+    pub fn mark_lost(&mut self, count: usize) -> Result<(), PocketError> {
+        if count > self.robustness as usize {
+            return Err(PocketError::InvalidParameter(format!(
+                "{count} lost packets exceed robustness R = {}",
+                self.robustness
+            )));
+        }
+        for _ in 0..count {
+            self.start_packet();
+            self.t += 1;
+        }
+        self.lost = count;
+        Ok(())
+    }
+
+    /// Resume decoding after a corrupted packet.
+    ///
+    /// Scans forward from the reader's position for the next packet
+    /// carrying both the full mask and the uncompressed packet
+    /// (ḟₜ = ṙₜ = 1), which restores the whole decoder state, and leaves the
+    /// reader at its start so the next
+    /// [`decompress_packet`](Self::decompress_packet) call decodes it.
+    /// Candidates are tried at every byte boundary in [`Alignment::Byte`]
+    /// streams and at every bit otherwise.
+    ///
+    /// The stream does not record how many packets the skipped bits held;
+    /// recover the gap from a time or sequence field of the packets. The
+    /// time step is unknown afterwards and is taken to be past the first
+    /// R + 1 packets.
+    ///
+    /// # Arguments
+    /// * `reader` - Compressed stream, positioned at or after the corrupted
+    ///   packet
+    ///
+    /// # Returns
+    /// The number of bits skipped, or
+    /// [`PocketError::UnexpectedEndOfInput`] if no such packet follows.
+    pub fn resync(&mut self, reader: &mut BitReader) -> Result<usize, PocketError> {
+        let start = reader.position();
+        let step = match self.alignment {
+            Alignment::Byte => {
+                reader.align_byte();
+                8
+            }
+            Alignment::Bit => 1,
+        };
+        while !is_anchor(reader.clone(), self.f) {
+            reader
+                .skip(step)
+                .map_err(|_| PocketError::UnexpectedEndOfInput)?;
+        }
+        self.resync_state();
+        Ok(reader.position() - start)
+    }
+
+    /// Prepare to decode from an anchor packet in the middle of a stream.
+    ///
+    /// Clears the mask and previous packet (both are restored by a packet
+    /// carrying a full mask and uncompressed data) and treats the next
+    /// packet as following the initial R + 1 packets.
+    pub(crate) fn resync_state(&mut self) {
+        self.restart();
+        self.mask.zero();
+        self.t = self.robustness as usize + 1;
+    }
+
+    /// Check a packet's Vₜ against the configured robustness R.
+    ///
+    /// The compressor signals Vₜ = R for the first R + 1 packets and
+    /// Vₜ = R + Cₜ ≥ R afterwards, so any other value means the stream was
+    /// compressed with a different R.
+    fn check_robustness(&self, vt: u8) -> Result<(), PocketError> {
+        let consistent = if self.t <= self.robustness as usize {
+            vt == self.robustness
+        } else {
+            vt >= self.robustness
+        };
+        if consistent {
+            Ok(())
+        } else {
+            Err(PocketError::RobustnessMismatch {
+                expected: self.robustness as usize,
+                actual: vt as usize,
+            })
+        }
+    }
+
     /// Decompress a single packet.
+    ///
+    /// Parses one oₜ = hₜ || qₜ || uₜ at the reader's position, leaving the
+    /// reader just after it (not byte-aligned), and advances the
+    /// decompressor state. On error the state is unspecified and the
+    /// decompressor should be [`reset`](Self::reset) before the next
+    /// stream.
+    ///
+    /// # Arguments
+    /// * `reader` - Compressed stream positioned at the packet
+    ///
+    /// # Returns
+    /// The reconstructed packet Iₜ, F bits long.
     pub fn decompress_packet(&mut self, reader: &mut BitReader) -> Result<BitVector, PocketError> {
         let mut output = BitVector::new(self.f);
+        self.decompress_packet_buf(reader, &mut output)?;
+        Ok(output)
+    }
+
+    /// Decompress a single packet into a caller-owned vector.
+    ///
+    /// Same as [`decompress_packet`](Self::decompress_packet) but reuses
+    /// `output` instead of allocating, for pipelines that stay in the bit
+    /// domain. On error the contents of `output` are unspecified.
+    ///
+    /// # Arguments
+    /// * `reader` - Compressed stream positioned at the packet
+    /// * `output` - Receives the packet; must be F bits long
+    pub fn decompress_packet_buf(
+        &mut self,
+        reader: &mut BitReader,
+        output: &mut BitVector,
+    ) -> Result<(), PocketError> {
+        if output.len() != self.f {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.f,
+                actual: output.len(),
+            });
+        }
+        self.start_packet();
 
         // Copy previous output as prediction base
         output.copy_from(&self.prev_output);
+        self.decode_packet(reader, output)?;
+        self.prev_output.copy_from(output);
+        Ok(())
+    }
+
+    /// Advance past a single packet without producing it.
+    ///
+    /// Parses the packet and updates the mask and previous-packet state
+    /// exactly like [`decompress_packet`](Self::decompress_packet), but
+    /// reconstructs the packet in place of the previous one instead of
+    /// building and copying an output vector. For filtering streams where
+    /// most packets are discarded. Leaves the reader just after the packet.
+    ///
+    /// # Arguments
+    /// * `reader` - Compressed stream positioned at the packet
+    ///
+    /// # Returns
+    /// The size and flags of the skipped packet.
+    pub fn skip_packet(&mut self, reader: &mut BitReader) -> Result<SkippedPacket, PocketError> {
+        self.start_packet();
+        let mut packet = std::mem::take(&mut self.prev_output);
+        let result = self.decode_packet(reader, &mut packet);
+        self.prev_output = packet;
+        result
+    }
+
+    /// Begin a packet; the compressor starts over at every epoch boundary.
+    fn start_packet(&mut self) {
+        if self.t > 0 && self.epoch == Some(self.t) {
+            self.restart();
+        }
+    }
+
+    /// Decode one packet into `output`, which holds the previous packet.
+    ///
+    /// Updates the mask, t and diagnostics; the caller keeps the previous
+    /// packet.
+    fn decode_packet(
+        &mut self,
+        reader: &mut BitReader,
+        output: &mut BitVector,
+    ) -> Result<SkippedPacket, PocketError> {
+        let start = reader.position();
 
         // Clear positive changes tracker
         self.xt.zero();
@@ -90,15 +683,21 @@ impl Decompressor {
         // hₜ = RLE(Xₜ) || BIT₄(Vₜ) || eₜ || kₜ || cₜ || ḋₜ
         // ====================================================================
 
-        // Decode RLE(Xₜ) - mask changes
-        let xt = rle_decode(reader, self.f)?;
+        // Decode RLE(Xₜ) - positions of the mask changes
+        let xt = rle_decode_positions(reader, self.f)?;
 
         // Read BIT₄(Vₜ) - effective robustness
         let vt = reader.read_bits(4)? as u8;
+        if self.mode == DecodeMode::Strict {
+            self.check_robustness(vt)?;
+        }
 
         // Process eₜ, kₜ, cₜ if Vₜ > 0 and there are changes
         let mut ct = false;
-        let change_count = xt.hamming_weight();
+        let change_count = xt.len();
+        if change_count > 0 {
+            self.positions_valid = false;
+        }
 
         if vt > 0 && change_count > 0 {
             // Read eₜ
@@ -107,17 +706,16 @@ impl Decompressor {
             if et {
                 // Read kₜ bits and apply mask updates directly (no allocation)
                 // kₜ has one bit per change in Xt
-                for i in 0..self.f {
-                    if xt.get_bit(i) != 0 {
-                        let kt_bit = reader.read_bit()? != 0;
-                        // kt=1 means positive update (mask becomes 0)
-                        // kt=0 means negative update (mask becomes 1)
-                        if kt_bit {
-                            self.mask.set_bit(i, 0);
-                            self.xt.set_bit(i, 1); // Track positive change
-                        } else {
-                            self.mask.set_bit(i, 1);
-                        }
+                for &i in &xt {
+                    let i = i as usize;
+                    let kt_bit = reader.read_bit()? != 0;
+                    // kt=1 means positive update (mask becomes 0)
+                    // kt=0 means negative update (mask becomes 1)
+                    if kt_bit {
+                        self.mask.set_bit(i, 0);
+                        self.xt.set_bit(i, 1); // Track positive change
+                    } else {
+                        self.mask.set_bit(i, 1);
                     }
                 }
 
@@ -125,20 +723,24 @@ impl Decompressor {
                 ct = reader.read_bit()? != 0;
             } else {
                 // et = 0: all updates are negative (mask bits become 1)
-                for i in 0..self.f {
-                    if xt.get_bit(i) != 0 {
-                        self.mask.set_bit(i, 1);
-                    }
+                for &i in &xt {
+                    self.mask.set_bit(i as usize, 1);
                 }
             }
+        } else if vt == 0 && change_count > 0 && self.t == 0 {
+            // Vt = 0 at t = 0: X₀ = D₀ = M₀ (M₋₁ = 0), so the change vector
+            // is the compressor's initial mask itself. Assigning rather than
+            // toggling keeps this correct when the decompressor was seeded
+            // with the same initial mask as the compressor.
+            self.mask.zero();
+            for &i in &xt {
+                self.mask.set_bit(i as usize, 1);
+            }
         } else if vt == 0 && change_count > 0 {
             // Vt = 0: toggle mask bits at change positions
-            for i in 0..self.f {
-                if xt.get_bit(i) != 0 {
-                    let current_val = self.mask.get_bit(i);
-                    let toggled = u8::from(current_val == 0);
-                    self.mask.set_bit(i, toggled);
-                }
+            for &i in &xt {
+                let i = i as usize;
+                self.mask.set_bit(i, self.mask.get_bit(i) ^ 1);
             }
         }
         // else: No changes to apply (change_count == 0)
@@ -151,33 +753,35 @@ impl Decompressor {
         // ====================================================================
 
         let mut rt = false;
+        let mut ft = false;
 
         // dt=1 means both ft=0 and rt=0 (optimization per CCSDS Eq. 13)
         // dt=0 means we need to read ft and rt from the stream
         if !dt {
             // Read ft flag
-            let ft = reader.read_bit()? != 0;
+            ft = reader.read_bit()? != 0;
 
             if ft {
                 // Full mask follows: decode RLE(M XOR (M<<))
-                let mask_diff = rle_decode(reader, self.f)?;
+                let mask_diff = rle_decode_positions(reader, self.f)?;
+                self.positions_valid = false;
 
                 // Reverse the horizontal XOR to get the actual mask.
                 // HXOR encoding: HXOR[i] = M[i] XOR M[i+1], with HXOR[F-1] = M[F-1]
-                // Reversal: start from LSB (position F-1) and work towards MSB (position 0)
-                // M[F-1] = HXOR[F-1] (just copy)
-                // M[i] = HXOR[i] XOR M[i+1] for i < F-1
-
-                // Copy LSB bit directly (position F-1 in bitvector)
-                let mut current = mask_diff.get_bit(self.f - 1);
-                self.mask.set_bit(self.f - 1, current);
-
-                // Process remaining bits from F-2 down to 0
-                for i in (0..self.f - 1).rev() {
-                    let hxor_bit = mask_diff.get_bit(i);
-                    // M[i] = HXOR[i] XOR M[i+1] = HXOR[i] XOR current
-                    current ^= hxor_bit;
-                    self.mask.set_bit(i, current);
+                // so M[i] is the parity of the HXOR bits at positions >= i.
+                // Walking the set positions from the end, M is 1 from each
+                // odd-numbered position down to just above the next one
+                // (or down to 0 for the last).
+                self.mask.zero();
+                for pair in mask_diff.rchunks(2) {
+                    let (low, high) = match *pair {
+                        [low, high] => (low as usize + 1, high as usize),
+                        [high] => (0, high as usize),
+                        _ => unreachable!(),
+                    };
+                    for i in low..=high {
+                        self.mask.set_bit(i, 1);
+                    }
                 }
             }
 
@@ -191,23 +795,39 @@ impl Decompressor {
 
         if rt {
             // Full packet follows: COUNT(F) || Iₜ
-            let _packet_length = count_decode(reader)?;
+            let packet_length = count_decode(reader)? as usize;
+            if self.mode == DecodeMode::Strict && packet_length != self.f {
+                return Err(PocketError::InvalidFormat(format!(
+                    "uncompressed packet length {packet_length} does not match F = {}",
+                    self.f
+                )));
+            }
 
             // Read full packet
-            for i in 0..self.f {
-                let bit = reader.read_bit()?;
-                output.set_bit(i, bit);
-            }
+            reader.read_into_bitvector(output, self.f)?;
         } else {
             // Compressed: extract unpredictable bits
             if ct && vt > 0 {
                 // BE(Iₜ, (Xₜ OR Mₜ)) - need combined mask
                 self.extraction_mask.copy_from(&self.mask);
                 self.extraction_mask.or_assign(&self.xt);
-                bit_insert(reader, &mut output, &self.extraction_mask)?;
+                bit_insert(reader, output, &self.extraction_mask)?;
             } else {
-                // BE(Iₜ, Mₜ) - use mask directly (no allocation)
-                bit_insert(reader, &mut output, &self.mask)?;
+                // BE(Iₜ, Mₜ) - most packets leave the mask unchanged, so
+                // its positions are derived once it settles and then reused
+                if !self.positions_valid && (change_count > 0 || ft) {
+                    bit_insert(reader, output, &self.mask)?;
+                } else {
+                    if !self.positions_valid {
+                        self.mask_positions.clear();
+                        self.mask_positions
+                            .reserve_exact(self.mask.hamming_weight());
+                        self.mask_positions
+                            .extend(self.mask.iter_ones().rev().map(|i| i as u32));
+                        self.positions_valid = true;
+                    }
+                    bit_insert_at(reader, output, &self.mask_positions)?;
+                }
             }
         }
 
@@ -215,20 +835,582 @@ impl Decompressor {
         // Update state for next cycle
         // ====================================================================
 
-        self.prev_output.copy_from(&output);
+        if self.lost > vt as usize && !(ft && rt) {
+            return Err(PocketError::InvalidFormat(format!(
+                "Vt = {vt} cannot bridge {} lost packets",
+                self.lost
+            )));
+        }
+        self.lost = 0;
         self.t += 1;
 
-        Ok(output)
+        let bits = reader.position() - start;
+        if let Some(diag) = &mut self.diagnostics {
+            diag.packets += 1;
+            diag.full_masks += usize::from(ft);
+            diag.uncompressed += usize::from(rt);
+            diag.mask_changes += change_count;
+            diag.bits_consumed += bits;
+        }
+
+        Ok(SkippedPacket {
+            bits,
+            full_mask: ft,
+            uncompressed: rt,
+        })
+    }
+
+    /// Decode the next packet of a stream into `packet`.
+    ///
+    /// Applies the configured [`DecodeMode`], [`Alignment`] and
+    /// [`DecodeLimits`], given the number of packets already decoded.
+    ///
+    /// # Returns
+    /// `false` at the end of the stream.
+    fn next_stream_packet(
+        &mut self,
+        reader: &mut BitReader,
+        packet: &mut BitVector,
+        packets: usize,
+    ) -> Result<bool, PocketError> {
+        if reader.remaining() == 0 {
+            return Ok(false);
+        }
+        if let Some(max) = self.limits.max_packets {
+            if packets >= max {
+                return Err(PocketError::LimitExceeded {
+                    limit: "packets",
+                    max,
+                });
+            }
+        }
+        if let Some(max) = self.limits.max_output_bytes {
+            if (packets + 1) * ((self.f + 7) / 8) > max {
+                return Err(PocketError::LimitExceeded {
+                    limit: "output bytes",
+                    max,
+                });
+            }
+        }
+
+        // Bit-packed streams end with up to 7 zero padding bits, which
+        // never decode as a whole packet
+        let padding = self.alignment == Alignment::Bit && reader.remaining() < 8;
+        let start = reader.position() / 8;
+
+        match self.decompress_packet_buf(reader, packet) {
+            Ok(()) => {}
+            Err(PocketError::UnexpectedEndOfInput | PocketError::Underflow)
+                if packets > 0 && (padding || self.mode == DecodeMode::Lenient) =>
+            {
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        }
+
+        if self.alignment == Alignment::Byte {
+            reader.align_byte();
+        }
+        if self.packet_crc {
+            let end = reader.position() / 8;
+            let stored = reader.read_bits(16)?;
+            if u32::from(crc16(&reader.data()[start..end])) != stored {
+                return Err(PocketError::CrcMismatch {
+                    packet_index: packets,
+                });
+            }
+        }
+        Ok(true)
+    }
+
+    /// Decompress a stream lazily, one packet at a time.
+    ///
+    /// The iterator applies the same policies as
+    /// [`decompress_stream`](Self::decompress_stream), starting from the
+    /// current state. A stream checksum that does not match is its first
+    /// item.
+    ///
+    /// # Arguments
+    /// * `data` - Compressed stream
+    pub fn iter_packets(self, data: &[u8]) -> DecompressIter<'_> {
+        let (data, error) = match apply_trailer(data, self.stream_checksum) {
+            Ok(data) => (data, None),
+            Err(e) => (data, Some(e)),
+        };
+        DecompressIter {
+            reader: BitReader::new(data, data.len() * 8),
+            packet: BitVector::new(self.f),
+            decomp: self,
+            packets: 0,
+            done: false,
+            error,
+        }
+    }
+
+    /// Decompress a whole stream with this decompressor's policies.
+    ///
+    /// Applies the configured [`DecodeMode`], [`Alignment`] and
+    /// [`DecodeLimits`]. State carries over between calls.
+    ///
+    /// # Arguments
+    /// * `data` - Compressed stream
+    ///
+    /// # Returns
+    /// The decompressed packets, concatenated.
+    pub fn decompress_stream(&mut self, data: &[u8]) -> Result<Vec<u8>, PocketError> {
+        self.decompress_stream_consumed(data)
+            .map(|(output, _)| output)
+    }
+
+    /// Decompress a whole stream and report how much input was used.
+    ///
+    /// Same as [`decompress_stream`](Self::decompress_stream); the
+    /// [`Consumed`] extent stops at the last packet decoded, so a
+    /// truncated tail skipped in [`DecodeMode::Lenient`] or trailing
+    /// padding in [`Alignment::Bit`] streams is not counted.
+    ///
+    /// # Arguments
+    /// * `data` - Compressed stream
+    ///
+    /// # Returns
+    /// The decompressed packets and the input extent they came from.
+    pub fn decompress_stream_consumed(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(Vec<u8>, Consumed), PocketError> {
+        let mut output = Vec::new();
+        let consumed = self.decompress_stream_into(data, &mut output)?;
+        Ok((output, consumed))
+    }
+
+    /// Decompress a whole stream into a caller-provided buffer.
+    ///
+    /// Same as [`decompress_stream_consumed`](Self::decompress_stream_consumed),
+    /// but the packets are appended to `output`, so a cleared buffer can be
+    /// reused across streams without reallocating. On error `output` holds
+    /// the packets decoded before the failure.
+    ///
+    /// # Arguments
+    /// * `data` - Compressed stream
+    /// * `output` - Buffer the decompressed packets are appended to
+    ///
+    /// # Returns
+    /// The input extent the packets came from.
+    pub fn decompress_stream_into(
+        &mut self,
+        data: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<Consumed, PocketError> {
+        self.decode_stream(data, output, |_, _| Ok(()))
+    }
+
+    /// Decompress the complete packets at the start of a partly received
+    /// byte-aligned stream.
+    ///
+    /// For data arriving in chunks (sockets, async readers, browser
+    /// downloads): call with everything received but not yet consumed, drop
+    /// the returned number of bytes and keep the rest for the next call. A
+    /// packet cut off by the end of `data` is decoded on a copy of the
+    /// state, so it is left for the next call instead of corrupting the
+    /// decompressor; copies are only made within the largest possible
+    /// packet of the end.
+    ///
+    /// # Arguments
+    /// * `data` - Received stream, starting at a packet boundary
+    /// * `output` - Destination; the decoded packets are appended
+    ///
+    /// A stream checksum trailer cannot be told from packet data before the
+    /// stream is complete, so it is not handled here; decode sealed streams
+    /// whole, e.g. with [`decompress_stream`](Self::decompress_stream).
+    ///
+    /// # Returns
+    /// The number of bytes of `data` consumed by whole packets.
+    pub fn decompress_available(
+        &mut self,
+        data: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<usize, PocketError> {
+        if self.alignment != Alignment::Byte {
+            return Err(PocketError::InvalidParameter(
+                "partial input needs a byte-aligned stream".into(),
+            ));
+        }
+        let packet_bytes = (self.f + 7) / 8;
+        // 10F + 42 bits bound every packet (full mask, uncompressed)
+        let max_bytes = (10 * self.f + 64) / 8 + if self.packet_crc { 2 } else { 0 };
+        let mut reader = BitReader::new(data, data.len() * 8);
+        let mut packet = BitVector::new(self.f);
+        let mut consumed = 0;
+
+        while consumed < data.len() {
+            if data.len() - consumed >= max_bytes {
+                if !self.next_stream_packet(&mut reader, &mut packet, 0)? {
+                    break;
+                }
+            } else {
+                let mut trial = self.clone();
+                let mut trial_reader = reader.clone();
+                match trial.next_stream_packet(&mut trial_reader, &mut packet, 0) {
+                    Ok(true) => {
+                        *self = trial;
+                        reader = trial_reader;
+                    }
+                    Ok(false) | Err(PocketError::UnexpectedEndOfInput | PocketError::Underflow) => {
+                        break
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            output.extend(packet.as_byte_chunks().flatten().take(packet_bytes));
+            consumed = reader.position() / 8;
+        }
+
+        Ok(consumed)
+    }
+
+    /// Shared stream loop of [`decompress_stream_into`](Self::decompress_stream_into);
+    /// `on_packet` is called with the number of packets decoded and bits
+    /// read so far after every packet, and an error from it aborts decoding.
+    fn decode_stream(
+        &mut self,
+        data: &[u8],
+        output: &mut Vec<u8>,
+        mut on_packet: impl FnMut(usize, usize) -> Result<(), PocketError>,
+    ) -> Result<Consumed, PocketError> {
+        let data = apply_trailer(data, self.stream_checksum)?;
+        let mut reader = BitReader::new(data, data.len() * 8);
+        let packet_bytes = (self.f + 7) / 8;
+        let mut packet = BitVector::new(self.f);
+        let mut packets = 0usize;
+        let mut end = 0usize;
+
+        while self.next_stream_packet(&mut reader, &mut packet, packets)? {
+            output.extend(packet.as_byte_chunks().flatten().take(packet_bytes));
+            packets += 1;
+            end = reader.position();
+            on_packet(packets, end)?;
+        }
+
+        Ok(Consumed::from_bits(end))
+    }
+}
+
+/// Returns true if a packet carrying ḟₜ = 1 and ṙₜ = 1 starts at the
+/// reader's position.
+///
+/// Only parses the packet header, which needs no decoder state.
+pub(crate) fn is_anchor(mut reader: BitReader, f: usize) -> bool {
+    let mut anchor = || -> Result<bool, PocketError> {
+        let changes = rle_decode_positions(&mut reader, f)?.len();
+        let vt = reader.read_bits(4)?;
+        if vt > 0 && changes > 0 && reader.read_bit()? == 1 {
+            reader.skip(changes)?;
+            reader.read_bit()?;
+        }
+        // ḋₜ = 0, ḟₜ = 1, full mask, ṙₜ = 1, COUNT(F)
+        if reader.read_bit()? != 0 || reader.read_bit()? != 1 {
+            return Ok(false);
+        }
+        rle_decode_positions(&mut reader, f)?;
+        Ok(reader.read_bit()? == 1 && count_decode(&mut reader)? as usize == f)
+    };
+    anchor().unwrap_or(false)
+}
+
+/// Lazy decompression of a stream, one packet per item.
+///
+/// Created by [`DecompressIter::new`] or [`Decompressor::iter_packets`].
+/// Only one packet is held in memory at a time, so consumers can stop
+/// early, filter packets or walk large archives in constant memory. After
+/// an error or the end of the stream the iterator is exhausted.
+pub struct DecompressIter<'a> {
+    decomp: Decompressor,
+    reader: BitReader<'a>,
+    packet: BitVector,
+    packets: usize,
+    done: bool,
+    /// Trailer error, reported before any packet.
+    error: Option<PocketError>,
+}
+
+impl<'a> DecompressIter<'a> {
+    /// Create an iterator with the parameters of [`decompress`].
+    ///
+    /// # Arguments
+    /// * `data` - Compressed stream
+    /// * `packet_size` - Size of each packet in bits
+    /// * `robustness` - Robustness parameter R (0-7)
+    pub fn new(data: &'a [u8], packet_size: usize, robustness: usize) -> Result<Self, PocketError> {
+        if packet_size == 0 {
+            return Err(PocketError::InvalidPacketSize(packet_size));
+        }
+        Ok(DecompressorBuilder::new(packet_size)
+            .robustness(robustness)
+            .build()?
+            .iter_packets(data))
+    }
+
+    /// Number of packets decoded so far.
+    pub fn packets(&self) -> usize {
+        self.packets
+    }
+
+    /// Input consumed so far, up to the end of the last packet decoded.
+    pub fn consumed(&self) -> Consumed {
+        Consumed::from_bits(self.reader.position())
+    }
+
+    /// The underlying decompressor.
+    pub fn decompressor(&self) -> &Decompressor {
+        &self.decomp
+    }
+}
+
+impl Iterator for DecompressIter<'_> {
+    type Item = Result<Vec<u8>, PocketError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(e) = self.error.take() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        let mut reader = self.reader.clone();
+        match self
+            .decomp
+            .next_stream_packet(&mut reader, &mut self.packet, self.packets)
+        {
+            Ok(true) => {
+                self.reader = reader;
+                self.packets += 1;
+                let packet_bytes = (self.decomp.f + 7) / 8;
+                Some(Ok(self
+                    .packet
+                    .as_byte_chunks()
+                    .flatten()
+                    .take(packet_bytes)
+                    .collect()))
+            }
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Builder for [`Decompressor`].
+///
+/// Replaces the positional arguments of [`Decompressor::new`] with named
+/// settings:
+///
+/// ```rust
+/// use pocketplus::{DecodeMode, DecompressorBuilder};
+///
+/// let decomp = DecompressorBuilder::new(720)
+///     .robustness(2)
+///     .mode(DecodeMode::Lenient)
+///     .diagnostics(true)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct DecompressorBuilder {
+    packet_size: usize,
+    robustness: usize,
+    initial_mask: Option<BitVector>,
+    initial_reference: Option<BitVector>,
+    mode: DecodeMode,
+    alignment: Alignment,
+    limits: DecodeLimits,
+    diagnostics: bool,
+    epoch: Option<usize>,
+    packet_crc: bool,
+    stream_checksum: Option<bool>,
+    extended: bool,
+}
+
+impl DecompressorBuilder {
+    /// Start a builder for packets of `packet_size` bits (F).
+    pub fn new(packet_size: usize) -> Self {
+        Self {
+            packet_size,
+            robustness: 0,
+            initial_mask: None,
+            initial_reference: None,
+            mode: DecodeMode::default(),
+            alignment: Alignment::default(),
+            limits: DecodeLimits::default(),
+            diagnostics: false,
+            epoch: None,
+            packet_crc: false,
+            stream_checksum: None,
+            extended: false,
+        }
+    }
+
+    /// Robustness level R (0-7, default 0).
+    #[must_use]
+    pub fn robustness(mut self, robustness: usize) -> Self {
+        self.robustness = robustness;
+        self
+    }
+
+    /// Initial mask M₀ (F bits, default all zeros).
+    #[must_use]
+    pub fn initial_mask(mut self, mask: &BitVector) -> Self {
+        self.initial_mask = Some(mask.clone());
+        self
+    }
+
+    /// Reference packet preceding the stream (F bits, default all zeros),
+    /// for streams compressed with
+    /// [`StartMode::Seeded`](crate::StartMode::Seeded).
+    #[must_use]
+    pub fn initial_reference(mut self, reference: &BitVector) -> Self {
+        self.initial_reference = Some(reference.clone());
+        self
+    }
+
+    /// Decoding strictness (default [`DecodeMode::Strict`]).
+    #[must_use]
+    pub fn mode(mut self, mode: DecodeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Packet alignment in the input (default [`Alignment::Byte`]).
+    #[must_use]
+    pub fn alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Resource limits for [`Decompressor::decompress_stream`].
+    #[must_use]
+    pub fn limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Collect [`DecodeDiagnostics`] while decoding.
+    #[must_use]
+    pub fn diagnostics(mut self, enabled: bool) -> Self {
+        self.diagnostics = enabled;
+        self
+    }
+
+    /// Reset the state every `packets` packets, for streams compressed
+    /// with the same epoch
+    /// ([`CompressorBuilder::epoch`](crate::CompressorBuilder::epoch)).
+    /// Default never.
+    #[must_use]
+    pub fn epoch(mut self, packets: usize) -> Self {
+        self.epoch = Some(packets);
+        self
+    }
+
+    /// Verify the CRC-16 following every packet of a stream, for streams
+    /// compressed with
+    /// [`CompressorBuilder::packet_crc`](crate::CompressorBuilder::packet_crc)
+    /// (default off). Requires [`Alignment::Byte`].
+    ///
+    /// Stream decoding fails with [`PocketError::CrcMismatch`] at the first
+    /// packet whose checksum does not match. A corrupted packet can also
+    /// fail to decode before its checksum is reached.
+    #[must_use]
+    pub fn packet_crc(mut self, enabled: bool) -> Self {
+        self.packet_crc = enabled;
+        self
+    }
+
+    /// Require a CRC-32 trailer at the end of whole streams, for streams
+    /// compressed with
+    /// [`CompressorBuilder::stream_checksum`](crate::CompressorBuilder::stream_checksum).
+    ///
+    /// By default a trailer is verified and stripped when the stream ends
+    /// in its magic (see [`crate::trailer`]). With `true`, stream decoding
+    /// also fails if the trailer is missing; with `false`, every byte is
+    /// decoded as stream data. A checksum that does not match fails with
+    /// [`PocketError::InvalidFormat`] before any packet is decoded.
+    #[must_use]
+    pub fn stream_checksum(mut self, enabled: bool) -> Self {
+        self.stream_checksum = Some(enabled);
+        self
+    }
+
+    /// Allow packets above 65535 bits (default off). **Non-standard**, see
+    /// [`Decompressor::new_extended`].
+    #[must_use]
+    pub fn extended(mut self, enabled: bool) -> Self {
+        self.extended = enabled;
+        self
+    }
+
+    /// Validate the settings and create the decompressor.
+    pub fn build(&self) -> Result<Decompressor, PocketError> {
+        if self.robustness > 7 {
+            return Err(PocketError::InvalidRobustness(self.robustness));
+        }
+        if self.packet_crc && self.alignment != Alignment::Byte {
+            return Err(PocketError::InvalidParameter(
+                "packet CRCs require byte-aligned packets".into(),
+            ));
+        }
+        if self.epoch == Some(0) {
+            return Err(PocketError::InvalidParameter(
+                "epoch must be at least one packet".into(),
+            ));
+        }
+
+        let create = if self.extended {
+            Decompressor::new_extended
+        } else {
+            Decompressor::new
+        };
+        let mut decomp = create(
+            self.packet_size,
+            self.initial_mask.as_ref(),
+            self.robustness as u8,
+        )?;
+        decomp.mode = self.mode;
+        decomp.alignment = self.alignment;
+        decomp.limits = self.limits;
+        decomp.diagnostics = self.diagnostics.then(DecodeDiagnostics::default);
+        decomp.epoch = self.epoch;
+        decomp.packet_crc = self.packet_crc;
+        decomp.stream_checksum = self.stream_checksum;
+        if let Some(reference) = &self.initial_reference {
+            if reference.len() != self.packet_size {
+                return Err(PocketError::InvalidInputLength {
+                    expected: self.packet_size,
+                    actual: reference.len(),
+                });
+            }
+            decomp.initial_reference = Some(reference.clone());
+            decomp.reset();
+        }
+        Ok(decomp)
     }
 }
 
 /// Decompress data using POCKET+ algorithm.
 ///
+/// A stream ending in an integrity trailer (see [`crate::trailer`]) is
+/// verified and the trailer removed before decoding.
+///
 /// # Arguments
 ///
 /// * `data` - Compressed data bytes to decompress
-/// * `packet_size` - Size of each packet in bits (must be divisible by 8)
-/// * `robustness` - Robustness parameter R (0-7)
+/// * `packet_size` - Size of each packet in bits
+/// * `robustness` - Robustness parameter R (0-7); must match the
+///   compressor, use [`detect_robustness`] if it is not known
 ///
 /// # Returns
 ///
@@ -237,16 +1419,51 @@ impl Decompressor {
 /// # Errors
 ///
 /// Returns `PocketError` if:
-/// - `packet_size` is 0 or not divisible by 8
+/// - `packet_size` is 0 or above 65535
 /// - `robustness` is greater than 7
+/// - `robustness` contradicts the Vₜ signalled in the stream
+/// - The stream ends in a trailer whose checksum does not match
 /// - Compressed data is invalid or corrupted
 pub fn decompress(
     data: &[u8],
     packet_size: usize,
     robustness: usize,
+) -> Result<Vec<u8>, PocketError> {
+    decompress_with_mask(data, packet_size, robustness, None)
+}
+
+/// Decompress data using POCKET+ algorithm with an initial mask.
+///
+/// Use this for streams produced by a compressor seeded with an initial
+/// mask (M₀) when the first packet does not carry a full mask. Pass the
+/// same mask that was given to the compressor.
+///
+/// # Arguments
+///
+/// * `data` - Compressed data bytes to decompress
+/// * `packet_size` - Size of each packet in bits
+/// * `robustness` - Robustness parameter R (0-7)
+/// * `initial_mask` - Initial mask vector (F bits), or `None` for all zeros
+///
+/// # Returns
+///
+/// Decompressed data as a byte vector, or an error if decompression fails.
+///
+/// # Errors
+///
+/// Returns `PocketError` if:
+/// - `packet_size` is 0 or above 65535
+/// - `robustness` is greater than 7
+/// - `initial_mask` length doesn't match `packet_size`
+/// - Compressed data is invalid or corrupted
+pub fn decompress_with_mask(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    initial_mask: Option<&BitVector>,
 ) -> Result<Vec<u8>, PocketError> {
     // Validate parameters
-    if packet_size == 0 || packet_size % 8 != 0 {
+    if packet_size == 0 {
         return Err(PocketError::InvalidPacketSize(packet_size));
     }
 
@@ -254,38 +1471,247 @@ pub fn decompress(
         return Err(PocketError::InvalidRobustness(robustness));
     }
 
+    let data = strip_trailer(data)?;
     if data.is_empty() {
         return Err(PocketError::UnexpectedEndOfInput);
     }
 
-    // Initialize decompressor
-    let mut decomp = Decompressor::new(packet_size, None, robustness as u8)?;
+    let mut builder = DecompressorBuilder::new(packet_size).robustness(robustness);
+    if let Some(mask) = initial_mask {
+        builder = builder.initial_mask(mask);
+    }
 
-    // Initialize bit reader
-    let mut reader = BitReader::new(data, data.len() * 8);
+    builder.build()?.decompress_stream(data)
+}
+
+/// Decompress data into a caller-provided buffer.
+///
+/// Takes the same parameters as [`decompress`] and appends the packets to
+/// `output`, reusing one packet vector throughout, so repeated calls with a
+/// cleared `output` do not reallocate it. On error `output` is left as it
+/// was.
+///
+/// # Returns
+/// The number of bytes appended.
+pub fn decompress_into(
+    data: &[u8],
+    output: &mut Vec<u8>,
+    packet_size: usize,
+    robustness: usize,
+) -> Result<usize, PocketError> {
+    if packet_size == 0 {
+        return Err(PocketError::InvalidPacketSize(packet_size));
+    }
+    let data = strip_trailer(data)?;
+    if data.is_empty() {
+        return Err(PocketError::UnexpectedEndOfInput);
+    }
+
+    let before = output.len();
+    let result = DecompressorBuilder::new(packet_size)
+        .robustness(robustness)
+        .build()
+        .and_then(|mut decomp| decomp.decompress_stream_into(data, output));
+    if let Err(e) = result {
+        output.truncate(before);
+        return Err(e);
+    }
+    Ok(output.len() - before)
+}
+
+/// Decompress data, checking a cancellation flag between packets.
+///
+/// Takes the same arguments as [`decompress`]. Long batch jobs can be
+/// aborted from another thread by setting `cancel`; the flag is checked
+/// before the first packet and after each packet.
+///
+/// # Arguments
+/// * `cancel` - Set to `true` to abort the decompression
+///
+/// # Errors
+///
+/// Returns [`PocketError::Cancelled`] once `cancel` is set, plus the errors
+/// of [`decompress`].
+pub fn decompress_cancellable(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    cancel: &AtomicBool,
+) -> Result<Vec<u8>, PocketError> {
+    if packet_size == 0 {
+        return Err(PocketError::InvalidPacketSize(packet_size));
+    }
+    let data = strip_trailer(data)?;
+    if data.is_empty() {
+        return Err(PocketError::UnexpectedEndOfInput);
+    }
+
+    let check = || {
+        if cancel.load(Ordering::Relaxed) {
+            Err(PocketError::Cancelled)
+        } else {
+            Ok(())
+        }
+    };
+    check()?;
 
-    // Output packet size in bytes
-    let packet_bytes = (packet_size + 7) / 8;
     let mut output = Vec::new();
+    DecompressorBuilder::new(packet_size)
+        .robustness(robustness)
+        .build()?
+        .decode_stream(data, &mut output, |_, _| check())?;
+    Ok(output)
+}
+
+/// Decompress data, reporting progress every `every` packets.
+///
+/// Takes the same arguments as [`decompress`]. `callback` is called after
+/// every `every`-th packet and once more after the last packet, unless
+/// that was just reported.
+///
+/// # Arguments
+/// * `every` - Packets between reports (at least 1)
+/// * `callback` - Receives the packets, bytes read and written and the
+///   ratio so far
+pub fn decompress_with_progress(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    every: usize,
+    mut callback: impl FnMut(ProgressInfo),
+) -> Result<Vec<u8>, PocketError> {
+    if packet_size == 0 {
+        return Err(PocketError::InvalidPacketSize(packet_size));
+    }
+    if every == 0 {
+        return Err(PocketError::InvalidParameter(
+            "progress interval must be at least 1".into(),
+        ));
+    }
+    let data = strip_trailer(data)?;
+    if data.is_empty() {
+        return Err(PocketError::UnexpectedEndOfInput);
+    }
 
-    // Decompress packets until input exhausted
-    while reader.remaining() > 0 {
-        let packet = decomp.decompress_packet(&mut reader)?;
+    let packet_bytes = (packet_size + 7) / 8;
+    let mut output = Vec::new();
+    let mut last = (0, 0);
+    DecompressorBuilder::new(packet_size)
+        .robustness(robustness)
+        .build()?
+        .decode_stream(data, &mut output, |packets, bits| {
+            last = (packets, bits);
+            if packets % every == 0 {
+                callback(ProgressInfo::new(
+                    packets,
+                    packets * packet_bytes,
+                    (bits + 7) / 8,
+                    false,
+                ));
+            }
+            Ok(())
+        })?;
+
+    let (packets, bits) = last;
+    if packets % every != 0 {
+        callback(ProgressInfo::new(
+            packets,
+            packets * packet_bytes,
+            (bits + 7) / 8,
+            false,
+        ));
+    }
+    Ok(output)
+}
 
-        // Convert to bytes and append
-        let packet_data = packet.to_bytes();
-        output.extend_from_slice(&packet_data[..packet_bytes]);
+/// Read the robustness level a stream was compressed with.
+///
+/// The first packet of a stream carries V₀ = R, so R can be recovered
+/// without out-of-band configuration and passed to [`decompress`].
+///
+/// # Arguments
+/// * `data` - Compressed stream, starting at its first packet
+/// * `packet_size` - Size of each packet in bits
+///
+/// # Returns
+/// The robustness level R (0-7).
+pub fn detect_robustness(data: &[u8], packet_size: usize) -> Result<usize, PocketError> {
+    if packet_size == 0 || packet_size > 65535 {
+        return Err(PocketError::InvalidPacketSize(packet_size));
+    }
+    let mut reader = BitReader::new(data, data.len() * 8);
+    rle_decode_positions(&mut reader, packet_size)?;
+    let r = reader.read_bits(4)? as usize;
+    if r > 7 {
+        return Err(PocketError::InvalidRobustness(r));
+    }
+    Ok(r)
+}
 
-        // Align to byte boundary for next packet
-        reader.align_byte();
+/// Decompress data and report how much of it was used.
+///
+/// Takes the same arguments as [`decompress`]. The returned [`Consumed`]
+/// extent ends after the last packet, so a payload embedded in a larger
+/// frame can be located precisely.
+///
+/// # Returns
+///
+/// The decompressed data and the input extent consumed.
+pub fn decompress_with_consumed(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+) -> Result<(Vec<u8>, Consumed), PocketError> {
+    if packet_size == 0 {
+        return Err(PocketError::InvalidPacketSize(packet_size));
     }
+    if data.is_empty() {
+        return Err(PocketError::UnexpectedEndOfInput);
+    }
+
+    DecompressorBuilder::new(packet_size)
+        .robustness(robustness)
+        .build()?
+        .decompress_stream_consumed(data)
+}
 
+/// Decompress exactly `num_packets` packets.
+///
+/// For callers that know the packet count: decoding stops after the last
+/// packet, so trailing padding or unrelated bytes after the stream are
+/// never parsed, and a stream holding fewer packets is an error instead of
+/// a short result.
+///
+/// # Arguments
+///
+/// * `data` - Compressed data bytes to decompress
+/// * `packet_size` - Size of each packet in bits
+/// * `robustness` - Robustness parameter R (0-7)
+/// * `num_packets` - Number of packets to decode
+///
+/// # Errors
+///
+/// Returns [`PocketError::UnexpectedEndOfInput`] if the stream ends before
+/// `num_packets` packets, plus the errors of [`decompress`].
+pub fn decompress_n(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    num_packets: usize,
+) -> Result<Vec<u8>, PocketError> {
+    let mut packets = DecompressIter::new(data, packet_size, robustness)?;
+    let mut output = Vec::new();
+    for _ in 0..num_packets {
+        let packet = packets.next().ok_or(PocketError::UnexpectedEndOfInput)??;
+        output.extend(packet);
+    }
     Ok(output)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "encoder")]
     use crate::compress::compress;
 
     #[test]
@@ -295,11 +1721,26 @@ mod tests {
         assert!(matches!(result, Err(PocketError::InvalidPacketSize(0))));
     }
 
+    #[cfg(feature = "encoder")]
     #[test]
-    fn test_decompress_invalid_packet_size_not_byte_aligned() {
-        let data = vec![0u8; 10];
-        let result = decompress(&data, 721, 1);
-        assert!(matches!(result, Err(PocketError::InvalidPacketSize(721))));
+    fn test_decompress_packet_size_not_byte_aligned() {
+        // 721-bit packets occupy 91 bytes, padded with 7 zero bits
+        let data: Vec<u8> = (0..91 * 12u32)
+            .map(|i| {
+                if i % 91 == 90 {
+                    0xFF
+                } else {
+                    (i % 7 + i / 91) as u8
+                }
+            })
+            .collect();
+        let compressed = compress(&data, 721, 1, 10, 20, 50).unwrap();
+        let decompressed = decompress(&compressed, 721, 1).unwrap();
+        assert_eq!(decompressed.len(), data.len());
+        for (out, input) in decompressed.chunks(91).zip(data.chunks(91)) {
+            assert_eq!(out[..90], input[..90]);
+            assert_eq!(out[90], 0x80);
+        }
     }
 
     #[test]
@@ -340,6 +1781,7 @@ mod tests {
         assert!(matches!(result, Err(PocketError::InvalidRobustness(8))));
     }
 
+    #[cfg(feature = "encoder")]
     #[test]
     fn test_round_trip_single_packet() {
         // Create a simple test packet
@@ -354,6 +1796,7 @@ mod tests {
         assert_eq!(decompressed, original);
     }
 
+    #[cfg(feature = "encoder")]
     #[test]
     fn test_round_trip_multiple_packets() {
         // Create test data with 2 packets of 8 bytes each (64 bits)
@@ -368,6 +1811,7 @@ mod tests {
         assert_eq!(decompressed, original);
     }
 
+    #[cfg(feature = "encoder")]
     #[test]
     fn test_round_trip_all_zeros() {
         let original = vec![0u8; 90]; // One packet of 720 bits
@@ -378,6 +1822,7 @@ mod tests {
         assert_eq!(decompressed, original);
     }
 
+    #[cfg(feature = "encoder")]
     #[test]
     fn test_round_trip_all_ones() {
         let original = vec![0xFF; 90]; // One packet of 720 bits
@@ -388,6 +1833,7 @@ mod tests {
         assert_eq!(decompressed, original);
     }
 
+    #[cfg(feature = "encoder")]
     #[test]
     fn test_round_trip_alternating() {
         let original: Vec<u8> = (0..90)
@@ -400,14 +1846,740 @@ mod tests {
         assert_eq!(decompressed, original);
     }
 
+    #[cfg(feature = "encoder")]
     #[test]
     fn test_round_trip_many_packets() {
         // Create 10 packets of housekeeping data
-        let original: Vec<u8> = (0..900).map(|i| (i % 256) as u8).collect();
+        let original: Vec<u8> = (0u32..900).map(|i| (i % 256) as u8).collect();
 
         let compressed = compress(&original, 720, 2, 20, 50, 100).unwrap();
         let decompressed = decompress(&compressed, 720, 2).unwrap();
 
         assert_eq!(decompressed, original);
     }
+
+    #[cfg(feature = "encoder")]
+    /// Compress packets with a seeded mask, sending the first packet
+    /// uncompressed but without a full mask.
+    fn compress_seeded(original: &[u8], mask: &BitVector, robustness: u8) -> Vec<u8> {
+        use crate::compress::{CompressionParams, Compressor};
+
+        let mut comp = Compressor::new(64, Some(mask), robustness, 0, 0, 0).unwrap();
+        let mut compressed = Vec::new();
+        for (i, chunk) in original.chunks(8).enumerate() {
+            let params = CompressionParams {
+                new_mask_flag: false,
+                send_mask_flag: false,
+                uncompressed_flag: i == 0,
+            };
+            let input = BitVector::from_bytes(chunk, 64);
+            compressed.extend(comp.compress_packet(&input, &params).unwrap().to_bytes());
+        }
+        compressed
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_decompress_with_mask_round_trip() {
+        let mut mask = BitVector::new(64);
+        for i in 40..64 {
+            mask.set_bit(i, 1);
+        }
+
+        let original: Vec<u8> = (0u8..20)
+            .flat_map(|n| [0xAA, 0xBB, 0xCC, 0xDD, 0x11, n, n.wrapping_mul(7), n ^ 0x5A])
+            .collect();
+
+        for robustness in [0u8, 1, 3] {
+            let compressed = compress_seeded(&original, &mask, robustness);
+            let decompressed =
+                decompress_with_mask(&compressed, 64, robustness as usize, Some(&mask)).unwrap();
+            assert_eq!(decompressed, original, "robustness {robustness}");
+        }
+    }
+
+    #[test]
+    fn test_decompress_with_mask_length_mismatch() {
+        let mask = BitVector::new(32);
+        let result = decompress_with_mask(&[0u8; 10], 64, 1, Some(&mask));
+        assert!(matches!(
+            result,
+            Err(PocketError::InvalidInputLength {
+                expected: 64,
+                actual: 32
+            })
+        ));
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_builder_matches_decompress() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 5 + i / 40) as u8).collect();
+        let compressed = compress(&data, 64, 2, 10, 20, 50).unwrap();
+
+        let mut decomp = DecompressorBuilder::new(64)
+            .robustness(2)
+            .diagnostics(true)
+            .build()
+            .unwrap();
+        assert_eq!(decomp.decompress_stream(&compressed).unwrap(), data);
+
+        let diag = *decomp.diagnostics().unwrap();
+        assert_eq!(diag.packets, 50);
+        assert!(diag.uncompressed >= 3);
+        assert!(diag.full_masks >= diag.uncompressed);
+        assert!(diag.bits_consumed <= compressed.len() * 8);
+
+        decomp.reset();
+        assert_eq!(decomp.diagnostics().unwrap().packets, 0);
+        assert!(DecompressorBuilder::new(64)
+            .build()
+            .unwrap()
+            .diagnostics()
+            .is_none());
+    }
+
+    #[test]
+    fn test_builder_invalid() {
+        assert!(matches!(
+            DecompressorBuilder::new(64).robustness(9).build(),
+            Err(PocketError::InvalidRobustness(9))
+        ));
+        assert!(DecompressorBuilder::new(0).build().is_err());
+        assert!(DecompressorBuilder::new(64)
+            .initial_mask(&BitVector::new(32))
+            .build()
+            .is_err());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_lenient_truncation() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i * 7 % 256) as u8).collect();
+        let compressed = compress(&data, 64, 1, 10, 20, 50).unwrap();
+        let truncated = &compressed[..compressed.len() - 1];
+
+        let mut strict = DecompressorBuilder::new(64).robustness(1).build().unwrap();
+        assert!(strict.decompress_stream(truncated).is_err());
+
+        let mut lenient = DecompressorBuilder::new(64)
+            .robustness(1)
+            .mode(DecodeMode::Lenient)
+            .build()
+            .unwrap();
+        let partial = lenient.decompress_stream(truncated).unwrap();
+        assert_eq!(partial, data[..partial.len()]);
+        assert!(partial.len() < data.len());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_strict_rejects_bad_length() {
+        // Uncompressed packet declaring COUNT(F) = 63 for F = 64
+        let mut buf = crate::bitbuffer::BitBuffer::new();
+        buf.append_value(0b10, 2); // RLE(X) = empty
+        buf.append_value(0, 4); // Vt = 0
+        buf.append_bit(0); // dt = 0
+        buf.append_bit(0); // ft = 0
+        buf.append_bit(1); // rt = 1
+        crate::encode::count_encode(&mut buf, 63).unwrap();
+        buf.append_bits(&[0xAB; 8], 64);
+        let stream = buf.to_bytes();
+
+        assert!(matches!(
+            decompress(&stream, 64, 0),
+            Err(PocketError::InvalidFormat(_))
+        ));
+        let mut lenient = DecompressorBuilder::new(64)
+            .mode(DecodeMode::Lenient)
+            .build()
+            .unwrap();
+        assert_eq!(lenient.decompress_stream(&stream).unwrap(), vec![0xAB; 8]);
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_bit_alignment_and_limits() {
+        let data: Vec<u8> = (0..80u32).map(|i| (i % 3) as u8).collect();
+
+        // Bit-pack the per-packet outputs without padding
+        let mut comp = crate::compress::Compressor::new(64, None, 1, 10, 20, 50).unwrap();
+        let mut packed = crate::bitbuffer::BitBuffer::new();
+        for packet in data.chunks(8) {
+            let params = comp.next_params();
+            let out = comp
+                .compress_packet(&BitVector::from_bytes(packet, 64), &params)
+                .unwrap();
+            packed.append_bits(&out.to_bytes(), out.len());
+        }
+        let stream = packed.to_bytes();
+
+        let builder = DecompressorBuilder::new(64)
+            .robustness(1)
+            .alignment(Alignment::Bit);
+        assert_eq!(
+            builder.build().unwrap().decompress_stream(&stream).unwrap(),
+            data
+        );
+
+        let limited = builder.limits(DecodeLimits {
+            max_packets: Some(5),
+            max_output_bytes: None,
+        });
+        assert!(matches!(
+            limited.build().unwrap().decompress_stream(&stream),
+            Err(PocketError::LimitExceeded {
+                limit: "packets",
+                max: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn test_heap_size() {
+        let decomp = Decompressor::new(720, None, 1).unwrap();
+        assert_eq!(decomp.heap_size(), 5 * 96);
+        assert!(Decompressor::peak_heap_size(720) > decomp.heap_size());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_decompress_packet_buf() {
+        let original: Vec<u8> = (0..80u8).map(|i| i / 16).collect();
+        let compressed = compress(&original, 64, 1, 10, 20, 50).unwrap();
+
+        let mut decomp = Decompressor::new(64, None, 1).unwrap();
+        let mut reader = BitReader::new(&compressed, compressed.len() * 8);
+        let mut packet = BitVector::new(64);
+        let mut output = Vec::new();
+        for _ in 0..10 {
+            decomp
+                .decompress_packet_buf(&mut reader, &mut packet)
+                .unwrap();
+            output.extend(packet.to_bytes());
+            reader.align_byte();
+        }
+        assert_eq!(output, original);
+
+        let mut wrong = BitVector::new(32);
+        assert!(matches!(
+            decomp.decompress_packet_buf(&mut reader, &mut wrong),
+            Err(PocketError::InvalidInputLength {
+                expected: 64,
+                actual: 32
+            })
+        ));
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_consumed_length() {
+        let original: Vec<u8> = (0..40u8).collect();
+        let compressed = compress(&original, 64, 1, 10, 20, 50).unwrap();
+
+        let (output, consumed) = decompress_with_consumed(&compressed, 64, 1).unwrap();
+        assert_eq!(output, original);
+        assert_eq!(consumed.bytes, compressed.len());
+        assert_eq!(consumed.bits, compressed.len() * 8);
+
+        // Bit-packed stream: trailing padding is not consumed
+        let mut comp = crate::compress::Compressor::new(64, None, 1, 10, 20, 50).unwrap();
+        let mut packed = crate::bitbuffer::BitBuffer::new();
+        let mut bits = 0;
+        for packet in original.chunks(8) {
+            let params = comp.next_params();
+            let out = comp
+                .compress_packet(&BitVector::from_bytes(packet, 64), &params)
+                .unwrap();
+            packed.append_bits(&out.to_bytes(), out.len());
+            bits += out.len();
+        }
+        let mut decomp = DecompressorBuilder::new(64)
+            .robustness(1)
+            .alignment(Alignment::Bit)
+            .build()
+            .unwrap();
+        let (output, consumed) = decomp
+            .decompress_stream_consumed(&packed.to_bytes())
+            .unwrap();
+        assert_eq!(output, original);
+        assert_eq!(consumed.bits, bits);
+        assert_eq!(consumed.bytes, (consumed.bits + 7) / 8);
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_robustness_validated() {
+        let original: Vec<u8> = (0..160u8).map(|i| i / 32).collect();
+        for r in [0usize, 1, 3, 7] {
+            let compressed = compress(&original, 64, r, 10, 20, 50).unwrap();
+            assert_eq!(detect_robustness(&compressed, 64).unwrap(), r);
+            assert_eq!(decompress(&compressed, 64, r).unwrap(), original);
+
+            let wrong = (r + 1) % 8;
+            assert_eq!(
+                decompress(&compressed, 64, wrong),
+                Err(PocketError::RobustnessMismatch {
+                    expected: wrong,
+                    actual: r
+                })
+            );
+
+            // Lenient decoding does not check R
+            let lenient = DecompressorBuilder::new(64)
+                .robustness(wrong)
+                .mode(DecodeMode::Lenient)
+                .build()
+                .unwrap()
+                .decompress_stream(&compressed)
+                .unwrap();
+            assert_eq!(lenient, original);
+        }
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_decompress_iter() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 5 + i / 40) as u8).collect();
+        let compressed = compress(&data, 64, 2, 10, 20, 50).unwrap();
+
+        let mut iter = DecompressIter::new(&compressed, 64, 2).unwrap();
+        let packets: Vec<Vec<u8>> = iter.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(packets.len(), 50);
+        assert_eq!(packets.concat(), data);
+        assert_eq!(iter.packets(), 50);
+        assert_eq!(iter.consumed().bytes, compressed.len());
+        assert!(iter.next().is_none());
+
+        // Stop early
+        let first: Vec<Vec<u8>> = DecompressIter::new(&compressed, 64, 2)
+            .unwrap()
+            .take(3)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(first.concat(), &data[..24]);
+
+        // Builder policies apply; errors end the iteration
+        let decomp = DecompressorBuilder::new(64)
+            .robustness(2)
+            .limits(DecodeLimits {
+                max_packets: Some(10),
+                ..DecodeLimits::default()
+            })
+            .build()
+            .unwrap();
+        let mut iter = decomp.iter_packets(&compressed);
+        assert_eq!(iter.by_ref().take(10).flatten().count(), 10);
+        assert!(matches!(
+            iter.next(),
+            Some(Err(PocketError::LimitExceeded { .. }))
+        ));
+        assert!(iter.next().is_none());
+
+        assert!(DecompressIter::new(&compressed, 0, 2).is_err());
+        assert_eq!(DecompressIter::new(&[], 64, 2).unwrap().count(), 0);
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_skip_packet() {
+        let data: Vec<u8> = (0..8 * 30u32).map(|i| (i % 5 + i / 8 % 3) as u8).collect();
+        let compressed = compress(&data, 64, 1, 10, 20, 50).unwrap();
+        let mut decomp = Decompressor::new(64, None, 1).unwrap();
+        let mut reader = BitReader::new(&compressed, compressed.len() * 8);
+
+        // Keep every fourth packet
+        let mut kept = Vec::new();
+        for t in 0..30 {
+            if t % 4 == 0 {
+                kept.extend(decomp.decompress_packet(&mut reader).unwrap().to_bytes());
+            } else {
+                let start = reader.position();
+                let skipped = decomp.skip_packet(&mut reader).unwrap();
+                assert_eq!(skipped.bits, reader.position() - start);
+                assert_eq!(skipped.uncompressed, t < 2);
+                assert_eq!(decomp.prev_output.to_bytes(), &data[t * 8..t * 8 + 8]);
+            }
+            reader.align_byte();
+        }
+        let expected: Vec<u8> = data.chunks(8).step_by(4).flatten().copied().collect();
+        assert_eq!(kept, expected);
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_mark_lost() {
+        // Quiet start (Vₜ > R), then mode changes move the volatile field
+        let data: Vec<u8> = (0..8 * 80u32)
+            .map(|i| {
+                let (t, byte) = (i / 8, i % 8);
+                if t >= 10 && byte == t / 20 {
+                    (t * 7) as u8
+                } else {
+                    byte as u8
+                }
+            })
+            .collect();
+        let mut comp = crate::Compressor::new(64, None, 2, 10, 30, 60).unwrap();
+        let packets: Vec<Vec<u8>> = data
+            .chunks(8)
+            .map(|packet| {
+                let params = comp.next_params();
+                let input = BitVector::from_bytes(packet, 64);
+                comp.compress_packet(&input, &params).unwrap().to_bytes()
+            })
+            .collect();
+
+        // Lose packets among the first R + 1, around mode changes and new
+        // masks
+        let lost = [1, 2, 19, 20, 39, 50, 51, 69];
+        let mut decomp = Decompressor::new(64, None, 2).unwrap();
+        let mut pending = 0;
+        for (t, packet) in packets.iter().enumerate() {
+            if lost.contains(&t) {
+                pending += 1;
+                continue;
+            }
+            decomp.mark_lost(pending).unwrap();
+            pending = 0;
+            let mut reader = BitReader::new(packet, packet.len() * 8);
+            let output = decomp.decompress_packet(&mut reader).unwrap();
+            assert_eq!(output.to_bytes(), &data[t * 8..t * 8 + 8], "packet {t}");
+        }
+
+        assert!(matches!(
+            decomp.mark_lost(3),
+            Err(PocketError::InvalidParameter(_))
+        ));
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_resync() {
+        let data: Vec<u8> = (0..8 * 60u32).map(|i| (i % 5 + i / 8 % 3) as u8).collect();
+        let mut comp = crate::Compressor::new(64, None, 1, 10, 10, 20).unwrap();
+        let mut compressed = Vec::new();
+        let mut offsets = Vec::new();
+        for packet in data.chunks(8) {
+            offsets.push(compressed.len());
+            let params = comp.next_params();
+            let input = BitVector::from_bytes(packet, 64);
+            comp.compress_packet_into(&input, &params, &mut compressed)
+                .unwrap();
+        }
+
+        // Corrupt packet 25; the next anchor is packet 40
+        compressed[offsets[25]] ^= 0xFF;
+        let mut decomp = Decompressor::new(64, None, 1).unwrap();
+        let mut reader = BitReader::new(&compressed, compressed.len() * 8);
+        let mut decoded = Vec::new();
+        for _ in 0..25 {
+            decoded.extend(decomp.decompress_packet(&mut reader).unwrap().to_bytes());
+            reader.align_byte();
+        }
+        assert_eq!(decoded, &data[..8 * 25]);
+
+        let skipped = decomp.resync(&mut reader).unwrap();
+        assert_eq!(skipped, (offsets[40] - offsets[25]) * 8);
+        let rest = decomp
+            .decompress_stream(&compressed[reader.position() / 8..])
+            .unwrap();
+        assert_eq!(rest, &data[8 * 40..]);
+
+        // No anchor after the last one
+        let mut reader = BitReader::new(&compressed, compressed.len() * 8);
+        reader.skip(offsets[41] * 8).unwrap();
+        assert!(matches!(
+            decomp.resync(&mut reader),
+            Err(PocketError::UnexpectedEndOfInput)
+        ));
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_set_state() {
+        use crate::compress::{CompressorBuilder, StartMode};
+
+        let data: Vec<u8> = (0..8 * 40u32).map(|i| (i % 3 + i / 8 % 4) as u8).collect();
+        let builder = CompressorBuilder::new(64).robustness(1);
+
+        // First pass
+        let mut comp = builder.build().unwrap();
+        let mut decomp = Decompressor::new(64, None, 1).unwrap();
+        for packet in data[..8 * 20].chunks(8) {
+            let params = comp.next_params();
+            let input = BitVector::from_bytes(packet, 64);
+            let bits = comp.compress_packet(&input, &params).unwrap().to_bytes();
+            let mut reader = BitReader::new(&bits, bits.len() * 8);
+            assert_eq!(decomp.decompress_packet(&mut reader).unwrap(), input);
+        }
+
+        // Second pass resumes from the injected state, without the forced
+        // uncompressed first packets
+        let reference = BitVector::from_bytes(&data[8 * 19..8 * 20], 64);
+        let mut resumed = builder.start(StartMode::Seeded).build().unwrap();
+        resumed.set_mask(comp.mask()).unwrap();
+        resumed.set_reference(&reference).unwrap();
+        let mut output = Vec::new();
+        for packet in data[8 * 20..].chunks(8) {
+            let params = resumed.next_params();
+            let input = BitVector::from_bytes(packet, 64);
+            resumed
+                .compress_packet_into(&input, &params, &mut output)
+                .unwrap();
+        }
+
+        let mut fresh = Decompressor::new(64, None, 1).unwrap();
+        fresh.set_state(decomp.mask(), &reference).unwrap();
+        assert_eq!(fresh.decompress_stream(&output).unwrap(), &data[8 * 20..]);
+
+        assert!(fresh.set_state(&BitVector::new(8), &reference).is_err());
+        assert!(resumed.set_mask(&BitVector::new(8)).is_err());
+        assert!(resumed.set_reference(&BitVector::new(65)).is_err());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_decompress_into() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 5 + i / 40) as u8).collect();
+        let compressed = compress(&data, 64, 2, 10, 20, 50).unwrap();
+
+        let mut output = vec![0xAA];
+        assert_eq!(
+            decompress_into(&compressed, &mut output, 64, 2).unwrap(),
+            data.len()
+        );
+        assert_eq!(output[0], 0xAA);
+        assert_eq!(&output[1..], data);
+
+        let capacity = output.capacity();
+        output.clear();
+        decompress_into(&compressed, &mut output, 64, 2).unwrap();
+        assert_eq!(output, data);
+        assert!(output.capacity() <= capacity);
+
+        // Errors leave the buffer untouched
+        assert!(decompress_into(&compressed[..20], &mut output, 64, 2).is_err());
+        assert_eq!(output, data);
+        assert!(decompress_into(&[], &mut output, 64, 2).is_err());
+        assert!(decompress_into(&compressed, &mut output, 60, 2).is_err());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_decompress_cancellable() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 5 + i / 40) as u8).collect();
+        let compressed = compress(&data, 64, 2, 10, 20, 50).unwrap();
+        let cancel = AtomicBool::new(false);
+        assert_eq!(
+            decompress_cancellable(&compressed, 64, 2, &cancel).unwrap(),
+            data
+        );
+
+        cancel.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            decompress_cancellable(&compressed, 64, 2, &cancel),
+            Err(PocketError::Cancelled)
+        ));
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_decompress_with_progress() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 5 + i / 40) as u8).collect();
+        let compressed = compress(&data, 64, 2, 10, 20, 50).unwrap();
+
+        let mut reports = Vec::new();
+        let output = decompress_with_progress(&compressed, 64, 2, 20, |p| reports.push(p)).unwrap();
+        assert_eq!(output, data);
+
+        let packets: Vec<usize> = reports.iter().map(|p| p.packets).collect();
+        assert_eq!(packets, vec![20, 40, 50]);
+        let last = reports.last().unwrap();
+        assert_eq!(last.bytes_read, compressed.len());
+        assert_eq!(last.bytes_written, data.len());
+        assert!(decompress_with_progress(&compressed, 64, 2, 0, |_| {}).is_err());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_decompress_n() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 5 + i / 40) as u8).collect();
+        let compressed = compress(&data, 64, 2, 10, 20, 50).unwrap();
+
+        assert_eq!(decompress_n(&compressed, 64, 2, 50).unwrap(), data);
+        assert_eq!(decompress_n(&compressed, 64, 2, 7).unwrap(), &data[..56]);
+        assert!(decompress_n(&compressed, 64, 2, 0).unwrap().is_empty());
+
+        // Trailing bytes after the last packet are never parsed
+        let mut padded = compressed.clone();
+        padded.extend([0xFF; 3]);
+        assert!(decompress(&padded, 64, 2).is_err());
+        assert_eq!(decompress_n(&padded, 64, 2, 50).unwrap(), data);
+
+        assert_eq!(
+            decompress_n(&compressed, 64, 2, 51),
+            Err(PocketError::UnexpectedEndOfInput)
+        );
+        assert!(decompress_n(&compressed, 60, 2, 1).is_err());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_save_restore_state() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i % 5 + i / 40) as u8).collect();
+        let compressed = compress(&data, 64, 2, 10, 20, 50).unwrap();
+
+        // First job decodes 23 packets and saves the state
+        let mut decomp = Decompressor::new(64, None, 2).unwrap();
+        let mut reader = BitReader::new(&compressed, compressed.len() * 8);
+        let mut output = Vec::new();
+        for _ in 0..23 {
+            output.extend(decomp.decompress_packet(&mut reader).unwrap().to_bytes());
+            reader.align_byte();
+        }
+        let snapshot = decomp.save_state();
+        let resume_at = reader.position() / 8;
+
+        // Second job continues from the snapshot
+        let mut resumed = Decompressor::new(64, None, 2).unwrap();
+        resumed.restore_state(&snapshot).unwrap();
+        output.extend(resumed.decompress_stream(&compressed[resume_at..]).unwrap());
+        assert_eq!(output, data);
+
+        let mut other = Decompressor::new(64, None, 1).unwrap();
+        assert!(matches!(
+            other.restore_state(&snapshot),
+            Err(PocketError::RobustnessMismatch { .. })
+        ));
+        assert!(Decompressor::new(32, None, 2)
+            .unwrap()
+            .restore_state(&snapshot)
+            .is_err());
+        let compressor_snapshot = crate::Compressor::new(64, None, 2, 10, 20, 50)
+            .unwrap()
+            .save_state();
+        assert!(resumed.restore_state(&compressor_snapshot).is_err());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_packet_crc() {
+        let data: Vec<u8> = (0..8 * 20u32).map(|i| (i % 7 + i / 48) as u8).collect();
+        let builder = crate::CompressorBuilder::new(64).packet_crc(true);
+        let compressed = crate::compress_with(&data, &builder).unwrap();
+        let plain = crate::compress_with(&data, &builder.clone().packet_crc(false)).unwrap();
+        assert_eq!(compressed.len(), plain.len() + 2 * 20);
+
+        let decomp = DecompressorBuilder::new(64).robustness(1).packet_crc(true);
+        assert_eq!(
+            decomp
+                .build()
+                .unwrap()
+                .decompress_stream(&compressed)
+                .unwrap(),
+            data
+        );
+
+        // Corrupt the CRC of packet 3
+        let mut comp = builder.build().unwrap();
+        let mut end = 0;
+        let mut out = Vec::new();
+        for packet in data.chunks(8).take(4) {
+            let params = comp.next_params();
+            let input = BitVector::from_bytes(packet, 64);
+            end += comp
+                .compress_packet_into(&input, &params, &mut out)
+                .unwrap();
+        }
+        let mut corrupted = compressed.clone();
+        corrupted[end - 1] ^= 0x01;
+        assert_eq!(
+            decomp.build().unwrap().decompress_stream(&corrupted),
+            Err(PocketError::CrcMismatch { packet_index: 3 })
+        );
+
+        assert!(decomp.alignment(Alignment::Bit).build().is_err());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_decompress_available() {
+        let data: Vec<u8> = (0..90 * 50)
+            .map(|i| u8::try_from((i / 90 % 5) ^ (i % 11)).unwrap())
+            .collect();
+        let stream = compress(&data, 720, 1, 10, 20, 50).unwrap();
+
+        for chunk in [1, 13, 100, stream.len()] {
+            let mut decomp = Decompressor::new(720, None, 1).unwrap();
+            let mut pending = Vec::new();
+            let mut output = Vec::new();
+            for piece in stream.chunks(chunk) {
+                pending.extend_from_slice(piece);
+                let consumed = decomp.decompress_available(&pending, &mut output).unwrap();
+                pending.drain(..consumed);
+                assert_eq!(output.len() % 90, 0);
+            }
+            assert!(pending.is_empty(), "chunk {chunk}");
+            assert_eq!(output, data, "chunk {chunk}");
+        }
+
+        let mut decomp = Decompressor::new(720, None, 1).unwrap();
+        let mut output = Vec::new();
+        assert_eq!(
+            decomp.decompress_available(&stream[..10], &mut output),
+            Ok(0)
+        );
+        assert!(output.is_empty());
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_stream_checksum() {
+        let data: Vec<u8> = (0..8 * 30u32).map(|i| (i % 9 + i / 64) as u8).collect();
+        let builder = crate::CompressorBuilder::new(64).stream_checksum(true);
+        let sealed = crate::compress_with(&data, &builder).unwrap();
+        assert!(crate::trailer::has_trailer(&sealed));
+        assert_eq!(decompress(&sealed, 64, 1).unwrap(), data);
+        assert_eq!(
+            decompress_into(&sealed, &mut Vec::new(), 64, 1).unwrap(),
+            data.len()
+        );
+
+        // Every whole-stream decoder strips the trailer
+        let builder = DecompressorBuilder::new(64).robustness(1);
+        assert_eq!(decompress_with_consumed(&sealed, 64, 1).unwrap().0, data);
+        assert_eq!(decompress_n(&sealed, 64, 1, 30).unwrap(), data);
+        let packets = builder.build().unwrap().iter_packets(&sealed);
+        let packets: Vec<_> = packets.collect::<Result<_, _>>().unwrap();
+        assert_eq!(packets.len(), 30);
+        assert_eq!(packets.concat(), data);
+        let mut decomp = builder.clone().stream_checksum(true).build().unwrap();
+        assert_eq!(decomp.decompress_stream(&sealed).unwrap(), data);
+
+        // A flipped bit is caught by the checksum, wherever it is
+        let mismatch = PocketError::InvalidFormat("stream checksum mismatch".into());
+        for offset in [0, sealed.len() / 3, sealed.len() / 2, sealed.len() - 9] {
+            let mut corrupted = sealed.clone();
+            corrupted[offset] ^= 0x10;
+            assert_eq!(decompress(&corrupted, 64, 1), Err(mismatch.clone()));
+            assert_eq!(
+                decompress_with_consumed(&corrupted, 64, 1).map(|(out, _)| out),
+                Err(mismatch.clone())
+            );
+            let mut packets = builder.build().unwrap().iter_packets(&corrupted);
+            assert_eq!(packets.next(), Some(Err(mismatch.clone())));
+            assert_eq!(packets.next(), None);
+        }
+
+        // A required trailer must be present; an ignored one is decoded
+        let plain = crate::compress(&data, 64, 1, 10, 20, 50).unwrap();
+        let mut decomp = builder.clone().stream_checksum(true).build().unwrap();
+        assert_eq!(
+            decomp.decompress_stream(&plain),
+            Err(PocketError::InvalidFormat("missing stream checksum".into()))
+        );
+        let mut decomp = builder.stream_checksum(false).build().unwrap();
+        assert_eq!(decomp.decompress_stream(&plain).unwrap(), data);
+    }
 }
@@ -10,8 +10,9 @@
 #![allow(clippy::cast_sign_loss)]
 
 use crate::bitbuffer::BitBuffer;
-use crate::bitvector::BitVector;
+use crate::bitvector::{BitVector, MAX_EXTENDED_PACKET_LENGTH, WORD_BITS};
 use crate::error::PocketError;
+use crate::json::JsonWriter;
 
 /// Pre-computed COUNT encodings for values 1-33.
 ///
@@ -25,11 +26,60 @@ const COUNT_VALUES: [u8; 34] = [
     0xD8, 0xD9, 0xDA, 0xDB, 0xDC, 0xDD, 0xDE, 0xDF, // 26-33
 ];
 
-/// `DeBruijn` lookup table for fast LSB position finding.
-const DEBRUIJN_LOOKUP: [u32; 32] = [
-    1, 2, 29, 3, 30, 15, 25, 4, 31, 23, 21, 16, 26, 18, 5, 9, 32, 28, 14, 24, 22, 20, 17, 8, 27,
-    13, 19, 7, 12, 6, 11, 10,
-];
+/// Opcode usage counters for an encoded stream.
+///
+/// Tracks which COUNT forms (Table 5-1), RLE terminators and BE bits were
+/// emitted, showing whether packets hit the expensive long COUNT form.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpcodeStats {
+    /// COUNT short form: A = 1 → '0' (1 bit).
+    pub count_short: u64,
+    /// COUNT medium form: 2 ≤ A ≤ 33 → '110' || BIT5(A-2) (8 bits).
+    pub count_medium: u64,
+    /// COUNT long form: A ≥ 34 → '111' || BIT_E(A-2).
+    pub count_long: u64,
+    /// RLE terminators ('10').
+    pub rle_terminators: u64,
+    /// Bits emitted by bit extraction (BE).
+    pub be_bits: u64,
+}
+
+impl OpcodeStats {
+    /// Record one COUNT encoding of `a`.
+    pub(crate) fn record_count(&mut self, a: u32) {
+        match a {
+            1 => self.count_short += 1,
+            2..=33 => self.count_medium += 1,
+            _ => self.count_long += 1,
+        }
+    }
+
+    /// Total number of COUNT encodings.
+    pub fn count_total(&self) -> u64 {
+        self.count_short + self.count_medium + self.count_long
+    }
+
+    /// Add another set of counters to this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.count_short += other.count_short;
+        self.count_medium += other.count_medium;
+        self.count_long += other.count_long;
+        self.rle_terminators += other.rle_terminators;
+        self.be_bits += other.be_bits;
+    }
+
+    /// Write the counters as a JSON object.
+    pub fn write_json(&self, json: &mut JsonWriter) {
+        json.begin_object();
+        json.key("count_short").uint(self.count_short);
+        json.key("count_medium").uint(self.count_medium);
+        json.key("count_long").uint(self.count_long);
+        json.key("rle_terminators").uint(self.rle_terminators);
+        json.key("be_bits").uint(self.be_bits);
+        json.end_object();
+    }
+}
 
 /// Counter Encoding (COUNT) - CCSDS Section 5.2.2.
 ///
@@ -45,41 +95,77 @@ const DEBRUIJN_LOOKUP: [u32; 32] = [
 /// # Returns
 /// `Ok(())` on success, error if value out of range or buffer overflow.
 pub fn count_encode(output: &mut BitBuffer, a: u32) -> Result<(), PocketError> {
-    if a == 0 || a > 65535 {
+    if a > 65535 {
+        return Err(PocketError::InvalidFormat(
+            "COUNT value out of range".into(),
+        ));
+    }
+    count_encode_extended(output, a)
+}
+
+/// COUNT without the standard's upper limit of 65535.
+///
+/// Non-standard extension for packets longer than 65535 bits: values up
+/// to 2²⁴ keep the form '111' || BIT_E(A-2),
+/// with E growing past 26 bits. [`count_decode`](crate::count_decode)
+/// reads them unchanged. The encoders use it for run lengths and COUNT(F),
+/// which never exceed the packet length checked on construction.
+pub(crate) fn count_encode_extended(output: &mut BitBuffer, a: u32) -> Result<(), PocketError> {
+    if a == 0 || a as usize > MAX_EXTENDED_PACKET_LENGTH {
         return Err(PocketError::InvalidFormat(
             "COUNT value out of range".into(),
         ));
     }
 
+    let (code, length) = count_code(a);
+    if !output.append_long(code, length) {
+        return Err(PocketError::BufferOverflow);
+    }
+
+    Ok(())
+}
+
+/// COUNT code of `a` (1 to 2²⁴), right-justified.
+///
+/// # Returns
+/// The code bits and their number (at most 45).
+#[inline]
+fn count_code(a: u32) -> (u64, usize) {
     if a == 1 {
         // Case 1: A = 1 → '0'
-        if !output.append_bit(0) {
-            return Err(PocketError::BufferOverflow);
-        }
+        (0, 1)
     } else if a <= 33 {
         // Case 2: 2 ≤ A ≤ 33 → '110' || BIT5(A-2)
         // Use pre-computed lookup table
-        if !output.append_value(u32::from(COUNT_VALUES[a as usize]), 8) {
-            return Err(PocketError::BufferOverflow);
-        }
+        (u64::from(COUNT_VALUES[a as usize]), 8)
     } else {
         // Case 3: A ≥ 34 → '111' || BIT_E(A-2)
-        // Append '111' prefix
-        if !output.append_value(0b111, 3) {
-            return Err(PocketError::BufferOverflow);
-        }
-
-        // Calculate E = 2⌊log₂(A-2)+1⌋ - 6
+        // with E = 2⌊log₂(A-2)+1⌋ - 6
         let value = a - 2;
-        let highest_bit = 31 - value.leading_zeros() as i32;
-        let e = (2 * (highest_bit + 1)) - 6;
+        let highest_bit = 31 - value.leading_zeros() as usize;
+        let e = 2 * (highest_bit + 1) - 6;
+        ((0b111 << e) | u64::from(value), 3 + e)
+    }
+}
 
-        // Append BIT_E(A-2)
-        if !output.append_value(value, e as usize) {
+/// Append a code to the pending bits of [`rle_encode_counted`], moving
+/// them to `output` first if the code would take them past 56 bits.
+#[inline]
+fn push_code(
+    output: &mut BitBuffer,
+    pending: &mut (u64, usize),
+    code: u64,
+    length: usize,
+) -> Result<(), PocketError> {
+    let (bits, len) = pending;
+    if *len + length > 56 {
+        if !output.append_long(*bits, *len) {
             return Err(PocketError::BufferOverflow);
         }
+        *len = 0;
     }
-
+    *bits = (*bits << length) | code;
+    *len += length;
     Ok(())
 }
 
@@ -99,10 +185,22 @@ pub fn count_encode(output: &mut BitBuffer, a: u32) -> Result<(), PocketError> {
 /// # Returns
 /// `Ok(())` on success, error if buffer overflow.
 pub fn rle_encode(output: &mut BitBuffer, input: &BitVector) -> Result<(), PocketError> {
+    rle_encode_counted(output, input, &mut OpcodeStats::default())
+}
+
+/// Run-Length Encoding that also records opcode usage in `stats`.
+pub(crate) fn rle_encode_counted(
+    output: &mut BitBuffer,
+    input: &BitVector,
+    stats: &mut OpcodeStats,
+) -> Result<(), PocketError> {
     // Start from the end of the vector
     let mut old_bit_position = input.len() as i32;
 
-    // Get the raw 32-bit word data
+    // COUNT codes are collected here and appended up to 56 bits at a time
+    let mut pending = (0u64, 0usize);
+
+    // Get the raw 64-bit word data
     let words = input.words();
     let num_words = words.len();
 
@@ -115,21 +213,19 @@ pub fn rle_encode(output: &mut BitBuffer, input: &BitVector) -> Result<(), Pocke
             // Isolate the LSB: x = word & -word
             let lsb = word_data & word_data.wrapping_neg();
 
-            // Find LSB position using DeBruijn sequence
-            let debruijn_index = (lsb.wrapping_mul(0x077C_B531)) >> 27;
-            let mut bit_position_in_word = DEBRUIJN_LOOKUP[debruijn_index as usize] as i32;
-
-            // Count from the other side
-            bit_position_in_word = 32 - bit_position_in_word;
+            // LSB position counted from the other side (MSB-first)
+            let bit_position_in_word = (WORD_BITS as u32 - 1 - lsb.trailing_zeros()) as i32;
 
             // Calculate global bit position
-            let new_bit_position = (word_idx as i32 * 32) + bit_position_in_word;
+            let new_bit_position = (word_idx * WORD_BITS) as i32 + bit_position_in_word;
 
             // Calculate delta (number of zeros + 1)
             let delta = old_bit_position - new_bit_position;
 
-            // Encode the count
-            count_encode(output, delta as u32)?;
+            // Encode the count (bounded by the vector length)
+            let (code, length) = count_code(delta as u32);
+            push_code(output, &mut pending, code, length)?;
+            stats.record_count(delta as u32);
 
             // Update old position for next iteration
             old_bit_position = new_bit_position;
@@ -139,10 +235,12 @@ pub fn rle_encode(output: &mut BitBuffer, input: &BitVector) -> Result<(), Pocke
         }
     }
 
-    // Append terminator '10'
-    if !output.append_value(0b10, 2) {
+    // Append terminator '10' and the pending codes
+    push_code(output, &mut pending, 0b10, 2)?;
+    if !output.append_long(pending.0, pending.1) {
         return Err(PocketError::BufferOverflow);
     }
+    stats.rle_terminators += 1;
 
     Ok(())
 }
@@ -187,13 +285,12 @@ pub fn bit_extract(
             // Isolate LSB
             let lsb = mask_word & mask_word.wrapping_neg();
 
-            // Find LSB position using DeBruijn
-            let debruijn_index = (lsb.wrapping_mul(0x077C_B531)) >> 27;
-            let bit_pos_in_word = 32 - DEBRUIJN_LOOKUP[debruijn_index as usize] as i32;
+            // LSB position counted from the other side (MSB-first)
+            let bit_pos_in_word = WORD_BITS - 1 - lsb.trailing_zeros() as usize;
 
             // Check if this bit is within the valid length
-            let global_pos = (word_idx as i32 * 32) + bit_pos_in_word;
-            if (global_pos as usize) < data.len() {
+            let global_pos = word_idx * WORD_BITS + bit_pos_in_word;
+            if global_pos < data.len() {
                 // Extract and output data bit
                 let bit = u8::from((data_word & lsb) != 0);
                 if !output.append_bit(bit) {
@@ -248,11 +345,11 @@ pub fn bit_extract_forward(
             let bit_pos_in_word = clz;
 
             // MSB-first: physical position 0 = bit index 0
-            let global_pos = (word_idx * 32) + bit_pos_in_word as usize;
+            let global_pos = (word_idx * WORD_BITS) + bit_pos_in_word as usize;
 
             if global_pos < data.len() {
                 // Extract data bit at this position
-                let bit_mask = 1u32 << (31 - clz);
+                let bit_mask = 1 << (WORD_BITS as u32 - 1 - clz);
                 let bit = u8::from((data_word & bit_mask) != 0);
                 if !output.append_bit(bit) {
                     return Err(PocketError::BufferOverflow);
@@ -260,7 +357,7 @@ pub fn bit_extract_forward(
             }
 
             // Clear the MSB we just processed
-            mask_word &= !(1u32 << (31 - clz));
+            mask_word &= !(1 << (WORD_BITS as u32 - 1 - clz));
         }
     }
 
@@ -428,4 +525,58 @@ mod tests {
         // Total: 1 + 8 + 2 = 11 bits
         assert_eq!(output.len(), 11);
     }
+
+    #[test]
+    fn test_rle_encode_batched() {
+        // Short, medium and long codes, with runs of codes crossing the
+        // 56-bit batches, match COUNT-by-COUNT encoding
+        let mut input = BitVector::new(100_000);
+        for pos in [0, 1, 2, 40, 41, 75, 99_000, 99_990, 99_999] {
+            input.set_bit(pos, 1);
+        }
+        for pos in (500..1000).step_by(3) {
+            input.set_bit(pos, 1);
+        }
+
+        let mut expected = BitBuffer::new();
+        let mut old = input.len();
+        for pos in input.iter_ones().rev() {
+            count_encode_extended(&mut expected, u32::try_from(old - pos).unwrap()).unwrap();
+            old = pos;
+        }
+        expected.append_value(0b10, 2);
+
+        let mut output = BitBuffer::new();
+        output.append_bit(1);
+        rle_encode(&mut output, &input).unwrap();
+        let mut prefixed = BitBuffer::new();
+        prefixed.append_bit(1);
+        prefixed.append_bits(&expected.to_bytes(), expected.len());
+        assert_eq!(output.len(), prefixed.len());
+        assert_eq!(output.to_bytes(), prefixed.to_bytes());
+    }
+
+    #[test]
+    fn test_opcode_stats_rle() {
+        // Set bits at positions 0, 1 and 60 of 64: deltas 4, 59, 1
+        let mut input = BitVector::new(64);
+        input.set_bit(0, 1);
+        input.set_bit(1, 1);
+        input.set_bit(60, 1);
+
+        let mut stats = OpcodeStats::default();
+        let mut output = BitBuffer::new();
+        rle_encode_counted(&mut output, &input, &mut stats).unwrap();
+
+        assert_eq!(stats.count_short, 1);
+        assert_eq!(stats.count_medium, 1);
+        assert_eq!(stats.count_long, 1);
+        assert_eq!(stats.rle_terminators, 1);
+        assert_eq!(stats.count_total(), 3);
+
+        let mut total = OpcodeStats::default();
+        total.merge(&stats);
+        total.merge(&stats);
+        assert_eq!(total.count_long, 2);
+    }
 }
@@ -1,16 +1,28 @@
 //! Error types for POCKET+ compression/decompression.
 
 use std::fmt;
+use std::io;
 
 /// Errors that can occur during POCKET+ compression or decompression.
+///
+/// New variants may be added in minor releases, so matches need a
+/// wildcard arm.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum PocketError {
-    /// Invalid packet size (must be > 0 and divisible by 8)
+    /// Invalid packet size (must be 1-65535 bits; some formats also
+    /// require a multiple of 8)
     InvalidPacketSize(usize),
 
     /// Invalid robustness parameter (must be 0-7)
     InvalidRobustness(usize),
 
+    /// Inconsistent compression parameters
+    InvalidParameter(String),
+
+    /// Robustness given to the decoder contradicts the Vₜ in the stream
+    RobustnessMismatch { expected: usize, actual: usize },
+
     /// Input data length doesn't match expected packet count
     InvalidInputLength { expected: usize, actual: usize },
 
@@ -28,20 +40,74 @@ pub enum PocketError {
 
     /// Invalid length parameter
     InvalidLength,
+
+    /// A configured resource limit was exceeded
+    LimitExceeded { limit: &'static str, max: usize },
+
+    /// I/O error from an underlying reader or writer
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
+
+    /// The operation was cancelled by the caller
+    Cancelled,
+
+    /// A compressed packet failed its CRC-16 check
+    CrcMismatch { packet_index: usize },
+}
+
+impl PocketError {
+    /// Stable integer code for this error.
+    ///
+    /// Codes -1 to -3 match the C implementation's `POCKET_ERROR_*`
+    /// values so errors map one-to-one across the FFI boundary:
+    ///
+    /// | Code | Meaning           | Variants                                                                  |
+    /// |------|-------------------|---------------------------------------------------------------------------|
+    /// | -1   | Invalid argument  | `InvalidPacketSize`, `InvalidRobustness`, `InvalidParameter`, `RobustnessMismatch`, `InvalidInputLength`, `InvalidLength` |
+    /// | -2   | Buffer overflow   | `BufferOverflow`, `LimitExceeded`                                         |
+    /// | -3   | Buffer underflow  | `UnexpectedEndOfInput`, `Underflow`                                       |
+    /// | -4   | Invalid format    | `InvalidFormat`, `CrcMismatch`                                            |
+    /// | -5   | I/O error         | `Io`                                                                      |
+    /// | -6   | Cancelled         | `Cancelled`                                                               |
+    ///
+    /// Success is 0 and is never returned by this method.
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::InvalidPacketSize(_)
+            | Self::InvalidRobustness(_)
+            | Self::InvalidParameter(_)
+            | Self::RobustnessMismatch { .. }
+            | Self::InvalidInputLength { .. }
+            | Self::InvalidLength => -1,
+            Self::BufferOverflow | Self::LimitExceeded { .. } => -2,
+            Self::UnexpectedEndOfInput | Self::Underflow => -3,
+            Self::InvalidFormat(_) | Self::CrcMismatch { .. } => -4,
+            Self::Io { .. } => -5,
+            Self::Cancelled => -6,
+        }
+    }
 }
 
 impl fmt::Display for PocketError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InvalidPacketSize(size) => {
-                write!(
-                    f,
-                    "invalid packet size: {size} (must be > 0 and divisible by 8)"
-                )
+                write!(f, "invalid packet size: {size} bits")
             }
             Self::InvalidRobustness(r) => {
                 write!(f, "invalid robustness: {r} (must be 0-7)")
             }
+            Self::InvalidParameter(msg) => {
+                write!(f, "invalid parameter: {msg}")
+            }
+            Self::RobustnessMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "robustness mismatch: decoder uses R = {expected}, stream signals Vt = {actual}"
+                )
+            }
             Self::InvalidInputLength { expected, actual } => {
                 write!(f, "invalid input length: expected {expected}, got {actual}")
             }
@@ -60,12 +126,48 @@ impl fmt::Display for PocketError {
             Self::InvalidLength => {
                 write!(f, "invalid length parameter")
             }
+            Self::LimitExceeded { limit, max } => {
+                write!(f, "limit exceeded: {limit} (max {max})")
+            }
+            Self::Io { message, .. } => {
+                write!(f, "I/O error: {message}")
+            }
+            Self::Cancelled => write!(f, "operation cancelled"),
+            Self::CrcMismatch { packet_index } => {
+                write!(f, "CRC mismatch in packet {packet_index}")
+            }
         }
     }
 }
 
 impl std::error::Error for PocketError {}
 
+impl From<io::Error> for PocketError {
+    fn from(err: io::Error) -> Self {
+        // A short read means the compressed stream ended early
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            return Self::UnexpectedEndOfInput;
+        }
+        Self::Io {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<PocketError> for io::Error {
+    fn from(err: PocketError) -> Self {
+        let kind = match &err {
+            PocketError::Io { kind, message } => return io::Error::new(*kind, message.clone()),
+            PocketError::UnexpectedEndOfInput => io::ErrorKind::UnexpectedEof,
+            PocketError::Cancelled => io::ErrorKind::Interrupted,
+            _ if err.code() == -1 => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,6 +180,15 @@ mod tests {
         let err = PocketError::InvalidRobustness(10);
         assert!(err.to_string().contains("invalid robustness"));
 
+        let err = PocketError::InvalidParameter("pt".to_string());
+        assert!(err.to_string().contains("invalid parameter: pt"));
+
+        let err = PocketError::RobustnessMismatch {
+            expected: 1,
+            actual: 3,
+        };
+        assert!(err.to_string().contains("robustness mismatch"));
+
         let err = PocketError::InvalidInputLength {
             expected: 100,
             actual: 50,
@@ -98,5 +209,84 @@ mod tests {
 
         let err = PocketError::InvalidLength;
         assert!(err.to_string().contains("invalid length"));
+
+        let err = PocketError::LimitExceeded {
+            limit: "packets",
+            max: 10,
+        };
+        assert!(err.to_string().contains("limit exceeded: packets"));
+
+        let err = PocketError::Io {
+            kind: io::ErrorKind::Other,
+            message: "disk full".to_string(),
+        };
+        assert!(err.to_string().contains("I/O error"));
+
+        let err = PocketError::Cancelled;
+        assert!(err.to_string().contains("cancelled"));
+
+        let err = PocketError::CrcMismatch { packet_index: 7 };
+        assert!(err.to_string().contains("CRC mismatch in packet 7"));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(PocketError::InvalidPacketSize(0).code(), -1);
+        assert_eq!(PocketError::InvalidRobustness(8).code(), -1);
+        assert_eq!(PocketError::InvalidParameter(String::new()).code(), -1);
+        assert_eq!(
+            PocketError::RobustnessMismatch {
+                expected: 1,
+                actual: 2
+            }
+            .code(),
+            -1
+        );
+        assert_eq!(PocketError::InvalidLength.code(), -1);
+        assert_eq!(PocketError::BufferOverflow.code(), -2);
+        assert_eq!(
+            PocketError::LimitExceeded {
+                limit: "packets",
+                max: 1
+            }
+            .code(),
+            -2
+        );
+        assert_eq!(PocketError::Underflow.code(), -3);
+        assert_eq!(PocketError::UnexpectedEndOfInput.code(), -3);
+        assert_eq!(PocketError::InvalidFormat(String::new()).code(), -4);
+        assert_eq!(PocketError::CrcMismatch { packet_index: 0 }.code(), -4);
+        assert_eq!(PocketError::Cancelled.code(), -6);
+    }
+
+    #[test]
+    fn test_from_io_error() {
+        let err: PocketError = io::Error::new(io::ErrorKind::PermissionDenied, "denied").into();
+        assert!(matches!(
+            err,
+            PocketError::Io {
+                kind: io::ErrorKind::PermissionDenied,
+                ..
+            }
+        ));
+        assert_eq!(err.code(), -5);
+
+        let err: PocketError = io::Error::from(io::ErrorKind::UnexpectedEof).into();
+        assert_eq!(err, PocketError::UnexpectedEndOfInput);
+    }
+
+    #[test]
+    fn test_into_io_error() {
+        let err: io::Error = PocketError::InvalidPacketSize(0).into();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err: io::Error = PocketError::InvalidFormat("bad".into()).into();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("bad"));
+        let err: io::Error = PocketError::UnexpectedEndOfInput.into();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // Round trip keeps the kind
+        let err: io::Error = PocketError::from(io::Error::from(io::ErrorKind::BrokenPipe)).into();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
     }
 }
@@ -0,0 +1,539 @@
+//! C-compatible interface (`ffi` feature).
+//!
+//! Exposes the codec to C and C++ ground software through `extern "C"`
+//! functions, so it can link this implementation in place of the C
+//! reference. The declarations are in `include/pocketplus_ffi.h`; build a
+//! static or shared library with
+//!
+//! ```text
+//! cargo rustc --lib --release --features ffi --crate-type staticlib
+//! cargo rustc --lib --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! ## Conventions
+//!
+//! - Functions return 0 on success or a negative [`PocketError::code`]
+//!   (`POCKETPLUS_ERROR_*`), the codes -1 to -3 matching the C reference's
+//!   `POCKET_ERROR_*`.
+//! - Packet sizes are in bits, like `F` in the C reference.
+//! - Output goes to caller-allocated buffers. If a buffer is too small the
+//!   function returns `POCKETPLUS_ERROR_OVERFLOW` and stores the required
+//!   size in `*output_len`, so the caller can retry.
+//! - Streaming handles are opaque pointers created by `*_new` and released
+//!   with `*_free`. A handle must not be used from two threads at once.
+//! - Null pointers are rejected with `POCKETPLUS_ERROR_INVALID_ARG`, and a
+//!   panic never unwinds into the caller: it is reported as
+//!   `POCKETPLUS_ERROR_INTERNAL`.
+
+#![allow(unsafe_code)]
+#![allow(clippy::too_many_arguments)]
+
+use crate::bitreader::BitReader;
+use crate::bitvector::{BitVector, MAX_PACKET_LENGTH};
+use crate::compress::{compress, Compressor};
+use crate::decompress::{decompress, Decompressor};
+use crate::error::PocketError;
+use std::ffi::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{ptr, slice};
+
+/// Success.
+pub const POCKETPLUS_OK: c_int = 0;
+
+/// Invalid argument, including null pointers.
+pub const POCKETPLUS_ERROR_INVALID_ARG: c_int = -1;
+
+/// Output buffer too small.
+pub const POCKETPLUS_ERROR_OVERFLOW: c_int = -2;
+
+/// Compressed input ended early.
+pub const POCKETPLUS_ERROR_UNDERFLOW: c_int = -3;
+
+/// Malformed compressed data.
+pub const POCKETPLUS_ERROR_FORMAT: c_int = -4;
+
+/// I/O error.
+pub const POCKETPLUS_ERROR_IO: c_int = -5;
+
+/// Operation cancelled.
+pub const POCKETPLUS_ERROR_CANCELLED: c_int = -6;
+
+/// Internal error (a panic caught at the boundary).
+pub const POCKETPLUS_ERROR_INTERNAL: c_int = -100;
+
+/// Opaque streaming compressor handle.
+pub struct PocketplusCompressor {
+    comp: Compressor,
+    input: BitVector,
+    output: Vec<u8>,
+}
+
+/// Opaque streaming decompressor handle.
+pub struct PocketplusDecompressor {
+    decomp: Decompressor,
+    output: BitVector,
+}
+
+/// Run `f`, mapping errors and panics to error codes.
+fn guard(f: impl FnOnce() -> Result<(), PocketError>) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => POCKETPLUS_OK,
+        Ok(Err(err)) => err.code(),
+        Err(_) => POCKETPLUS_ERROR_INTERNAL,
+    }
+}
+
+/// Check a packet size before it reaches a constructor.
+fn check_packet_size(packet_size: usize) -> Result<(), PocketError> {
+    if packet_size == 0 || packet_size > MAX_PACKET_LENGTH {
+        return Err(PocketError::InvalidPacketSize(packet_size));
+    }
+    Ok(())
+}
+
+/// Borrow a caller buffer, treating a null pointer as an error unless the
+/// length is 0.
+unsafe fn input<'a>(data: *const u8, len: usize) -> Result<&'a [u8], PocketError> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if data.is_null() {
+        return Err(PocketError::InvalidParameter("null input".into()));
+    }
+    Ok(slice::from_raw_parts(data, len))
+}
+
+/// Copy `data` into a caller buffer and store its length.
+unsafe fn output(
+    data: &[u8],
+    buffer: *mut u8,
+    capacity: usize,
+    len: *mut usize,
+) -> Result<(), PocketError> {
+    if len.is_null() {
+        return Err(PocketError::InvalidParameter("null output length".into()));
+    }
+    *len = data.len();
+    if data.len() > capacity {
+        return Err(PocketError::BufferOverflow);
+    }
+    if !data.is_empty() {
+        if buffer.is_null() {
+            return Err(PocketError::InvalidParameter("null output".into()));
+        }
+        ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+    }
+    Ok(())
+}
+
+/// Compress a whole buffer of packets.
+///
+/// # Arguments
+/// * `input`, `input_len` - Raw packets of `⌈packet_size / 8⌉` bytes each
+///   (see [`compress`] for the padding of a partial last byte)
+/// * `packet_size` - Packet size in bits
+/// * `robustness` - Robustness level R (0-7)
+/// * `pt_limit`, `ft_limit`, `rt_limit` - Flag periods (0 = never)
+/// * `output`, `output_capacity` - Caller buffer for the stream
+/// * `output_len` - Receives the stream length
+///
+/// # Safety
+/// `input` must point to `input_len` readable bytes and `output` to
+/// `output_capacity` writable bytes, not overlapping; either may be null
+/// if its length is 0. `output_len` must be null or point to a writable
+/// `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pocketplus_compress(
+    input: *const u8,
+    input_len: usize,
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let data = self::input(input, input_len)?;
+        let stream = compress(data, packet_size, robustness, pt_limit, ft_limit, rt_limit)?;
+        self::output(&stream, output, output_capacity, output_len)
+    })
+}
+
+/// Decompress a whole byte-aligned stream.
+///
+/// # Arguments
+/// * `input`, `input_len` - Compressed stream
+/// * `packet_size` - Packet size in bits; each packet is written as
+///   `⌈packet_size / 8⌉` bytes with zero padding
+/// * `robustness` - Robustness level R used for compression
+/// * `output`, `output_capacity` - Caller buffer for the packets
+/// * `output_len` - Receives the decompressed length
+///
+/// # Safety
+/// `input` must point to `input_len` readable bytes and `output` to
+/// `output_capacity` writable bytes, not overlapping; either may be null
+/// if its length is 0. `output_len` must be null or point to a writable
+/// `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pocketplus_decompress(
+    input: *const u8,
+    input_len: usize,
+    packet_size: usize,
+    robustness: usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let data = self::input(input, input_len)?;
+        let packets = decompress(data, packet_size, robustness)?;
+        self::output(&packets, output, output_capacity, output_len)
+    })
+}
+
+/// Create a streaming compressor.
+///
+/// # Returns
+/// A handle to release with [`pocketplus_compressor_free`], or null if the
+/// parameters are invalid.
+#[no_mangle]
+pub extern "C" fn pocketplus_compressor_new(
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> *mut PocketplusCompressor {
+    let mut handle = None;
+    guard(|| {
+        check_packet_size(packet_size)?;
+        let robustness =
+            u8::try_from(robustness).map_err(|_| PocketError::InvalidRobustness(robustness))?;
+        let comp = Compressor::new(packet_size, None, robustness, pt_limit, ft_limit, rt_limit)?;
+        handle = Some(Box::new(PocketplusCompressor {
+            comp,
+            input: BitVector::new(packet_size),
+            output: Vec::new(),
+        }));
+        Ok(())
+    });
+    handle.map_or(ptr::null_mut(), Box::into_raw)
+}
+
+/// Compress one packet with the automatic pt/ft/rt schedule.
+///
+/// # Arguments
+/// * `handle` - Compressor from [`pocketplus_compressor_new`]
+/// * `packet`, `packet_len` - One packet of `⌈packet_size / 8⌉` bytes
+/// * `output`, `output_capacity` - Caller buffer for the compressed packet
+/// * `output_len` - Receives the compressed length in bytes (padded)
+///
+/// # Safety
+/// `handle` must be null or a handle from [`pocketplus_compressor_new`]
+/// that has not been freed and is not in use on another thread. `packet`
+/// must point to `packet_len` readable bytes and `output` to
+/// `output_capacity` writable bytes, not overlapping; either may be null
+/// if its length is 0. `output_len` must be null or point to a writable
+/// `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pocketplus_compressor_compress_packet(
+    handle: *mut PocketplusCompressor,
+    packet: *const u8,
+    packet_len: usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let handle = handle
+            .as_mut()
+            .ok_or_else(|| PocketError::InvalidParameter("null handle".into()))?;
+        let data = input(packet, packet_len)?;
+        if data.len() != handle.input.num_bytes() {
+            return Err(PocketError::InvalidInputLength {
+                expected: handle.input.num_bytes(),
+                actual: data.len(),
+            });
+        }
+        handle.input.load_bytes(data);
+        let params = handle.comp.next_params();
+        handle.output.clear();
+        handle
+            .comp
+            .compress_packet_into(&handle.input, &params, &mut handle.output)?;
+        self::output(&handle.output, output, output_capacity, output_len)
+    })
+}
+
+/// Release a compressor. Null is ignored.
+///
+/// # Safety
+/// `handle` must be null or a handle from [`pocketplus_compressor_new`]
+/// that has not been freed and is not in use on another thread. It must
+/// not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn pocketplus_compressor_free(handle: *mut PocketplusCompressor) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Create a streaming decompressor.
+///
+/// # Returns
+/// A handle to release with [`pocketplus_decompressor_free`], or null if
+/// the parameters are invalid.
+#[no_mangle]
+pub extern "C" fn pocketplus_decompressor_new(
+    packet_size: usize,
+    robustness: usize,
+) -> *mut PocketplusDecompressor {
+    let mut handle = None;
+    guard(|| {
+        check_packet_size(packet_size)?;
+        let robustness =
+            u8::try_from(robustness).map_err(|_| PocketError::InvalidRobustness(robustness))?;
+        let decomp = Decompressor::new(packet_size, None, robustness)?;
+        handle = Some(Box::new(PocketplusDecompressor {
+            decomp,
+            output: BitVector::new(packet_size),
+        }));
+        Ok(())
+    });
+    handle.map_or(ptr::null_mut(), Box::into_raw)
+}
+
+/// Decompress the packet at the start of a byte-aligned buffer.
+///
+/// # Arguments
+/// * `handle` - Decompressor from [`pocketplus_decompressor_new`]
+/// * `input`, `input_len` - Compressed data starting at a packet
+/// * `consumed` - Receives the number of input bytes the packet used
+/// * `output`, `output_capacity` - Caller buffer for the packet
+/// * `output_len` - Receives the packet length in bytes
+///
+/// # Safety
+/// `handle` must be null or a handle from [`pocketplus_decompressor_new`]
+/// that has not been freed and is not in use on another thread. `input`
+/// must point to `input_len` readable bytes and `output` to
+/// `output_capacity` writable bytes, not overlapping; either may be null
+/// if its length is 0. `consumed` and `output_len` must each be null or
+/// point to a writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pocketplus_decompressor_decompress_packet(
+    handle: *mut PocketplusDecompressor,
+    input: *const u8,
+    input_len: usize,
+    consumed: *mut usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let handle = handle
+            .as_mut()
+            .ok_or_else(|| PocketError::InvalidParameter("null handle".into()))?;
+        if consumed.is_null() {
+            return Err(PocketError::InvalidParameter("null consumed".into()));
+        }
+        let data = self::input(input, input_len)?;
+        let mut reader = BitReader::new(data, data.len() * 8);
+        handle
+            .decomp
+            .decompress_packet_buf(&mut reader, &mut handle.output)?;
+        reader.align_byte();
+        *consumed = reader.position() / 8;
+        self::output(
+            &handle.output.to_bytes(),
+            output,
+            output_capacity,
+            output_len,
+        )
+    })
+}
+
+/// Tell the decompressor that `count` packets were lost (at most R).
+///
+/// # Safety
+/// `handle` must be null or a handle from [`pocketplus_decompressor_new`]
+/// that has not been freed and is not in use on another thread.
+#[no_mangle]
+pub unsafe extern "C" fn pocketplus_decompressor_notify_packet_loss(
+    handle: *mut PocketplusDecompressor,
+    count: usize,
+) -> c_int {
+    guard(|| {
+        let handle = handle
+            .as_mut()
+            .ok_or_else(|| PocketError::InvalidParameter("null handle".into()))?;
+        handle.decomp.mark_lost(count)
+    })
+}
+
+/// Release a decompressor. Null is ignored.
+///
+/// # Safety
+/// `handle` must be null or a handle from [`pocketplus_decompressor_new`]
+/// that has not been freed and is not in use on another thread. It must
+/// not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn pocketplus_decompressor_free(handle: *mut PocketplusDecompressor) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Library version as a static NUL-terminated string.
+#[no_mangle]
+pub extern "C" fn pocketplus_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Description of an error code as a static NUL-terminated string.
+#[no_mangle]
+pub extern "C" fn pocketplus_error_string(code: c_int) -> *const c_char {
+    let text: &'static str = match code {
+        POCKETPLUS_OK => "success\0",
+        POCKETPLUS_ERROR_INVALID_ARG => "invalid argument\0",
+        POCKETPLUS_ERROR_OVERFLOW => "buffer overflow\0",
+        POCKETPLUS_ERROR_UNDERFLOW => "buffer underflow\0",
+        POCKETPLUS_ERROR_FORMAT => "invalid format\0",
+        POCKETPLUS_ERROR_IO => "I/O error\0",
+        POCKETPLUS_ERROR_CANCELLED => "operation cancelled\0",
+        POCKETPLUS_ERROR_INTERNAL => "internal error\0",
+        _ => "unknown error\0",
+    };
+    text.as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let data: Vec<u8> = (0..90 * 20)
+            .map(|i| u8::try_from(i % 90 + i / 450).unwrap())
+            .collect();
+        let mut stream = vec![0u8; 4096];
+        let mut stream_len = 0;
+        let rc = unsafe {
+            pocketplus_compress(
+                data.as_ptr(),
+                data.len(),
+                720,
+                1,
+                10,
+                20,
+                50,
+                stream.as_mut_ptr(),
+                stream.len(),
+                &mut stream_len,
+            )
+        };
+        assert_eq!(rc, POCKETPLUS_OK);
+        stream.truncate(stream_len);
+
+        // Too small: required size reported
+        let mut out = vec![0u8; 10];
+        let mut out_len = 0;
+        let rc = unsafe {
+            pocketplus_decompress(
+                stream.as_ptr(),
+                stream.len(),
+                720,
+                1,
+                out.as_mut_ptr(),
+                out.len(),
+                &mut out_len,
+            )
+        };
+        assert_eq!((rc, out_len), (POCKETPLUS_ERROR_OVERFLOW, data.len()));
+        out.resize(out_len, 0);
+        let rc = unsafe {
+            pocketplus_decompress(
+                stream.as_ptr(),
+                stream.len(),
+                720,
+                1,
+                out.as_mut_ptr(),
+                out.len(),
+                &mut out_len,
+            )
+        };
+        assert_eq!(rc, POCKETPLUS_OK);
+        assert_eq!(out, data);
+
+        let rc = unsafe {
+            pocketplus_decompress(ptr::null(), 5, 720, 1, ptr::null_mut(), 0, &mut out_len)
+        };
+        assert_eq!(rc, POCKETPLUS_ERROR_INVALID_ARG);
+    }
+
+    #[test]
+    fn test_ffi_streaming() {
+        let data: Vec<u8> = (0..90 * 20)
+            .map(|i| u8::try_from(i % 7 + i / 900).unwrap())
+            .collect();
+        let comp = pocketplus_compressor_new(720, 1, 10, 20, 50);
+        let decomp = pocketplus_decompressor_new(720, 1);
+        assert!(!comp.is_null() && !decomp.is_null());
+        assert!(pocketplus_compressor_new(0, 1, 10, 20, 50).is_null());
+        assert!(pocketplus_decompressor_new(720, 8).is_null());
+
+        let mut compressed = [0u8; 512];
+        let mut packet = [0u8; 90];
+        for chunk in data.chunks(90) {
+            let (mut len, mut consumed, mut packet_len) = (0, 0, 0);
+            unsafe {
+                let rc = pocketplus_compressor_compress_packet(
+                    comp,
+                    chunk.as_ptr(),
+                    chunk.len(),
+                    compressed.as_mut_ptr(),
+                    compressed.len(),
+                    &mut len,
+                );
+                assert_eq!(rc, POCKETPLUS_OK);
+                let rc = pocketplus_decompressor_decompress_packet(
+                    decomp,
+                    compressed.as_ptr(),
+                    len,
+                    &mut consumed,
+                    packet.as_mut_ptr(),
+                    packet.len(),
+                    &mut packet_len,
+                );
+                assert_eq!(rc, POCKETPLUS_OK);
+            }
+            assert_eq!((consumed, packet_len), (len, 90));
+            assert_eq!(&packet, chunk);
+        }
+
+        unsafe {
+            assert_eq!(
+                pocketplus_decompressor_notify_packet_loss(decomp, 2),
+                POCKETPLUS_ERROR_INVALID_ARG
+            );
+            assert_eq!(
+                pocketplus_decompressor_notify_packet_loss(ptr::null_mut(), 1),
+                POCKETPLUS_ERROR_INVALID_ARG
+            );
+            pocketplus_compressor_free(comp);
+            pocketplus_decompressor_free(decomp);
+            pocketplus_compressor_free(ptr::null_mut());
+
+            let version = CStr::from_ptr(pocketplus_version());
+            assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+            let message = CStr::from_ptr(pocketplus_error_string(POCKETPLUS_ERROR_OVERFLOW));
+            assert_eq!(message.to_str().unwrap(), "buffer overflow");
+            let message = CStr::from_ptr(pocketplus_error_string(POCKETPLUS_ERROR_CANCELLED));
+            assert_eq!(message.to_str().unwrap(), "operation cancelled");
+        }
+        assert_eq!(PocketError::Cancelled.code(), POCKETPLUS_ERROR_CANCELLED);
+    }
+}
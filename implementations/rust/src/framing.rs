@@ -0,0 +1,810 @@
+//! Per-packet framing with sequence numbers.
+//!
+//! Ground links that carry compressed packets as individual datagrams
+//! (e.g. UDP) may deliver them out of order or lose some. This layer wraps
+//! every compressed packet in a small frame carrying a sequence number, so
+//! the receiver can:
+//! - Reorder packets arriving within a configurable window
+//! - Drop duplicates and late arrivals
+//! - Report true gaps as losses
+//!
+//! [`FrameDecoder`] reads these frames; links with their own packet
+//! sequence count can feed [`StreamReassembler`] directly.
+//!
+//! ## Length-Prefixed Frames
+//!
+//! Links that keep packets in order but need each compressed packet as a
+//! separately addressable unit can use the lighter length-prefixed format
+//! instead: every packet is written as its byte length followed by the
+//! packet, so it is cut out of the stream without relying on the byte
+//! alignment of the decoder. The length is either a big-endian u16 or a
+//! COUNT code padded to a byte boundary (see [`LengthPrefix`]).
+//! [`compress_framed`] and [`decompress_framed`] handle whole streams;
+//! [`FrameReader`] iterates the payloads of a framed stream.
+//!
+//! ## Frame Layout (big-endian)
+//!
+//! | Offset | Size | Field                      |
+//! |--------|------|----------------------------|
+//! | 0      | 4    | Sequence number            |
+//! | 4      | 4    | Payload length in bytes    |
+//! | 8      | n    | One compressed packet      |
+//!
+//! Sequence numbers start at 0 and count packets; they do not wrap.
+//! With [`PayloadEncoding::Raw`] on both ends, packets bypass POCKET+ but
+//! use the same frames, reordering and loss reporting.
+//!
+//! A lost packet leaves the decompressor without one step of history.
+//! Gaps of up to R packets are bridged by the robustness mechanism (see
+//! [`Decompressor::mark_lost`]). After a longer gap the following packets
+//! cannot be decoded reliably; they are reported as skipped until the next
+//! packet carrying both the full mask and the uncompressed packet, which
+//! restores the whole decoder state.
+
+#![allow(clippy::cast_possible_truncation)]
+
+#[cfg(feature = "decoder")]
+use crate::bitreader::BitReader;
+#[cfg(feature = "encoder")]
+use crate::bitvector::BitVector;
+#[cfg(feature = "encoder")]
+use crate::compress::{compress_indexed, Compressor};
+use crate::container::PayloadEncoding;
+#[cfg(feature = "decoder")]
+use crate::decompress::{is_anchor, Decompressor, DecompressorBuilder};
+use crate::error::PocketError;
+use crate::raw;
+#[cfg(feature = "decoder")]
+use std::collections::BTreeMap;
+
+/// Length of the frame header.
+pub const FRAME_HEADER_LEN: usize = 8;
+
+/// One framed compressed packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// Packet sequence number.
+    pub seq: u32,
+    /// Compressed packet.
+    pub payload: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    /// Serialize the frame.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(FRAME_HEADER_LEN + self.payload.len());
+        out.extend_from_slice(&self.seq.to_be_bytes());
+        out.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(self.payload);
+        out
+    }
+
+    /// Parse a frame from the start of a byte slice.
+    ///
+    /// # Arguments
+    /// * `data` - Bytes starting at a frame header
+    ///
+    /// # Returns
+    /// The frame and the total number of bytes it occupies.
+    pub fn parse(data: &'a [u8]) -> Result<(Self, usize), PocketError> {
+        if data.len() < FRAME_HEADER_LEN {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        let seq = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let len = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let end = FRAME_HEADER_LEN + len;
+        if data.len() < end {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        Ok((
+            Self {
+                seq,
+                payload: &data[FRAME_HEADER_LEN..end],
+            },
+            end,
+        ))
+    }
+}
+
+/// Length field of length-prefixed frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LengthPrefix {
+    /// Big-endian u16 byte length.
+    #[default]
+    U16,
+    /// Byte length as a COUNT code (CCSDS Section 5.2.2), padded to a byte
+    /// boundary: one byte for packets of up to 33 bytes.
+    Count,
+}
+
+/// Append one length-prefixed frame.
+///
+/// # Arguments
+/// * `out` - Output bytes
+/// * `payload` - One compressed packet (1-65535 bytes)
+/// * `prefix` - Length field format
+#[cfg(feature = "encoder")]
+pub fn write_length_prefixed(
+    out: &mut Vec<u8>,
+    payload: &[u8],
+    prefix: LengthPrefix,
+) -> Result<(), PocketError> {
+    let len = u16::try_from(payload.len())
+        .ok()
+        .filter(|&len| len > 0)
+        .ok_or_else(|| {
+            PocketError::InvalidParameter(format!(
+                "frame payload of {} bytes, expected 1-65535",
+                payload.len()
+            ))
+        })?;
+    match prefix {
+        LengthPrefix::U16 => out.extend_from_slice(&len.to_be_bytes()),
+        LengthPrefix::Count => {
+            // Grows `out` to the byte holding the last bit, i.e. pads it
+            raw::count_encode(out, out.len() * 8, u32::from(len))?;
+        }
+    }
+    out.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Iterator over the payloads of a length-prefixed stream.
+///
+/// Stops after the first error.
+#[cfg(feature = "decoder")]
+#[derive(Clone, Debug)]
+pub struct FrameReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    prefix: LengthPrefix,
+}
+
+#[cfg(feature = "decoder")]
+impl<'a> FrameReader<'a> {
+    /// Create a reader.
+    ///
+    /// # Arguments
+    /// * `data` - Length-prefixed frames, back to back
+    /// * `prefix` - Length field format
+    pub fn new(data: &'a [u8], prefix: LengthPrefix) -> Self {
+        Self {
+            data,
+            pos: 0,
+            prefix,
+        }
+    }
+
+    /// Offset of the next frame in bytes.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Read the frame at the current position.
+    fn read_frame(&mut self) -> Result<&'a [u8], PocketError> {
+        let rest = &self.data[self.pos..];
+        let (len, header) = match self.prefix {
+            LengthPrefix::U16 => {
+                if rest.len() < 2 {
+                    return Err(PocketError::UnexpectedEndOfInput);
+                }
+                (usize::from(u16::from_be_bytes([rest[0], rest[1]])), 2)
+            }
+            LengthPrefix::Count => {
+                let (len, end) = raw::count_decode(rest, 0)?;
+                (len as usize, (end + 7) / 8)
+            }
+        };
+        let payload = rest
+            .get(header..header + len)
+            .ok_or(PocketError::UnexpectedEndOfInput)?;
+        self.pos += header + len;
+        Ok(payload)
+    }
+}
+
+#[cfg(feature = "decoder")]
+impl<'a> Iterator for FrameReader<'a> {
+    type Item = Result<&'a [u8], PocketError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let frame = self.read_frame();
+        if frame.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(frame)
+    }
+}
+
+/// Compress multiple packets into length-prefixed frames.
+///
+/// Takes the same arguments as [`compress`](crate::compress), plus the
+/// length field format.
+///
+/// # Returns
+/// One frame per input packet, back to back.
+#[cfg(feature = "encoder")]
+pub fn compress_framed(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+    prefix: LengthPrefix,
+) -> Result<Vec<u8>, PocketError> {
+    let (compressed, spans) =
+        compress_indexed(data, packet_size, robustness, pt_limit, ft_limit, rt_limit)?;
+    let mut out = Vec::with_capacity(compressed.len() + 2 * spans.len());
+    for span in spans {
+        let payload = &compressed[span.byte_offset..span.byte_offset + span.byte_length];
+        write_length_prefixed(&mut out, payload, prefix)?;
+    }
+    Ok(out)
+}
+
+/// Decompress a stream of length-prefixed frames.
+///
+/// # Arguments
+/// * `data` - Frames as produced by [`compress_framed`]
+/// * `packet_size` - Packet size in bits
+/// * `robustness` - Robustness level R (0-7)
+/// * `prefix` - Length field format
+///
+/// # Returns
+/// The decompressed packets, concatenated.
+#[cfg(feature = "decoder")]
+pub fn decompress_framed(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    prefix: LengthPrefix,
+) -> Result<Vec<u8>, PocketError> {
+    let mut decomp = DecompressorBuilder::new(packet_size)
+        .robustness(robustness)
+        .build()?;
+    let mut out = Vec::new();
+    for payload in FrameReader::new(data, prefix) {
+        let payload = payload?;
+        let mut reader = BitReader::new(payload, payload.len() * 8);
+        out.extend(decomp.decompress_packet(&mut reader)?.to_bytes());
+    }
+    Ok(out)
+}
+
+/// Compressor emitting one sequence-numbered frame per packet.
+#[cfg(feature = "encoder")]
+#[derive(Clone)]
+pub struct FrameEncoder {
+    comp: Compressor,
+    packet_size: usize,
+    encoding: PayloadEncoding,
+    seq: u32,
+}
+
+#[cfg(feature = "encoder")]
+impl FrameEncoder {
+    /// Create a new frame encoder.
+    ///
+    /// # Arguments
+    /// * `packet_size` - Packet size in bits (must be divisible by 8)
+    /// * `robustness` - Robustness level R (0-7)
+    /// * `pt_limit` - New mask period
+    /// * `ft_limit` - Full mask period
+    /// * `rt_limit` - Uncompressed packet period
+    pub fn new(
+        packet_size: usize,
+        robustness: usize,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> Result<Self, PocketError> {
+        if packet_size == 0 || packet_size % 8 != 0 {
+            return Err(PocketError::InvalidPacketSize(packet_size));
+        }
+        if robustness > 7 {
+            return Err(PocketError::InvalidRobustness(robustness));
+        }
+
+        let comp = Compressor::new(
+            packet_size,
+            None,
+            robustness as u8,
+            pt_limit,
+            ft_limit,
+            rt_limit,
+        )?;
+        Ok(Self {
+            comp,
+            packet_size,
+            encoding: PayloadEncoding::Pocket,
+            seq: 0,
+        })
+    }
+
+    /// Set how packets are stored in the following frames.
+    #[must_use]
+    pub fn encoding(mut self, encoding: PayloadEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Compress one packet into a frame.
+    ///
+    /// # Arguments
+    /// * `packet` - One uncompressed packet
+    ///
+    /// # Returns
+    /// The frame bytes (header and compressed packet).
+    pub fn encode(&mut self, packet: &[u8]) -> Result<Vec<u8>, PocketError> {
+        if packet.len() * 8 != self.packet_size {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.packet_size / 8,
+                actual: packet.len(),
+            });
+        }
+
+        let payload = if self.encoding == PayloadEncoding::Raw {
+            packet.to_vec()
+        } else {
+            let input = BitVector::from_bytes(packet, self.packet_size);
+            let params = self.comp.next_params();
+            self.comp.compress_packet(&input, &params)?.into_bytes()
+        };
+
+        let frame = Frame {
+            seq: self.seq,
+            payload: &payload,
+        };
+        self.seq = self.seq.checked_add(1).ok_or(PocketError::BufferOverflow)?;
+        Ok(frame.to_bytes())
+    }
+}
+
+/// Outcome reported by [`StreamReassembler`] and [`FrameDecoder`].
+#[cfg(feature = "decoder")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameEvent {
+    /// A packet decoded in sequence.
+    Packet {
+        /// Sequence number.
+        seq: u32,
+        /// Decompressed packet.
+        data: Vec<u8>,
+    },
+    /// Packets that never arrived within the reorder window.
+    Lost {
+        /// First missing sequence number.
+        first: u32,
+        /// Number of consecutive packets missing.
+        count: u32,
+    },
+    /// A packet received after more than R consecutive losses, discarded
+    /// because it cannot be decoded before the decoder state is restored.
+    Skipped {
+        /// Sequence number.
+        seq: u32,
+    },
+}
+
+/// Counters kept by [`StreamReassembler`] and [`FrameDecoder`].
+#[cfg(feature = "decoder")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Packets decoded.
+    pub delivered: usize,
+    /// Packets that arrived ahead of sequence and were held back.
+    pub reordered: usize,
+    /// Packets declared lost.
+    pub lost: usize,
+    /// Duplicate or late frames dropped.
+    pub dropped: usize,
+    /// Packets discarded while waiting for the decoder state to be
+    /// restored after a gap longer than R.
+    pub skipped: usize,
+}
+
+/// Decompressor accepting sequence-numbered packets in any order within a
+/// window.
+///
+/// Packets ahead of the next expected sequence number are held until the
+/// gap is filled. When more than `window` packets are held, the oldest gap
+/// is declared lost and decoding resumes after it. A window of 0 reports
+/// every gap immediately.
+///
+/// Gaps of up to R packets are handed to [`Decompressor::mark_lost`].
+/// After a longer gap, packets are reported as [`FrameEvent::Skipped`]
+/// until one carries both the full mask and the uncompressed packet.
+#[cfg(feature = "decoder")]
+pub struct StreamReassembler {
+    decomp: Decompressor,
+    encoding: PayloadEncoding,
+    window: usize,
+    next_seq: u32,
+    pending: BTreeMap<u32, Vec<u8>>,
+    /// Waiting for a packet that restores the whole decoder state.
+    resync: bool,
+    stats: FrameStats,
+}
+
+#[cfg(feature = "decoder")]
+impl StreamReassembler {
+    /// Create a stream reassembler.
+    ///
+    /// # Arguments
+    /// * `decomp` - Decompressor configured for the stream
+    /// * `window` - Maximum number of out-of-order packets held back
+    pub fn new(decomp: Decompressor, window: usize) -> Self {
+        Self {
+            decomp,
+            encoding: PayloadEncoding::Pocket,
+            window,
+            next_seq: 0,
+            pending: BTreeMap::new(),
+            resync: false,
+            stats: FrameStats::default(),
+        }
+    }
+
+    /// Set how packets are stored in the payloads (must match the encoder).
+    #[must_use]
+    pub fn encoding(mut self, encoding: PayloadEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Counters since creation.
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Accept one compressed packet.
+    ///
+    /// # Arguments
+    /// * `seq` - Sequence count of the packet, starting at 0
+    /// * `payload` - One byte-aligned compressed packet
+    ///
+    /// # Returns
+    /// Packets that became decodable, gaps declared lost and packets
+    /// skipped, in sequence order.
+    pub fn push(&mut self, seq: u32, payload: &[u8]) -> Result<Vec<FrameEvent>, PocketError> {
+        let mut events = Vec::new();
+
+        if seq < self.next_seq || self.pending.contains_key(&seq) {
+            self.stats.dropped += 1;
+            return Ok(events);
+        }
+        if seq != self.next_seq {
+            self.stats.reordered += 1;
+        }
+        self.pending.insert(seq, payload.to_vec());
+
+        self.drain(&mut events)?;
+        while self.pending.len() > self.window {
+            self.skip_gap(&mut events);
+            self.drain(&mut events)?;
+        }
+        Ok(events)
+    }
+
+    /// Flush all held packets at the end of the stream.
+    ///
+    /// Remaining gaps are declared lost.
+    pub fn finish(&mut self) -> Result<Vec<FrameEvent>, PocketError> {
+        let mut events = Vec::new();
+        while !self.pending.is_empty() {
+            self.skip_gap(&mut events);
+            self.drain(&mut events)?;
+        }
+        Ok(events)
+    }
+
+    /// Decode held packets while the next expected one is available.
+    fn drain(&mut self, events: &mut Vec<FrameEvent>) -> Result<(), PocketError> {
+        while let Some(payload) = self.pending.remove(&self.next_seq) {
+            let seq = self.next_seq;
+            self.next_seq += 1;
+
+            if self.encoding == PayloadEncoding::Raw {
+                events.push(FrameEvent::Packet { seq, data: payload });
+                self.stats.delivered += 1;
+                continue;
+            }
+
+            let f = self.decomp.packet_size();
+            let mut reader = BitReader::new(&payload, payload.len() * 8);
+            if self.resync {
+                if !is_anchor(reader.clone(), f) {
+                    events.push(FrameEvent::Skipped { seq });
+                    self.stats.skipped += 1;
+                    continue;
+                }
+                self.decomp.resync_state();
+                self.resync = false;
+            }
+            let data = self.decomp.decompress_packet(&mut reader)?.to_bytes();
+            events.push(FrameEvent::Packet { seq, data });
+            self.stats.delivered += 1;
+        }
+        Ok(())
+    }
+
+    /// Declare the gap before the oldest held packet lost.
+    fn skip_gap(&mut self, events: &mut Vec<FrameEvent>) {
+        if let Some(&first_held) = self.pending.keys().next() {
+            let count = first_held - self.next_seq;
+            events.push(FrameEvent::Lost {
+                first: self.next_seq,
+                count,
+            });
+            self.stats.lost += count as usize;
+            self.next_seq = first_held;
+
+            if self.encoding == PayloadEncoding::Pocket
+                && !self.resync
+                && self.decomp.mark_lost(count as usize).is_err()
+            {
+                self.resync = true;
+            }
+        }
+    }
+}
+
+/// Decompressor accepting frames in any order within a window.
+///
+/// Parses frames and hands their payloads to a [`StreamReassembler`],
+/// which reorders them, drops duplicates and recovers from losses.
+#[cfg(feature = "decoder")]
+pub struct FrameDecoder {
+    inner: StreamReassembler,
+}
+
+#[cfg(feature = "decoder")]
+impl FrameDecoder {
+    /// Create a frame decoder.
+    ///
+    /// # Arguments
+    /// * `decomp` - Decompressor configured for the stream
+    /// * `window` - Maximum number of out-of-order frames held back
+    pub fn new(decomp: Decompressor, window: usize) -> Self {
+        Self {
+            inner: StreamReassembler::new(decomp, window),
+        }
+    }
+
+    /// Set how packets are stored in the frames (must match the encoder).
+    #[must_use]
+    pub fn encoding(mut self, encoding: PayloadEncoding) -> Self {
+        self.inner = self.inner.encoding(encoding);
+        self
+    }
+
+    /// Counters since creation.
+    pub fn stats(&self) -> &FrameStats {
+        self.inner.stats()
+    }
+
+    /// Accept one frame.
+    ///
+    /// # Arguments
+    /// * `frame` - Frame bytes as produced by [`FrameEncoder::encode`]
+    ///
+    /// # Returns
+    /// Packets that became decodable, gaps declared lost and packets
+    /// skipped, in sequence order.
+    pub fn push(&mut self, frame: &[u8]) -> Result<Vec<FrameEvent>, PocketError> {
+        let (frame, _) = Frame::parse(frame)?;
+        self.inner.push(frame.seq, frame.payload)
+    }
+
+    /// Flush all held frames at the end of the stream.
+    ///
+    /// Remaining gaps are declared lost.
+    pub fn finish(&mut self) -> Result<Vec<FrameEvent>, PocketError> {
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    fn frames(data: &[u8]) -> Vec<Vec<u8>> {
+        let mut encoder = FrameEncoder::new(64, 1, 10, 20, 50).unwrap();
+        data.chunks(8).map(|p| encoder.encode(p).unwrap()).collect()
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    fn decoder(window: usize) -> FrameDecoder {
+        FrameDecoder::new(Decompressor::new(64, None, 1).unwrap(), window)
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    fn packets(events: &[FrameEvent]) -> Vec<u8> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                FrameEvent::Packet { data, .. } => Some(data.clone()),
+                FrameEvent::Lost { .. } | FrameEvent::Skipped { .. } => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let frame = Frame {
+            seq: 7,
+            payload: &[1, 2, 3],
+        };
+        let bytes = frame.to_bytes();
+        assert_eq!(Frame::parse(&bytes).unwrap(), (frame, 11));
+        assert!(Frame::parse(&bytes[..10]).is_err());
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_reorder_within_window() {
+        let data: Vec<u8> = (0..80u8).map(|i| i / 5).collect();
+        let frames = frames(&data);
+
+        // Swap neighbouring pairs
+        let mut dec = decoder(2);
+        let mut events = Vec::new();
+        for pair in frames.chunks(2) {
+            events.extend(dec.push(&pair[1]).unwrap());
+            events.extend(dec.push(&pair[0]).unwrap());
+        }
+        events.extend(dec.finish().unwrap());
+
+        assert_eq!(packets(&events), data);
+        assert_eq!(dec.stats().reordered, 5);
+        assert_eq!(dec.stats().lost, 0);
+
+        // Duplicates are dropped
+        assert!(dec.push(&frames[3]).unwrap().is_empty());
+        assert_eq!(dec.stats().dropped, 1);
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_gap_reported_lost() {
+        // Constant data: a lost packet carries no information
+        let data = vec![0x5A; 80];
+        let frames = frames(&data);
+
+        let mut dec = decoder(1);
+        let mut events = Vec::new();
+        for (i, frame) in frames.iter().enumerate() {
+            if i != 4 && i != 6 {
+                events.extend(dec.push(frame).unwrap());
+            }
+        }
+        events.extend(dec.finish().unwrap());
+
+        assert!(events.contains(&FrameEvent::Lost { first: 4, count: 1 }));
+        assert!(events.contains(&FrameEvent::Lost { first: 6, count: 1 }));
+        assert_eq!(dec.stats().lost, 2);
+        assert_eq!(dec.stats().delivered, 8);
+        assert_eq!(packets(&events), vec![0x5A; 64]);
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_raw_frames() {
+        let data: Vec<u8> = (0..40u8).collect();
+        let mut encoder = FrameEncoder::new(64, 1, 10, 20, 50)
+            .unwrap()
+            .encoding(PayloadEncoding::Raw);
+        let frames: Vec<Vec<u8>> = data.chunks(8).map(|p| encoder.encode(p).unwrap()).collect();
+        assert_eq!(Frame::parse(&frames[2]).unwrap().0.payload, &data[16..24]);
+
+        let mut dec = decoder(4).encoding(PayloadEncoding::Raw);
+        let mut events = Vec::new();
+        for i in [1, 0, 2, 4, 3] {
+            events.extend(dec.push(&frames[i]).unwrap());
+        }
+        assert_eq!(packets(&events), data);
+        assert_eq!(dec.stats().reordered, 2);
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_reassembler_loss_recovery() {
+        let data: Vec<u8> = (0..8 * 30)
+            .map(|i| u8::try_from(i % 5 * (i / 8 % 3) + i / 80).unwrap())
+            .collect();
+        let mut encoder = FrameEncoder::new(64, 1, 5, 10, 10).unwrap();
+        let payloads: Vec<Vec<u8>> = data
+            .chunks(8)
+            .map(|p| {
+                let frame = encoder.encode(p).unwrap();
+                Frame::parse(&frame).unwrap().0.payload.to_vec()
+            })
+            .collect();
+
+        // Packet 4 lost (bridged by R = 1), packets 13 and 14 lost (state
+        // restored by the anchor at packet 20), with reordering and a
+        // duplicate
+        let order = (0..30u32).filter(|&s| s != 4 && s != 13 && s != 14);
+        let mut order: Vec<u32> = order.collect();
+        order.swap(1, 2);
+        order.insert(3, 1);
+        let mut stream = StreamReassembler::new(Decompressor::new(64, None, 1).unwrap(), 2);
+        let mut events = Vec::new();
+        for seq in order {
+            events.extend(stream.push(seq, &payloads[seq as usize]).unwrap());
+        }
+        events.extend(stream.finish().unwrap());
+
+        let mut delivered = Vec::new();
+        for event in &events {
+            if let FrameEvent::Packet { seq, data: packet } = event {
+                let at = *seq as usize * 8;
+                assert_eq!(packet, &data[at..at + 8], "packet {seq}");
+                delivered.push(*seq);
+            }
+        }
+        let expected: Vec<u32> = (0..30)
+            .filter(|s| *s != 4 && !(13..20).contains(s))
+            .collect();
+        assert_eq!(delivered, expected);
+        assert!(events.contains(&FrameEvent::Lost { first: 4, count: 1 }));
+        assert!(events.contains(&FrameEvent::Lost {
+            first: 13,
+            count: 2
+        }));
+        assert!(events.contains(&FrameEvent::Skipped { seq: 15 }));
+
+        let stats = stream.stats();
+        assert_eq!((stats.lost, stats.skipped, stats.dropped), (3, 5, 1));
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_length_prefixed_round_trip() {
+        let data: Vec<u8> = (0..90 * 40)
+            .map(|i| u8::try_from(i % 90 / 10 + i / 900).unwrap())
+            .collect();
+        for prefix in [LengthPrefix::U16, LengthPrefix::Count] {
+            let framed = compress_framed(&data, 720, 1, 10, 20, 50, prefix).unwrap();
+            assert_eq!(FrameReader::new(&framed, prefix).count(), 40, "{prefix:?}");
+            assert_eq!(decompress_framed(&framed, 720, 1, prefix).unwrap(), data);
+        }
+
+        // Frame payloads are the packets of the plain stream
+        let plain = crate::compress(&data, 720, 1, 10, 20, 50).unwrap();
+        let framed = compress_framed(&data, 720, 1, 10, 20, 50, LengthPrefix::U16).unwrap();
+        let payloads: Result<Vec<&[u8]>, _> =
+            FrameReader::new(&framed, LengthPrefix::U16).collect();
+        assert_eq!(payloads.unwrap().concat(), plain);
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_length_prefix_encoding() {
+        let mut out = Vec::new();
+        write_length_prefixed(&mut out, &[0xAA], LengthPrefix::Count).unwrap();
+        write_length_prefixed(&mut out, &[0xBB; 33], LengthPrefix::Count).unwrap();
+        write_length_prefixed(&mut out, &[0xCC; 300], LengthPrefix::Count).unwrap();
+        assert_eq!(&out[..2], &[0x00, 0xAA]);
+        assert_eq!(out[2], 0b1101_1111);
+        assert_eq!(out.len(), 2 + 34 + 2 + 300);
+
+        let lengths: Vec<usize> = FrameReader::new(&out, LengthPrefix::Count)
+            .map(|p| p.unwrap().len())
+            .collect();
+        assert_eq!(lengths, vec![1, 33, 300]);
+
+        assert!(write_length_prefixed(&mut out, &[], LengthPrefix::U16).is_err());
+
+        // Truncated payload: one error, then the iterator ends
+        let mut reader = FrameReader::new(&[0x00, 0x05, 1, 2], LengthPrefix::U16);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
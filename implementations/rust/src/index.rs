@@ -0,0 +1,428 @@
+//! Random access into compressed streams.
+//!
+//! A compressed packet can only be located by decoding every packet before
+//! it, because packet lengths depend on the decoder state. [`PacketIndex`]
+//! records the position and flags of every packet in one pass, so a
+//! viewer can later jump to any packet with
+//! [`Decompressor::seek_to`](crate::Decompressor::seek_to), decoding only
+//! from the nearest preceding packet that restores the whole state.
+//!
+//! A packet is independently decodable when it carries both the full mask
+//! (ḟₜ = 1) and the uncompressed packet (ṙₜ = 1): the first R + 1 packets
+//! of a stream or epoch (see
+//! [`DecompressorBuilder::epoch`](crate::DecompressorBuilder::epoch)), and
+//! wherever the ft and rt schedules coincide.
+//!
+//! ```rust
+//! # #[cfg(feature = "encoder")]
+//! # {
+//! use pocketplus::index::PacketIndex;
+//! use pocketplus::DecompressorBuilder;
+//!
+//! let data: Vec<u8> = (0..90 * 60).map(|i| (i % 90 + i / 900) as u8).collect();
+//! let compressed = pocketplus::compress(&data, 720, 1, 10, 10, 10).unwrap();
+//!
+//! let builder = DecompressorBuilder::new(720).robustness(1);
+//! let index = PacketIndex::build(&compressed, &builder).unwrap();
+//! assert_eq!(index.len(), 60);
+//!
+//! let mut decomp = builder.build().unwrap();
+//! let mut reader = decomp.seek_to(&compressed, &index, 45).unwrap();
+//! let packet = decomp.decompress_packet(&mut reader).unwrap();
+//! assert_eq!(packet.to_bytes(), &data[45 * 90..46 * 90]);
+//! # }
+//! ```
+//!
+//! ## Sidecar Files
+//!
+//! Building an index decodes the whole stream, which is too slow to repeat
+//! every time a multi-gigabyte file is opened. [`PacketIndex::to_bytes`]
+//! serializes the index into a compact sidecar file (by convention the
+//! stream's file name with `.idx` appended), and
+//! [`PacketIndex::from_bytes`] reads it back without touching the stream.
+//!
+//! | Offset | Size | Field                                 |
+//! |--------|------|---------------------------------------|
+//! | 0      | 4    | Magic `"PKPI"`                        |
+//! | 4      | 1    | Format version                        |
+//! | 5      | 3    | Reserved (0)                          |
+//! | 8      | 4    | Packet size F in bits                 |
+//! | 12     | 4    | Number of entries                     |
+//! | 16     | 4    | CRC-32 of the entries                 |
+//! | 20     | 4    | CRC-32 of header bytes 0-19           |
+//!
+//! One [`INDEX_ENTRY_LEN`]-byte entry per packet follows the header, so
+//! entry n starts at byte 24 + 16n (big-endian):
+//!
+//! | Offset | Size | Field                                         |
+//! |--------|------|-----------------------------------------------|
+//! | 0      | 8    | Offset of the packet in bits                  |
+//! | 8      | 4    | Bit 31: ḟₜ, bit 30: ṙₜ, bits 0-29: length in bits |
+//! | 12     | 4    | Decoder time step t                           |
+//!
+//! ```rust
+//! # #[cfg(feature = "encoder")]
+//! # {
+//! use pocketplus::index::PacketIndex;
+//! use pocketplus::DecompressorBuilder;
+//!
+//! let data: Vec<u8> = (0..90 * 60).map(|i| (i % 90 + i / 900) as u8).collect();
+//! let compressed = pocketplus::compress(&data, 720, 1, 10, 10, 10).unwrap();
+//! let builder = DecompressorBuilder::new(720).robustness(1);
+//!
+//! let sidecar = PacketIndex::build(&compressed, &builder).unwrap().to_bytes().unwrap();
+//! let index = PacketIndex::from_bytes(&sidecar).unwrap();
+//! assert_eq!(index.keyframes().collect::<Vec<_>>(), [0, 1, 10, 20, 30, 40, 50]);
+//!
+//! let mut decomp = builder.build().unwrap();
+//! let packets = decomp.decompress_range(&compressed, &index, 45..48).unwrap();
+//! assert_eq!(packets, &data[45 * 90..48 * 90]);
+//! # }
+//! ```
+
+use crate::crc::crc32;
+use crate::decompress::{DecodeDiagnostics, DecompressorBuilder};
+use crate::error::PocketError;
+
+/// Magic bytes starting every sidecar index file.
+pub const INDEX_MAGIC: [u8; 4] = *b"PKPI";
+
+/// Current sidecar index format version.
+pub const INDEX_VERSION: u8 = 1;
+
+/// Length of the sidecar index header.
+pub const INDEX_HEADER_LEN: usize = 24;
+
+/// Length of one serialized [`IndexEntry`].
+pub const INDEX_ENTRY_LEN: usize = 16;
+
+/// Entry flag: the packet carries the full mask.
+const ENTRY_FULL_MASK: u32 = 1 << 31;
+
+/// Entry flag: the packet is sent uncompressed.
+const ENTRY_UNCOMPRESSED: u32 = 1 << 30;
+
+/// Largest packet length in bits a serialized entry can hold.
+const ENTRY_MAX_LENGTH: usize = (1 << 30) - 1;
+
+/// Position and flags of one compressed packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexEntry {
+    /// Offset of the packet in the stream, in bits.
+    pub bit_offset: usize,
+    /// Length of the packet in bits, excluding alignment padding.
+    pub bit_length: usize,
+    /// The packet carries the full mask (ḟₜ = 1).
+    pub full_mask: bool,
+    /// The packet is sent uncompressed (ṙₜ = 1).
+    pub uncompressed: bool,
+    /// Decoder time step t at which the packet is decoded.
+    pub time_step: usize,
+}
+
+impl IndexEntry {
+    /// Returns true if decoding can start at this packet.
+    pub fn is_independent(&self) -> bool {
+        self.full_mask && self.uncompressed
+    }
+}
+
+/// Index of every packet in a compressed stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PacketIndex {
+    /// Packet size in bits (F) of the indexed stream.
+    pub packet_size: usize,
+    /// One entry per packet, in stream order.
+    pub entries: Vec<IndexEntry>,
+}
+
+impl PacketIndex {
+    /// Index a compressed stream by decoding it once.
+    ///
+    /// # Arguments
+    /// * `data` - Compressed stream
+    /// * `decomp` - Settings of the decompressor the stream is read with
+    ///
+    /// # Returns
+    /// The index, or the first decoding error.
+    pub fn build(data: &[u8], decomp: &DecompressorBuilder) -> Result<Self, PocketError> {
+        let decomp = decomp.clone().diagnostics(true).build()?;
+        let packet_size = decomp.packet_size();
+        let mut iter = decomp.iter_packets(data);
+        let mut entries = Vec::new();
+        let mut bit_offset = 0;
+        let mut last = DecodeDiagnostics::default();
+
+        while let Some(packet) = iter.next() {
+            packet?;
+            let decomp = iter.decompressor();
+            let diag = *decomp.diagnostics().expect("diagnostics enabled above");
+            entries.push(IndexEntry {
+                bit_offset,
+                bit_length: diag.bits_consumed - last.bits_consumed,
+                full_mask: diag.full_masks > last.full_masks,
+                uncompressed: diag.uncompressed > last.uncompressed,
+                time_step: decomp.time_step() - 1,
+            });
+            bit_offset = iter.consumed().bits;
+            last = diag;
+        }
+
+        Ok(Self {
+            packet_size,
+            entries,
+        })
+    }
+
+    /// Number of indexed packets.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the stream holds no packets.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Position of the last independently decodable packet at or before
+    /// packet `n`, where decoding towards `n` has to start.
+    pub fn anchor(&self, n: usize) -> Option<usize> {
+        self.entries
+            .get(..=n)?
+            .iter()
+            .rposition(IndexEntry::is_independent)
+    }
+
+    /// Positions of all independently decodable packets, in stream order.
+    pub fn keyframes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.is_independent())
+            .map(|(n, _)| n)
+    }
+
+    /// Serialize the index into the sidecar file format.
+    ///
+    /// # Returns
+    /// The sidecar bytes, or [`PocketError::InvalidParameter`] if the
+    /// stream holds more than 2³² - 1 packets or a field exceeds its width.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PocketError> {
+        let too_large = |what: &str| {
+            PocketError::InvalidParameter(format!("{what} too large for an index file"))
+        };
+        let packet_size = u32::try_from(self.packet_size).map_err(|_| too_large("packet size"))?;
+        let count = u32::try_from(self.entries.len()).map_err(|_| too_large("packet count"))?;
+
+        let mut out = Vec::with_capacity(INDEX_HEADER_LEN + self.entries.len() * INDEX_ENTRY_LEN);
+        out.resize(INDEX_HEADER_LEN, 0);
+        for entry in &self.entries {
+            if entry.bit_length > ENTRY_MAX_LENGTH {
+                return Err(too_large("packet length"));
+            }
+            let mut length =
+                u32::try_from(entry.bit_length).map_err(|_| too_large("packet length"))?;
+            if entry.full_mask {
+                length |= ENTRY_FULL_MASK;
+            }
+            if entry.uncompressed {
+                length |= ENTRY_UNCOMPRESSED;
+            }
+            let offset = u64::try_from(entry.bit_offset).map_err(|_| too_large("offset"))?;
+            let time_step = u32::try_from(entry.time_step).map_err(|_| too_large("time step"))?;
+            out.extend_from_slice(&offset.to_be_bytes());
+            out.extend_from_slice(&length.to_be_bytes());
+            out.extend_from_slice(&time_step.to_be_bytes());
+        }
+
+        let entries_crc = crc32(&out[INDEX_HEADER_LEN..]);
+        out[0..4].copy_from_slice(&INDEX_MAGIC);
+        out[4] = INDEX_VERSION;
+        out[8..12].copy_from_slice(&packet_size.to_be_bytes());
+        out[12..16].copy_from_slice(&count.to_be_bytes());
+        out[16..20].copy_from_slice(&entries_crc.to_be_bytes());
+        let header_crc = crc32(&out[..20]);
+        out[20..24].copy_from_slice(&header_crc.to_be_bytes());
+        Ok(out)
+    }
+
+    /// Parse and verify a sidecar index file.
+    ///
+    /// # Arguments
+    /// * `data` - Bytes written by [`to_bytes`](Self::to_bytes)
+    ///
+    /// # Returns
+    /// The index. Fails with [`PocketError::InvalidFormat`] if the magic,
+    /// version or a checksum does not match, or with
+    /// [`PocketError::UnexpectedEndOfInput`] if the file is truncated.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PocketError> {
+        if data.len() < INDEX_HEADER_LEN {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        if data[0..4] != INDEX_MAGIC {
+            return Err(PocketError::InvalidFormat("not a packet index file".into()));
+        }
+        if data[4] != INDEX_VERSION {
+            return Err(PocketError::InvalidFormat(format!(
+                "unsupported index version {}",
+                data[4]
+            )));
+        }
+        let be32 =
+            |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        if crc32(&data[..20]) != be32(20) {
+            return Err(PocketError::InvalidFormat(
+                "index header checksum mismatch".into(),
+            ));
+        }
+
+        let count = be32(12) as usize;
+        let body = &data[INDEX_HEADER_LEN..];
+        if body.len() < count * INDEX_ENTRY_LEN {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        let body = &body[..count * INDEX_ENTRY_LEN];
+        if crc32(body) != be32(16) {
+            return Err(PocketError::InvalidFormat(
+                "index entries checksum mismatch".into(),
+            ));
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        for record in body.chunks_exact(INDEX_ENTRY_LEN) {
+            let mut offset = [0u8; 8];
+            offset.copy_from_slice(&record[0..8]);
+            let bit_offset = usize::try_from(u64::from_be_bytes(offset)).map_err(|_| {
+                PocketError::InvalidFormat("index offset exceeds the address space".into())
+            })?;
+            let length = u32::from_be_bytes([record[8], record[9], record[10], record[11]]);
+            let time_step = u32::from_be_bytes([record[12], record[13], record[14], record[15]]);
+            entries.push(IndexEntry {
+                bit_offset,
+                bit_length: (length & !(ENTRY_FULL_MASK | ENTRY_UNCOMPRESSED)) as usize,
+                full_mask: length & ENTRY_FULL_MASK != 0,
+                uncompressed: length & ENTRY_UNCOMPRESSED != 0,
+                time_step: time_step as usize,
+            });
+        }
+
+        Ok(Self {
+            packet_size: be32(8) as usize,
+            entries,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "encoder"))]
+mod tests {
+    use super::*;
+    use crate::compress::compress;
+    use crate::Alignment;
+
+    #[test]
+    fn test_index_and_seek() {
+        let data: Vec<u8> = (0..8 * 50)
+            .map(|i| u8::try_from(i % 3 + i / 8 % 6).unwrap())
+            .collect();
+        let compressed = compress(&data, 64, 2, 5, 10, 20).unwrap();
+        let builder = DecompressorBuilder::new(64).robustness(2);
+        let index = PacketIndex::build(&compressed, &builder).unwrap();
+
+        assert_eq!(index.len(), 50);
+        assert_eq!(index.entries[0].bit_offset, 0);
+        let last = index.entries[49];
+        assert_eq!(
+            (last.bit_offset + last.bit_length + 7) / 8,
+            compressed.len()
+        );
+        let anchors: Vec<usize> = (0..50)
+            .filter(|&n| index.entries[n].is_independent())
+            .collect();
+        assert_eq!(anchors, vec![0, 1, 2, 20, 40]);
+        assert_eq!(index.anchor(39), Some(20));
+        assert_eq!(index.anchor(50), None);
+
+        // Every packet is reachable, in any order
+        let mut decomp = builder.build().unwrap();
+        for n in [33, 7, 49, 0, 20, 21] {
+            let mut reader = decomp.seek_to(&compressed, &index, n).unwrap();
+            let packet = decomp.decompress_packet(&mut reader).unwrap();
+            assert_eq!(packet.to_bytes(), &data[n * 8..n * 8 + 8], "packet {n}");
+        }
+        assert!(decomp.seek_to(&compressed, &index, 50).is_err());
+    }
+
+    #[test]
+    fn test_sidecar_round_trip() {
+        let data: Vec<u8> = (0..8 * 40)
+            .map(|i| u8::try_from(i % 5 + i / 16 % 7).unwrap())
+            .collect();
+        let compressed = compress(&data, 64, 1, 4, 8, 16).unwrap();
+        let builder = DecompressorBuilder::new(64).robustness(1);
+        let index = PacketIndex::build(&compressed, &builder).unwrap();
+
+        let sidecar = index.to_bytes().unwrap();
+        assert_eq!(sidecar.len(), INDEX_HEADER_LEN + 40 * INDEX_ENTRY_LEN);
+        assert_eq!(PacketIndex::from_bytes(&sidecar).unwrap(), index);
+        assert_eq!(
+            PacketIndex::default().to_bytes().unwrap().len(),
+            INDEX_HEADER_LEN
+        );
+
+        let mut decomp = builder.build().unwrap();
+        assert_eq!(
+            decomp
+                .decompress_range(&compressed, &index, 17..40)
+                .unwrap(),
+            &data[17 * 8..]
+        );
+        assert!(decomp
+            .decompress_range(&compressed, &index, 30..41)
+            .is_err());
+        assert!(decomp.decompress_range(&compressed, &index, 5..5).is_err());
+
+        // Damage anywhere is detected
+        for (at, expected) in [(0, "not a packet"), (9, "header"), (30, "entries")] {
+            let mut bad = sidecar.clone();
+            bad[at] ^= 0x40;
+            let err = PacketIndex::from_bytes(&bad).unwrap_err().to_string();
+            assert!(err.contains(expected), "byte {at}: {err}");
+        }
+        assert_eq!(
+            PacketIndex::from_bytes(&sidecar[..sidecar.len() - 1]),
+            Err(PocketError::UnexpectedEndOfInput)
+        );
+    }
+
+    #[test]
+    fn test_index_bit_aligned() {
+        let data: Vec<u8> = (0..8 * 30)
+            .map(|i| u8::try_from(i / 8 % 4).unwrap())
+            .collect();
+        let builder = DecompressorBuilder::new(64)
+            .robustness(1)
+            .alignment(Alignment::Bit);
+        let mut comp = crate::Compressor::new(64, None, 1, 10, 10, 10).unwrap();
+        let mut stream = crate::BitBuffer::new();
+        for packet in data.chunks(8) {
+            let params = comp.next_params();
+            let input = crate::BitVector::from_bytes(packet, 64);
+            let out = comp.compress_packet(&input, &params).unwrap();
+            stream.append_bits(&out.to_bytes(), out.len());
+        }
+        let compressed = stream.to_bytes();
+
+        let index = PacketIndex::build(&compressed, &builder).unwrap();
+        assert!(index
+            .entries
+            .windows(2)
+            .all(|w| w[0].bit_offset + w[0].bit_length == w[1].bit_offset));
+
+        let mut decomp = builder.build().unwrap();
+        let mut reader = decomp.seek_to(&compressed, &index, 25).unwrap();
+        let packet = decomp.decompress_packet(&mut reader).unwrap();
+        assert_eq!(packet.to_bytes(), &data[25 * 8..26 * 8]);
+    }
+}
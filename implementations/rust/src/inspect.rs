@@ -0,0 +1,449 @@
+//! Annotated bit-level dumps of compressed streams.
+//!
+//! When another implementation disagrees with this one, the first
+//! question is which field of which packet differs. [`annotate`] decodes a
+//! stream and reports, for every packet oₜ = hₜ || qₜ || uₜ (CCSDS
+//! 124.0-B-1 Section 5.3), the bit range and decoded value of each field:
+//!
+//! - hₜ: RLE(Xₜ), BIT₄(Vₜ), eₜ, kₜ, cₜ (when present) and ḋₜ
+//! - qₜ: ḟₜ and the run-length coded full mask (when ḋₜ = 0)
+//! - uₜ: ṙₜ (when ḋₜ = 0), then COUNT(F) and Iₜ, or the unpredictable
+//!   bits BE(Iₜ, ...)
+//!
+//! plus byte alignment padding and packet CRCs. Bit offsets count from
+//! the start of the stream, MSB of the first byte first.
+//!
+//! ```rust
+//! # #[cfg(feature = "encoder")]
+//! # {
+//! use pocketplus::inspect::annotate;
+//! use pocketplus::DecompressorBuilder;
+//!
+//! let data: Vec<u8> = (0..90 * 5).map(|i| (i % 90 / 10) as u8).collect();
+//! let stream = pocketplus::compress(&data, 720, 1, 10, 20, 50).unwrap();
+//!
+//! let packets = annotate(&stream, &DecompressorBuilder::new(720).robustness(1)).unwrap();
+//! assert_eq!(packets.len(), 5);
+//! assert_eq!(packets[0].field("BIT₄(Vₜ)").unwrap().bits, 2..6);
+//! println!("{}", packets[3]);
+//! # }
+//! ```
+
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::bitreader::BitReader;
+use crate::bitvector::BitVector;
+use crate::decode::{count_decode, rle_decode};
+use crate::decompress::{Alignment, DecompressorBuilder};
+use crate::error::PocketError;
+use std::fmt;
+use std::ops::Range;
+
+/// Bits of a field value printed before eliding the rest.
+const DISPLAY_BITS: usize = 64;
+
+/// Part of a compressed packet a field belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Component {
+    /// hₜ: mask change information.
+    MaskChange,
+    /// qₜ: optional full mask.
+    FullMask,
+    /// uₜ: packet data.
+    Data,
+    /// Alignment padding and packet CRC around the packet.
+    Framing,
+}
+
+/// Decoded value of a field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldValue {
+    /// A single flag bit.
+    Flag(bool),
+    /// A number, e.g. Vₜ or the COUNT-coded packet length.
+    Number(u64),
+    /// Positions (0 = first bit of the packet) of the bits set in a
+    /// run-length coded vector.
+    Positions(Vec<usize>),
+    /// Bits as they appear in the stream, one per element.
+    Bits(Vec<u8>),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flag(flag) => write!(f, "{}", u8::from(*flag)),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::Positions(positions) => write!(f, "{positions:?}"),
+            Self::Bits(bits) => {
+                for bit in bits.iter().take(DISPLAY_BITS) {
+                    write!(f, "{bit}")?;
+                }
+                if bits.len() > DISPLAY_BITS {
+                    write!(f, "... ({} bits)", bits.len())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// One field of a compressed packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    /// Field name as in the standard, e.g. `RLE(Xₜ)` or `ḋₜ`.
+    pub name: &'static str,
+    /// Packet component the field belongs to.
+    pub component: Component,
+    /// Bit range in the stream.
+    pub bits: Range<usize>,
+    /// Decoded value.
+    pub value: FieldValue,
+}
+
+/// Annotation of one compressed packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacketAnnotation {
+    /// Packet index t.
+    pub index: usize,
+    /// Bit range of the packet in the stream, framing included.
+    pub bits: Range<usize>,
+    /// Fields in stream order.
+    pub fields: Vec<Field>,
+    /// The decompressed packet, unless decoding failed.
+    pub packet: Option<Vec<u8>>,
+    /// Why decoding stopped at this packet; the fields parsed before the
+    /// failure are kept.
+    pub error: Option<PocketError>,
+}
+
+impl PacketAnnotation {
+    /// The first field with the given name.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+impl fmt::Display for PacketAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "packet {} @ bits {}..{} ({} bits)",
+            self.index,
+            self.bits.start,
+            self.bits.end,
+            self.bits.len()
+        )?;
+        for field in &self.fields {
+            writeln!(
+                f,
+                "  {:>8}..{:<8} {:<20} {}",
+                field.bits.start, field.bits.end, field.name, field.value
+            )?;
+        }
+        if let Some(error) = &self.error {
+            writeln!(f, "  error: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the fields of one packet.
+struct Fields<'r, 'a> {
+    reader: &'r mut BitReader<'a>,
+    fields: Vec<Field>,
+}
+
+impl<'a> Fields<'_, 'a> {
+    /// Read one field with `read` and record its range.
+    fn read<T>(
+        &mut self,
+        name: &'static str,
+        component: Component,
+        read: impl FnOnce(&mut BitReader<'a>) -> Result<T, PocketError>,
+        value: impl FnOnce(&T) -> FieldValue,
+    ) -> Result<T, PocketError> {
+        let start = self.reader.position();
+        let result = read(self.reader)?;
+        self.fields.push(Field {
+            name,
+            component,
+            bits: start..self.reader.position(),
+            value: value(&result),
+        });
+        Ok(result)
+    }
+
+    /// Read a flag bit.
+    fn flag(&mut self, name: &'static str, component: Component) -> Result<bool, PocketError> {
+        self.read(
+            name,
+            component,
+            |r| Ok(r.read_bit()? != 0),
+            |&b| FieldValue::Flag(b),
+        )
+    }
+
+    /// Read `count` raw bits.
+    fn bits(
+        &mut self,
+        name: &'static str,
+        component: Component,
+        count: usize,
+    ) -> Result<(), PocketError> {
+        self.read(
+            name,
+            component,
+            |r| (0..count).map(|_| r.read_bit()).collect(),
+            |bits: &Vec<u8>| FieldValue::Bits(bits.clone()),
+        )
+        .map(drop)
+    }
+}
+
+/// Positions of the bits set in a vector.
+fn positions(vector: &BitVector) -> Vec<usize> {
+    (0..vector.len())
+        .filter(|&i| vector.get_bit(i) != 0)
+        .collect()
+}
+
+/// Parse hₜ, qₜ and the length of uₜ of one packet.
+///
+/// The number of unpredictable bits depends on the decoder state, so the
+/// compressed data field is left to the caller, which knows where the
+/// decoder stopped.
+///
+/// # Returns
+/// ṙₜ.
+fn parse_header(fields: &mut Fields<'_, '_>, f: usize) -> Result<bool, PocketError> {
+    use Component::{Data, FullMask, MaskChange};
+
+    let xt = fields.read(
+        "RLE(Xₜ)",
+        MaskChange,
+        |r| rle_decode(r, f),
+        |x| FieldValue::Positions(positions(x)),
+    )?;
+    let vt = fields.read(
+        "BIT₄(Vₜ)",
+        MaskChange,
+        |r| r.read_bits(4),
+        |&v| FieldValue::Number(u64::from(v)),
+    )?;
+    let changes = xt.hamming_weight();
+    if vt > 0 && changes > 0 && fields.flag("eₜ", MaskChange)? {
+        fields.bits("kₜ", MaskChange, changes)?;
+        fields.flag("cₜ", MaskChange)?;
+    }
+    if fields.flag("ḋₜ", MaskChange)? {
+        return Ok(false);
+    }
+
+    if fields.flag("ḟₜ", FullMask)? {
+        fields.read(
+            "RLE(Mₜ XOR (Mₜ<<))",
+            FullMask,
+            |r| rle_decode(r, f),
+            |diff| {
+                // Undo the horizontal XOR to show the mask itself
+                let mut mask = Vec::new();
+                let mut current = 0;
+                for i in (0..f).rev() {
+                    current ^= diff.get_bit(i);
+                    if current != 0 {
+                        mask.push(i);
+                    }
+                }
+                mask.reverse();
+                FieldValue::Positions(mask)
+            },
+        )?;
+    }
+    let rt = fields.flag("ṙₜ", Data)?;
+    if rt {
+        fields.read("COUNT(F)", Data, count_decode, |&n| {
+            FieldValue::Number(u64::from(n))
+        })?;
+        fields.bits("Iₜ", Data, f)?;
+    }
+    Ok(rt)
+}
+
+/// Decode a stream and annotate every packet.
+///
+/// Decoding stops at the first packet that fails; its annotation holds
+/// the fields parsed so far and the error.
+///
+/// # Arguments
+/// * `data` - Compressed stream
+/// * `params` - Decoder settings: packet size, robustness, initial mask,
+///   alignment, packet CRCs
+///
+/// # Returns
+/// One annotation per packet. Fails only if `params` are invalid.
+pub fn annotate(
+    data: &[u8],
+    params: &DecompressorBuilder,
+) -> Result<Vec<PacketAnnotation>, PocketError> {
+    let mut decomp = params.build()?;
+    let f = decomp.packet_size();
+    let mut reader = BitReader::new(data, data.len() * 8);
+    let mut annotations = Vec::new();
+
+    while reader.remaining() > 0 {
+        // Bit-packed streams end with up to 7 zero padding bits
+        if decomp.alignment() == Alignment::Bit && reader.remaining() < 8 && !annotations.is_empty()
+        {
+            break;
+        }
+        let start = reader.position();
+        let mut parse = reader.clone();
+        let mut fields = Fields {
+            reader: &mut parse,
+            fields: Vec::new(),
+        };
+        let header = parse_header(&mut fields, f);
+        let mut fields = fields.fields;
+        let mut annotation = PacketAnnotation {
+            index: annotations.len(),
+            bits: start..start,
+            fields: Vec::new(),
+            packet: None,
+            error: None,
+        };
+
+        let decoded = header.and_then(|rt| {
+            let packet = decomp.decompress_packet(&mut reader)?;
+            if !rt {
+                let bits = parse.position()..reader.position();
+                let mut raw = BitReader::new(data, data.len() * 8);
+                raw.skip(bits.start)?;
+                let value = (0..bits.len())
+                    .map(|_| raw.read_bit())
+                    .collect::<Result<_, _>>()?;
+                fields.push(Field {
+                    name: "BE(Iₜ)",
+                    component: Component::Data,
+                    bits,
+                    value: FieldValue::Bits(value),
+                });
+            } else if parse.position() != reader.position() {
+                return Err(PocketError::InvalidFormat(
+                    "annotation out of step with the decoder".into(),
+                ));
+            }
+            Ok(packet)
+        });
+        match decoded {
+            Ok(packet) => annotation.packet = Some(packet.to_bytes()),
+            Err(e) => annotation.error = Some(e),
+        }
+
+        if annotation.error.is_none() {
+            let mut framing = Fields {
+                reader: &mut reader,
+                fields: Vec::new(),
+            };
+            let padding = (8 - framing.reader.position() % 8) % 8;
+            let framed = if decomp.alignment() == Alignment::Byte && padding > 0 {
+                framing.bits("padding", Component::Framing, padding)
+            } else {
+                Ok(())
+            }
+            .and_then(|()| {
+                if decomp.packet_crc() {
+                    framing.read(
+                        "CRC-16",
+                        Component::Framing,
+                        |r| r.read_bits(16),
+                        |&crc| FieldValue::Number(u64::from(crc)),
+                    )?;
+                }
+                Ok(())
+            });
+            fields.append(&mut framing.fields);
+            annotation.error = framed.err();
+        }
+
+        annotation.bits = start..fields.last().map_or(start, |field| field.bits.end);
+        annotation.fields = fields;
+        let failed = annotation.error.is_some();
+        annotations.push(annotation);
+        if failed {
+            break;
+        }
+    }
+    Ok(annotations)
+}
+
+#[cfg(all(test, feature = "encoder"))]
+mod tests {
+    use super::*;
+    use crate::{compress, CompressorBuilder};
+
+    fn sample() -> Vec<u8> {
+        (0..90 * 30)
+            .map(|i| u8::try_from((i % 90 / 8 + i / 900 * 3) % 256).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_annotate_fields_tile_the_stream() {
+        let data = sample();
+        let stream = compress(&data, 720, 2, 5, 10, 20).unwrap();
+        let packets = annotate(&stream, &DecompressorBuilder::new(720).robustness(2)).unwrap();
+        assert_eq!(packets.len(), 30);
+
+        // Fields are contiguous and cover the whole stream
+        let mut next = 0;
+        for packet in &packets {
+            assert!(packet.error.is_none());
+            assert_eq!(packet.bits.start, next);
+            for field in &packet.fields {
+                assert_eq!(field.bits.start, next, "{packet}");
+                next = field.bits.end;
+            }
+        }
+        assert_eq!(next, stream.len() * 8);
+
+        let decoded: Vec<u8> = packets
+            .iter()
+            .flat_map(|p| p.packet.clone().unwrap())
+            .collect();
+        assert_eq!(decoded, data);
+
+        // The first packet is sent uncompressed with its full mask
+        let first = &packets[0];
+        assert_eq!(
+            first.field("BIT₄(Vₜ)").unwrap().value,
+            FieldValue::Number(2)
+        );
+        assert_eq!(first.field("ḋₜ").unwrap().value, FieldValue::Flag(false));
+        assert_eq!(first.field("ṙₜ").unwrap().value, FieldValue::Flag(true));
+        assert_eq!(
+            first.field("COUNT(F)").unwrap().value,
+            FieldValue::Number(720)
+        );
+        assert_eq!(first.field("Iₜ").unwrap().bits.len(), 720);
+        assert!(packets.iter().any(|p| p.field("BE(Iₜ)").is_some()));
+        assert!(format!("{first}").contains("COUNT(F)"));
+    }
+
+    #[test]
+    fn test_annotate_crc_and_errors() {
+        let data = sample();
+        let options = CompressorBuilder::new(720).robustness(1).packet_crc(true);
+        let stream = crate::compress_with(&data, &options).unwrap();
+        let params = DecompressorBuilder::new(720).robustness(1).packet_crc(true);
+        let packets = annotate(&stream, &params).unwrap();
+        assert_eq!(packets.len(), 30);
+        assert!(packets.iter().all(|p| p.field("CRC-16").is_some()));
+
+        // A truncated stream ends with an annotated failure
+        let packets = annotate(&stream[..stream.len() - 3], &params).unwrap();
+        let last = packets.last().unwrap();
+        assert!(last.error.is_some());
+        assert!(last.packet.is_none());
+    }
+}
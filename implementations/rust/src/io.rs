@@ -0,0 +1,200 @@
+//! `std::io` adapters.
+//!
+//! [`PocketWriter`] wraps any [`Write`] and compresses the raw housekeeping
+//! bytes written to it, so POCKET+ can be inserted into an existing chain
+//! of writers (files, sockets, [`BufWriter`](std::io::BufWriter), ...)
+//! without collecting all packets first:
+//!
+//! ```rust
+//! # #[cfg(feature = "encoder")]
+//! # {
+//! use pocketplus::io::PocketWriter;
+//! use pocketplus::Compressor;
+//! use std::io::Write;
+//!
+//! let comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+//! let mut writer = PocketWriter::new(Vec::new(), comp).unwrap();
+//! writer.write_all(&[0u8; 900]).unwrap();
+//! let compressed = writer.finish().unwrap();
+//! # assert_eq!(compressed, pocketplus::compress(&[0u8; 900], 720, 1, 10, 20, 50).unwrap());
+//! # }
+//! ```
+
+#[cfg(feature = "encoder")]
+use crate::bitvector::BitVector;
+#[cfg(feature = "encoder")]
+use crate::compress::Compressor;
+#[cfg(feature = "encoder")]
+use crate::error::PocketError;
+#[cfg(feature = "encoder")]
+use std::io::{self, Write};
+
+/// Writer that compresses raw packets into an inner writer.
+///
+/// Bytes are buffered until a whole packet is available; each packet is
+/// then compressed and its byte-aligned output written downstream, giving
+/// exactly the stream [`compress`](crate::compress) produces for the same
+/// input. At most one partial packet is held back.
+///
+/// If the inner writer fails, the packet being written is lost and the
+/// compressor state has already advanced past it.
+#[cfg(feature = "encoder")]
+pub struct PocketWriter<W: Write> {
+    inner: W,
+    comp: Compressor,
+    packet_bytes: usize,
+    /// Partial packet.
+    buffer: Vec<u8>,
+}
+
+#[cfg(feature = "encoder")]
+impl<W: Write> PocketWriter<W> {
+    /// Create a writer.
+    ///
+    /// # Arguments
+    /// * `inner` - Destination of the compressed stream
+    /// * `comp` - Compressor; each packet is read as ⌈F/8⌉ bytes
+    pub fn new(inner: W, comp: Compressor) -> Result<Self, PocketError> {
+        let packet_bytes = (comp.packet_size() + 7) / 8;
+        Ok(Self {
+            inner,
+            comp,
+            packet_bytes,
+            buffer: Vec::with_capacity(packet_bytes),
+        })
+    }
+
+    /// The inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// The inner writer, mutably. Writing to it directly corrupts the
+    /// compressed stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// The compressor.
+    pub fn compressor(&self) -> &Compressor {
+        &self.comp
+    }
+
+    /// Number of buffered bytes of the next, incomplete packet.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Flush the inner writer and return it.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if a partial packet is
+    /// buffered.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.buffer.is_empty() {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.packet_bytes,
+                actual: self.buffer.len(),
+            }
+            .into());
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Compress one whole packet and write it downstream.
+    fn write_packet(&mut self, packet: &[u8]) -> io::Result<()> {
+        let input = BitVector::from_bytes(packet, self.comp.packet_size());
+        let params = self.comp.next_params();
+        let output = self.comp.compress_packet(&input, &params)?;
+        self.inner.write_all(&output.into_bytes())
+    }
+}
+
+#[cfg(feature = "encoder")]
+impl<W: Write> Write for PocketWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+
+        // Complete the buffered packet first
+        if !self.buffer.is_empty() {
+            let take = (self.packet_bytes - self.buffer.len()).min(rest.len());
+            self.buffer.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+            if self.buffer.len() < self.packet_bytes {
+                return Ok(buf.len());
+            }
+            let packet = std::mem::take(&mut self.buffer);
+            let result = self.write_packet(&packet);
+            self.buffer = packet;
+            self.buffer.clear();
+            result?;
+        }
+
+        // Whole packets straight from the caller's buffer
+        let mut packets = rest.chunks_exact(self.packet_bytes);
+        for packet in &mut packets {
+            self.write_packet(packet)?;
+        }
+        self.buffer.extend_from_slice(packets.remainder());
+        Ok(buf.len())
+    }
+
+    /// Flush the inner writer. A buffered partial packet stays buffered.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(all(test, feature = "encoder"))]
+mod tests {
+    use super::*;
+    use crate::compress;
+
+    fn sample(packets: usize) -> Vec<u8> {
+        (0..packets * 90)
+            .map(|i| u8::try_from((i / 90 % 4) ^ (i % 7)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_writer_matches_compress() {
+        let data = sample(40);
+        let expected = compress(&data, 720, 2, 10, 20, 50).unwrap();
+
+        // Arbitrary write sizes, straddling packet boundaries
+        for chunk in [1, 7, 90, 100, 500, data.len()] {
+            let comp = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+            let mut writer = PocketWriter::new(Vec::new(), comp).unwrap();
+            for piece in data.chunks(chunk) {
+                writer.write_all(piece).unwrap();
+            }
+            assert_eq!(writer.pending(), 0);
+            assert_eq!(writer.finish().unwrap(), expected, "chunk {chunk}");
+        }
+    }
+
+    #[test]
+    fn test_writer_partial_packet() {
+        let comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+        let mut writer = PocketWriter::new(Vec::new(), comp).unwrap();
+        writer.write_all(&sample(1)[..50]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.pending(), 50);
+        assert!(writer.get_ref().is_empty());
+
+        let err = writer.finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_writer_packet_size_not_byte_aligned() {
+        let data = sample(10);
+        let comp = Compressor::new(713, None, 1, 10, 20, 50).unwrap();
+        let mut writer = PocketWriter::new(Vec::new(), comp).unwrap();
+        writer.write_all(&data).unwrap();
+        assert_eq!(
+            writer.finish().unwrap(),
+            compress(&data, 713, 1, 10, 20, 50).unwrap()
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Minimal JSON writer for machine-readable reports.
+//!
+//! Statistics, container headers and CLI reports are emitted as JSON so
+//! dashboards and ingestion scripts can consume them without parsing the
+//! human-readable output. [`JsonWriter`] streams one document into a
+//! `String`, inserting commas and escaping strings; it does not check that
+//! keys and values alternate correctly inside objects.
+//!
+//! ```rust
+//! use pocketplus::json::JsonWriter;
+//!
+//! let mut json = JsonWriter::new();
+//! json.begin_object();
+//! json.key("file").string("data.bin");
+//! json.key("ratio").float(2.5);
+//! json.key("sizes").begin_array().uint(90).uint(45).end_array();
+//! json.end_object();
+//! assert_eq!(json.finish(), r#"{"file":"data.bin","ratio":2.5,"sizes":[90,45]}"#);
+//! ```
+
+use std::fmt::Write;
+
+/// Streaming JSON writer.
+#[derive(Clone, Debug, Default)]
+pub struct JsonWriter {
+    out: String,
+    /// A value was just completed, so the next one needs a comma.
+    needs_comma: bool,
+}
+
+impl JsonWriter {
+    /// Create an empty writer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Separate the next value from the previous one.
+    fn separate(&mut self) {
+        if self.needs_comma {
+            self.out.push(',');
+        }
+        self.needs_comma = false;
+    }
+
+    /// Write a complete scalar value.
+    fn value(&mut self, text: &str) -> &mut Self {
+        self.separate();
+        self.out.push_str(text);
+        self.needs_comma = true;
+        self
+    }
+
+    /// Open an object.
+    pub fn begin_object(&mut self) -> &mut Self {
+        self.separate();
+        self.out.push('{');
+        self
+    }
+
+    /// Close the innermost object.
+    pub fn end_object(&mut self) -> &mut Self {
+        self.out.push('}');
+        self.needs_comma = true;
+        self
+    }
+
+    /// Open an array.
+    pub fn begin_array(&mut self) -> &mut Self {
+        self.separate();
+        self.out.push('[');
+        self
+    }
+
+    /// Close the innermost array.
+    pub fn end_array(&mut self) -> &mut Self {
+        self.out.push(']');
+        self.needs_comma = true;
+        self
+    }
+
+    /// Write an object key; the next call writes its value.
+    pub fn key(&mut self, key: &str) -> &mut Self {
+        self.separate();
+        write_escaped(&mut self.out, key);
+        self.out.push(':');
+        self
+    }
+
+    /// Write a string value.
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.separate();
+        write_escaped(&mut self.out, value);
+        self.needs_comma = true;
+        self
+    }
+
+    /// Write an unsigned integer value.
+    pub fn uint(&mut self, value: u64) -> &mut Self {
+        self.value(&value.to_string())
+    }
+
+    /// Write a signed integer value.
+    pub fn int(&mut self, value: i64) -> &mut Self {
+        self.value(&value.to_string())
+    }
+
+    /// Write a floating-point value; NaN and infinities become `null`.
+    pub fn float(&mut self, value: f64) -> &mut Self {
+        if value.is_finite() {
+            self.value(&value.to_string())
+        } else {
+            self.null()
+        }
+    }
+
+    /// Write a boolean value.
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.value(if value { "true" } else { "false" })
+    }
+
+    /// Write `null`.
+    pub fn null(&mut self) -> &mut Self {
+        self.value("null")
+    }
+
+    /// Return the document written so far.
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+/// Append `text` as a quoted JSON string.
+fn write_escaped(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_writer() {
+        let mut json = JsonWriter::new();
+        json.begin_array();
+        json.begin_object().end_object();
+        json.begin_object();
+        json.key("a\"b\\").string("x\ny\u{1}");
+        json.key("n").int(-3).key("f").float(f64::NAN);
+        json.key("t").bool(true).key("z").null();
+        json.key("e").begin_array().end_array();
+        json.end_object();
+        json.float(0.25).uint(7);
+        json.end_array();
+        assert_eq!(
+            json.finish(),
+            r#"[{},{"a\"b\\":"x\ny\u0001","n":-3,"f":null,"t":true,"z":null,"e":[]},0.25,7]"#
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Initial masks from a housekeeping field layout.
+//!
+//! Telemetry engineers usually know which fields of a packet change every
+//! packet (counters, timestamps) and which stay put (status words,
+//! configuration). A [`Layout`] declares this per field, and
+//! [`Layout::initial_mask`] turns it into an initial mask M₀ so the
+//! compressor does not have to learn the volatile bits first.
+//!
+//! Offsets count bits from the start of the packet in transmission order,
+//! so bit offset 0 is the MSB of the first byte, matching the bit order of
+//! [`BitVector::from_bytes`].
+//!
+//! ## Layout Text Format
+//!
+//! One field per line, `name bit_offset width volatile`, offsets and widths
+//! in bits, `volatile` either `yes` or `no`, `#` starts a comment. Fields
+//! may not overlap; bits not covered by any field are not volatile:
+//!
+//! ```text
+//! sync      0 16 no
+//! counter  16 14 yes   # sequence count
+//! mode     30  2 no
+//! time     32 32 yes
+//! ```
+
+use crate::bitvector::{BitVector, MAX_PACKET_LENGTH};
+use crate::error::PocketError;
+
+/// A named bit field within a packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayoutField {
+    /// Field name.
+    pub name: String,
+    /// Offset of the first bit from the start of the packet.
+    pub bit_offset: usize,
+    /// Width in bits.
+    pub bits: usize,
+    /// The field is expected to change from packet to packet.
+    pub volatile: bool,
+}
+
+/// Field layout of a fixed-length packet, with volatility per field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// Packet size in bits (F).
+    pub packet_size: usize,
+    /// Fields in declaration order.
+    pub fields: Vec<LayoutField>,
+}
+
+impl Layout {
+    /// Create a layout, checking every field fits in the packet and no two
+    /// fields overlap.
+    ///
+    /// # Arguments
+    /// * `packet_size` - Packet size in bits
+    /// * `fields` - Field definitions
+    pub fn new(packet_size: usize, fields: Vec<LayoutField>) -> Result<Self, PocketError> {
+        if packet_size == 0 || packet_size > MAX_PACKET_LENGTH {
+            return Err(PocketError::InvalidPacketSize(packet_size));
+        }
+        let mut spans: Vec<(usize, usize, &str)> = Vec::with_capacity(fields.len());
+        for field in &fields {
+            if field.bits == 0 || field.bit_offset + field.bits > packet_size {
+                return Err(PocketError::InvalidFormat(format!(
+                    "field '{}' ({} bits at offset {}) does not fit a {packet_size}-bit packet",
+                    field.name, field.bits, field.bit_offset
+                )));
+            }
+            spans.push((field.bit_offset, field.bit_offset + field.bits, &field.name));
+        }
+        spans.sort_unstable();
+        if let Some(w) = spans.windows(2).find(|w| w[1].0 < w[0].1) {
+            return Err(PocketError::InvalidFormat(format!(
+                "fields '{}' and '{}' overlap",
+                w[0].2, w[1].2
+            )));
+        }
+        Ok(Self {
+            packet_size,
+            fields,
+        })
+    }
+
+    /// Parse a layout from its text description.
+    ///
+    /// # Arguments
+    /// * `packet_size` - Packet size in bits
+    /// * `text` - Layout description (see module documentation)
+    pub fn parse(packet_size: usize, text: &str) -> Result<Self, PocketError> {
+        let mut fields = Vec::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err =
+                |msg: &str| PocketError::InvalidFormat(format!("line {}: {msg}", line_no + 1));
+
+            let mut tokens = line.split_whitespace();
+            let name = tokens.next().unwrap_or("").to_string();
+            let bit_offset = tokens
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| err("invalid bit offset"))?;
+            let bits = tokens
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| err("invalid width"))?;
+            let volatile = match tokens.next() {
+                Some("yes") => true,
+                Some("no") => false,
+                _ => return Err(err("volatile must be 'yes' or 'no'")),
+            };
+            if let Some(extra) = tokens.next() {
+                return Err(err(&format!("unexpected '{extra}'")));
+            }
+
+            fields.push(LayoutField {
+                name,
+                bit_offset,
+                bits,
+                volatile,
+            });
+        }
+
+        Self::new(packet_size, fields)
+    }
+
+    /// Initial mask with the bits of every volatile field set.
+    ///
+    /// # Returns
+    /// An F-bit mask, usable as
+    /// [`CompressorBuilder::initial_mask`](crate::CompressorBuilder::initial_mask)
+    /// and the matching decompressor setting.
+    pub fn initial_mask(&self) -> BitVector {
+        let mut mask = BitVector::new(self.packet_size);
+        for field in self.fields.iter().filter(|f| f.volatile) {
+            for bit in field.bit_offset..field.bit_offset + field.bits {
+                mask.set_bit(bit, 1);
+            }
+        }
+        mask
+    }
+
+    /// Field covering a bit, if any.
+    ///
+    /// # Arguments
+    /// * `bit_offset` - Offset of the bit from the start of the packet
+    pub fn field_at(&self, bit_offset: usize) -> Option<&LayoutField> {
+        self.fields
+            .iter()
+            .find(|f| (f.bit_offset..f.bit_offset + f.bits).contains(&bit_offset))
+    }
+
+    /// Number of bits declared volatile.
+    pub fn volatile_bits(&self) -> usize {
+        self.fields
+            .iter()
+            .filter(|f| f.volatile)
+            .map(|f| f.bits)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_initial_mask() {
+        let layout = Layout::parse(
+            24,
+            "status 0 4 no\n\
+             # bits 4-7 unused\n\
+             counter 8 6 yes\n\
+             flag 14 1 no\n\
+             time 20 4 yes # low nibble",
+        )
+        .unwrap();
+        assert_eq!(layout.fields.len(), 4);
+        assert_eq!(layout.volatile_bits(), 10);
+        assert_eq!(layout.field_at(13).unwrap().name, "counter");
+        assert_eq!(layout.field_at(5), None);
+
+        let mask = layout.initial_mask();
+        assert_eq!(mask.len(), 24);
+        assert_eq!(mask.to_bytes(), vec![0x00, 0xFC, 0x0F]);
+    }
+
+    #[test]
+    fn test_layout_parse_errors() {
+        assert!(Layout::parse(16, "a 10 8 yes").is_err());
+        assert!(Layout::parse(16, "a 0 0 no").is_err());
+        assert!(Layout::parse(16, "a 0 8 maybe").is_err());
+        assert!(Layout::parse(16, "a 0 8").is_err());
+        assert!(Layout::parse(16, "a x 8 no").is_err());
+        assert!(Layout::parse(16, "a 0 8 no extra").is_err());
+        assert!(Layout::parse(16, "a 0 8 no\nb 7 2 yes").is_err());
+        assert!(Layout::parse(0, "").is_err());
+    }
+}
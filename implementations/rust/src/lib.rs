@@ -16,29 +16,136 @@
 //!
 //! ## Design
 //!
-//! - **Zero external dependencies** - Standard library only
-//! - **Safe Rust** - `#![forbid(unsafe_code)]`
+//! - **No required dependencies** - Standard library only by default; optional features
+//!   (`serde`, `wasm`, `python`, `async`, `test-utils`) pull in their own crates
+//! - **Safe Rust** - `#![forbid(unsafe_code)]` (only the opt-in `ffi` module uses `unsafe`)
 //! - **Byte-identical output** - Matches C reference implementation exactly
 //! - **Ground systems** - Optimized for 64-bit systems
 //!
+//! ## Cargo Features
+//!
+//! The `encoder` and `decoder` features (both on by default) select the
+//! halves of the codec that are compiled. A ground station that only
+//! decodes, or an on-board encoder, can disable the other half with
+//! `default-features = false`. Items that need a disabled half, such as
+//! [`container::SegmentEncoder`] without `encoder`, are compiled out.
+//!
+//! The optional `serde` feature derives `Serialize`/`Deserialize` for
+//! [`CompressOptions`], [`CompressionStats`] and `index::PacketIndex`.
+//! Codec state has no serde type of its own: `save_state` returns a
+//! versioned, CRC-checked byte snapshot (see the `state` module) that
+//! stays valid across changes to the internal layout, so serialize that
+//! `Vec<u8>` and hand it back to `restore_state`.
+//!
+//! The optional `ffi` feature adds the `ffi` module, an `extern "C"`
+//! interface for linking from C and C++ (declared in
+//! `include/pocketplus_ffi.h`). It is the only code allowed to use
+//! `unsafe`.
+//!
+//! The optional `wasm` feature adds the `wasm` module: `wasm-bindgen`
+//! exports of `compress`, `decompress` and a chunk-fed
+//! `StreamDecompressor` for browser-based telemetry tools. Build it with
+//! `--target wasm32-unknown-unknown`.
+//!
+//! The optional `python` feature builds the `PyO3` extension module
+//! `pocketplus_rs` (`compress`, `decompress`, `Compressor`,
+//! `StreamDecompressor`) for Python analysis scripts; `pyproject.toml`
+//! builds it with maturin.
+//!
+//! The optional `async` feature adds the `async_io` module:
+//! `AsyncPocketWriter` and `AsyncPocketReader`, implementing the
+//! `futures-io` `AsyncWrite` and `AsyncRead` traits for async pipelines.
+//!
+//! The optional `simd` feature processes the bulk [`BitVector`] word loops
+//! (XOR/OR/AND, Hamming weight, the eₜ intersection test) four words per
+//! step, which stable Rust vectorizes. It pays off for long packets; the
+//! output is identical.
+//!
 //! ## API Overview
 //!
 //! ### High-Level Functions
 //!
 //! - [`compress()`] - Compress entire input buffer
+//! - [`compress_with()`] - Compress with named, validated settings ([`CompressorBuilder`])
 //! - [`decompress()`] - Decompress entire compressed buffer
+//! - [`compress_indexed()`] - Compress and return the offset/length of each packet ([`PacketSpan`])
+//! - [`compress_into()`] / [`decompress_into()`] - Append to caller-provided buffers
+//! - [`compress_cancellable()`] / [`decompress_cancellable()`] - Abort long batch jobs via an `AtomicBool`
+//! - [`compress_with_progress()`] / [`decompress_with_progress()`] - Report [`ProgressInfo`] every N packets
+//! - [`compress_many()`] - Compress independent [`StreamInput`]s on worker threads
+//! - [`decompress_parallel()`] - Decode an indexed stream on worker threads, split at keyframes
+//! - [`compress_iter()`] - Compress packets lazily from an iterator ([`CompressIter`])
+//! - [`compress_with_mask()`] - Compress with an initial mask, skipping the warm-up packets
+//! - [`decompress_with_mask()`] - Decompress a stream seeded with an initial mask
+//! - [`StartMode::Seeded`] - Skip the forced first packets for a pre-seeded decompressor
+//! - [`CompressOptions::exclude_ranges`] - Keep known-volatile bit ranges out of mask
+//!   learning and send them verbatim
+//! - [`CompressorBuilder::epoch`] - Reset the state every N packets so each epoch
+//!   decodes independently
+//! - [`CompressorBuilder::packet_crc`] - Append a CRC-16 to every packet, verified by
+//!   [`DecompressorBuilder::packet_crc`]
+//! - [`CompressorBuilder::stream_checksum`] - End the stream with a CRC-32 trailer,
+//!   verified and stripped by [`decompress()`] and the other stream decoders
+//!   (required with [`DecompressorBuilder::stream_checksum`])
+//! - [`Compressor::new_extended`] / [`Decompressor::new_extended`] - Non-standard
+//!   packets above 65535 bits (flagged in containers with `FLAG_EXTENDED`)
+//! - [`decompress_n()`] - Decompress exactly N packets, ignoring anything after them
+//! - [`decompress_with_consumed()`] - Decompress and report the input length used
+//! - [`DecompressIter`] - Decompress lazily, one packet per iteration
+//! - [`detect_robustness()`] - Read the robustness level from a stream
+//! - [`compress_with_stats()`] - Compress and report encoder opcode usage ([`OpcodeStats`])
+//! - [`compress_with_packet_stats()`] - Compress and report per-packet size, flags, mask weight and ratio ([`CompressionStats`])
+//! - [`CompressionStats::to_json`] - Statistics as JSON, written with [`json::JsonWriter`]
+//! - [`compressed_stream_upper_bound()`] - Worst-case output size for buffer sizing
+//! - [`estimate_packet_size()`] - Size of the next packet for given flags, without compressing
+//!
+//! ### Bit Order
+//!
+//! - [`bitorder::compress_with_bit_order`] / [`bitorder::decompress_with_bit_order`] -
+//!   Accept and produce LSB-first bytes without pre-reversing them
 //!
 //! ### Low-Level Components
 //!
-//! - [`BitVector`] - Fixed-length bit vectors with 32-bit word storage
+//! - [`BitVector`] - Fixed-length bit vectors with 64-bit word storage
+//! - [`BitVector::save_mask_file`] / [`BitVector::load_mask_file`] - Persist a converged mask to seed the next pass
 //! - [`BitBuffer`] - Variable-length output buffer for compressed data
 //! - [`BitReader`] - Sequential bit reading from compressed data
+//! - [`Compressor::compress_packet_with_flags`] - Choose the [`CompressionParams`] of one packet
+//! - [`Compressor::peak_heap_size`] / [`Decompressor::peak_heap_size`] - Per-context memory
+//!   footprint for admission control
+//! - [`Workspace`] / [`Compressor::new_in`] / [`CompressorBuilder::build_in`] - Preallocated storage
+//!   for allocation-free compression, reusable across compressors
+//! - [`Compressor::with_shared_mask`] - Share one initial mask across contexts
+//! - [`Compressor::set_mask`] / [`Decompressor::set_state`] - Inject the mask and
+//!   previous packet from an earlier pass
+//! - [`Compressor::mask`] / [`Compressor::build`] / [`Compressor::time_step`] -
+//!   Read-only encoder state, e.g. for dashboards ([`Decompressor::mask`] on
+//!   the decoder side)
+//! - [`Compressor::set_refresh_policy`] - Force full mask + uncompressed packets by
+//!   packet count or wall-clock age ([`RefreshPolicy`])
+//! - [`Compressor::save_state`] / [`Decompressor::save_state`] (and `restore_state`) - Persist and
+//!   resume a stream across restarts or processing jobs
+//! - [`Compressor::set_adaptive_policy`] - Raise ṗₜ/ḟₜ from stale mask bits and large mask changes
+//!   ([`AdaptivePolicy`])
+//! - [`Compressor::set_monitor`] - Sliding-window ratio alerts ([`monitor::RatioMonitor`])
+//! - [`Decompressor::skip_packet`] - Advance past a packet without producing it
+//! - [`Decompressor::mark_lost`] - Continue after up to R lost packets (robustness recovery)
+//! - [`Decompressor::resync`] - Resume at the next full-mask, uncompressed packet after corruption
+//! - [`Codec`] - Matched compressor/decompressor pair; [`Codec::roundtrip`] checks one packet
+//! - [`DecompressorBuilder`] - Configure a [`Decompressor`] (initial mask, [`DecodeMode`],
+//!   [`Alignment`], [`DecodeLimits`], [`DecodeDiagnostics`])
+//!
+//! ### I/O Adapters
+//!
+//! - [`io::PocketWriter`] - Compress bytes written through [`std::io::Write`]
 //!
 //! ### Encoding Primitives (CCSDS Section 5.2)
 //!
 //! - [`count_encode`] / [`count_decode`] - Counter encoding (Equation 9)
 //! - [`rle_encode`] / [`rle_decode`] - Run-length encoding (Equation 10)
+//! - [`rle_decode_positions`] - RLE decoding to the set bit positions
 //! - [`bit_extract`] / [`bit_insert`] - Bit extraction (Equation 11)
+//! - [`raw`] - The same primitives on byte slices with explicit bit offsets
 //!
 //! ### Mask Operations (CCSDS Section 4)
 //!
@@ -46,6 +153,43 @@
 //! - [`update_mask`] - Mask vector update (Equation 7)
 //! - [`compute_change`] - Change vector computation (Equation 8)
 //!
+//! ### Container Format
+//!
+//! - [`container::compress_segment`] - Compress into a self-delimiting segment
+//! - [`container::decompress_segments`] - Decode concatenated segments
+//! - [`container::merge_segments`] - Stitch segments from a resumed compressor
+//! - [`framing::FrameEncoder`] / [`framing::FrameDecoder`] - Sequence-numbered packets, reordered within a window
+//! - [`framing::compress_framed`] / [`framing::FrameReader`] - Length-prefixed per-packet frames
+//! - [`framing::StreamReassembler`] - In-order decoding of sequence-counted packets with loss recovery
+//! - [`spp::SpacePacketEncoder`] / [`spp::SpacePacketDecoder`] - CCSDS Space Packet encapsulation
+//! - [`spp::extract_packets`] - Housekeeping payloads of one APID from a raw space packet capture
+//! - [`telemetry::compress_dump`] - One compressed archive per APID from a dump of TM transfer frames
+//! - [`mux::MuxCompressor`] / [`mux::MuxDecompressor`] - Independent compressors per stream ID in one tagged output
+//! - [`passthrough::compress_passthrough`] / [`passthrough::decompress_passthrough`] - Time fields carried verbatim outside the compressed payload
+//! - [`sync::compress_synced`] / [`sync::decompress_synced`] - Packets behind Attached Sync Markers, located by scanning a noisy byte stream
+//! - [`merge::merge_captures`] - Reconstruct a stream from overlapping partial captures
+//! - [`archive::compress_archive`] / [`archive::decompress_archive`] - Chunked archives with restart points, decodable after partial delivery
+//! - [`index::PacketIndex`] / [`Decompressor::seek_to`] - Random access to any packet
+//!   of a compressed archive
+//! - [`index::PacketIndex::to_bytes`] / [`Decompressor::decompress_range`] - `.idx`
+//!   sidecar files for decoding packet ranges without re-indexing
+//! - [`manifest::compress_manifest`] - Compress several files/ranges with their own packet sizes
+//! - [`armor::Armor`] - Hex/Base64 text armor for binary-unsafe transfer paths
+//!
+//! ### Analysis
+//!
+//! - [`analyze::suggest_packet_sizes`] - Detect likely packet sizes in raw dumps
+//! - [`analyze::bit_change_profile`] - Per-bit change frequencies of a raw dump, as CSV
+//! - [`layout::Layout`] - Initial mask from a field layout declaring which fields are volatile
+//! - [`schema::field_stats`] - Per-field change statistics from a [`schema::Schema`]
+//! - [`schema::export_csv`] - Decoded field values as CSV (big/little-endian, signed)
+//! - [`sweep::sweep`] - Compression statistics over a parameter grid (CSV/JSON)
+//! - [`inspect::annotate`] - Bit ranges and decoded values of every field of every packet
+//!
+//! ### Synthetic Data
+//!
+//! - [`synth::generate`] - Deterministic pseudo-housekeeping streams from a [`synth::Profile`]
+//!
 //! ## Usage
 //!
 //! ```rust,ignore
@@ -70,42 +214,203 @@
 //! assert_eq!(data, decompressed);
 //! ```
 //!
+//! ## Streaming
+//!
+//! [`Compressor`] and [`Decompressor`] keep their state between packets,
+//! so a live downlink can be processed one packet at a time without
+//! buffering the whole pass:
+//!
+//! ```rust,ignore
+//! use pocketplus::{BitReader, BitVector, Compressor, Decompressor};
+//!
+//! let mut comp = Compressor::new(720, None, 1, 10, 20, 50).unwrap();
+//! let mut decomp = Decompressor::new(720, None, 1).unwrap();
+//!
+//! for packet in live_source() {
+//!     // Compress: flags from the pt/ft/rt schedule, then the packet
+//!     let params = comp.next_params();
+//!     let input = BitVector::from_bytes(&packet, 720);
+//!     let bytes = comp.compress_packet(&input, &params).unwrap().into_bytes();
+//!
+//!     // Decompress: packets are byte-aligned in the stream
+//!     let mut reader = BitReader::new(&bytes, bytes.len() * 8);
+//!     let output = decomp.decompress_packet(&mut reader).unwrap();
+//!     assert_eq!(output.to_bytes(), packet);
+//! }
+//! ```
+//!
 //! ## References
 //!
 //! - [CCSDS 124.0-B-1 Standard](https://ccsds.org/Pubs/124x0b1.pdf)
 //! - [ESA POCKET+ Reference](https://opssat.esa.int/pocket-plus/)
 
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
 #![warn(clippy::pedantic)]
 #![allow(clippy::must_use_candidate)]
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::missing_panics_doc)]
 
+#[cfg(not(any(feature = "encoder", feature = "decoder")))]
+compile_error!("enable at least one of the `encoder` and `decoder` features");
+
+pub mod analyze;
+pub mod archive;
+pub mod armor;
+#[cfg(feature = "async")]
+pub mod async_io;
+mod batch;
+#[cfg(feature = "encoder")]
 mod bitbuffer;
+pub mod bitorder;
+#[cfg(feature = "decoder")]
 mod bitreader;
 mod bitvector;
+mod buffer;
+#[cfg(all(feature = "encoder", feature = "decoder"))]
+mod codec;
+#[cfg(feature = "encoder")]
 mod compress;
+pub mod container;
+mod crc;
+#[cfg(feature = "decoder")]
 mod decode;
+#[cfg(feature = "decoder")]
 mod decompress;
+#[cfg(feature = "encoder")]
 mod encode;
 mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod framing;
+#[cfg(feature = "decoder")]
+pub mod index;
+#[cfg(feature = "decoder")]
+pub mod inspect;
+pub mod io;
+pub mod json;
+pub mod layout;
+#[cfg(feature = "encoder")]
+pub mod manifest;
+#[cfg(feature = "encoder")]
 mod mask;
+#[cfg(feature = "decoder")]
+pub mod merge;
+#[cfg(feature = "encoder")]
+pub mod monitor;
+pub mod mux;
+pub mod passthrough;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+pub mod raw;
+pub mod schema;
+#[cfg(feature = "simd")]
+mod simd;
+pub mod spp;
+mod state;
+#[cfg(feature = "encoder")]
+pub mod sweep;
+pub mod sync;
+pub mod synth;
+pub mod telemetry;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+pub mod trailer;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "encoder")]
+mod workspace;
 
+#[cfg(feature = "decoder")]
+pub use batch::decompress_parallel;
+#[cfg(feature = "encoder")]
+pub use batch::{compress_many, StreamInput};
+#[cfg(feature = "encoder")]
 pub use bitbuffer::BitBuffer;
+#[cfg(feature = "decoder")]
 pub use bitreader::BitReader;
 pub use bitvector::BitVector;
-pub use compress::compress;
-pub use decode::{bit_insert, count_decode, rle_decode};
-pub use decompress::decompress;
-pub use encode::{bit_extract, bit_extract_forward, count_encode, rle_encode};
+#[cfg(all(feature = "encoder", feature = "decoder"))]
+pub use codec::Codec;
+#[cfg(feature = "encoder")]
+pub use compress::{
+    compress, compress_cancellable, compress_indexed, compress_into, compress_iter, compress_with,
+    compress_with_mask, compress_with_packet_stats, compress_with_progress, compress_with_stats,
+    compressed_stream_upper_bound, estimate_packet_size, exclusion_mask, AdaptivePolicy,
+    CompressIter, CompressOptions, CompressionParams, CompressionStats, Compressor,
+    CompressorBuilder, PacketSpan, PacketStats, RefreshPolicy, StartMode,
+};
+#[cfg(feature = "decoder")]
+pub use decode::{bit_insert, count_decode, rle_decode, rle_decode_positions};
+#[cfg(feature = "decoder")]
+pub use decompress::{
+    decompress, decompress_cancellable, decompress_into, decompress_n, decompress_with_consumed,
+    decompress_with_mask, decompress_with_progress, detect_robustness, Alignment, Consumed,
+    DecodeDiagnostics, DecodeLimits, DecodeMode, DecompressIter, Decompressor, DecompressorBuilder,
+    SkippedPacket,
+};
+#[cfg(feature = "encoder")]
+pub use encode::{bit_extract, bit_extract_forward, count_encode, rle_encode, OpcodeStats};
 pub use error::PocketError;
-pub use mask::{compute_change, update_build, update_mask};
+#[cfg(feature = "encoder")]
+pub use mask::{compute_change, compute_change_into, update_build, update_mask};
+pub use progress::ProgressInfo;
+#[cfg(feature = "encoder")]
+pub use workspace::Workspace;
 
 #[cfg(test)]
 mod tests {
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
     #[test]
     fn test_library_loads() {
         // Basic smoke test - ensure library compiles and loads
-        assert!(true);
+        assert!(!crate::compress(&[0u8; 90], 720, 1, 10, 20, 50)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(all(feature = "serde", feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_serde_round_trip() {
+        fn round_trip<T>(value: &T) -> T
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned,
+        {
+            serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+        }
+
+        let data: Vec<u8> = (0..90 * 30u32)
+            .map(|i| u8::try_from(i / 90 % 3 + i % 11).unwrap())
+            .collect();
+
+        let mut options = crate::CompressOptions::new(2, 10, 20, 50);
+        options.start = crate::StartMode::Seeded;
+        options.epoch = Some(12);
+        options.packet_crc = true;
+        options.exclude_ranges = vec![0..16, 40..48];
+        assert_eq!(round_trip(&options), options);
+
+        let (compressed, stats) =
+            crate::compress_with_packet_stats(&data, 720, 2, 10, 20, 50).unwrap();
+        assert_eq!(stats.packets.len(), 30);
+        assert_eq!(round_trip(&stats), stats);
+
+        let builder = crate::DecompressorBuilder::new(720).robustness(2);
+        let index = crate::index::PacketIndex::build(&compressed, &builder).unwrap();
+        assert_eq!(index.len(), 30);
+        assert_eq!(round_trip(&index), index);
+
+        // Saved state serializes as its byte snapshot and restores from it
+        let mut comp = crate::Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        for packet in data[..17 * 90].chunks(90) {
+            let params = comp.next_params();
+            comp.compress_packet(&crate::BitVector::from_bytes(packet, 720), &params)
+                .unwrap();
+        }
+        let snapshot = round_trip(&comp.save_state());
+        let mut resumed = crate::Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+        resumed.restore_state(&snapshot).unwrap();
+        assert_eq!(resumed.save_state(), comp.save_state());
     }
 }
@@ -0,0 +1,341 @@
+//! Multi-file manifests with per-entry packet sizes.
+//!
+//! A downlink session often mixes housekeeping structures from several
+//! subsystems, each with its own packet size and tuning. A [`Manifest`]
+//! lists the files (or byte ranges within files) and their parameters so
+//! all of them can be compressed in one pass by [`compress_manifest`].
+//!
+//! The output is a container (see [`container`](crate::container)) with
+//! one segment per entry. Each segment header records its own packet size
+//! and robustness, so the whole session decompresses with
+//! [`decompress_segments`](crate::container::decompress_segments).
+//!
+//! ## Manifest Text Format
+//!
+//! One entry per line, `path packet_bytes [key=value ...]`, `#` starts a
+//! comment. Keys are `r`, `pt`, `ft`, `rt` (defaulting to
+//! [`CompressOptions::default`]), `refresh` to force a full mask and
+//! uncompressed packet at least every N packets, and `offset`/`length` to
+//! select a byte range (default: the whole file):
+//!
+//! ```text
+//! power.bin    90  r=1 pt=10 ft=20 rt=50
+//! thermal.bin  64  r=2
+//! capture.raw  32  offset=1024 length=4096 refresh=64
+//! ```
+
+#![allow(clippy::cast_precision_loss)]
+
+use crate::compress::{CompressOptions, RefreshPolicy};
+use crate::container::SegmentEncoder;
+use crate::error::PocketError;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One file or byte range to compress.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Input file, relative to the manifest's base directory.
+    pub path: PathBuf,
+    /// Packet size in bytes.
+    pub packet_bytes: usize,
+    /// Compression parameters.
+    pub options: CompressOptions,
+    /// Byte offset of the range within the file.
+    pub offset: usize,
+    /// Range length in bytes, or `None` for the rest of the file.
+    pub length: Option<usize>,
+}
+
+/// List of entries compressed together.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// Entries in processing order.
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Parse a manifest from its text description.
+    ///
+    /// # Arguments
+    /// * `text` - Manifest description (see module documentation)
+    pub fn parse(text: &str) -> Result<Self, PocketError> {
+        let mut entries = Vec::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err =
+                |msg: &str| PocketError::InvalidFormat(format!("line {}: {msg}", line_no + 1));
+
+            let mut tokens = line.split_whitespace();
+            let path = PathBuf::from(tokens.next().unwrap_or(""));
+            let packet_bytes = tokens
+                .next()
+                .and_then(|t| t.parse().ok())
+                .filter(|&b| b > 0)
+                .ok_or_else(|| err("invalid packet size"))?;
+
+            let mut entry = ManifestEntry {
+                path,
+                packet_bytes,
+                options: CompressOptions::default(),
+                offset: 0,
+                length: None,
+            };
+            for token in tokens {
+                let (key, value) = token
+                    .split_once('=')
+                    .ok_or_else(|| err(&format!("expected key=value, got '{token}'")))?;
+                let value: usize = value.parse().map_err(|_| err(&format!("invalid {key}")))?;
+                match key {
+                    "r" => entry.options.robustness = value,
+                    "pt" => entry.options.pt_limit = value,
+                    "ft" => entry.options.ft_limit = value,
+                    "rt" => entry.options.rt_limit = value,
+                    "refresh" => entry.options.refresh = RefreshPolicy::packets(value),
+                    "offset" => entry.offset = value,
+                    "length" => entry.length = Some(value),
+                    other => return Err(err(&format!("unknown key '{other}'"))),
+                }
+            }
+            entries.push(entry);
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+/// Result for one manifest entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryReport {
+    /// Input file.
+    pub path: PathBuf,
+    /// Byte offset of the range within the file.
+    pub offset: usize,
+    /// Packet size in bytes.
+    pub packet_bytes: usize,
+    /// Number of packets compressed.
+    pub packets: usize,
+    /// Input size in bytes.
+    pub input_bytes: usize,
+    /// Segment size in bytes, including its header.
+    pub compressed_bytes: usize,
+}
+
+impl EntryReport {
+    /// Input size divided by compressed size.
+    pub fn ratio(&self) -> f64 {
+        ratio(self.input_bytes, self.compressed_bytes)
+    }
+}
+
+/// Combined report for a manifest run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ManifestReport {
+    /// One report per entry, in manifest order.
+    pub entries: Vec<EntryReport>,
+}
+
+impl ManifestReport {
+    /// Total input size in bytes.
+    pub fn input_bytes(&self) -> usize {
+        self.entries.iter().map(|e| e.input_bytes).sum()
+    }
+
+    /// Total output size in bytes.
+    pub fn compressed_bytes(&self) -> usize {
+        self.entries.iter().map(|e| e.compressed_bytes).sum()
+    }
+
+    /// Overall compression ratio.
+    pub fn ratio(&self) -> f64 {
+        ratio(self.input_bytes(), self.compressed_bytes())
+    }
+
+    /// Export the report as CSV with a header row and a totals row.
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("path,offset,packet_bytes,packets,input_bytes,compressed_bytes,ratio\n");
+        for e in &self.entries {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{:.4}",
+                e.path.display(),
+                e.offset,
+                e.packet_bytes,
+                e.packets,
+                e.input_bytes,
+                e.compressed_bytes,
+                e.ratio()
+            );
+        }
+        let _ = writeln!(
+            csv,
+            "total,,,{},{},{},{:.4}",
+            self.entries.iter().map(|e| e.packets).sum::<usize>(),
+            self.input_bytes(),
+            self.compressed_bytes(),
+            self.ratio()
+        );
+        csv
+    }
+}
+
+/// Ratio of two sizes, 0 when the denominator is 0.
+fn ratio(input: usize, output: usize) -> f64 {
+    if output == 0 {
+        0.0
+    } else {
+        input as f64 / output as f64
+    }
+}
+
+/// Select an entry's byte range from its file contents.
+fn entry_range<'a>(entry: &ManifestEntry, data: &'a [u8]) -> Result<&'a [u8], PocketError> {
+    let end = entry
+        .length
+        .map_or(data.len(), |length| entry.offset.saturating_add(length));
+    data.get(entry.offset..end).ok_or_else(|| {
+        PocketError::InvalidFormat(format!(
+            "range {}..{end} is outside '{}' ({} bytes)",
+            entry.offset,
+            entry.path.display(),
+            data.len()
+        ))
+    })
+}
+
+/// Compress every entry of a manifest into one container.
+///
+/// Consecutive entries naming the same file read it only once.
+///
+/// # Arguments
+/// * `manifest` - Entries to compress
+/// * `base_dir` - Directory that relative entry paths are resolved against
+///
+/// # Returns
+/// The container (one segment per entry) and the combined report.
+pub fn compress_manifest(
+    manifest: &Manifest,
+    base_dir: &Path,
+) -> Result<(Vec<u8>, ManifestReport), PocketError> {
+    for entry in &manifest.entries {
+        entry.options.reject_stream_checksum("compress_manifest")?;
+    }
+    let mut output = Vec::new();
+    let mut report = ManifestReport::default();
+    let mut cached: Option<(&Path, Vec<u8>)> = None;
+
+    for entry in &manifest.entries {
+        if cached
+            .as_ref()
+            .map_or(true, |(path, _)| *path != entry.path)
+        {
+            cached = Some((&entry.path, fs::read(base_dir.join(&entry.path))?));
+        }
+        let data = cached.as_ref().map_or(&[][..], |(_, data)| data);
+        let input = entry_range(entry, data)?;
+
+        let options = &entry.options;
+        let segment = SegmentEncoder::new(
+            entry.packet_bytes * 8,
+            options.robustness,
+            options.pt_limit,
+            options.ft_limit,
+            options.rt_limit,
+        )?
+        .refresh(options.refresh)
+        .encode(input)?;
+
+        report.entries.push(EntryReport {
+            path: entry.path.clone(),
+            offset: entry.offset,
+            packet_bytes: entry.packet_bytes,
+            packets: input.len() / entry.packet_bytes,
+            input_bytes: input.len(),
+            compressed_bytes: segment.len(),
+        });
+        output.extend_from_slice(&segment);
+    }
+
+    Ok((output, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "decoder")]
+    use crate::container::decompress_segments;
+    #[cfg(feature = "decoder")]
+    use crate::synth::{generate, Profile};
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(
+            "# session\n\
+             power.bin 90 r=2 pt=5 ft=10 rt=20\n\
+             capture.raw 8 offset=16 length=32 refresh=4 # range\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.entries.len(), 2);
+        assert_eq!(
+            manifest.entries[0].options,
+            CompressOptions::new(2, 5, 10, 20)
+        );
+        assert_eq!(
+            manifest.entries[1].options.refresh,
+            RefreshPolicy::packets(4)
+        );
+        assert_eq!(
+            (manifest.entries[1].offset, manifest.entries[1].length),
+            (16, Some(32))
+        );
+
+        assert!(Manifest::parse("a.bin").is_err());
+        assert!(Manifest::parse("a.bin 0").is_err());
+        assert!(Manifest::parse("a.bin 8 r").is_err());
+        assert!(Manifest::parse("a.bin 8 speed=1").is_err());
+    }
+
+    #[cfg(feature = "decoder")]
+    #[test]
+    fn test_compress_manifest() {
+        let dir = std::env::temp_dir().join(format!("pocketplus-manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let hk = generate(&Profile::housekeeping(), 50, 1);
+        let small: Vec<u8> = (0..64u8).map(|i| i / 8).collect();
+        fs::write(dir.join("hk.bin"), &hk).unwrap();
+        fs::write(dir.join("small.bin"), &small).unwrap();
+
+        let manifest = Manifest::parse(
+            "hk.bin 90 r=2\n\
+             small.bin 8 offset=16 length=32\n\
+             small.bin 4 r=0",
+        )
+        .unwrap();
+        let (container, report) = compress_manifest(&manifest, &dir).unwrap();
+
+        let segments = decompress_segments(&container).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0].data, hk);
+        assert_eq!(segments[1].data, &small[16..48]);
+        assert_eq!(segments[1].header.packet_size, 64);
+        assert_eq!(segments[2].data, small);
+        assert_eq!(segments[2].header.robustness, 0);
+
+        assert_eq!(report.entries[1].packets, 4);
+        assert_eq!(report.input_bytes(), hk.len() + 32 + small.len());
+        assert_eq!(report.compressed_bytes(), container.len());
+        assert_eq!(report.to_csv().lines().count(), 5);
+
+        // Range past the end of the file
+        let bad = Manifest::parse("small.bin 8 offset=60 length=8").unwrap();
+        assert!(compress_manifest(&bad, &dir).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
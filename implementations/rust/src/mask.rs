@@ -37,12 +37,7 @@ pub fn update_build(
         // Case 2: Normal operation (t > 0 and new_mask_flag = 0)
         // Bₜ = (Iₜ XOR Iₜ₋₁) OR Bₜ₋₁
 
-        // Calculate changes: Iₜ XOR Iₜ₋₁
-        let changes = input.xor(prev_input);
-
-        // Update build: Bₜ = changes OR Bₜ₋₁
-        let new_build = changes.or(build);
-        build.copy_from(&new_build);
+        build.or_xor_assign(input, prev_input);
     }
 }
 
@@ -68,18 +63,12 @@ pub fn update_mask(
     build_prev: &BitVector,
     new_mask_flag: bool,
 ) {
-    // Calculate changes: Iₜ XOR Iₜ₋₁
-    let changes = input.xor(prev_input);
-
     if new_mask_flag {
         // Case 1: new_mask_flag set → Mₜ = (Iₜ XOR Iₜ₋₁) OR Bₜ₋₁
-        let new_mask = changes.or(build_prev);
-        mask.copy_from(&new_mask);
-    } else {
-        // Case 2: Normal operation → Mₜ = (Iₜ XOR Iₜ₋₁) OR Mₜ₋₁
-        let new_mask = changes.or(mask);
-        mask.copy_from(&new_mask);
+        mask.copy_from(build_prev);
     }
+    // Case 2: Normal operation → Mₜ = (Iₜ XOR Iₜ₋₁) OR Mₜ₋₁
+    mask.or_xor_assign(input, prev_input);
 }
 
 /// Compute the change vector.
@@ -99,12 +88,32 @@ pub fn update_mask(
 /// # Returns
 /// The change vector Dₜ
 pub fn compute_change(mask: &BitVector, prev_mask: &BitVector, t: usize) -> BitVector {
-    if t == 0 {
-        // At t=0, D₀ = M₀ (all initially predictable bits)
-        mask.clone()
-    } else {
+    let mut change = BitVector::new(mask.len());
+    compute_change_into(mask, prev_mask, t, &mut change);
+    change
+}
+
+/// Compute the change vector into an existing vector.
+///
+/// Same as [`compute_change`] without allocating, for compressors running
+/// from a [`Workspace`](crate::Workspace).
+///
+/// # Arguments
+/// * `mask` - Current mask vector Mₜ
+/// * `prev_mask` - Previous mask vector Mₜ₋₁
+/// * `t` - Current time step
+/// * `change` - Destination for Dₜ (overwritten)
+pub fn compute_change_into(
+    mask: &BitVector,
+    prev_mask: &BitVector,
+    t: usize,
+    change: &mut BitVector,
+) {
+    // At t=0, D₀ = M₀ (all initially predictable bits)
+    change.copy_from(mask);
+    if t > 0 {
         // Dₜ = Mₜ XOR Mₜ₋₁
-        mask.xor(prev_mask)
+        change.xor_assign(prev_mask);
     }
 }
 
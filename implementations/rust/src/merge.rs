@@ -0,0 +1,332 @@
+//! Merging partial captures of one compressed stream.
+//!
+//! Different ground-station passes often record overlapping pieces of the
+//! same POCKET+ stream, each starting and ending at arbitrary points.
+//! [`merge_captures`] reconstructs as much of the stream as can be decoded.
+//!
+//! Decoding can only start at an *anchor*: a packet carrying both the full
+//! mask (ḟₜ = 1) and the uncompressed packet (ṙₜ = 1), which restores the
+//! whole decoder state. The first R + 1 packets of a stream are anchors;
+//! later ones appear where the ft and rt schedules coincide, so choosing
+//! `rt_limit` as a multiple of `ft_limit` gives an anchor every `rt_limit`
+//! packets.
+//!
+//! Each capture is scanned for anchors and decoded from there into runs of
+//! packets. Runs that contain the same anchor packet are aligned on it,
+//! checked for agreement where they overlap and stitched together. The
+//! result is one [`MergedSequence`] per group of overlapping runs, plus the
+//! byte ranges of each capture that could not be decoded.
+//!
+//! Alignment relies on anchor packets being distinguishable, e.g. by a
+//! time or sequence field in the housekeeping data.
+
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_possible_wrap)]
+#![allow(clippy::cast_sign_loss)]
+
+use crate::bitreader::BitReader;
+use crate::decompress::{is_anchor, Decompressor};
+use crate::error::PocketError;
+use std::collections::HashMap;
+
+/// Contiguous stretch of the stream recovered from one or more captures.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergedSequence {
+    /// Decoded packets, concatenated.
+    pub data: Vec<u8>,
+    /// Number of packets.
+    pub packets: usize,
+    /// Indices of the captures that contributed, ascending.
+    pub captures: Vec<usize>,
+}
+
+/// Byte range of a capture that could not be decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gap {
+    /// Index of the capture.
+    pub capture: usize,
+    /// Offset of the range in the capture, in bytes.
+    pub offset: usize,
+    /// Length of the range in bytes.
+    pub length: usize,
+}
+
+/// Result of [`merge_captures`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Recovered sequences, in order of their first contributing capture.
+    /// Their relative order in the stream is unknown.
+    pub sequences: Vec<MergedSequence>,
+    /// Undecodable ranges: data before a capture's first anchor, after a
+    /// decode failure until the next anchor, and truncated trailing packets.
+    pub gaps: Vec<Gap>,
+}
+
+/// Run of decoded packets.
+#[derive(Clone, Debug)]
+struct Run {
+    packets: Vec<Vec<u8>>,
+    /// Indices of the anchor packets in `packets`.
+    anchors: Vec<usize>,
+    captures: Vec<usize>,
+}
+
+/// Decode one capture into runs starting at anchors.
+fn decode_runs(
+    capture: usize,
+    data: &[u8],
+    f: usize,
+    robustness: u8,
+    runs: &mut Vec<Run>,
+    gaps: &mut Vec<Gap>,
+) -> Result<(), PocketError> {
+    let mut decomp = Decompressor::new(f, None, robustness)?;
+    let mut gap_start = 0;
+    let mut offset = 0;
+
+    while offset < data.len() {
+        if !is_anchor(
+            BitReader::new(&data[offset..], (data.len() - offset) * 8),
+            f,
+        ) {
+            offset += 1;
+            continue;
+        }
+
+        decomp.resync_state();
+        let mut reader = BitReader::new(&data[offset..], (data.len() - offset) * 8);
+        let mut run = Run {
+            packets: Vec::new(),
+            anchors: Vec::new(),
+            captures: vec![capture],
+        };
+        let mut end = 0;
+        while reader.remaining() > 0 {
+            let anchor = is_anchor(reader.clone(), f);
+            let Ok(packet) = decomp.decompress_packet(&mut reader) else {
+                break;
+            };
+            reader.align_byte();
+            if anchor {
+                run.anchors.push(run.packets.len());
+            }
+            run.packets.push(packet.to_bytes());
+            end = reader.position() / 8;
+        }
+        if run.packets.is_empty() {
+            offset += 1;
+            continue;
+        }
+
+        if gap_start < offset {
+            gaps.push(Gap {
+                capture,
+                offset: gap_start,
+                length: offset - gap_start,
+            });
+        }
+        runs.push(run);
+        offset += end;
+        gap_start = offset;
+    }
+
+    if gap_start < data.len() {
+        gaps.push(Gap {
+            capture,
+            offset: gap_start,
+            length: data.len() - gap_start,
+        });
+    }
+    Ok(())
+}
+
+/// Position of `b` relative to `a` if they share an anchor and agree
+/// wherever they overlap.
+fn align(a: &Run, b: &Run) -> Option<isize> {
+    let mut positions: HashMap<&[u8], usize> = HashMap::new();
+    for &i in &a.anchors {
+        positions.entry(&a.packets[i]).or_insert(i);
+    }
+
+    b.anchors.iter().find_map(|&j| {
+        let shift = *positions.get(b.packets[j].as_slice())? as isize - j as isize;
+        let agree = b.packets.iter().enumerate().all(|(k, packet)| {
+            let i = k as isize + shift;
+            i < 0 || i >= a.packets.len() as isize || a.packets[i as usize] == *packet
+        });
+        agree.then_some(shift)
+    })
+}
+
+/// Stitch `b` onto `a` at the given relative position.
+fn stitch(a: Run, b: Run, shift: isize) -> Run {
+    let start = shift.min(0);
+    let end = (a.packets.len() as isize).max(shift + b.packets.len() as isize);
+    let a_at = (-start) as usize;
+    let b_at = (shift - start) as usize;
+
+    let mut packets: Vec<Option<Vec<u8>>> = vec![None; (end - start) as usize];
+    let mut anchors = Vec::new();
+    for (k, packet) in b.packets.into_iter().enumerate() {
+        packets[b_at + k] = Some(packet);
+    }
+    for (k, packet) in a.packets.into_iter().enumerate() {
+        packets[a_at + k] = Some(packet);
+    }
+    anchors.extend(a.anchors.iter().map(|i| a_at + i));
+    anchors.extend(b.anchors.iter().map(|i| b_at + i));
+    anchors.sort_unstable();
+    anchors.dedup();
+
+    let mut captures = a.captures;
+    captures.extend(b.captures);
+    captures.sort_unstable();
+    captures.dedup();
+
+    Run {
+        // Aligned runs share a packet, so together they leave no holes
+        packets: packets.into_iter().flatten().collect(),
+        anchors,
+        captures,
+    }
+}
+
+/// Merge partial captures of one compressed stream.
+///
+/// # Arguments
+/// * `captures` - Compressed byte ranges of the same stream (byte-aligned
+///   packets), in any order and possibly overlapping
+/// * `packet_size` - Packet size in bits
+/// * `robustness` - Robustness level R
+///
+/// # Returns
+/// The recovered sequences and the undecodable ranges of each capture.
+pub fn merge_captures(
+    captures: &[&[u8]],
+    packet_size: usize,
+    robustness: usize,
+) -> Result<MergeReport, PocketError> {
+    if packet_size == 0 || packet_size % 8 != 0 {
+        return Err(PocketError::InvalidPacketSize(packet_size));
+    }
+    let robustness = u8::try_from(robustness)
+        .ok()
+        .filter(|&r| r <= 7)
+        .ok_or(PocketError::InvalidRobustness(robustness))?;
+
+    let mut runs = Vec::new();
+    let mut gaps = Vec::new();
+    for (capture, data) in captures.iter().enumerate() {
+        decode_runs(capture, data, packet_size, robustness, &mut runs, &mut gaps)?;
+    }
+
+    // Stitch runs pairwise until no two share an anchor
+    let mut merged: Vec<Run> = Vec::new();
+    for mut run in runs {
+        while let Some((index, shift)) = merged
+            .iter()
+            .enumerate()
+            .find_map(|(i, m)| align(m, &run).map(|shift| (i, shift)))
+        {
+            run = stitch(merged.remove(index), run, shift);
+        }
+        let at = merged
+            .iter()
+            .position(|m| m.captures[0] > run.captures[0])
+            .unwrap_or(merged.len());
+        merged.insert(at, run);
+    }
+
+    Ok(MergeReport {
+        sequences: merged
+            .into_iter()
+            .map(|run| MergedSequence {
+                packets: run.packets.len(),
+                data: run.packets.concat(),
+                captures: run.captures,
+            })
+            .collect(),
+        gaps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "encoder")]
+    use crate::compress::Compressor;
+    #[cfg(feature = "encoder")]
+    use crate::synth::{generate, Profile};
+    #[cfg(feature = "encoder")]
+    use crate::BitVector;
+
+    #[cfg(feature = "encoder")]
+    /// Compress packets one by one, returning the stream and packet offsets.
+    fn stream(data: &[u8], packet_bytes: usize) -> (Vec<u8>, Vec<usize>) {
+        let mut comp = Compressor::new(packet_bytes * 8, None, 1, 10, 10, 10).unwrap();
+        let mut output = Vec::new();
+        let mut offsets = Vec::new();
+        for packet in data.chunks(packet_bytes) {
+            offsets.push(output.len());
+            let params = comp.next_params();
+            let input = BitVector::from_bytes(packet, packet_bytes * 8);
+            output.extend(comp.compress_packet(&input, &params).unwrap().to_bytes());
+        }
+        offsets.push(output.len());
+        (output, offsets)
+    }
+
+    #[cfg(feature = "encoder")]
+    #[test]
+    fn test_merge_overlapping_captures() {
+        let profile = Profile::housekeeping();
+        let bytes = profile.packet_bytes();
+        let data = generate(&profile, 200, 7);
+        let (compressed, at) = stream(&data, bytes);
+
+        // Out of order: a late pass, a middle pass starting between anchors
+        // (packet 45, anchors every 10) and the start of the stream
+        let late = &compressed[at[150]..at[200] - 3];
+        let middle = &compressed[at[45]..at[120]];
+        let early = &compressed[at[0]..at[60]];
+        let report = merge_captures(&[late, middle, early], bytes * 8, 1).unwrap();
+
+        assert_eq!(report.sequences.len(), 2);
+        let late_seq = &report.sequences[0];
+        assert_eq!(late_seq.captures, vec![0]);
+        assert_eq!(late_seq.packets, 49);
+        assert_eq!(late_seq.data, &data[150 * bytes..199 * bytes]);
+
+        let merged = &report.sequences[1];
+        assert_eq!(merged.captures, vec![1, 2]);
+        assert_eq!(merged.packets, 120);
+        assert_eq!(merged.data, &data[..120 * bytes]);
+
+        // Truncated last packet of the late pass, lead-in of the middle pass
+        assert_eq!(
+            report.gaps,
+            vec![
+                Gap {
+                    capture: 0,
+                    offset: at[199] - at[150],
+                    length: at[200] - 3 - at[199],
+                },
+                Gap {
+                    capture: 1,
+                    offset: 0,
+                    length: at[50] - at[45],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_invalid_parameters() {
+        assert!(merge_captures(&[], 0, 1).is_err());
+        assert!(merge_captures(&[], 720, 8).is_err());
+        assert_eq!(
+            merge_captures(&[&[0u8; 4]], 720, 1).unwrap().sequences,
+            Vec::new()
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Online compression ratio monitoring.
+//!
+//! A [`RatioMonitor`] attached to a [`Compressor`](crate::Compressor) with
+//! [`set_monitor`](crate::Compressor::set_monitor) tracks the compression
+//! ratio over a sliding window of recent packets, either the last N
+//! packets or the packets of the last N seconds/minutes.
+//!
+//! When the windowed ratio falls below a threshold, the observer hook
+//! registered with [`RatioMonitor::on_degraded`] is called once; it is
+//! re-armed when the ratio recovers. A sudden drop is an early warning that
+//! the on-board packet layout changed or the mask has gone stale.
+
+#![allow(clippy::cast_precision_loss)]
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Extent of the sliding window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RatioWindow {
+    /// The last N packets.
+    Packets(usize),
+    /// The packets compressed within the given wall-clock duration.
+    Duration(Duration),
+}
+
+/// Notification passed to the observer hook.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RatioAlert {
+    /// Windowed compression ratio that triggered the alert.
+    pub ratio: f64,
+    /// Configured threshold.
+    pub threshold: f64,
+    /// Number of packets in the window.
+    pub packets: usize,
+}
+
+/// Observer hook called when the ratio degrades.
+pub type RatioObserver = Arc<dyn Fn(&RatioAlert) + Send + Sync>;
+
+/// One compressed packet.
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    at: Instant,
+    input_bits: usize,
+    output_bits: usize,
+}
+
+/// Sliding-window compression ratio monitor.
+#[derive(Clone)]
+pub struct RatioMonitor {
+    window: RatioWindow,
+    threshold: f64,
+    samples: VecDeque<Sample>,
+    input_bits: usize,
+    output_bits: usize,
+    /// Time of the first sample since the last clear.
+    started: Option<Instant>,
+    degraded: bool,
+    observer: Option<RatioObserver>,
+}
+
+impl fmt::Debug for RatioMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RatioMonitor")
+            .field("window", &self.window)
+            .field("threshold", &self.threshold)
+            .field("packets", &self.samples.len())
+            .field("degraded", &self.degraded)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RatioMonitor {
+    /// Create a monitor.
+    ///
+    /// # Arguments
+    /// * `window` - Extent of the sliding window
+    /// * `threshold` - Ratio (input/output size) below which the window is
+    ///   considered degraded
+    pub fn new(window: RatioWindow, threshold: f64) -> Self {
+        Self {
+            window,
+            threshold,
+            samples: VecDeque::new(),
+            input_bits: 0,
+            output_bits: 0,
+            started: None,
+            degraded: false,
+            observer: None,
+        }
+    }
+
+    /// Register the hook called when the ratio falls below the threshold.
+    #[must_use]
+    pub fn on_degraded(mut self, observer: impl Fn(&RatioAlert) + Send + Sync + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Extent of the sliding window.
+    pub fn window(&self) -> RatioWindow {
+        self.window
+    }
+
+    /// Degradation threshold.
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Number of packets currently in the window.
+    pub fn packets(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Compression ratio over the window, `None` before the first packet.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.output_bits > 0).then(|| self.input_bits as f64 / self.output_bits as f64)
+    }
+
+    /// Returns true while the windowed ratio is below the threshold.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    /// Forget all samples and re-arm the hook.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.input_bits = 0;
+        self.output_bits = 0;
+        self.started = None;
+        self.degraded = false;
+    }
+
+    /// Record one packet compressed now.
+    ///
+    /// # Arguments
+    /// * `input_bits` - Uncompressed packet size
+    /// * `output_bits` - Compressed packet size
+    pub fn record(&mut self, input_bits: usize, output_bits: usize) {
+        self.record_at(input_bits, output_bits, Instant::now());
+    }
+
+    /// Record one packet compressed at a given time.
+    ///
+    /// Times must not decrease between calls.
+    pub fn record_at(&mut self, input_bits: usize, output_bits: usize, at: Instant) {
+        let started = *self.started.get_or_insert(at);
+        self.samples.push_back(Sample {
+            at,
+            input_bits,
+            output_bits,
+        });
+        self.input_bits += input_bits;
+        self.output_bits += output_bits;
+
+        // Evict old samples; the ratio is only judged once the window has
+        // filled, so the uncompressed start of a stream raises no alert
+        let full = match self.window {
+            RatioWindow::Packets(n) => {
+                while self.samples.len() > n {
+                    self.evict();
+                }
+                self.samples.len() == n
+            }
+            RatioWindow::Duration(d) => {
+                while self
+                    .samples
+                    .front()
+                    .is_some_and(|s| at.duration_since(s.at) > d)
+                {
+                    self.evict();
+                }
+                at.duration_since(started) >= d
+            }
+        };
+        if !full {
+            return;
+        }
+
+        let ratio = self.ratio().unwrap_or(0.0);
+        if ratio >= self.threshold {
+            self.degraded = false;
+        } else if !self.degraded {
+            self.degraded = true;
+            if let Some(observer) = &self.observer {
+                observer(&RatioAlert {
+                    ratio,
+                    threshold: self.threshold,
+                    packets: self.samples.len(),
+                });
+            }
+        }
+    }
+
+    /// Drop the oldest sample.
+    fn evict(&mut self) {
+        if let Some(sample) = self.samples.pop_front() {
+            self.input_bits -= sample.input_bits;
+            self.output_bits -= sample.output_bits;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_packet_window() {
+        let alerts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&alerts);
+        let mut monitor = RatioMonitor::new(RatioWindow::Packets(8), 2.0).on_degraded(move |a| {
+            assert!(a.ratio < a.threshold);
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // Uncompressed start of the stream is diluted by the window
+        monitor.record(720, 720);
+        monitor.record(720, 720);
+        assert_eq!(monitor.ratio(), Some(1.0));
+        for _ in 0..6 {
+            monitor.record(720, 90);
+        }
+        assert!(!monitor.is_degraded());
+        assert_eq!(alerts.load(Ordering::SeqCst), 0);
+
+        // One alert per degradation
+        for _ in 0..6 {
+            monitor.record(720, 720);
+        }
+        assert!(monitor.is_degraded());
+        assert_eq!(monitor.packets(), 8);
+        assert_eq!(alerts.load(Ordering::SeqCst), 1);
+
+        for _ in 0..8 {
+            monitor.record(720, 90);
+        }
+        for _ in 0..3 {
+            monitor.record(720, 720);
+        }
+        assert_eq!(alerts.load(Ordering::SeqCst), 1);
+        monitor.record(720, 720);
+        assert_eq!(alerts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_duration_window() {
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut monitor = RatioMonitor::new(RatioWindow::Duration(10 * second), 4.0);
+
+        for i in 0..10 {
+            monitor.record_at(720, 90, start + i * second);
+        }
+        assert_eq!(monitor.ratio(), Some(8.0));
+        monitor.record_at(720, 720, start + 10 * second);
+        assert!(!monitor.is_degraded());
+
+        // Only the last 10 seconds count
+        for i in 11..15 {
+            monitor.record_at(720, 720, start + i * second);
+        }
+        assert_eq!(monitor.packets(), 11);
+        assert!(monitor.is_degraded());
+
+        monitor.clear();
+        assert_eq!(monitor.ratio(), None);
+        assert!(!monitor.is_degraded());
+    }
+}
@@ -0,0 +1,437 @@
+//! Multiplexed compression of interleaved streams.
+//!
+//! Spacecraft usually emit several housekeeping structures (one per APID
+//! or subsystem) interleaved on one link. Each structure needs its own
+//! compressor state, since masks only converge on packets of the same
+//! layout. [`MuxCompressor`] keeps one [`Compressor`] per stream ID and
+//! writes every compressed packet as a tagged frame into a single output;
+//! [`MuxDecompressor`] routes the frames back to one [`Decompressor`] per
+//! stream.
+//!
+//! ## Frame Layout (big-endian)
+//!
+//! | Offset | Size | Field                                   |
+//! |--------|------|-----------------------------------------|
+//! | 0      | 2    | Stream ID                               |
+//! | 2      | 2    | Sequence count within the stream (wraps) |
+//! | 4      | 4    | Payload length in bytes                 |
+//! | 8      | n    | One compressed packet                   |
+//!
+//! Frames are written back to back. Sequence counts are kept per stream,
+//! so a lost frame only affects its own stream: gaps of up to R packets
+//! are bridged (see [`Decompressor::mark_lost`]); after a longer gap the
+//! stream's packets are skipped until one carries both the full mask and
+//! the uncompressed packet, while the other streams decode on.
+//!
+//! ```rust
+//! # #[cfg(all(feature = "encoder", feature = "decoder"))]
+//! # {
+//! use pocketplus::mux::{MuxCompressor, MuxDecompressor};
+//! use pocketplus::{Compressor, Decompressor};
+//!
+//! let mut mux = MuxCompressor::new();
+//! mux.add_stream(0x101, Compressor::new(64, None, 1, 10, 20, 50).unwrap()).unwrap();
+//! mux.add_stream(0x102, Compressor::new(128, None, 1, 10, 20, 50).unwrap()).unwrap();
+//!
+//! let mut output = Vec::new();
+//! mux.encode_into(0x101, &[1; 8], &mut output).unwrap();
+//! mux.encode_into(0x102, &[2; 16], &mut output).unwrap();
+//! mux.encode_into(0x101, &[1; 8], &mut output).unwrap();
+//!
+//! let mut demux = MuxDecompressor::new();
+//! demux.add_stream(0x101, Decompressor::new(64, None, 1).unwrap()).unwrap();
+//! demux.add_stream(0x102, Decompressor::new(128, None, 1).unwrap()).unwrap();
+//! let packets = demux.decode_all(&output).unwrap();
+//! assert_eq!(packets.len(), 3);
+//! assert_eq!(packets[1].stream_id, 0x102);
+//! assert_eq!(packets[1].packet.as_deref(), Some(&[2u8; 16][..]));
+//! # }
+//! ```
+
+#![allow(clippy::cast_possible_truncation)]
+
+#[cfg(feature = "decoder")]
+use crate::bitreader::BitReader;
+#[cfg(feature = "encoder")]
+use crate::bitvector::BitVector;
+#[cfg(feature = "encoder")]
+use crate::compress::Compressor;
+#[cfg(feature = "decoder")]
+use crate::decompress::{is_anchor, Decompressor};
+use crate::error::PocketError;
+use std::collections::BTreeMap;
+
+/// Length of the mux frame header.
+pub const MUX_HEADER_LEN: usize = 8;
+
+/// One tagged compressed packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MuxFrame<'a> {
+    /// Stream ID.
+    pub stream_id: u16,
+    /// Sequence count within the stream.
+    pub seq: u16,
+    /// Compressed packet.
+    pub payload: &'a [u8],
+}
+
+impl<'a> MuxFrame<'a> {
+    /// Append the serialized frame to `out`.
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.stream_id.to_be_bytes());
+        out.extend_from_slice(&self.seq.to_be_bytes());
+        out.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(self.payload);
+    }
+
+    /// Parse a frame from the start of a byte slice.
+    ///
+    /// # Arguments
+    /// * `data` - Bytes starting at a frame header
+    ///
+    /// # Returns
+    /// The frame and the total number of bytes it occupies.
+    pub fn parse(data: &'a [u8]) -> Result<(Self, usize), PocketError> {
+        if data.len() < MUX_HEADER_LEN {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        let stream_id = u16::from_be_bytes([data[0], data[1]]);
+        let seq = u16::from_be_bytes([data[2], data[3]]);
+        let len = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let end = MUX_HEADER_LEN + len;
+        if data.len() < end {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        Ok((
+            Self {
+                stream_id,
+                seq,
+                payload: &data[MUX_HEADER_LEN..end],
+            },
+            end,
+        ))
+    }
+}
+
+/// Compressor state of one multiplexed stream.
+#[cfg(feature = "encoder")]
+struct MuxEncoderStream {
+    comp: Compressor,
+    input: BitVector,
+    payload: Vec<u8>,
+    seq: u16,
+}
+
+/// Compressor for several interleaved streams, keyed by stream ID.
+#[cfg(feature = "encoder")]
+#[derive(Default)]
+pub struct MuxCompressor {
+    streams: BTreeMap<u16, MuxEncoderStream>,
+}
+
+#[cfg(feature = "encoder")]
+impl MuxCompressor {
+    /// Create a multiplexer without streams.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a stream.
+    ///
+    /// # Arguments
+    /// * `stream_id` - Tag of the stream's frames, e.g. its APID
+    /// * `comp` - Compressor for the stream; each packet is read as
+    ///   ⌈F/8⌉ bytes
+    pub fn add_stream(&mut self, stream_id: u16, comp: Compressor) -> Result<(), PocketError> {
+        if self.streams.contains_key(&stream_id) {
+            return Err(PocketError::InvalidParameter(format!(
+                "stream {stream_id} already registered"
+            )));
+        }
+        let input = BitVector::new(comp.packet_size());
+        self.streams.insert(
+            stream_id,
+            MuxEncoderStream {
+                comp,
+                input,
+                payload: Vec::new(),
+                seq: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Registered stream IDs, in ascending order.
+    pub fn stream_ids(&self) -> impl Iterator<Item = u16> + '_ {
+        self.streams.keys().copied()
+    }
+
+    /// The compressor of a stream.
+    pub fn compressor(&self, stream_id: u16) -> Option<&Compressor> {
+        self.streams.get(&stream_id).map(|s| &s.comp)
+    }
+
+    /// Compress one packet of a stream and append its frame to `out`.
+    ///
+    /// # Arguments
+    /// * `stream_id` - Registered stream ID
+    /// * `packet` - One uncompressed packet of that stream
+    /// * `out` - Multiplexed output
+    ///
+    /// # Returns
+    /// The number of bytes appended.
+    pub fn encode_into(
+        &mut self,
+        stream_id: u16,
+        packet: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<usize, PocketError> {
+        let stream = self.streams.get_mut(&stream_id).ok_or_else(|| {
+            PocketError::InvalidParameter(format!("stream {stream_id} not registered"))
+        })?;
+        if packet.len() != stream.input.num_bytes() {
+            return Err(PocketError::InvalidInputLength {
+                expected: stream.input.num_bytes(),
+                actual: packet.len(),
+            });
+        }
+
+        stream.input.load_bytes(packet);
+        let params = stream.comp.next_params();
+        stream.payload.clear();
+        stream
+            .comp
+            .compress_packet_into(&stream.input, &params, &mut stream.payload)?;
+
+        let before = out.len();
+        MuxFrame {
+            stream_id,
+            seq: stream.seq,
+            payload: &stream.payload,
+        }
+        .write_to(out);
+        stream.seq = stream.seq.wrapping_add(1);
+        Ok(out.len() - before)
+    }
+
+    /// Compress one packet of a stream into a frame.
+    ///
+    /// # Returns
+    /// The frame bytes.
+    pub fn encode(&mut self, stream_id: u16, packet: &[u8]) -> Result<Vec<u8>, PocketError> {
+        let mut out = Vec::new();
+        self.encode_into(stream_id, packet, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// One packet recovered from a multiplexed stream.
+#[cfg(feature = "decoder")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MuxPacket {
+    /// Stream the packet belongs to.
+    pub stream_id: u16,
+    /// The decompressed packet, or `None` if it was skipped while waiting
+    /// for the stream's decoder state to be restored after a gap longer
+    /// than R.
+    pub packet: Option<Vec<u8>>,
+}
+
+/// Decompressor state of one multiplexed stream.
+#[cfg(feature = "decoder")]
+struct MuxDecoderStream {
+    decomp: Decompressor,
+    /// Expected sequence count, unknown before the first frame.
+    next_seq: Option<u16>,
+    /// Waiting for a packet that restores the whole decoder state.
+    resync: bool,
+    lost: usize,
+}
+
+/// Decompressor for multiplexed streams, routing frames by stream ID.
+#[cfg(feature = "decoder")]
+#[derive(Default)]
+pub struct MuxDecompressor {
+    streams: BTreeMap<u16, MuxDecoderStream>,
+}
+
+#[cfg(feature = "decoder")]
+impl MuxDecompressor {
+    /// Create a demultiplexer without streams.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a stream.
+    ///
+    /// # Arguments
+    /// * `stream_id` - Tag of the stream's frames
+    /// * `decomp` - Decompressor configured for the stream
+    pub fn add_stream(&mut self, stream_id: u16, decomp: Decompressor) -> Result<(), PocketError> {
+        if self.streams.contains_key(&stream_id) {
+            return Err(PocketError::InvalidParameter(format!(
+                "stream {stream_id} already registered"
+            )));
+        }
+        self.streams.insert(
+            stream_id,
+            MuxDecoderStream {
+                decomp,
+                next_seq: None,
+                resync: false,
+                lost: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// The decompressor of a stream.
+    pub fn decompressor(&self, stream_id: u16) -> Option<&Decompressor> {
+        self.streams.get(&stream_id).map(|s| &s.decomp)
+    }
+
+    /// Number of packets of a stream missing from its sequence counts.
+    pub fn lost(&self, stream_id: u16) -> usize {
+        self.streams.get(&stream_id).map_or(0, |s| s.lost)
+    }
+
+    /// Decode the frame at the start of `data`.
+    ///
+    /// # Returns
+    /// The recovered packet and the number of bytes the frame occupies.
+    /// Frames of unregistered streams are rejected.
+    pub fn decode(&mut self, data: &[u8]) -> Result<(MuxPacket, usize), PocketError> {
+        let (frame, len) = MuxFrame::parse(data)?;
+        let stream = self.streams.get_mut(&frame.stream_id).ok_or_else(|| {
+            PocketError::InvalidFormat(format!("frame of unknown stream {}", frame.stream_id))
+        })?;
+
+        if let Some(expected) = stream.next_seq {
+            let gap = usize::from(frame.seq.wrapping_sub(expected));
+            if gap > 0 {
+                stream.lost += gap;
+                if !stream.resync && stream.decomp.mark_lost(gap).is_err() {
+                    stream.resync = true;
+                }
+            }
+        }
+        stream.next_seq = Some(frame.seq.wrapping_add(1));
+
+        let mut reader = BitReader::new(frame.payload, frame.payload.len() * 8);
+        let packet = if stream.resync && !is_anchor(reader.clone(), stream.decomp.packet_size()) {
+            None
+        } else {
+            if stream.resync {
+                stream.decomp.resync_state();
+                stream.resync = false;
+            }
+            Some(stream.decomp.decompress_packet(&mut reader)?.to_bytes())
+        };
+
+        Ok((
+            MuxPacket {
+                stream_id: frame.stream_id,
+                packet,
+            },
+            len,
+        ))
+    }
+
+    /// Decode every frame of a multiplexed output.
+    ///
+    /// # Returns
+    /// The packets in frame order.
+    pub fn decode_all(&mut self, mut data: &[u8]) -> Result<Vec<MuxPacket>, PocketError> {
+        let mut packets = Vec::new();
+        while !data.is_empty() {
+            let (packet, len) = self.decode(data)?;
+            packets.push(packet);
+            data = &data[len..];
+        }
+        Ok(packets)
+    }
+}
+
+#[cfg(all(test, feature = "encoder", feature = "decoder"))]
+mod tests {
+    use super::*;
+
+    fn packet(stream: u16, i: usize, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|b| u8::try_from((usize::from(stream) * 7 + b + i / 5) % 256).unwrap())
+            .collect()
+    }
+
+    fn streams() -> (MuxCompressor, MuxDecompressor) {
+        let mut mux = MuxCompressor::new();
+        let mut demux = MuxDecompressor::new();
+        for (id, bytes) in [(1u16, 8usize), (2, 16), (3, 90)] {
+            let comp = Compressor::new(bytes * 8, None, 1, 10, 20, 5).unwrap();
+            mux.add_stream(id, comp).unwrap();
+            let decomp = Decompressor::new(bytes * 8, None, 1).unwrap();
+            demux.add_stream(id, decomp).unwrap();
+        }
+        (mux, demux)
+    }
+
+    #[test]
+    fn test_mux_round_trip() {
+        let (mut mux, mut demux) = streams();
+        assert!(mux
+            .add_stream(1, Compressor::new(8, None, 1, 10, 20, 50).unwrap())
+            .is_err());
+        assert!(mux.encode(9, &[0]).is_err());
+        assert!(mux.encode(1, &[0; 7]).is_err());
+
+        let mut output = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..60 {
+            // Irregular interleaving
+            let id = [1, 2, 1, 3, 2, 1][i % 6];
+            let len = [0, 8, 16, 90][usize::from(id)];
+            let raw = packet(id, i, len);
+            mux.encode_into(id, &raw, &mut output).unwrap();
+            expected.push((id, raw));
+        }
+        assert_eq!(mux.stream_ids().collect::<Vec<_>>(), [1, 2, 3]);
+
+        let packets = demux.decode_all(&output).unwrap();
+        assert_eq!(packets.len(), expected.len());
+        for (packet, (id, raw)) in packets.iter().zip(&expected) {
+            assert_eq!(packet.stream_id, *id);
+            assert_eq!(packet.packet.as_ref(), Some(raw));
+        }
+        assert!(demux.decode(&[0, 9, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_mux_loss_is_per_stream() {
+        let (mut mux, mut demux) = streams();
+        let mut frames = Vec::new();
+        for i in 0..40 {
+            for id in [1u16, 3] {
+                let len = if id == 1 { 8 } else { 90 };
+                frames.push((id, i, mux.encode(id, &packet(id, i, len)).unwrap()));
+            }
+        }
+
+        // Lose stream 3 packets 10-13 (more than R = 1)
+        let mut skipped = 0;
+        for (id, i, frame) in &frames {
+            if *id == 3 && (10..14).contains(i) {
+                continue;
+            }
+            let (decoded, _) = demux.decode(frame).unwrap();
+            if let Some(bytes) = decoded.packet {
+                let len = if *id == 1 { 8 } else { 90 };
+                assert_eq!(bytes, packet(*id, *i, len));
+            } else {
+                assert_eq!(*id, 3);
+                skipped += 1;
+            }
+        }
+        assert!(skipped > 0 && skipped < 26);
+        assert_eq!(demux.lost(3), 4);
+        assert_eq!(demux.lost(1), 0);
+    }
+}
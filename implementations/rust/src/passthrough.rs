@@ -0,0 +1,283 @@
+//! Timestamp passthrough wrapper.
+//!
+//! Most housekeeping packets start with a time field that changes every
+//! packet. POCKET+ would learn these bits into the mask and send them as
+//! unpredictable bits anyway, while mask renewals around them cost extra
+//! flags and mask updates. In passthrough mode the first K bits of every
+//! packet are carried verbatim, outside the compressed payload, and only
+//! the remaining F − K bits go through POCKET+.
+//!
+//! ## Stream Layout
+//!
+//! For every packet: the K time bits, padded with zeros to ⌈K/8⌉ bytes,
+//! followed by the compressed packet of the remaining bits (byte-aligned).
+//!
+//! ```rust
+//! # #[cfg(all(feature = "encoder", feature = "decoder"))]
+//! # {
+//! use pocketplus::passthrough::{compress_passthrough, decompress_passthrough};
+//!
+//! // 4-byte time field followed by 8 bytes of slowly changing data
+//! let data: Vec<u8> = (0u32..100)
+//!     .flat_map(|t| {
+//!         let mut packet = (1000 + t).to_be_bytes().to_vec();
+//!         packet.extend_from_slice(&[0xA5, 0x5A, 0, 0, 0, 0, (t / 10) as u8, 0]);
+//!         packet
+//!     })
+//!     .collect();
+//!
+//! let stream = compress_passthrough(&data, 96, 32, 1, 10, 20, 50).unwrap();
+//! assert_eq!(&stream[..4], &1000u32.to_be_bytes());
+//! assert_eq!(decompress_passthrough(&stream, 96, 32, 1).unwrap(), data);
+//! # }
+//! ```
+
+#[cfg(feature = "decoder")]
+use crate::bitreader::BitReader;
+use crate::bitvector::BitVector;
+#[cfg(feature = "encoder")]
+use crate::compress::Compressor;
+#[cfg(feature = "decoder")]
+use crate::decompress::DecompressorBuilder;
+use crate::error::PocketError;
+
+/// Check that the time field leaves at least one bit to compress.
+fn check_time_bits(packet_size: usize, time_bits: usize) -> Result<(), PocketError> {
+    if time_bits == 0 || time_bits >= packet_size {
+        return Err(PocketError::InvalidParameter(format!(
+            "time field of {time_bits} bits must be shorter than the {packet_size}-bit packet"
+        )));
+    }
+    Ok(())
+}
+
+/// Compressor carrying the time field of every packet verbatim.
+#[cfg(feature = "encoder")]
+pub struct PassthroughCompressor {
+    comp: Compressor,
+    time_bits: usize,
+    packet: BitVector,
+    rest: BitVector,
+}
+
+#[cfg(feature = "encoder")]
+impl PassthroughCompressor {
+    /// Create a passthrough compressor.
+    ///
+    /// # Arguments
+    /// * `comp` - Compressor for the bits after the time field, so its
+    ///   packet size is F − K
+    /// * `time_bits` - Length K of the time field at the start of each packet
+    pub fn new(comp: Compressor, time_bits: usize) -> Result<Self, PocketError> {
+        let packet_size = time_bits + comp.packet_size();
+        check_time_bits(packet_size, time_bits)?;
+        let rest = BitVector::new(comp.packet_size());
+        Ok(Self {
+            comp,
+            time_bits,
+            packet: BitVector::new(packet_size),
+            rest,
+        })
+    }
+
+    /// Full packet size F in bits, time field included.
+    pub fn packet_size(&self) -> usize {
+        self.packet.len()
+    }
+
+    /// Length K of the time field in bits.
+    pub fn time_bits(&self) -> usize {
+        self.time_bits
+    }
+
+    /// The compressor of the bits after the time field.
+    pub fn compressor(&self) -> &Compressor {
+        &self.comp
+    }
+
+    /// Compress one packet and append it to `out`.
+    ///
+    /// # Arguments
+    /// * `packet` - One uncompressed packet of ⌈F/8⌉ bytes
+    /// * `out` - Destination for the time field and compressed packet
+    ///
+    /// # Returns
+    /// The number of bytes appended.
+    pub fn compress_packet_into(
+        &mut self,
+        packet: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<usize, PocketError> {
+        if packet.len() != self.packet.num_bytes() {
+            return Err(PocketError::InvalidInputLength {
+                expected: self.packet.num_bytes(),
+                actual: packet.len(),
+            });
+        }
+        self.packet.load_bytes(packet);
+        for i in 0..self.rest.len() {
+            self.rest
+                .set_bit(i, self.packet.get_bit(self.time_bits + i));
+        }
+
+        let before = out.len();
+        out.extend_from_slice(&packet[..(self.time_bits + 7) / 8]);
+        if self.time_bits % 8 != 0 {
+            // Drop the bits after the time field from its last byte
+            *out.last_mut().unwrap_or(&mut 0) &= 0xFF << (8 - self.time_bits % 8);
+        }
+        let params = self.comp.next_params();
+        if let Err(e) = self.comp.compress_packet_into(&self.rest, &params, out) {
+            out.truncate(before);
+            return Err(e);
+        }
+        Ok(out.len() - before)
+    }
+}
+
+/// Compress multiple packets, passing the time field of each through.
+///
+/// # Arguments
+/// * `data` - Input data (whole packets)
+/// * `packet_size` - Packet size F in bits, time field included
+/// * `time_bits` - Length K of the time field at the start of each packet
+/// * `robustness`, `pt_limit`, `ft_limit`, `rt_limit` - As for
+///   [`compress`](crate::compress)
+///
+/// # Returns
+/// The passthrough stream.
+#[cfg(feature = "encoder")]
+pub fn compress_passthrough(
+    data: &[u8],
+    packet_size: usize,
+    time_bits: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> Result<Vec<u8>, PocketError> {
+    check_time_bits(packet_size, time_bits)?;
+    let robustness =
+        u8::try_from(robustness).map_err(|_| PocketError::InvalidRobustness(robustness))?;
+    let comp = Compressor::new(
+        packet_size - time_bits,
+        None,
+        robustness,
+        pt_limit,
+        ft_limit,
+        rt_limit,
+    )?;
+    let mut passthrough = PassthroughCompressor::new(comp, time_bits)?;
+
+    let packet_bytes = (packet_size + 7) / 8;
+    if data.len() % packet_bytes != 0 {
+        return Err(PocketError::InvalidInputLength {
+            expected: (data.len() / packet_bytes + 1) * packet_bytes,
+            actual: data.len(),
+        });
+    }
+    let mut out = Vec::new();
+    for packet in data.chunks_exact(packet_bytes) {
+        passthrough.compress_packet_into(packet, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Decompress a passthrough stream, reattaching the time fields.
+///
+/// # Arguments
+/// * `data` - Stream written by [`compress_passthrough`] or
+///   [`PassthroughCompressor`]
+/// * `packet_size` - Packet size F in bits, time field included
+/// * `time_bits` - Length K of the time field
+/// * `robustness` - Robustness level R used for compression
+///
+/// # Returns
+/// The packets, concatenated.
+#[cfg(feature = "decoder")]
+pub fn decompress_passthrough(
+    data: &[u8],
+    packet_size: usize,
+    time_bits: usize,
+    robustness: usize,
+) -> Result<Vec<u8>, PocketError> {
+    check_time_bits(packet_size, time_bits)?;
+    let mut decomp = DecompressorBuilder::new(packet_size - time_bits)
+        .robustness(robustness)
+        .build()?;
+    let time_bytes = (time_bits + 7) / 8;
+    let mut packet = BitVector::new(packet_size);
+    let mut output = Vec::new();
+    let mut reader = BitReader::new(data, data.len() * 8);
+
+    while reader.has_bits() {
+        let start = reader.position() / 8;
+        let time = data
+            .get(start..start + time_bytes)
+            .ok_or(PocketError::UnexpectedEndOfInput)?;
+        reader.skip(time_bytes * 8)?;
+        let rest = decomp.decompress_packet(&mut reader)?;
+        reader.align_byte();
+
+        packet.load_bytes(&vec![0; packet.num_bytes()]);
+        let time = BitVector::from_bytes(time, time_bits);
+        for i in 0..time_bits {
+            packet.set_bit(i, time.get_bit(i));
+        }
+        for i in 0..rest.len() {
+            packet.set_bit(time_bits + i, rest.get_bit(i));
+        }
+        output.extend_from_slice(&packet.to_bytes());
+    }
+    Ok(output)
+}
+
+#[cfg(all(test, feature = "encoder", feature = "decoder"))]
+mod tests {
+    use super::*;
+
+    /// Packets of `bytes` bytes starting with a 32-bit time field, with
+    /// one slowly changing byte.
+    fn timed(bytes: usize, count: u32) -> Vec<u8> {
+        (0..count)
+            .flat_map(|t| {
+                let mut packet = (0x1234_0000 + t * 7).to_be_bytes().to_vec();
+                packet.extend((4..bytes).map(|i| u8::try_from(i % 5).unwrap()));
+                packet[10] = u8::try_from(t / 16).unwrap();
+                packet
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_passthrough_round_trip() {
+        let data = timed(90, 200);
+        let stream = compress_passthrough(&data, 720, 32, 1, 10, 20, 50).unwrap();
+        assert_eq!(&stream[..4], &data[..4]);
+        assert_eq!(decompress_passthrough(&stream, 720, 32, 1).unwrap(), data);
+
+        // A time field not ending on a byte boundary
+        let stream = compress_passthrough(&data, 720, 27, 2, 10, 20, 50).unwrap();
+        assert_eq!(decompress_passthrough(&stream, 720, 27, 2).unwrap(), data);
+
+        assert!(compress_passthrough(&data, 720, 0, 1, 10, 20, 50).is_err());
+        assert!(compress_passthrough(&data, 720, 720, 1, 10, 20, 50).is_err());
+        assert!(decompress_passthrough(&stream[..stream.len() - 1], 720, 27, 2).is_err());
+    }
+
+    #[test]
+    fn test_passthrough_keeps_time_out_of_mask() {
+        let data = timed(90, 200);
+        let comp = Compressor::new(720 - 32, None, 1, 10, 20, 50).unwrap();
+        let mut passthrough = PassthroughCompressor::new(comp, 32).unwrap();
+        assert_eq!(passthrough.packet_size(), 720);
+        let mut out = Vec::new();
+        for packet in data.chunks(90) {
+            passthrough.compress_packet_into(packet, &mut out).unwrap();
+        }
+        assert!(passthrough.compressor().mask().hamming_weight() < 32);
+        assert!(passthrough
+            .compress_packet_into(&[0; 89], &mut out)
+            .is_err());
+    }
+}
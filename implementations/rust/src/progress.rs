@@ -0,0 +1,71 @@
+//! Progress reporting for batch jobs.
+//!
+//! [`compress_with_progress`](crate::compress_with_progress) and
+//! [`decompress_with_progress`](crate::decompress_with_progress) pass a
+//! [`ProgressInfo`] to a caller-supplied callback every N packets, so
+//! multi-minute jobs can drive a progress display.
+
+#![allow(clippy::cast_precision_loss)]
+
+/// Snapshot of a running batch job.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProgressInfo {
+    /// Packets processed so far.
+    pub packets: usize,
+    /// Bytes of input consumed so far.
+    pub bytes_read: usize,
+    /// Bytes of output produced so far.
+    pub bytes_written: usize,
+    /// Compression ratio so far (uncompressed / compressed size).
+    pub ratio: f64,
+}
+
+impl ProgressInfo {
+    /// Create from byte counts.
+    ///
+    /// # Arguments
+    /// * `packets` - Packets processed
+    /// * `uncompressed` - Uncompressed bytes processed
+    /// * `compressed` - Compressed bytes processed
+    /// * `compressing` - True if the job reads uncompressed data
+    pub(crate) fn new(
+        packets: usize,
+        uncompressed: usize,
+        compressed: usize,
+        compressing: bool,
+    ) -> Self {
+        let (bytes_read, bytes_written) = if compressing {
+            (uncompressed, compressed)
+        } else {
+            (compressed, uncompressed)
+        };
+        Self {
+            packets,
+            bytes_read,
+            bytes_written,
+            ratio: if compressed == 0 {
+                0.0
+            } else {
+                uncompressed as f64 / compressed as f64
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_info_direction() {
+        let comp = ProgressInfo::new(4, 360, 90, true);
+        assert_eq!((comp.bytes_read, comp.bytes_written), (360, 90));
+        assert!((comp.ratio - 4.0).abs() < f64::EPSILON);
+
+        let decomp = ProgressInfo::new(4, 360, 90, false);
+        assert_eq!((decomp.bytes_read, decomp.bytes_written), (90, 360));
+        assert!((decomp.ratio - 4.0).abs() < f64::EPSILON);
+
+        assert!(ProgressInfo::new(0, 0, 0, true).ratio.abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,324 @@
+//! Python extension module (`python` feature).
+//!
+//! `PyO3` bindings so analysis scripts can call the codec in-process instead
+//! of running the CLI once per file. The module is named `pocketplus_rs`,
+//! leaving `pocketplus` to the pure-Python implementation. Inputs accept
+//! any object exporting a byte buffer (`bytes`, `bytearray`, `memoryview`,
+//! `NumPy` `uint8` arrays); outputs are `bytes`. Packet sizes are in bits and
+//! errors raise `pocketplus_rs.PocketError`, a `ValueError` subclass.
+//!
+//! ```text
+//! pip install maturin
+//! maturin develop --release      # uses pyproject.toml
+//! ```
+//!
+//! ```python
+//! import numpy as np
+//! import pocketplus_rs
+//!
+//! packets = np.fromfile("housekeeping.bin", dtype=np.uint8)
+//! stream = pocketplus_rs.compress(packets, 720, robustness=1)
+//! restored = np.frombuffer(pocketplus_rs.decompress(stream, 720), dtype=np.uint8)
+//!
+//! decoder = pocketplus_rs.StreamDecompressor(720)
+//! with open("downlink.pkt", "rb") as f:
+//!     while chunk := f.read(4096):
+//!         frames = np.frombuffer(decoder.push(chunk), dtype=np.uint8).reshape(-1, 90)
+//! decoder.finish()
+//! ```
+
+#![allow(clippy::needless_pass_by_value)]
+
+use crate::bitvector::{BitVector, MAX_PACKET_LENGTH};
+use crate::compress::Compressor as RustCompressor;
+use crate::decompress::Decompressor;
+use crate::error::PocketError as RustPocketError;
+use pyo3::buffer::PyBuffer;
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+create_exception!(
+    pocketplus_rs,
+    PocketError,
+    PyValueError,
+    "Invalid parameters or malformed compressed data."
+);
+
+/// Convert a codec error into a Python exception.
+fn py_error(err: RustPocketError) -> PyErr {
+    PocketError::new_err(err.to_string())
+}
+
+/// Copy the bytes of any object exporting a byte buffer.
+fn buffer_bytes(data: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    if let Ok(bytes) = data.downcast::<PyBytes>() {
+        return Ok(bytes.as_bytes().to_vec());
+    }
+    PyBuffer::<u8>::get(data)?.to_vec(data.py())
+}
+
+/// Validate a streaming packet size: byte-aligned packets only.
+fn check_packet_size(packet_size: usize) -> PyResult<()> {
+    if packet_size == 0 || packet_size > MAX_PACKET_LENGTH || packet_size % 8 != 0 {
+        return Err(py_error(RustPocketError::InvalidPacketSize(packet_size)));
+    }
+    Ok(())
+}
+
+/// Validate a robustness level.
+fn robustness_u8(robustness: usize) -> PyResult<u8> {
+    u8::try_from(robustness).map_err(|_| py_error(RustPocketError::InvalidRobustness(robustness)))
+}
+
+/// Compress whole packets.
+///
+/// # Arguments
+/// * `data` - Raw packets (a multiple of `⌈packet_size / 8⌉` bytes)
+/// * `packet_size` - Packet size in bits
+/// * `robustness` - Robustness level R (0-7)
+/// * `pt_limit`, `ft_limit`, `rt_limit` - Flag periods (0 = never)
+#[pyfunction]
+#[pyo3(signature = (data, packet_size, robustness = 1, pt_limit = 10, ft_limit = 20, rt_limit = 50))]
+fn compress<'py>(
+    data: &Bound<'py, PyAny>,
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let py = data.py();
+    let input = buffer_bytes(data)?;
+    let output = py
+        .allow_threads(|| {
+            crate::compress(
+                &input,
+                packet_size,
+                robustness,
+                pt_limit,
+                ft_limit,
+                rt_limit,
+            )
+        })
+        .map_err(py_error)?;
+    Ok(PyBytes::new(py, &output))
+}
+
+/// Decompress a stream.
+///
+/// # Arguments
+/// * `data` - Compressed stream
+/// * `packet_size` - Packet size in bits
+/// * `robustness` - Robustness level R used for compression
+#[pyfunction]
+#[pyo3(signature = (data, packet_size, robustness = 1))]
+fn decompress<'py>(
+    data: &Bound<'py, PyAny>,
+    packet_size: usize,
+    robustness: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let py = data.py();
+    let input = buffer_bytes(data)?;
+    let output = py
+        .allow_threads(|| crate::decompress(&input, packet_size, robustness))
+        .map_err(py_error)?;
+    Ok(PyBytes::new(py, &output))
+}
+
+/// Compressor fed one packet at a time, with the automatic pt/ft/rt
+/// schedule. Each compressed packet is padded to a byte boundary.
+#[pyclass(module = "pocketplus_rs")]
+pub struct Compressor {
+    comp: RustCompressor,
+    input: BitVector,
+    output: Vec<u8>,
+}
+
+#[pymethods]
+impl Compressor {
+    /// Create a compressor.
+    ///
+    /// # Arguments
+    /// * `packet_size` - Packet size in bits (multiple of 8)
+    /// * `robustness` - Robustness level R (0-7)
+    /// * `pt_limit`, `ft_limit`, `rt_limit` - Flag periods (0 = never)
+    #[new]
+    #[pyo3(signature = (packet_size, robustness = 1, pt_limit = 10, ft_limit = 20, rt_limit = 50))]
+    fn new(
+        packet_size: usize,
+        robustness: usize,
+        pt_limit: usize,
+        ft_limit: usize,
+        rt_limit: usize,
+    ) -> PyResult<Self> {
+        check_packet_size(packet_size)?;
+        let comp = RustCompressor::new(
+            packet_size,
+            None,
+            robustness_u8(robustness)?,
+            pt_limit,
+            ft_limit,
+            rt_limit,
+        )
+        .map_err(py_error)?;
+        Ok(Self {
+            comp,
+            input: BitVector::new(packet_size),
+            output: Vec::new(),
+        })
+    }
+
+    /// Compress one packet of `packet_size / 8` bytes.
+    fn compress_packet<'py>(
+        &mut self,
+        packet: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let data = buffer_bytes(packet)?;
+        if data.len() != self.input.num_bytes() {
+            return Err(py_error(RustPocketError::InvalidInputLength {
+                expected: self.input.num_bytes(),
+                actual: data.len(),
+            }));
+        }
+        self.input.load_bytes(&data);
+        let params = self.comp.next_params();
+        self.output.clear();
+        self.comp
+            .compress_packet_into(&self.input, &params, &mut self.output)
+            .map_err(py_error)?;
+        Ok(PyBytes::new(packet.py(), &self.output))
+    }
+}
+
+/// Decompressor fed with arbitrary chunks of a byte-aligned stream.
+///
+/// Each `push` returns the packets completed by the chunk, concatenated;
+/// bytes of an incomplete packet are kept until the next chunk.
+#[pyclass(module = "pocketplus_rs")]
+pub struct StreamDecompressor {
+    decomp: Decompressor,
+    pending: Vec<u8>,
+    packets: usize,
+}
+
+#[pymethods]
+impl StreamDecompressor {
+    /// Create a decompressor.
+    ///
+    /// # Arguments
+    /// * `packet_size` - Packet size in bits (multiple of 8)
+    /// * `robustness` - Robustness level R used for compression
+    #[new]
+    #[pyo3(signature = (packet_size, robustness = 1))]
+    fn new(packet_size: usize, robustness: usize) -> PyResult<Self> {
+        check_packet_size(packet_size)?;
+        let decomp =
+            Decompressor::new(packet_size, None, robustness_u8(robustness)?).map_err(py_error)?;
+        Ok(Self {
+            decomp,
+            pending: Vec::new(),
+            packets: 0,
+        })
+    }
+
+    /// Append a chunk and decode every packet it completes.
+    fn push<'py>(&mut self, chunk: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyBytes>> {
+        self.pending.extend(buffer_bytes(chunk)?);
+        let mut output = Vec::new();
+        let consumed = self
+            .decomp
+            .decompress_available(&self.pending, &mut output)
+            .map_err(py_error)?;
+        self.pending.drain(..consumed);
+        self.packets += output.len() / (self.decomp.packet_size() / 8);
+        Ok(PyBytes::new(chunk.py(), &output))
+    }
+
+    /// Report `count` (at most R) lost packets before the next one.
+    fn notify_packet_loss(&mut self, count: usize) -> PyResult<()> {
+        if !self.pending.is_empty() {
+            return Err(py_error(RustPocketError::InvalidParameter(
+                "packet loss reported in the middle of a packet".into(),
+            )));
+        }
+        self.decomp.mark_lost(count).map_err(py_error)
+    }
+
+    /// Check that the stream ended on a packet boundary.
+    fn finish(&self) -> PyResult<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            Err(py_error(RustPocketError::UnexpectedEndOfInput))
+        }
+    }
+
+    /// Number of packets decoded so far.
+    #[getter]
+    fn packets(&self) -> usize {
+        self.packets
+    }
+
+    /// Bytes of an incomplete packet waiting for the next chunk.
+    #[getter]
+    fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// The `pocketplus_rs` extension module.
+#[pymodule]
+fn pocketplus_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("PocketError", m.py().get_type::<PocketError>())?;
+    m.add_function(wrap_pyfunction!(compress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_class::<Compressor>()?;
+    m.add_class::<StreamDecompressor>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_module() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "pocketplus_rs").unwrap();
+            pocketplus_rs(&module).unwrap();
+            let locals = pyo3::types::PyDict::new(py);
+            locals.set_item("pp", module).unwrap();
+            py.run(
+                pyo3::ffi::c_str!(
+                    r#"
+data = bytes((i % 90 // 3 + i // 900) % 256 for i in range(90 * 40))
+stream = pp.compress(bytearray(data), 720, robustness=1)
+assert pp.decompress(memoryview(stream), 720) == data
+
+comp = pp.Compressor(720)
+packets = b"".join(comp.compress_packet(data[i:i + 90]) for i in range(0, len(data), 90))
+assert packets == stream
+
+decoder = pp.StreamDecompressor(720)
+out = b"".join(decoder.push(stream[i:i + 7]) for i in range(0, len(stream), 7))
+decoder.finish()
+assert out == data and decoder.packets == 40 and decoder.pending == 0
+
+try:
+    pp.decompress(b"\xff", 720)
+    raise AssertionError("no error")
+except ValueError as e:
+    assert isinstance(e, pp.PocketError)
+"#
+                ),
+                Some(&locals),
+                None,
+            )
+            .unwrap();
+        });
+    }
+}
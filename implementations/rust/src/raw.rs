@@ -0,0 +1,350 @@
+//! Section 5.2 primitives on plain byte slices.
+//!
+//! The functions in this module implement COUNT, RLE and BE (and their
+//! inverses) directly on `&[u8]` / `&mut Vec<u8>` with explicit bit
+//! offsets, for embedding individual primitives in a custom packet encoder
+//! without constructing a [`BitVector`](crate::BitVector) or
+//! [`BitBuffer`](crate::BitBuffer).
+//!
+//! Bit numbering follows the rest of the crate: bit 0 is the MSB of the
+//! first byte. Writers overwrite the bits from `bit_pos` onwards, grow the
+//! output with zero bytes as needed and return the bit position after the
+//! last bit written. Readers return the decoded value together with the
+//! bit position after the last bit read.
+//!
+//! The output is bit-identical to [`count_encode`](crate::count_encode),
+//! [`rle_encode`](crate::rle_encode) and [`bit_extract`](crate::bit_extract).
+
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::error::PocketError;
+
+/// Read bit `pos` of `data` (MSB-first).
+#[inline]
+fn get_bit(data: &[u8], pos: usize) -> u8 {
+    (data[pos >> 3] >> (7 - (pos & 7))) & 1
+}
+
+/// Set bit `pos` of `data` (MSB-first) to `bit`.
+#[inline]
+fn set_bit(data: &mut [u8], pos: usize, bit: u8) {
+    let shift = 7 - (pos & 7);
+    data[pos >> 3] = (data[pos >> 3] & !(1 << shift)) | (bit << shift);
+}
+
+/// Check that `data` holds at least `bits` bits.
+fn check_len(data: &[u8], bits: usize) -> Result<(), PocketError> {
+    let expected = (bits + 7) / 8;
+    if data.len() < expected {
+        return Err(PocketError::InvalidInputLength {
+            expected,
+            actual: data.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Write the low `num_bits` bits of `value` (MSB first) at `bit_pos`.
+#[cfg(feature = "encoder")]
+fn write_bits(out: &mut Vec<u8>, bit_pos: usize, value: u32, num_bits: usize) -> usize {
+    let end = bit_pos + num_bits;
+    if out.len() * 8 < end {
+        out.resize((end + 7) / 8, 0);
+    }
+    for i in 0..num_bits {
+        set_bit(out, bit_pos + i, ((value >> (num_bits - 1 - i)) & 1) as u8);
+    }
+    end
+}
+
+/// Read `num_bits` bits (MSB first) at `*bit_pos`, advancing it.
+#[cfg(feature = "decoder")]
+fn read_bits(data: &[u8], bit_pos: &mut usize, num_bits: usize) -> Result<u32, PocketError> {
+    if *bit_pos + num_bits > data.len() * 8 {
+        return Err(PocketError::Underflow);
+    }
+    let mut value = 0;
+    for _ in 0..num_bits {
+        value = (value << 1) | u32::from(get_bit(data, *bit_pos));
+        *bit_pos += 1;
+    }
+    Ok(value)
+}
+
+/// Counter Encoding (COUNT) - CCSDS Section 5.2.2.
+///
+/// # Arguments
+/// * `out` - Output bytes
+/// * `bit_pos` - Bit position to write at
+/// * `a` - Value to encode (1-65535)
+///
+/// # Returns
+/// Bit position after the encoded value.
+#[cfg(feature = "encoder")]
+pub fn count_encode(out: &mut Vec<u8>, bit_pos: usize, a: u32) -> Result<usize, PocketError> {
+    match a {
+        1 => Ok(write_bits(out, bit_pos, 0, 1)),
+        2..=33 => Ok(write_bits(out, bit_pos, 0b110_00000 | (a - 2), 8)),
+        34..=65535 => {
+            let value = a - 2;
+            let e = 2 * (32 - value.leading_zeros() as usize) - 6;
+            let pos = write_bits(out, bit_pos, 0b111, 3);
+            Ok(write_bits(out, pos, value, e))
+        }
+        _ => Err(PocketError::InvalidFormat(
+            "COUNT value out of range".into(),
+        )),
+    }
+}
+
+/// Counter Decoding - inverse of [`count_encode`].
+///
+/// # Arguments
+/// * `data` - Encoded bytes
+/// * `bit_pos` - Bit position to read from
+///
+/// # Returns
+/// The decoded value (0 for the RLE terminator '10') and the bit position
+/// after it.
+#[cfg(feature = "decoder")]
+pub fn count_decode(data: &[u8], bit_pos: usize) -> Result<(u32, usize), PocketError> {
+    let mut pos = bit_pos;
+    if read_bits(data, &mut pos, 1)? == 0 {
+        return Ok((1, pos));
+    }
+    if read_bits(data, &mut pos, 1)? == 0 {
+        return Ok((0, pos));
+    }
+    if read_bits(data, &mut pos, 1)? == 0 {
+        return Ok((read_bits(data, &mut pos, 5)? + 2, pos));
+    }
+
+    // '111': leading zeros of the value field are skipped; the value from
+    // its first '1' onwards is 5 bits longer than the zero run
+    let start = pos;
+    while read_bits(data, &mut pos, 1)? == 0 {}
+    let value_bits = pos - start + 5;
+    if value_bits > 32 {
+        return Err(PocketError::InvalidFormat("COUNT value too long".into()));
+    }
+    pos -= 1;
+    Ok((read_bits(data, &mut pos, value_bits)? + 2, pos))
+}
+
+/// Run-Length Encoding (RLE) - CCSDS Section 5.2.3.
+///
+/// # Arguments
+/// * `out` - Output bytes
+/// * `bit_pos` - Bit position to write at
+/// * `input` - Vector to encode
+/// * `input_bits` - Length of the vector in bits
+///
+/// # Returns
+/// Bit position after the terminator.
+#[cfg(feature = "encoder")]
+pub fn rle_encode(
+    out: &mut Vec<u8>,
+    bit_pos: usize,
+    input: &[u8],
+    input_bits: usize,
+) -> Result<usize, PocketError> {
+    check_len(input, input_bits)?;
+
+    let mut pos = bit_pos;
+    let mut previous = input_bits;
+    for i in (0..input_bits).rev() {
+        if get_bit(input, i) == 1 {
+            let delta = u32::try_from(previous - i).map_err(|_| PocketError::BufferOverflow)?;
+            pos = count_encode(out, pos, delta)?;
+            previous = i;
+        }
+    }
+    Ok(write_bits(out, pos, 0b10, 2))
+}
+
+/// Run-Length Decoding - inverse of [`rle_encode`].
+///
+/// # Arguments
+/// * `data` - Encoded bytes
+/// * `bit_pos` - Bit position to read from
+/// * `length` - Length of the decoded vector in bits
+///
+/// # Returns
+/// The decoded vector (`length` bits, zero-padded to whole bytes) and the
+/// bit position after the terminator.
+#[cfg(feature = "decoder")]
+pub fn rle_decode(
+    data: &[u8],
+    bit_pos: usize,
+    length: usize,
+) -> Result<(Vec<u8>, usize), PocketError> {
+    let mut result = vec![0; (length + 7) / 8];
+    let mut position = length;
+    let mut pos = bit_pos;
+    loop {
+        let (delta, next) = count_decode(data, pos)?;
+        pos = next;
+        if delta == 0 {
+            return Ok((result, pos));
+        }
+        if (delta as usize) <= position {
+            position -= delta as usize;
+            set_bit(&mut result, position, 1);
+        }
+    }
+}
+
+/// Bit Extraction (BE) - CCSDS Section 5.2.4.
+///
+/// Writes the bits of `data` at the '1' positions of `mask`, highest
+/// position first.
+///
+/// # Arguments
+/// * `out` - Output bytes
+/// * `bit_pos` - Bit position to write at
+/// * `data` - Source vector
+/// * `mask` - Extraction mask
+/// * `bits` - Length of both vectors in bits
+///
+/// # Returns
+/// Bit position after the extracted bits.
+#[cfg(feature = "encoder")]
+pub fn bit_extract(
+    out: &mut Vec<u8>,
+    bit_pos: usize,
+    data: &[u8],
+    mask: &[u8],
+    bits: usize,
+) -> Result<usize, PocketError> {
+    check_len(data, bits)?;
+    check_len(mask, bits)?;
+
+    let mut pos = bit_pos;
+    for i in (0..bits).rev() {
+        if get_bit(mask, i) == 1 {
+            pos = write_bits(out, pos, u32::from(get_bit(data, i)), 1);
+        }
+    }
+    Ok(pos)
+}
+
+/// Bit Insertion - inverse of [`bit_extract`].
+///
+/// # Arguments
+/// * `data` - Encoded bytes
+/// * `bit_pos` - Bit position to read from
+/// * `output` - Vector to insert bits into
+/// * `mask` - Insertion mask
+/// * `bits` - Length of both vectors in bits
+///
+/// # Returns
+/// Bit position after the inserted bits.
+#[cfg(feature = "decoder")]
+pub fn bit_insert(
+    data: &[u8],
+    bit_pos: usize,
+    output: &mut [u8],
+    mask: &[u8],
+    bits: usize,
+) -> Result<usize, PocketError> {
+    check_len(output, bits)?;
+    check_len(mask, bits)?;
+
+    let mut pos = bit_pos;
+    for i in (0..bits).rev() {
+        if get_bit(mask, i) == 1 {
+            let bit = read_bits(data, &mut pos, 1)? as u8;
+            set_bit(output, i, bit);
+        }
+    }
+    Ok(pos)
+}
+
+#[cfg(all(test, feature = "encoder", feature = "decoder"))]
+mod tests {
+    use super::*;
+    use crate::{BitBuffer, BitReader, BitVector};
+
+    fn pattern(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8 & (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_count_matches_bitbuffer() {
+        for a in [1, 2, 17, 33, 34, 100, 1000, 65535] {
+            let mut buf = BitBuffer::new();
+            crate::count_encode(&mut buf, a).unwrap();
+
+            let mut out = Vec::new();
+            let end = count_encode(&mut out, 0, a).unwrap();
+            assert_eq!(end, buf.len());
+            assert_eq!(out, buf.to_bytes());
+            assert_eq!(count_decode(&out, 0).unwrap(), (a, end));
+        }
+        assert!(count_encode(&mut Vec::new(), 0, 0).is_err());
+        assert!(count_encode(&mut Vec::new(), 0, 65536).is_err());
+        assert_eq!(count_decode(&[0x80], 0).unwrap(), (0, 2));
+        assert!(matches!(
+            count_decode(&[0xFF], 6),
+            Err(PocketError::Underflow)
+        ));
+    }
+
+    #[test]
+    fn test_rle_and_be_match_bitvector() {
+        let bits = 720;
+        for seed in 1..5 {
+            let input = pattern(90, seed);
+            let mask = pattern(90, seed + 100);
+            let input_bv = BitVector::from_bytes(&input, bits);
+            let mask_bv = BitVector::from_bytes(&mask, bits);
+
+            let mut buf = BitBuffer::new();
+            crate::rle_encode(&mut buf, &input_bv).unwrap();
+            crate::bit_extract(&mut buf, &input_bv, &mask_bv).unwrap();
+
+            let mut out = Vec::new();
+            let pos = rle_encode(&mut out, 0, &input, bits).unwrap();
+            let end = bit_extract(&mut out, pos, &input, &mask, bits).unwrap();
+            assert_eq!(end, buf.len());
+            assert_eq!(out, buf.to_bytes());
+
+            let (decoded, pos) = rle_decode(&out, 0, bits).unwrap();
+            assert_eq!(decoded, input);
+            let mut inserted = vec![0; 90];
+            assert_eq!(
+                bit_insert(&out, pos, &mut inserted, &mask, bits).unwrap(),
+                end
+            );
+            let mut reader = BitReader::new(&out, end);
+            crate::rle_decode(&mut reader, bits).unwrap();
+            let mut expected = BitVector::new(bits);
+            crate::bit_insert(&mut reader, &mut expected, &mask_bv).unwrap();
+            assert_eq!(inserted, expected.to_bytes());
+        }
+    }
+
+    #[test]
+    fn test_unaligned_offsets() {
+        // Primitives embedded after a 3-bit custom header
+        let input = [0x00, 0x41, 0x00];
+        let mut out = vec![0b1010_0000];
+        let end = rle_encode(&mut out, 3, &input, 20).unwrap();
+        assert_eq!(out[0] >> 5, 0b101);
+        let (decoded, pos) = rle_decode(&out, 3, 20).unwrap();
+        assert_eq!(pos, end);
+        assert_eq!(decoded, input);
+
+        // Short inputs are rejected
+        assert!(matches!(
+            rle_encode(&mut out, 0, &input, 25),
+            Err(PocketError::InvalidInputLength { .. })
+        ));
+    }
+}
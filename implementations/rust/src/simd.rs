@@ -0,0 +1,152 @@
+//! Word kernels for bulk bit-vector operations (`simd` feature).
+//!
+//! The bitwise operations, the Hamming weight and the intersection test of
+//! [`BitVector`](crate::BitVector) walk every word of the vector, which
+//! dominates compression and decompression of long packets. These kernels
+//! process four 64-bit words per step with independent lanes, so the
+//! compiler can keep them in one 256-bit register (or two 128-bit ones)
+//! and popcounts run in parallel instead of through a single accumulator.
+//! `std::simd` is not stable, so the lanes are plain `[u64; 4]` arrays,
+//! which stable Rust auto-vectorizes. Without the feature the same
+//! operations are simple word loops.
+
+use crate::bitvector::Word;
+
+/// Words per step.
+const LANES: usize = 4;
+
+/// `dst[i] = op(dst[i], src[i])` over the common words.
+#[inline]
+pub(crate) fn zip_words(dst: &mut [Word], src: &[Word], op: impl Fn(Word, Word) -> Word) {
+    let n = dst.len().min(src.len());
+    let mut dst = dst[..n].chunks_exact_mut(LANES);
+    let mut src = src[..n].chunks_exact(LANES);
+
+    for (d, s) in (&mut dst).zip(&mut src) {
+        for lane in 0..LANES {
+            d[lane] = op(d[lane], s[lane]);
+        }
+    }
+    for (d, &s) in dst.into_remainder().iter_mut().zip(src.remainder()) {
+        *d = op(*d, s);
+    }
+}
+
+/// `dst[i] |= a[i] ^ b[i]` over the common words.
+#[inline]
+pub(crate) fn or_xor_words(dst: &mut [Word], a: &[Word], b: &[Word]) {
+    let n = dst.len().min(a.len()).min(b.len());
+    let mut dst = dst[..n].chunks_exact_mut(LANES);
+    let mut a = a[..n].chunks_exact(LANES);
+    let mut b = b[..n].chunks_exact(LANES);
+
+    for ((d, a), b) in (&mut dst).zip(&mut a).zip(&mut b) {
+        for lane in 0..LANES {
+            d[lane] |= a[lane] ^ b[lane];
+        }
+    }
+    for ((d, &a), &b) in dst
+        .into_remainder()
+        .iter_mut()
+        .zip(a.remainder())
+        .zip(b.remainder())
+    {
+        *d |= a ^ b;
+    }
+}
+
+/// Number of '1' bits in `words`.
+#[inline]
+pub(crate) fn count_ones(words: &[Word]) -> usize {
+    let mut chunks = words.chunks_exact(LANES);
+    let mut counts = [0u32; LANES];
+
+    for chunk in &mut chunks {
+        for lane in 0..LANES {
+            counts[lane] += chunk[lane].count_ones();
+        }
+    }
+    let tail: u32 = chunks.remainder().iter().map(|w| w.count_ones()).sum();
+    counts.iter().sum::<u32>() as usize + tail as usize
+}
+
+/// True if `a[i] & b[i]` is non-zero for any common word.
+///
+/// Stops at the first step with a hit.
+#[cfg(feature = "encoder")]
+#[inline]
+pub(crate) fn intersects(a: &[Word], b: &[Word]) -> bool {
+    let n = a.len().min(b.len());
+    let mut a = a[..n].chunks_exact(LANES);
+    let mut b = b[..n].chunks_exact(LANES);
+
+    for (a, b) in (&mut a).zip(&mut b) {
+        let mut any = 0;
+        for lane in 0..LANES {
+            any |= a[lane] & b[lane];
+        }
+        if any != 0 {
+            return true;
+        }
+    }
+    a.remainder()
+        .iter()
+        .zip(b.remainder())
+        .any(|(a, b)| a & b != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random words for lengths around the lane count.
+    fn words(len: usize, seed: u64) -> Vec<Word> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                state
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_kernels_match_word_loops() {
+        for len in [0, 1, 3, 4, 5, 11, 12, 13] {
+            let a = words(len, 1);
+            let b = words(len, 2);
+
+            let mut xor = a.clone();
+            zip_words(&mut xor, &b, |x, y| x ^ y);
+            let expected: Vec<Word> = a.iter().zip(&b).map(|(x, y)| x ^ y).collect();
+            assert_eq!(xor, expected, "len {len}");
+
+            let mut or_xor = b.clone();
+            or_xor_words(&mut or_xor, &a, &b);
+            let expected: Vec<Word> = a.iter().zip(&b).map(|(x, y)| y | (x ^ y)).collect();
+            assert_eq!(or_xor, expected, "len {len}");
+
+            let weight: u32 = a.iter().map(|w| w.count_ones()).sum();
+            assert_eq!(count_ones(&a), weight as usize, "len {len}");
+
+            #[cfg(feature = "encoder")]
+            assert_eq!(
+                intersects(&a, &b),
+                a.iter().zip(&b).any(|(x, y)| x & y != 0)
+            );
+        }
+
+        // A single shared bit in the tail or in a full step is found
+        #[cfg(feature = "encoder")]
+        for len in [4, 7] {
+            let mut a = vec![0; len];
+            let mut b = vec![0; len];
+            assert!(!intersects(&a, &b));
+            a[len - 1] = 1 << 5;
+            b[len - 1] = 1 << 5;
+            assert!(intersects(&a, &b));
+        }
+    }
+}
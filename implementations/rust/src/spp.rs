@@ -0,0 +1,584 @@
+//! CCSDS Space Packet encapsulation.
+//!
+//! Downlinks that only transport space packets (CCSDS 133.0-B-2) carry
+//! each compressed POCKET+ packet in the data field of one space packet.
+//! [`SpacePacketEncoder`] compresses packets and wraps them under a fixed
+//! APID, maintaining the sequence count; [`SpacePacketDecoder`] checks the
+//! APID and sequence count and feeds the compressed packets to a
+//! decompressor.
+//!
+//! ## Primary Header (big-endian bit fields)
+//!
+//! | Bits | Field                                              |
+//! |------|----------------------------------------------------|
+//! | 3    | Packet version number (0)                          |
+//! | 1    | Packet type (0 = telemetry)                        |
+//! | 1    | Secondary header flag (0)                          |
+//! | 11   | APID                                               |
+//! | 2    | Sequence flags (`11` = unsegmented)                |
+//! | 14   | Packet sequence count, modulo 16384                |
+//! | 16   | Packet data length: data field bytes minus 1       |
+//!
+//! [`extract_packets`] goes the other way for raw captures of
+//! uncompressed telemetry: it scans back-to-back space packets, keeps
+//! those of one APID and cuts out their fixed-length payloads, ready for
+//! [`compress_capture`] or any other compression entry point.
+//!
+//! A gap in the sequence count of up to R packets is bridged by the
+//! robustness mechanism (see [`Decompressor::mark_lost`]). After a longer
+//! gap, packets are skipped until one carries both the full mask and the
+//! uncompressed packet. Gaps are counted modulo 16384, so losing a
+//! multiple of 16384 packets in a row goes unnoticed.
+
+#![allow(clippy::cast_possible_truncation)]
+
+#[cfg(feature = "decoder")]
+use crate::bitreader::BitReader;
+#[cfg(feature = "encoder")]
+use crate::bitvector::BitVector;
+#[cfg(feature = "encoder")]
+use crate::compress::{compress_with, CompressOptions, Compressor, CompressorBuilder};
+#[cfg(feature = "decoder")]
+use crate::decompress::{is_anchor, Decompressor};
+use crate::error::PocketError;
+
+/// Length of the primary header.
+pub const PRIMARY_HEADER_LEN: usize = 6;
+
+/// Highest APID; 0x7FF itself is reserved for idle packets.
+pub const MAX_APID: u16 = 0x7FF;
+
+/// Sequence counts wrap at this value.
+const SEQ_MODULUS: u16 = 1 << 14;
+
+/// Sequence flags of an unsegmented packet.
+const UNSEGMENTED: u16 = 0b11;
+
+/// Secondary header flag in the first header word.
+const SECONDARY_HEADER: u16 = 0x0800;
+
+/// Read the first two header words of the space packet at the start of
+/// `data` and the packet's total length in bytes, checking the version and
+/// that the whole packet is present.
+pub(crate) fn read_header(data: &[u8]) -> Result<(u16, u16, usize), PocketError> {
+    if data.len() < PRIMARY_HEADER_LEN {
+        return Err(PocketError::UnexpectedEndOfInput);
+    }
+    let id = u16::from_be_bytes([data[0], data[1]]);
+    let seq = u16::from_be_bytes([data[2], data[3]]);
+    let length = usize::from(u16::from_be_bytes([data[4], data[5]])) + 1;
+
+    if id >> 13 != 0 {
+        return Err(PocketError::InvalidFormat(format!(
+            "unsupported space packet version {}",
+            id >> 13
+        )));
+    }
+    let end = PRIMARY_HEADER_LEN + length;
+    if data.len() < end {
+        return Err(PocketError::UnexpectedEndOfInput);
+    }
+    Ok((id, seq, end))
+}
+
+/// One space packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpacePacket<'a> {
+    /// Application process identifier (0-2046).
+    pub apid: u16,
+    /// Packet sequence count (0-16383).
+    pub seq_count: u16,
+    /// Packet data field: one compressed packet.
+    pub data: &'a [u8],
+}
+
+impl<'a> SpacePacket<'a> {
+    /// Serialize the packet.
+    ///
+    /// Fails if the APID or sequence count is out of range, or the data
+    /// field is empty or longer than 65536 bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PocketError> {
+        if self.apid >= MAX_APID {
+            return Err(PocketError::InvalidParameter(format!(
+                "APID {} out of range 0-2046",
+                self.apid
+            )));
+        }
+        if self.seq_count >= SEQ_MODULUS {
+            return Err(PocketError::InvalidParameter(format!(
+                "sequence count {} out of range 0-16383",
+                self.seq_count
+            )));
+        }
+        let length = self
+            .data
+            .len()
+            .checked_sub(1)
+            .and_then(|n| u16::try_from(n).ok())
+            .ok_or_else(|| {
+                PocketError::InvalidParameter(format!(
+                    "data field of {} bytes, expected 1-65536",
+                    self.data.len()
+                ))
+            })?;
+
+        let mut out = Vec::with_capacity(PRIMARY_HEADER_LEN + self.data.len());
+        out.extend_from_slice(&self.apid.to_be_bytes());
+        out.extend_from_slice(&(UNSEGMENTED << 14 | self.seq_count).to_be_bytes());
+        out.extend_from_slice(&length.to_be_bytes());
+        out.extend_from_slice(self.data);
+        Ok(out)
+    }
+
+    /// Parse a space packet from the start of a byte slice.
+    ///
+    /// Only version 0, unsegmented packets without a secondary header are
+    /// accepted.
+    ///
+    /// # Arguments
+    /// * `data` - Bytes starting at a primary header
+    ///
+    /// # Returns
+    /// The packet and the total number of bytes it occupies.
+    pub fn parse(data: &'a [u8]) -> Result<(Self, usize), PocketError> {
+        let (id, seq, end) = read_header(data)?;
+        if id & SECONDARY_HEADER != 0 {
+            return Err(PocketError::InvalidFormat(
+                "space packet secondary header not supported".into(),
+            ));
+        }
+        if seq >> 14 != UNSEGMENTED {
+            return Err(PocketError::InvalidFormat(
+                "segmented space packets not supported".into(),
+            ));
+        }
+        Ok((
+            Self {
+                apid: id & MAX_APID,
+                seq_count: seq & (SEQ_MODULUS - 1),
+                data: &data[PRIMARY_HEADER_LEN..end],
+            },
+            end,
+        ))
+    }
+}
+
+/// Housekeeping packets to extract from a raw capture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacketFilter {
+    apid: u16,
+    data_length: usize,
+    skip: usize,
+    keep_header: bool,
+}
+
+impl PacketFilter {
+    /// Select the packets of one APID.
+    ///
+    /// # Arguments
+    /// * `apid` - APID of the housekeeping packets
+    /// * `data_length` - Expected packet data field length in bytes,
+    ///   including any secondary header
+    pub fn new(apid: u16, data_length: usize) -> Self {
+        Self {
+            apid,
+            data_length,
+            skip: 0,
+            keep_header: false,
+        }
+    }
+
+    /// Drop the first `bytes` of each data field, e.g. a secondary header
+    /// time stamp that changes with every packet (default 0).
+    #[must_use]
+    pub fn skip(mut self, bytes: usize) -> Self {
+        self.skip = bytes;
+        self
+    }
+
+    /// Keep the primary header in front of each payload, so the extracted
+    /// data is the capture itself minus other packets (default false).
+    #[must_use]
+    pub fn keep_header(mut self, keep: bool) -> Self {
+        self.keep_header = keep;
+        self
+    }
+
+    /// Length of each extracted payload in bytes.
+    pub fn payload_len(&self) -> usize {
+        let header = if self.keep_header {
+            PRIMARY_HEADER_LEN
+        } else {
+            0
+        };
+        header + self.data_length.saturating_sub(self.skip)
+    }
+}
+
+/// Outcome of [`extract_packets`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IngestReport {
+    /// Packets of the selected APID extracted.
+    pub extracted: usize,
+    /// Packets of other APIDs skipped.
+    pub other_apids: usize,
+    /// Packets of the selected APID skipped for an unexpected length.
+    pub wrong_length: usize,
+    /// Bytes after the last complete packet.
+    pub trailing_bytes: usize,
+}
+
+/// Extract the fixed-length payloads of one APID from a raw capture.
+///
+/// The capture must hold version 0 space packets back to back. Packets of
+/// other APIDs are skipped, as are packets of the selected APID whose data
+/// field does not have the expected length; a truncated last packet is
+/// reported as trailing bytes.
+///
+/// # Arguments
+/// * `capture` - Raw capture (e.g. a `.ccsds` file)
+/// * `filter` - APID, data field length and payload layout
+///
+/// # Returns
+/// The payloads, concatenated, and counts of what was skipped.
+pub fn extract_packets(
+    capture: &[u8],
+    filter: &PacketFilter,
+) -> Result<(Vec<u8>, IngestReport), PocketError> {
+    if filter.apid > MAX_APID || filter.skip > filter.data_length || filter.payload_len() == 0 {
+        return Err(PocketError::InvalidParameter(format!(
+            "invalid packet filter: APID {}, data length {}, skip {}",
+            filter.apid, filter.data_length, filter.skip
+        )));
+    }
+
+    let mut payloads = Vec::new();
+    let mut report = IngestReport::default();
+    let mut pos = 0;
+    while pos < capture.len() {
+        let (id, _, end) = match read_header(&capture[pos..]) {
+            Ok(header) => header,
+            Err(PocketError::UnexpectedEndOfInput) => {
+                report.trailing_bytes = capture.len() - pos;
+                break;
+            }
+            Err(PocketError::InvalidFormat(msg)) => {
+                return Err(PocketError::InvalidFormat(format!("{msg} at byte {pos}")));
+            }
+            Err(e) => return Err(e),
+        };
+        let packet = &capture[pos..pos + end];
+        pos += end;
+
+        if id & MAX_APID != filter.apid {
+            report.other_apids += 1;
+        } else if end - PRIMARY_HEADER_LEN != filter.data_length {
+            report.wrong_length += 1;
+        } else {
+            if filter.keep_header {
+                payloads.extend_from_slice(&packet[..PRIMARY_HEADER_LEN]);
+            }
+            payloads.extend_from_slice(&packet[PRIMARY_HEADER_LEN + filter.skip..]);
+            report.extracted += 1;
+        }
+    }
+    Ok((payloads, report))
+}
+
+/// Extract the payloads of one APID from a raw capture and compress them.
+///
+/// # Arguments
+/// * `capture` - Raw capture (e.g. a `.ccsds` file)
+/// * `filter` - APID, data field length and payload layout; the packet
+///   size F is [`PacketFilter::payload_len`] bytes
+/// * `options` - Compression parameters
+///
+/// # Returns
+/// The compressed stream and the extraction report.
+#[cfg(feature = "encoder")]
+pub fn compress_capture(
+    capture: &[u8],
+    filter: &PacketFilter,
+    options: &CompressOptions,
+) -> Result<(Vec<u8>, IngestReport), PocketError> {
+    let (payloads, report) = extract_packets(capture, filter)?;
+    let builder = CompressorBuilder::new(filter.payload_len() * 8).options(options.clone());
+    Ok((compress_with(&payloads, &builder)?, report))
+}
+
+/// Compressor emitting one space packet per packet.
+#[cfg(feature = "encoder")]
+pub struct SpacePacketEncoder {
+    comp: Compressor,
+    apid: u16,
+    seq_count: u16,
+}
+
+#[cfg(feature = "encoder")]
+impl SpacePacketEncoder {
+    /// Create a space packet encoder.
+    ///
+    /// # Arguments
+    /// * `comp` - Compressor; each packet is read as ⌈F/8⌉ bytes
+    /// * `apid` - APID of the emitted space packets (0-2046)
+    pub fn new(comp: Compressor, apid: u16) -> Result<Self, PocketError> {
+        if apid >= MAX_APID {
+            return Err(PocketError::InvalidParameter(format!(
+                "APID {apid} out of range 0-2046"
+            )));
+        }
+        Ok(Self {
+            comp,
+            apid,
+            seq_count: 0,
+        })
+    }
+
+    /// Set the sequence count of the next space packet (modulo 16384).
+    #[must_use]
+    pub fn seq_count(mut self, seq_count: u16) -> Self {
+        self.seq_count = seq_count % SEQ_MODULUS;
+        self
+    }
+
+    /// Sequence count of the next space packet.
+    pub fn next_seq_count(&self) -> u16 {
+        self.seq_count
+    }
+
+    /// The compressor.
+    pub fn compressor(&self) -> &Compressor {
+        &self.comp
+    }
+
+    /// Compress one packet into a space packet.
+    ///
+    /// # Arguments
+    /// * `packet` - One uncompressed packet
+    ///
+    /// # Returns
+    /// The space packet bytes (primary header and compressed packet).
+    pub fn encode(&mut self, packet: &[u8]) -> Result<Vec<u8>, PocketError> {
+        let f = self.comp.packet_size();
+        if packet.len() != (f + 7) / 8 {
+            return Err(PocketError::InvalidInputLength {
+                expected: (f + 7) / 8,
+                actual: packet.len(),
+            });
+        }
+
+        let input = BitVector::from_bytes(packet, f);
+        let params = self.comp.next_params();
+        let payload = self.comp.compress_packet(&input, &params)?.into_bytes();
+        let bytes = SpacePacket {
+            apid: self.apid,
+            seq_count: self.seq_count,
+            data: &payload,
+        }
+        .to_bytes()?;
+        self.seq_count = (self.seq_count + 1) % SEQ_MODULUS;
+        Ok(bytes)
+    }
+}
+
+/// Decompressor reading space packets in order.
+#[cfg(feature = "decoder")]
+pub struct SpacePacketDecoder {
+    decomp: Decompressor,
+    apid: u16,
+    /// Expected sequence count, unknown before the first packet.
+    next_count: Option<u16>,
+    /// Waiting for a packet that restores the whole decoder state.
+    resync: bool,
+    lost: usize,
+}
+
+#[cfg(feature = "decoder")]
+impl SpacePacketDecoder {
+    /// Create a space packet decoder.
+    ///
+    /// # Arguments
+    /// * `decomp` - Decompressor configured for the stream
+    /// * `apid` - APID of the stream; other packets are rejected
+    pub fn new(decomp: Decompressor, apid: u16) -> Self {
+        Self {
+            decomp,
+            apid,
+            next_count: None,
+            resync: false,
+            lost: 0,
+        }
+    }
+
+    /// The decompressor.
+    pub fn decompressor(&self) -> &Decompressor {
+        &self.decomp
+    }
+
+    /// Number of packets missing from the sequence count so far.
+    pub fn lost(&self) -> usize {
+        self.lost
+    }
+
+    /// Decode one space packet.
+    ///
+    /// # Arguments
+    /// * `bytes` - One space packet as produced by
+    ///   [`SpacePacketEncoder::encode`]
+    ///
+    /// # Returns
+    /// The decompressed packet, or `None` if it was skipped while waiting
+    /// for the decoder state to be restored after a gap longer than R.
+    pub fn decode(&mut self, bytes: &[u8]) -> Result<Option<Vec<u8>>, PocketError> {
+        let (packet, _) = SpacePacket::parse(bytes)?;
+        if packet.apid != self.apid {
+            return Err(PocketError::InvalidFormat(format!(
+                "space packet APID {} does not match stream APID {}",
+                packet.apid, self.apid
+            )));
+        }
+
+        if let Some(expected) = self.next_count {
+            let gap = usize::from(packet.seq_count.wrapping_sub(expected) % SEQ_MODULUS);
+            if gap > 0 {
+                self.lost += gap;
+                if !self.resync && self.decomp.mark_lost(gap).is_err() {
+                    self.resync = true;
+                }
+            }
+        }
+        self.next_count = Some((packet.seq_count + 1) % SEQ_MODULUS);
+
+        let mut reader = BitReader::new(packet.data, packet.data.len() * 8);
+        if self.resync {
+            if !is_anchor(reader.clone(), self.decomp.packet_size()) {
+                return Ok(None);
+            }
+            self.decomp.resync_state();
+            self.resync = false;
+        }
+        Ok(Some(self.decomp.decompress_packet(&mut reader)?.to_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primary_header() {
+        let packet = SpacePacket {
+            apid: 0x123,
+            seq_count: 16383,
+            data: &[0xAB, 0xCD],
+        };
+        let bytes = packet.to_bytes().unwrap();
+        assert_eq!(bytes, [0x01, 0x23, 0xFF, 0xFF, 0x00, 0x01, 0xAB, 0xCD]);
+        assert_eq!(SpacePacket::parse(&bytes).unwrap(), (packet, 8));
+        assert!(SpacePacket::parse(&bytes[..7]).is_err());
+
+        // Secondary header, segmentation, idle APID, empty data field
+        let mut bad = bytes.clone();
+        bad[0] |= 0x08;
+        assert!(SpacePacket::parse(&bad).is_err());
+        let mut bad = bytes.clone();
+        bad[2] &= 0x7F;
+        assert!(SpacePacket::parse(&bad).is_err());
+        let idle = SpacePacket {
+            apid: MAX_APID,
+            seq_count: 0,
+            data: &[0],
+        };
+        assert!(idle.to_bytes().is_err());
+        let empty = SpacePacket {
+            apid: 1,
+            seq_count: 0,
+            data: &[],
+        };
+        assert!(empty.to_bytes().is_err());
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_space_packet_round_trip() {
+        let data: Vec<u8> = (0..8 * 40)
+            .map(|i| u8::try_from(i % 5 * (i / 8 % 3) + i / 80).unwrap())
+            .collect();
+        let comp = Compressor::new(64, None, 1, 5, 10, 10).unwrap();
+        let mut encoder = SpacePacketEncoder::new(comp, 0x42)
+            .unwrap()
+            .seq_count(16380);
+        let packets: Vec<Vec<u8>> = data.chunks(8).map(|p| encoder.encode(p).unwrap()).collect();
+        assert_eq!(encoder.next_seq_count(), 36);
+
+        // Packet 4 lost (bridged by R = 1), packets 13 and 14 lost
+        // (skipped until the anchor at packet 20)
+        let mut decoder = SpacePacketDecoder::new(Decompressor::new(64, None, 1).unwrap(), 0x42);
+        for (i, packet) in packets.iter().enumerate() {
+            if i == 4 || i == 13 || i == 14 {
+                continue;
+            }
+            let output = decoder.decode(packet).unwrap();
+            if (15..20).contains(&i) {
+                assert_eq!(output, None, "packet {i}");
+            } else {
+                assert_eq!(output.unwrap(), &data[i * 8..i * 8 + 8], "packet {i}");
+            }
+        }
+        assert_eq!(decoder.lost(), 3);
+
+        let mut other = SpacePacketDecoder::new(Decompressor::new(64, None, 1).unwrap(), 0x43);
+        assert!(other.decode(&packets[0]).is_err());
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_extract_packets() {
+        // Secondary header flag set, as in real captures
+        let packet = |apid: u16, seq: u16, data: &[u8]| {
+            let mut bytes = SpacePacket {
+                apid,
+                seq_count: seq,
+                data,
+            }
+            .to_bytes()
+            .unwrap();
+            bytes[0] |= 0x08;
+            bytes
+        };
+        let mut capture = Vec::new();
+        for seq in 0..6u16 {
+            let stamp = u8::try_from(seq).unwrap();
+            capture.extend(packet(0x10, seq, &[stamp, 1, 2, 3]));
+            capture.extend(packet(0x20, seq, &[9; 7]));
+        }
+        capture.extend(packet(0x10, 6, &[0; 3]));
+        capture.extend(&packet(0x10, 7, &[0; 4])[..5]);
+
+        let filter = PacketFilter::new(0x10, 4).skip(1);
+        assert_eq!(filter.payload_len(), 3);
+        let (payloads, report) = extract_packets(&capture, &filter).unwrap();
+        assert_eq!(payloads, [1, 2, 3].repeat(6));
+        assert_eq!(
+            report,
+            IngestReport {
+                extracted: 6,
+                other_apids: 6,
+                wrong_length: 1,
+                trailing_bytes: 5,
+            }
+        );
+
+        let filter = PacketFilter::new(0x10, 4).keep_header(true);
+        let (payloads, _) = extract_packets(&capture, &filter).unwrap();
+        assert_eq!(&payloads[..10], &capture[..10]);
+
+        let (compressed, _) =
+            compress_capture(&capture, &filter, &CompressOptions::default()).unwrap();
+        assert_eq!(crate::decompress(&compressed, 80, 1).unwrap(), payloads);
+
+        // Not a space packet stream, or an impossible filter
+        assert!(extract_packets(&[0xE0; 12], &filter).is_err());
+        assert!(extract_packets(&capture, &PacketFilter::new(0x10, 4).skip(4)).is_err());
+    }
+}
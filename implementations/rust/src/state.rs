@@ -0,0 +1,207 @@
+//! Binary snapshots of codec state.
+//!
+//! Shared encoding for `Compressor::save_state` and
+//! `Decompressor::save_state`, so long-running services and chunked
+//! ground processing can persist their state and resume a stream later.
+//!
+//! ## Layout (big-endian)
+//!
+//! | Size | Field                                      |
+//! |------|--------------------------------------------|
+//! | 4    | Magic `"PKS"` followed by a kind byte      |
+//! | 1    | Format version                             |
+//! | ...  | Fields, in the order written by the codec  |
+//! | 4    | CRC-32 of all preceding bytes              |
+//!
+//! Integers are written as u64, bit vectors as their F bits packed into
+//! ⌈F/8⌉ bytes.
+//!
+//! The snapshot is also what the `serde` feature serializes for codec
+//! state: the codecs derive no `Serialize` themselves, because their
+//! fields change between releases while the version byte and checksum
+//! keep old snapshots readable or reject them cleanly.
+
+use crate::bitvector::BitVector;
+use crate::crc::crc32;
+use crate::error::PocketError;
+
+/// Snapshot magic, followed by the kind byte.
+const MAGIC: [u8; 3] = *b"PKS";
+
+/// Snapshot format version.
+const VERSION: u8 = 1;
+
+/// Kind byte of compressor snapshots.
+#[cfg(feature = "encoder")]
+pub(crate) const KIND_COMPRESSOR: u8 = b'C';
+
+/// Kind byte of decompressor snapshots.
+#[cfg(feature = "decoder")]
+pub(crate) const KIND_DECOMPRESSOR: u8 = b'D';
+
+/// Serializer for one snapshot.
+pub(crate) struct StateWriter {
+    out: Vec<u8>,
+}
+
+impl StateWriter {
+    /// Start a snapshot of the given kind.
+    pub(crate) fn new(kind: u8) -> Self {
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC);
+        out.push(kind);
+        out.push(VERSION);
+        Self { out }
+    }
+
+    /// Append an integer.
+    pub(crate) fn int(&mut self, value: usize) {
+        self.out.extend_from_slice(&(value as u64).to_be_bytes());
+    }
+
+    /// Append a flag.
+    #[cfg(feature = "encoder")]
+    pub(crate) fn flag(&mut self, value: bool) {
+        self.out.push(u8::from(value));
+    }
+
+    /// Append a bit vector.
+    pub(crate) fn vector(&mut self, vector: &BitVector) {
+        self.out.extend(vector.to_bytes());
+    }
+
+    /// Append the checksum and return the snapshot.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        let crc = crc32(&self.out);
+        self.out.extend_from_slice(&crc.to_be_bytes());
+        self.out
+    }
+}
+
+/// Parser for one snapshot.
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    /// Check the header and checksum of a snapshot of the given kind.
+    pub(crate) fn new(data: &'a [u8], kind: u8) -> Result<Self, PocketError> {
+        let header = MAGIC.len() + 2;
+        if data.len() < header + 4 {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        if data[..MAGIC.len()] != MAGIC || data[MAGIC.len()] != kind {
+            return Err(PocketError::InvalidFormat(
+                "not a state snapshot of this kind".into(),
+            ));
+        }
+        if data[MAGIC.len() + 1] != VERSION {
+            return Err(PocketError::InvalidFormat(format!(
+                "unsupported snapshot version {}",
+                data[MAGIC.len() + 1]
+            )));
+        }
+
+        let (body, crc) = data.split_at(data.len() - 4);
+        if crc32(body).to_be_bytes() != crc {
+            return Err(PocketError::InvalidFormat(
+                "snapshot checksum mismatch".into(),
+            ));
+        }
+        Ok(Self {
+            data: body,
+            pos: header,
+        })
+    }
+
+    /// Take the next `n` bytes.
+    fn take(&mut self, n: usize) -> Result<&'a [u8], PocketError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or(PocketError::UnexpectedEndOfInput)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// Read an integer.
+    pub(crate) fn int(&mut self) -> Result<usize, PocketError> {
+        let mut be = [0u8; 8];
+        be.copy_from_slice(self.take(8)?);
+        usize::try_from(u64::from_be_bytes(be))
+            .map_err(|_| PocketError::InvalidFormat("snapshot integer out of range".into()))
+    }
+
+    /// Read an integer that must be below `limit`.
+    #[cfg(feature = "encoder")]
+    pub(crate) fn index(&mut self, limit: usize) -> Result<usize, PocketError> {
+        let value = self.int()?;
+        if value >= limit {
+            return Err(PocketError::InvalidFormat(format!(
+                "snapshot index {value} out of range"
+            )));
+        }
+        Ok(value)
+    }
+
+    /// Read a flag.
+    #[cfg(feature = "encoder")]
+    pub(crate) fn flag(&mut self) -> Result<bool, PocketError> {
+        match self.take(1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(PocketError::InvalidFormat(format!(
+                "invalid snapshot flag {other}"
+            ))),
+        }
+    }
+
+    /// Read a bit vector of `f` bits.
+    pub(crate) fn vector(&mut self, f: usize) -> Result<BitVector, PocketError> {
+        Ok(BitVector::from_bytes(self.take((f + 7) / 8)?, f))
+    }
+
+    /// Check that the whole snapshot was read.
+    pub(crate) fn finish(self) -> Result<(), PocketError> {
+        if self.pos != self.data.len() {
+            return Err(PocketError::InvalidFormat(
+                "trailing bytes in snapshot".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "encoder", feature = "decoder"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut vector = BitVector::new(12);
+        vector.set_bit(3, 1);
+
+        let mut writer = StateWriter::new(KIND_COMPRESSOR);
+        writer.int(70_000);
+        writer.flag(true);
+        writer.vector(&vector);
+        let snapshot = writer.finish();
+
+        let mut reader = StateReader::new(&snapshot, KIND_COMPRESSOR).unwrap();
+        assert_eq!(reader.int().unwrap(), 70_000);
+        assert!(reader.flag().unwrap());
+        assert_eq!(reader.vector(12).unwrap(), vector);
+        reader.finish().unwrap();
+
+        // Wrong kind, corruption, truncation
+        assert!(StateReader::new(&snapshot, KIND_DECOMPRESSOR).is_err());
+        let mut corrupted = snapshot.clone();
+        corrupted[6] ^= 1;
+        assert!(StateReader::new(&corrupted, KIND_COMPRESSOR).is_err());
+        assert!(StateReader::new(&snapshot[..8], KIND_COMPRESSOR).is_err());
+
+        let mut reader = StateReader::new(&snapshot, KIND_COMPRESSOR).unwrap();
+        assert!(reader.index(70_000).is_err());
+    }
+}
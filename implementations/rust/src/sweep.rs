@@ -0,0 +1,283 @@
+//! Parameter sweeps for trade studies.
+//!
+//! Compresses a representative sample with every combination of a
+//! [`ParameterGrid`] and records, per combination:
+//! - Compression ratio and compressed size
+//! - Number of packets carrying the full mask (ḟₜ = 1)
+//! - Number of uncompressed packets (ṙₜ = 1)
+//! - Worst-case compressed packet size
+//!
+//! The resulting [`SweepReport`] exports to CSV or JSON for inclusion in
+//! mission trade-study documents.
+
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_precision_loss)]
+
+use crate::bitvector::BitVector;
+use crate::compress::Compressor;
+use crate::error::PocketError;
+use std::fmt::Write;
+
+/// Parameter values to sweep.
+///
+/// Every combination of robustness, pt, ft and rt is evaluated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParameterGrid {
+    /// Packet size in bits (F, fixed by the sample).
+    pub packet_size: usize,
+    /// Robustness levels (R, 0-7).
+    pub robustness: Vec<usize>,
+    /// New mask periods.
+    pub pt: Vec<usize>,
+    /// Full mask periods.
+    pub ft: Vec<usize>,
+    /// Uncompressed packet periods.
+    pub rt: Vec<usize>,
+}
+
+impl ParameterGrid {
+    /// Number of combinations in the grid.
+    pub fn len(&self) -> usize {
+        self.robustness.len() * self.pt.len() * self.ft.len() * self.rt.len()
+    }
+
+    /// Returns true if the grid has no combinations.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Result for one parameter combination.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepResult {
+    /// Robustness level (R).
+    pub robustness: usize,
+    /// New mask period.
+    pub pt: usize,
+    /// Full mask period.
+    pub ft: usize,
+    /// Uncompressed packet period.
+    pub rt: usize,
+    /// Total compressed size in bytes.
+    pub compressed_bytes: usize,
+    /// Input size divided by compressed size.
+    pub ratio: f64,
+    /// Packets sent with the full mask.
+    pub full_mask_count: usize,
+    /// Packets sent uncompressed.
+    pub uncompressed_count: usize,
+    /// Largest compressed packet in bytes.
+    pub worst_packet_bytes: usize,
+}
+
+/// Results of a parameter sweep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepReport {
+    /// Sample size in bytes.
+    pub input_bytes: usize,
+    /// Number of packets in the sample.
+    pub num_packets: usize,
+    /// One result per grid combination, in grid order.
+    pub results: Vec<SweepResult>,
+}
+
+impl SweepReport {
+    /// Combination with the highest compression ratio.
+    pub fn best_ratio(&self) -> Option<&SweepResult> {
+        self.results
+            .iter()
+            .max_by(|a, b| a.ratio.total_cmp(&b.ratio))
+    }
+
+    /// Export the results as CSV with a header row.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "robustness,pt,ft,rt,compressed_bytes,ratio,full_mask_count,uncompressed_count,worst_packet_bytes\n",
+        );
+        for r in &self.results {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{:.4},{},{},{}",
+                r.robustness,
+                r.pt,
+                r.ft,
+                r.rt,
+                r.compressed_bytes,
+                r.ratio,
+                r.full_mask_count,
+                r.uncompressed_count,
+                r.worst_packet_bytes
+            );
+        }
+        csv
+    }
+
+    /// Export the report as JSON.
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\n  \"input_bytes\": {},\n  \"num_packets\": {},\n  \"results\": [",
+            self.input_bytes, self.num_packets
+        );
+        for (i, r) in self.results.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            let _ = write!(
+                json,
+                "{sep}\n    {{\"robustness\": {}, \"pt\": {}, \"ft\": {}, \"rt\": {}, \
+                 \"compressed_bytes\": {}, \"ratio\": {:.4}, \"full_mask_count\": {}, \
+                 \"uncompressed_count\": {}, \"worst_packet_bytes\": {}}}",
+                r.robustness,
+                r.pt,
+                r.ft,
+                r.rt,
+                r.compressed_bytes,
+                r.ratio,
+                r.full_mask_count,
+                r.uncompressed_count,
+                r.worst_packet_bytes
+            );
+        }
+        json.push_str("\n  ]\n}\n");
+        json
+    }
+}
+
+/// Compress the sample with one combination and collect statistics.
+fn evaluate(
+    sample: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt: usize,
+    ft: usize,
+    rt: usize,
+) -> Result<SweepResult, PocketError> {
+    if robustness > 7 {
+        return Err(PocketError::InvalidRobustness(robustness));
+    }
+
+    let mut comp = Compressor::new(packet_size, None, robustness as u8, pt, ft, rt)?;
+    let mut result = SweepResult {
+        robustness,
+        pt,
+        ft,
+        rt,
+        compressed_bytes: 0,
+        ratio: 0.0,
+        full_mask_count: 0,
+        uncompressed_count: 0,
+        worst_packet_bytes: 0,
+    };
+
+    for packet in sample.chunks_exact(packet_size / 8) {
+        let input = BitVector::from_bytes(packet, packet_size);
+        let params = comp.next_params();
+        let packet_bytes = (comp.compress_packet(&input, &params)?.len() + 7) / 8;
+
+        result.compressed_bytes += packet_bytes;
+        result.worst_packet_bytes = result.worst_packet_bytes.max(packet_bytes);
+        result.full_mask_count += usize::from(params.send_mask_flag);
+        result.uncompressed_count += usize::from(params.uncompressed_flag);
+    }
+
+    if result.compressed_bytes > 0 {
+        result.ratio = sample.len() as f64 / result.compressed_bytes as f64;
+    }
+    Ok(result)
+}
+
+/// Compress a sample with every combination in a parameter grid.
+///
+/// # Arguments
+/// * `sample` - Representative input (whole packets)
+/// * `grid` - Parameter values to combine
+///
+/// # Returns
+/// A report with one result per combination.
+pub fn sweep(sample: &[u8], grid: &ParameterGrid) -> Result<SweepReport, PocketError> {
+    let packet_size = grid.packet_size;
+    if packet_size == 0 || packet_size % 8 != 0 {
+        return Err(PocketError::InvalidPacketSize(packet_size));
+    }
+    let packet_bytes = packet_size / 8;
+    if sample.len() % packet_bytes != 0 {
+        return Err(PocketError::InvalidInputLength {
+            expected: (sample.len() / packet_bytes + 1) * packet_bytes,
+            actual: sample.len(),
+        });
+    }
+
+    let mut results = Vec::with_capacity(grid.len());
+    for &robustness in &grid.robustness {
+        for &pt in &grid.pt {
+            for &ft in &grid.ft {
+                for &rt in &grid.rt {
+                    results.push(evaluate(sample, packet_size, robustness, pt, ft, rt)?);
+                }
+            }
+        }
+    }
+
+    Ok(SweepReport {
+        input_bytes: sample.len(),
+        num_packets: sample.len() / packet_bytes,
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress::compress;
+    use crate::synth::{generate, Profile};
+
+    fn grid() -> ParameterGrid {
+        ParameterGrid {
+            packet_size: 720,
+            robustness: vec![1, 2],
+            pt: vec![10, 20],
+            ft: vec![20],
+            rt: vec![50, 100],
+        }
+    }
+
+    #[test]
+    fn test_sweep_matches_compress() {
+        let sample = generate(&Profile::housekeeping(), 200, 5);
+        let report = sweep(&sample, &grid()).unwrap();
+
+        assert_eq!(report.results.len(), grid().len());
+        assert_eq!(report.num_packets, 200);
+        for r in &report.results {
+            let stream = compress(&sample, 720, r.robustness, r.pt, r.ft, r.rt).unwrap();
+            assert_eq!(r.compressed_bytes, stream.len());
+            assert!(r.worst_packet_bytes >= 90);
+            assert!(r.uncompressed_count >= 1);
+            assert!(r.full_mask_count > r.robustness);
+        }
+        assert!(report.best_ratio().unwrap().ratio > 1.0);
+    }
+
+    #[test]
+    fn test_sweep_exports() {
+        let sample = generate(&Profile::housekeeping(), 20, 1);
+        let report = sweep(&sample, &grid()).unwrap();
+
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 1 + grid().len());
+        assert!(csv.starts_with("robustness,pt,ft,rt,"));
+
+        let json = report.to_json();
+        assert!(json.contains("\"num_packets\": 20"));
+        assert_eq!(json.matches("\"worst_packet_bytes\"").count(), grid().len());
+    }
+
+    #[test]
+    fn test_sweep_invalid() {
+        let mut g = grid();
+        g.packet_size = 7;
+        assert!(sweep(&[0; 90], &g).is_err());
+        assert!(sweep(&[0; 91], &grid()).is_err());
+        g = grid();
+        g.robustness = vec![8];
+        assert!(sweep(&[0; 90], &g).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Attached Sync Marker (ASM) framing.
+//!
+//! Frame-synchronized downlinks often deliver compressed packets as an
+//! unstructured byte stream, possibly with noise between or inside
+//! packets. [`compress_synced`] puts a sync marker (by default the CCSDS
+//! ASM `0x1ACFFC1D`) in front of every compressed packet, and
+//! [`decompress_synced`] scans for the markers to find packet boundaries.
+//!
+//! The decoder *locks* onto the stream at a marker followed by a packet
+//! carrying both the full mask and the uncompressed packet, which restores
+//! the whole decoder state. While locked, every packet must be followed
+//! immediately by the next marker (or the end of the data); a packet that
+//! fails to decode or does not end at a marker loses the lock, its bytes
+//! are discarded and the decoder waits for the next such packet. The first
+//! R + 1 packets of a stream qualify, so a clean stream decodes from its
+//! first packet.
+
+#[cfg(feature = "decoder")]
+use crate::bitreader::BitReader;
+#[cfg(feature = "encoder")]
+use crate::compress::compress_indexed;
+#[cfg(feature = "decoder")]
+use crate::decompress::{is_anchor, Decompressor, DecompressorBuilder};
+use crate::error::PocketError;
+
+/// The CCSDS Attached Sync Marker.
+pub const CCSDS_ASM: [u8; 4] = [0x1A, 0xCF, 0xFC, 0x1D];
+
+/// Result of [`decompress_synced`].
+#[cfg(feature = "decoder")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Decoded packets, concatenated.
+    pub data: Vec<u8>,
+    /// Number of packets decoded.
+    pub packets: usize,
+    /// Input bytes not part of a decoded packet and its marker.
+    pub discarded_bytes: usize,
+    /// Number of times the lock was lost after a bad packet.
+    pub resyncs: usize,
+}
+
+/// Check that a sync marker is usable.
+fn check_marker(marker: &[u8]) -> Result<(), PocketError> {
+    if marker.is_empty() {
+        return Err(PocketError::InvalidParameter(
+            "sync marker must not be empty".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Position of the first occurrence of `marker` at or after `from`.
+#[cfg(feature = "decoder")]
+fn find_marker(data: &[u8], marker: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(marker.len())
+        .position(|w| w == marker)
+        .map(|i| from + i)
+}
+
+/// Compress multiple packets, each preceded by a sync marker.
+///
+/// Takes the same arguments as [`compress`](crate::compress), plus the
+/// marker.
+///
+/// # Arguments
+/// * `marker` - Sync marker, e.g. [`CCSDS_ASM`]
+///
+/// # Returns
+/// The marked stream.
+#[cfg(feature = "encoder")]
+pub fn compress_synced(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+    marker: &[u8],
+) -> Result<Vec<u8>, PocketError> {
+    check_marker(marker)?;
+    let (compressed, spans) =
+        compress_indexed(data, packet_size, robustness, pt_limit, ft_limit, rt_limit)?;
+    let mut out = Vec::with_capacity(compressed.len() + marker.len() * spans.len());
+    for span in spans {
+        out.extend_from_slice(marker);
+        out.extend_from_slice(&compressed[span.byte_offset..span.byte_offset + span.byte_length]);
+    }
+    Ok(out)
+}
+
+/// Decompress a marked byte stream, locking onto the sync markers.
+///
+/// # Arguments
+/// * `data` - Byte stream holding marked packets, possibly with noise
+/// * `packet_size` - Packet size in bits
+/// * `robustness` - Robustness level R (0-7)
+/// * `marker` - Sync marker the packets were written with
+///
+/// # Returns
+/// The packets decoded while locked and counts of what was discarded.
+#[cfg(feature = "decoder")]
+pub fn decompress_synced(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    marker: &[u8],
+) -> Result<SyncReport, PocketError> {
+    check_marker(marker)?;
+    let mut decomp = DecompressorBuilder::new(packet_size)
+        .robustness(robustness)
+        .build()?;
+    let mut report = SyncReport::default();
+    let mut locked = false;
+    let mut accepted = 0;
+
+    let mut next = find_marker(data, marker, 0);
+    while let Some(pos) = next {
+        let start = pos + marker.len();
+        match decode_marked(&mut decomp, &data[start..], packet_size, locked) {
+            Some((packet, len))
+                if start + len == data.len() || data[start + len..].starts_with(marker) =>
+            {
+                report.data.extend(packet);
+                report.packets += 1;
+                accepted += marker.len() + len;
+                locked = true;
+                next = Some(start + len).filter(|&end| end < data.len());
+            }
+            _ => {
+                if locked {
+                    report.resyncs += 1;
+                    locked = false;
+                }
+                next = find_marker(data, marker, pos + 1);
+            }
+        }
+    }
+
+    report.discarded_bytes = data.len() - accepted;
+    Ok(report)
+}
+
+/// Decode the packet at the start of `payload`, requiring a packet that
+/// restores the whole state unless `locked`.
+///
+/// # Returns
+/// The packet and its length in bytes, or `None` if it cannot be used.
+#[cfg(feature = "decoder")]
+fn decode_marked(
+    decomp: &mut Decompressor,
+    payload: &[u8],
+    packet_size: usize,
+    locked: bool,
+) -> Option<(Vec<u8>, usize)> {
+    let mut reader = BitReader::new(payload, payload.len() * 8);
+    if !locked {
+        if !is_anchor(reader.clone(), packet_size) {
+            return None;
+        }
+        decomp.resync_state();
+    }
+    let packet = decomp.decompress_packet(&mut reader).ok()?;
+    reader.align_byte();
+    Some((packet.to_bytes(), reader.position() / 8))
+}
+
+#[cfg(all(test, feature = "encoder", feature = "decoder"))]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        (0..8 * 60)
+            .map(|i| u8::try_from(i % 8 * (i / 8 % 4) + i / 160).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_synced_round_trip() {
+        let data = sample();
+        let marked = compress_synced(&data, 64, 1, 5, 10, 10, &CCSDS_ASM).unwrap();
+        assert!(marked.starts_with(&CCSDS_ASM));
+
+        let report = decompress_synced(&marked, 64, 1, &CCSDS_ASM).unwrap();
+        assert_eq!(report.data, data);
+        assert_eq!(report.packets, 60);
+        assert_eq!((report.discarded_bytes, report.resyncs), (0, 0));
+
+        assert!(compress_synced(&data, 64, 1, 5, 10, 10, &[]).is_err());
+    }
+
+    #[test]
+    fn test_synced_noise() {
+        let data = sample();
+        let (_, spans) = compress_indexed(&data, 64, 1, 5, 10, 10).unwrap();
+        let marked = compress_synced(&data, 64, 1, 5, 10, 10, &CCSDS_ASM).unwrap();
+        let at = |n: usize| spans[n].byte_offset + 4 * n;
+
+        // Noise before the stream and a burst inside packet 25
+        let mut noisy = vec![0x1A, 0xCF, 0x55, 0x00, 0xFF];
+        noisy.extend_from_slice(&marked[..at(25) + 5]);
+        noisy.extend_from_slice(&[0xA5; 3]);
+        noisy.extend_from_slice(&marked[at(25) + 8..]);
+
+        // Packets 25 to 29 are lost until the anchor at packet 30
+        let report = decompress_synced(&noisy, 64, 1, &CCSDS_ASM).unwrap();
+        assert_eq!(report.packets, 55);
+        assert_eq!(report.resyncs, 1);
+        assert_eq!(&report.data[..25 * 8], &data[..25 * 8]);
+        assert_eq!(&report.data[25 * 8..], &data[30 * 8..]);
+        assert_eq!(report.discarded_bytes, 5 + at(30) - at(25));
+    }
+}
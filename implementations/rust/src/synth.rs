@@ -0,0 +1,337 @@
+//! Synthetic housekeeping data generator.
+//!
+//! Produces deterministic pseudo-housekeeping packet streams from a seed and
+//! a [`Profile`] describing the packet layout. Each field is one of:
+//! - `const`   - fixed value
+//! - `counter` - incremented every packet (wraps at the field width)
+//! - `adc`     - noisy analog channel around a mean value
+//! - `mode`    - cycles through a list of values every `period` packets
+//!
+//! Used by the benchmarks, the CLI generator (`pocketplus -g`) and tests.
+//!
+//! ## Profile Text Format
+//!
+//! One field per line, `kind width [key=value ...]`, widths in bytes (1-8),
+//! `#` starts a comment:
+//!
+//! ```text
+//! counter 2 start=0 step=1
+//! mode    1 period=50 values=1,2,4
+//! adc     2 mean=2048 noise=8
+//! const   4 value=0xDEADBEEF
+//! ```
+
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::error::PocketError;
+
+/// Small xorshift64 generator.
+///
+/// Deterministic across platforms so generated streams are reproducible
+/// from the seed alone.
+#[derive(Clone, Debug)]
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    /// Create a generator from a seed.
+    pub(crate) fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        Self(seed | 1)
+    }
+
+    /// Next 64-bit value.
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// How a field's value evolves from packet to packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Fixed value.
+    Constant(u64),
+    /// Counter starting at `start`, incremented by `step` every packet.
+    Counter { start: u64, step: u64 },
+    /// Analog channel: `mean` plus uniform noise in `-noise..=noise`.
+    Adc { mean: u64, noise: u64 },
+    /// Cycles through `values`, switching every `period` packets.
+    Mode { period: usize, values: Vec<u64> },
+}
+
+/// A field in a synthetic packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldSpec {
+    /// Field width in bytes (1-8), written big-endian.
+    pub bytes: usize,
+    /// Value behaviour.
+    pub kind: FieldKind,
+}
+
+/// Packet layout for the generator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// Fields in packet order.
+    pub fields: Vec<FieldSpec>,
+}
+
+impl Profile {
+    /// Representative 90-byte housekeeping profile.
+    ///
+    /// Sequence counter, timestamp, operating mode, eight ADC channels and
+    /// constant configuration words.
+    pub fn housekeeping() -> Self {
+        let mut fields = vec![
+            FieldSpec {
+                bytes: 2,
+                kind: FieldKind::Counter { start: 0, step: 1 },
+            },
+            FieldSpec {
+                bytes: 4,
+                kind: FieldKind::Counter {
+                    start: 0x6000_0000,
+                    step: 10,
+                },
+            },
+            FieldSpec {
+                bytes: 1,
+                kind: FieldKind::Mode {
+                    period: 200,
+                    values: vec![1, 2, 3],
+                },
+            },
+        ];
+        for channel in 0..8 {
+            fields.push(FieldSpec {
+                bytes: 2,
+                kind: FieldKind::Adc {
+                    mean: 1024 + channel * 256,
+                    noise: 1 + channel % 4,
+                },
+            });
+        }
+        // Pad to 90 bytes with configuration words
+        for word in 0..67 {
+            fields.push(FieldSpec {
+                bytes: 1,
+                kind: FieldKind::Constant(word * 3),
+            });
+        }
+        Self { fields }
+    }
+
+    /// Packet size in bytes.
+    pub fn packet_bytes(&self) -> usize {
+        self.fields.iter().map(|f| f.bytes).sum()
+    }
+
+    /// Parse a profile from its text description.
+    ///
+    /// # Arguments
+    /// * `text` - Profile description (see module documentation)
+    pub fn parse(text: &str) -> Result<Self, PocketError> {
+        let mut fields = Vec::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err =
+                |msg: &str| PocketError::InvalidFormat(format!("line {}: {msg}", line_no + 1));
+
+            let mut tokens = line.split_whitespace();
+            let kind = tokens.next().unwrap_or("");
+            let bytes: usize = tokens
+                .next()
+                .and_then(|w| w.parse().ok())
+                .filter(|w| (1..=8).contains(w))
+                .ok_or_else(|| err("field width must be 1-8 bytes"))?;
+
+            let get = |key: &str| -> Option<&str> {
+                tokens
+                    .clone()
+                    .find_map(|t| t.strip_prefix(key).and_then(|r| r.strip_prefix('=')))
+            };
+            let num = |key: &str, value: Option<&str>, default: u64| -> Result<u64, PocketError> {
+                value.map_or(Ok(default), |v| {
+                    parse_number(v).ok_or_else(|| err(&format!("invalid {key}")))
+                })
+            };
+
+            let kind = match kind {
+                "const" => FieldKind::Constant(num("value", get("value"), 0)?),
+                "counter" => FieldKind::Counter {
+                    start: num("start", get("start"), 0)?,
+                    step: num("step", get("step"), 1)?,
+                },
+                "adc" => FieldKind::Adc {
+                    mean: num("mean", get("mean"), 0)?,
+                    noise: num("noise", get("noise"), 0)?,
+                },
+                "mode" => {
+                    let period = num("period", get("period"), 1)? as usize;
+                    let values = get("values")
+                        .ok_or_else(|| err("mode requires values"))?
+                        .split(',')
+                        .map(|v| parse_number(v).ok_or_else(|| err("invalid values")))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if period == 0 {
+                        return Err(err("period must be positive"));
+                    }
+                    FieldKind::Mode { period, values }
+                }
+                other => return Err(err(&format!("unknown field kind '{other}'"))),
+            };
+
+            fields.push(FieldSpec { bytes, kind });
+        }
+
+        if fields.is_empty() {
+            return Err(PocketError::InvalidFormat("profile has no fields".into()));
+        }
+        Ok(Self { fields })
+    }
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal number.
+fn parse_number(s: &str) -> Option<u64> {
+    s.strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .map_or_else(|| s.parse().ok(), |hex| u64::from_str_radix(hex, 16).ok())
+}
+
+/// Iterator producing synthetic packets one at a time.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    profile: Profile,
+    rng: XorShift,
+    t: u64,
+}
+
+impl Generator {
+    /// Create a generator for a profile and seed.
+    pub fn new(profile: Profile, seed: u64) -> Self {
+        Self {
+            profile,
+            rng: XorShift::new(seed),
+            t: 0,
+        }
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let mut packet = Vec::with_capacity(self.profile.packet_bytes());
+
+        for field in &self.profile.fields {
+            let value = match &field.kind {
+                FieldKind::Constant(v) => *v,
+                FieldKind::Counter { start, step } => start.wrapping_add(step.wrapping_mul(self.t)),
+                FieldKind::Adc { mean, noise } => {
+                    let span = noise * 2 + 1;
+                    let offset = self.rng.next_u64() % span;
+                    (mean + offset).wrapping_sub(*noise)
+                }
+                FieldKind::Mode { period, values } => {
+                    let index = (self.t / *period as u64) as usize % values.len().max(1);
+                    values.get(index).copied().unwrap_or(0)
+                }
+            };
+
+            let be = value.to_be_bytes();
+            packet.extend_from_slice(&be[8 - field.bytes..]);
+        }
+
+        self.t += 1;
+        Some(packet)
+    }
+}
+
+/// Generate a stream of synthetic packets.
+///
+/// # Arguments
+/// * `profile` - Packet layout
+/// * `num_packets` - Number of packets to generate
+/// * `seed` - Generator seed; the same seed always yields the same stream
+///
+/// # Returns
+/// `num_packets * profile.packet_bytes()` bytes.
+pub fn generate(profile: &Profile, num_packets: usize, seed: u64) -> Vec<u8> {
+    Generator::new(profile.clone(), seed)
+        .take(num_packets)
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    use crate::{compress, decompress};
+
+    #[test]
+    fn test_housekeeping_profile_size() {
+        assert_eq!(Profile::housekeeping().packet_bytes(), 90);
+    }
+
+    #[test]
+    fn test_generate_deterministic() {
+        let profile = Profile::housekeeping();
+        let a = generate(&profile, 50, 1);
+        let b = generate(&profile, 50, 1);
+        let c = generate(&profile, 50, 2);
+        assert_eq!(a.len(), 4500);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_field_behaviour() {
+        let profile = Profile::parse(
+            "counter 2 start=0xFFFE step=1\n\
+             mode 1 period=2 values=7,9\n\
+             const 1 value=0xAB\n\
+             adc 2 mean=100 noise=2",
+        )
+        .unwrap();
+        let packets: Vec<Vec<u8>> = Generator::new(profile, 3).take(4).collect();
+
+        // Counter wraps at the field width
+        assert_eq!(&packets[0][..2], &[0xFF, 0xFE]);
+        assert_eq!(&packets[2][..2], &[0x00, 0x00]);
+        // Mode switches every two packets
+        assert_eq!(
+            packets.iter().map(|p| p[2]).collect::<Vec<_>>(),
+            vec![7, 7, 9, 9]
+        );
+        // Constant and ADC within range
+        for p in &packets {
+            assert_eq!(p[3], 0xAB);
+            let adc = u16::from_be_bytes([p[4], p[5]]);
+            assert!((98..=102).contains(&adc));
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Profile::parse("").is_err());
+        assert!(Profile::parse("counter 9").is_err());
+        assert!(Profile::parse("bogus 1").is_err());
+        assert!(Profile::parse("mode 1 period=4").is_err());
+        assert!(Profile::parse("adc 2 mean=abc").is_err());
+    }
+
+    #[cfg(all(feature = "encoder", feature = "decoder"))]
+    #[test]
+    fn test_synthetic_round_trip() {
+        let data = generate(&Profile::housekeeping(), 200, 42);
+        let compressed = compress(&data, 720, 2, 20, 50, 100).unwrap();
+        assert!(compressed.len() < data.len() / 2);
+        assert_eq!(decompress(&compressed, 720, 2).unwrap(), data);
+    }
+}
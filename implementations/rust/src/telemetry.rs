@@ -0,0 +1,647 @@
+//! TM transfer frame ingestion.
+//!
+//! Recorder dumps hold fixed-length TM transfer frames (CCSDS 132.0-B-3),
+//! each carrying a slice of the space packets of one virtual channel.
+//! [`frames`] walks the frames of a dump, [`PacketReassembler`] follows
+//! the first header pointers to cut the packets back out (packets may span
+//! frames), and [`compress_dump`] groups the packets by APID and
+//! compresses each group into a container segment, one file per APID.
+//!
+//! ## Frame Layout
+//!
+//! | Field                  | Size      | Notes                                   |
+//! |------------------------|-----------|-----------------------------------------|
+//! | Attached Sync Marker   | 4         | Optional, see [`FrameFormat::asm`]      |
+//! | Primary header         | 6         | Version 0, SCID, VCID, OCF flag, counts |
+//! | Secondary header       | 1-64      | If flagged; length in its first byte    |
+//! | Data field             | remainder | Space packets                           |
+//! | Operational control    | 4         | If flagged in the primary header        |
+//! | Frame error control    | 2         | Optional CRC-16, see [`FrameFormat::fecf`] |
+//!
+//! A frame whose FECF does not match, or whose virtual channel frame count
+//! skips, breaks the packet in progress on that channel; reassembly
+//! resumes at the first packet header of a later frame.
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "encoder")]
+//! # {
+//! use pocketplus::telemetry::{compress_dump, FrameFormat, TelemetryConfig};
+//! use pocketplus::CompressOptions;
+//!
+//! let dump = std::fs::read("recorder.tm").unwrap();
+//! let config = TelemetryConfig::new(FrameFormat::new(1115).asm(true).fecf(true))
+//!     .options(CompressOptions::new(1, 10, 20, 50));
+//! let report = compress_dump(&dump, &config).unwrap();
+//! report.write_files("archives").unwrap(); // archives/apid0100.pkt, ...
+//! # }
+//! ```
+
+use crate::crc::crc16;
+use crate::error::PocketError;
+use crate::spp::read_header;
+use crate::sync::CCSDS_ASM;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "encoder")]
+use crate::compress::CompressOptions;
+#[cfg(feature = "encoder")]
+use crate::container::SegmentEncoder;
+#[cfg(feature = "encoder")]
+use crate::spp::{MAX_APID, PRIMARY_HEADER_LEN};
+#[cfg(feature = "encoder")]
+use std::path::{Path, PathBuf};
+
+/// Length of the TM transfer frame primary header.
+pub const TM_PRIMARY_HEADER_LEN: usize = 6;
+
+/// First header pointer of a frame in which no packet starts.
+pub const FHP_NO_PACKET: u16 = 0x7FF;
+
+/// First header pointer of a frame holding only idle data.
+pub const FHP_IDLE: u16 = 0x7FE;
+
+/// Length of the operational control field.
+const OCF_LEN: usize = 4;
+
+/// Length of the frame error control field.
+const FECF_LEN: usize = 2;
+
+/// Physical layout of the frames in a dump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameFormat {
+    frame_len: usize,
+    asm: bool,
+    fecf: bool,
+}
+
+impl FrameFormat {
+    /// Frames of `frame_len` bytes (primary header to FECF inclusive),
+    /// without sync markers or FECF.
+    pub fn new(frame_len: usize) -> Self {
+        Self {
+            frame_len,
+            asm: false,
+            fecf: false,
+        }
+    }
+
+    /// Every frame is preceded by the CCSDS Attached Sync Marker
+    /// (default false).
+    #[must_use]
+    pub fn asm(mut self, asm: bool) -> Self {
+        self.asm = asm;
+        self
+    }
+
+    /// Frames end with a CRC-16 frame error control field (default false).
+    #[must_use]
+    pub fn fecf(mut self, fecf: bool) -> Self {
+        self.fecf = fecf;
+        self
+    }
+
+    /// Bytes taken by one frame in the dump, sync marker included.
+    pub fn slot_len(&self) -> usize {
+        self.frame_len + if self.asm { CCSDS_ASM.len() } else { 0 }
+    }
+}
+
+/// One TM transfer frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TmFrame<'a> {
+    /// Spacecraft identifier.
+    pub spacecraft_id: u16,
+    /// Virtual channel identifier (0-7).
+    pub virtual_channel: u8,
+    /// Master channel frame count.
+    pub mc_count: u8,
+    /// Virtual channel frame count.
+    pub vc_count: u8,
+    /// Offset of the first packet header in the data field, or
+    /// [`FHP_NO_PACKET`] / [`FHP_IDLE`].
+    pub first_header_pointer: u16,
+    /// The frame data field.
+    pub data: &'a [u8],
+}
+
+impl<'a> TmFrame<'a> {
+    /// Parse one frame.
+    ///
+    /// # Arguments
+    /// * `frame` - Exactly one frame, without sync marker
+    /// * `fecf` - The frame ends with a CRC-16, which is verified
+    ///
+    /// # Returns
+    /// The frame. Fails on a version other than 0, a FECF mismatch or
+    /// fields that do not fit in the frame.
+    pub fn parse(frame: &'a [u8], fecf: bool) -> Result<Self, PocketError> {
+        if frame.len() < TM_PRIMARY_HEADER_LEN + if fecf { FECF_LEN } else { 0 } {
+            return Err(PocketError::UnexpectedEndOfInput);
+        }
+        let id = u16::from_be_bytes([frame[0], frame[1]]);
+        if id >> 14 != 0 {
+            return Err(PocketError::InvalidFormat(format!(
+                "unsupported transfer frame version {}",
+                id >> 14
+            )));
+        }
+        let mut end = frame.len();
+        if fecf {
+            end -= FECF_LEN;
+            let expected = u16::from_be_bytes([frame[end], frame[end + 1]]);
+            if crc16(&frame[..end]) != expected {
+                return Err(PocketError::InvalidFormat(
+                    "transfer frame FECF mismatch".into(),
+                ));
+            }
+        }
+        if id & 1 != 0 {
+            end = end
+                .checked_sub(OCF_LEN)
+                .ok_or(PocketError::UnexpectedEndOfInput)?;
+        }
+
+        let status = u16::from_be_bytes([frame[4], frame[5]]);
+        let mut start = TM_PRIMARY_HEADER_LEN;
+        if status & 0x8000 != 0 {
+            // Secondary header: its first byte holds its length minus 1
+            let len =
+                usize::from(*frame.get(start).ok_or(PocketError::UnexpectedEndOfInput)? & 0x3F) + 1;
+            start += len;
+        }
+        if start > end {
+            return Err(PocketError::InvalidFormat(
+                "transfer frame headers exceed the frame".into(),
+            ));
+        }
+
+        Ok(Self {
+            spacecraft_id: (id >> 4) & 0x3FF,
+            virtual_channel: ((id >> 1) & 0x7) as u8,
+            mc_count: frame[2],
+            vc_count: frame[3],
+            first_header_pointer: status & 0x7FF,
+            data: &frame[start..end],
+        })
+    }
+}
+
+/// Iterator over the frames of a dump, created by [`frames`].
+///
+/// Yields one result per frame slot; a slot that does not parse yields an
+/// error and the walk continues with the next slot. A partial slot at the
+/// end of the dump is ignored.
+pub struct Frames<'a> {
+    dump: &'a [u8],
+    format: FrameFormat,
+    pos: usize,
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Result<TmFrame<'a>, PocketError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.dump.get(self.pos..self.pos + self.format.slot_len())?;
+        self.pos += slot.len();
+        let frame = if self.format.asm {
+            if slot[..CCSDS_ASM.len()] != CCSDS_ASM {
+                return Some(Err(PocketError::InvalidFormat(
+                    "missing sync marker before transfer frame".into(),
+                )));
+            }
+            &slot[CCSDS_ASM.len()..]
+        } else {
+            slot
+        };
+        Some(TmFrame::parse(frame, self.format.fecf))
+    }
+}
+
+/// Walk the frames of a recorder dump.
+///
+/// # Arguments
+/// * `dump` - Frames back to back, each in a slot of
+///   [`FrameFormat::slot_len`] bytes
+/// * `format` - Frame length, sync markers and FECF
+pub fn frames<'a>(dump: &'a [u8], format: &FrameFormat) -> Frames<'a> {
+    Frames {
+        dump,
+        format: format.clone(),
+        pos: 0,
+    }
+}
+
+/// Reassembly state of one virtual channel.
+#[derive(Default)]
+struct Channel {
+    /// Expected frame count of the next frame.
+    next_count: Option<u8>,
+    /// Bytes of the packet in progress.
+    partial: Vec<u8>,
+    /// `partial` starts at a packet header.
+    synced: bool,
+}
+
+/// Space packets cut out of the frames of each virtual channel.
+#[derive(Default)]
+pub struct PacketReassembler {
+    channels: BTreeMap<u8, Channel>,
+    lost_frames: usize,
+    dropped_bytes: usize,
+}
+
+impl PacketReassembler {
+    /// Create a reassembler for all virtual channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Frames missing from the virtual channel frame counts.
+    pub fn lost_frames(&self) -> usize {
+        self.lost_frames
+    }
+
+    /// Bytes of partial packets dropped after a break in a channel.
+    pub fn dropped_bytes(&self) -> usize {
+        self.dropped_bytes
+    }
+
+    /// Feed one frame and hand every packet it completes to `emit`.
+    ///
+    /// # Arguments
+    /// * `frame` - The next frame of the dump
+    /// * `emit` - Called with each complete space packet, primary header
+    ///   included
+    pub fn push(&mut self, frame: &TmFrame<'_>, mut emit: impl FnMut(&[u8])) {
+        let channel = self.channels.entry(frame.virtual_channel).or_default();
+        if let Some(expected) = channel.next_count {
+            let gap = frame.vc_count.wrapping_sub(expected);
+            if gap > 0 {
+                self.lost_frames += usize::from(gap);
+                self.dropped_bytes += channel.partial.len();
+                channel.partial.clear();
+                channel.synced = false;
+            }
+        }
+        channel.next_count = Some(frame.vc_count.wrapping_add(1));
+
+        let pointer = usize::from(frame.first_header_pointer);
+        match frame.first_header_pointer {
+            FHP_IDLE => {}
+            FHP_NO_PACKET => {
+                if channel.synced {
+                    channel.partial.extend_from_slice(frame.data);
+                    Self::drain(channel, &mut self.dropped_bytes, &mut emit);
+                }
+            }
+            _ if pointer > frame.data.len() => {
+                self.dropped_bytes += channel.partial.len();
+                channel.partial.clear();
+                channel.synced = false;
+            }
+            _ => {
+                if channel.synced {
+                    channel.partial.extend_from_slice(&frame.data[..pointer]);
+                    Self::drain(channel, &mut self.dropped_bytes, &mut emit);
+                }
+                // Whatever did not complete a packet before the pointer is lost
+                self.dropped_bytes += channel.partial.len();
+                channel.partial.clear();
+                channel.partial.extend_from_slice(&frame.data[pointer..]);
+                channel.synced = true;
+                Self::drain(channel, &mut self.dropped_bytes, &mut emit);
+            }
+        }
+    }
+
+    /// Emit the complete packets at the start of a channel's buffer.
+    fn drain(channel: &mut Channel, dropped: &mut usize, emit: &mut impl FnMut(&[u8])) {
+        let mut pos = 0;
+        loop {
+            match read_header(&channel.partial[pos..]) {
+                Ok((_, _, len)) => {
+                    emit(&channel.partial[pos..pos + len]);
+                    pos += len;
+                }
+                Err(PocketError::UnexpectedEndOfInput) => break,
+                Err(_) => {
+                    // Not a packet header: wait for the next pointer
+                    *dropped += channel.partial.len() - pos;
+                    pos = channel.partial.len();
+                    channel.synced = false;
+                    break;
+                }
+            }
+        }
+        channel.partial.drain(..pos);
+    }
+}
+
+/// Settings of [`compress_dump`].
+#[cfg(feature = "encoder")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TelemetryConfig {
+    format: FrameFormat,
+    options: CompressOptions,
+    skip: usize,
+}
+
+#[cfg(feature = "encoder")]
+impl TelemetryConfig {
+    /// Compress the packets of frames in `format` with R = 1, pt = 10,
+    /// ft = 20, rt = 50.
+    pub fn new(format: FrameFormat) -> Self {
+        Self {
+            format,
+            options: CompressOptions::new(1, 10, 20, 50),
+            skip: 0,
+        }
+    }
+
+    /// Compression parameters for every APID.
+    #[must_use]
+    pub fn options(mut self, options: CompressOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Drop the first `bytes` of the data field of packets with the
+    /// secondary header flag set, e.g. a PUS TM secondary header (default 0).
+    #[must_use]
+    pub fn skip_secondary_header(mut self, bytes: usize) -> Self {
+        self.skip = bytes;
+        self
+    }
+}
+
+/// The compressed packets of one APID.
+#[cfg(feature = "encoder")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApidArchive {
+    /// Application process identifier.
+    pub apid: u16,
+    /// Length of each compressed packet before compression, in bytes: the
+    /// most common data field length of the APID.
+    pub packet_len: usize,
+    /// Packets compressed.
+    pub packets: usize,
+    /// Packets of the APID skipped for another data field length.
+    pub wrong_length: usize,
+    /// One container segment holding the compressed packets.
+    pub segment: Vec<u8>,
+}
+
+#[cfg(feature = "encoder")]
+impl ApidArchive {
+    /// File name of the archive, e.g. `apid0100.pkt`.
+    pub fn file_name(&self) -> String {
+        format!("apid{:04}.pkt", self.apid)
+    }
+}
+
+/// Outcome of [`compress_dump`].
+#[cfg(feature = "encoder")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TelemetryReport {
+    /// Frames parsed.
+    pub frames: usize,
+    /// Frame slots that failed to parse (bad sync marker, FECF, header).
+    pub bad_frames: usize,
+    /// Frames missing from the virtual channel frame counts.
+    pub lost_frames: usize,
+    /// Bytes of packets broken by bad or lost frames.
+    pub dropped_bytes: usize,
+    /// Idle packets (APID 0x7FF) discarded.
+    pub idle_packets: usize,
+    /// One archive per APID, in ascending APID order.
+    pub archives: Vec<ApidArchive>,
+}
+
+#[cfg(feature = "encoder")]
+impl TelemetryReport {
+    /// Write every archive to `dir` under its [`ApidArchive::file_name`].
+    ///
+    /// # Returns
+    /// The paths written.
+    pub fn write_files(&self, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>, PocketError> {
+        std::fs::create_dir_all(&dir)?;
+        self.archives
+            .iter()
+            .map(|archive| {
+                let path = dir.as_ref().join(archive.file_name());
+                std::fs::write(&path, &archive.segment)?;
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+/// Compress a recorder dump into one archive per APID.
+///
+/// # Arguments
+/// * `dump` - TM transfer frames back to back
+/// * `config` - Frame format and compression settings
+///
+/// # Returns
+/// The archives and counts of what could not be used.
+#[cfg(feature = "encoder")]
+pub fn compress_dump(
+    dump: &[u8],
+    config: &TelemetryConfig,
+) -> Result<TelemetryReport, PocketError> {
+    config.options.validate()?;
+    config.options.reject_stream_checksum("compress_dump")?;
+    let mut report = TelemetryReport::default();
+    let mut reassembler = PacketReassembler::new();
+    // APID -> data field length -> data fields, concatenated
+    let mut groups: BTreeMap<u16, BTreeMap<usize, Vec<u8>>> = BTreeMap::new();
+
+    for frame in frames(dump, &config.format) {
+        let Ok(frame) = frame else {
+            report.bad_frames += 1;
+            continue;
+        };
+        report.frames += 1;
+        reassembler.push(&frame, |packet| {
+            let id = u16::from_be_bytes([packet[0], packet[1]]);
+            let apid = id & MAX_APID;
+            if apid == MAX_APID {
+                report.idle_packets += 1;
+                return;
+            }
+            let mut data = &packet[PRIMARY_HEADER_LEN..];
+            if id & 0x0800 != 0 {
+                data = data.get(config.skip..).unwrap_or_default();
+            }
+            groups
+                .entry(apid)
+                .or_default()
+                .entry(data.len())
+                .or_default()
+                .extend_from_slice(data);
+        });
+    }
+    report.lost_frames = reassembler.lost_frames();
+    report.dropped_bytes = reassembler.dropped_bytes();
+
+    let options = &config.options;
+    for (apid, lengths) in groups {
+        let total: usize = lengths
+            .iter()
+            .map(|(&len, data)| data.len().checked_div(len).unwrap_or(0))
+            .sum();
+        let Some((&packet_len, data)) = lengths
+            .iter()
+            .filter(|(&len, _)| len > 0)
+            .max_by_key(|(&len, data)| (data.len() / len, std::cmp::Reverse(len)))
+        else {
+            continue;
+        };
+        let packets = data.len() / packet_len;
+        let segment = SegmentEncoder::new(
+            packet_len * 8,
+            options.robustness,
+            options.pt_limit,
+            options.ft_limit,
+            options.rt_limit,
+        )?
+        .refresh(options.refresh)
+        .encode(data)?;
+        report.archives.push(ApidArchive {
+            apid,
+            packet_len,
+            packets,
+            wrong_length: total - packets,
+            segment,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(all(test, feature = "encoder", feature = "decoder"))]
+mod tests {
+    use super::*;
+    use crate::container::decompress_segments;
+
+    const FRAME_LEN: usize = 128;
+
+    fn packet(apid: u16, seq: u16, data: &[u8]) -> Vec<u8> {
+        let mut out = (0x0800 | apid).to_be_bytes().to_vec();
+        out.extend_from_slice(&(0xC000 | seq).to_be_bytes());
+        out.extend_from_slice(&u16::try_from(data.len() - 1).unwrap().to_be_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    /// Pack a packet stream into ASM + frame + FECF slots on VC 2.
+    fn pack(stream: &[u8], packet_starts: &[usize]) -> Vec<u8> {
+        let field = FRAME_LEN - TM_PRIMARY_HEADER_LEN - FECF_LEN;
+        let mut dump = Vec::new();
+        for (count, chunk) in stream.chunks(field).enumerate() {
+            let start = count * field;
+            let pointer = packet_starts
+                .iter()
+                .find(|&&p| p >= start && p < start + field)
+                .map_or(FHP_NO_PACKET, |&p| u16::try_from(p - start).unwrap());
+            let mut frame = (0x0123u16 << 4 | 2 << 1).to_be_bytes().to_vec();
+            frame.push(u8::try_from(count % 256).unwrap());
+            frame.push(u8::try_from(count % 256).unwrap());
+            frame.extend_from_slice(&(0x1800 | pointer).to_be_bytes());
+            frame.extend_from_slice(chunk);
+            // Idle fill after the last packet
+            frame.resize(FRAME_LEN - FECF_LEN, 0x55);
+            let crc = crc16(&frame);
+            frame.extend_from_slice(&crc.to_be_bytes());
+            dump.extend_from_slice(&CCSDS_ASM);
+            dump.extend_from_slice(&frame);
+        }
+        dump
+    }
+
+    fn dump() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut stream = Vec::new();
+        let mut starts = Vec::new();
+        let (mut hk, mut aocs) = (Vec::new(), Vec::new());
+        for t in 0..120u16 {
+            let mut data = vec![0xAA; 6]; // PUS secondary header
+            data[5] = u8::try_from(t % 256).unwrap();
+            let payload: Vec<u8> = (0..40)
+                .map(|i| u8::try_from((i + t / 10) % 7).unwrap())
+                .collect();
+            data.extend_from_slice(&payload);
+            hk.extend_from_slice(&payload);
+            starts.push(stream.len());
+            stream.extend(packet(0x100, t, &data));
+
+            if t % 3 == 0 {
+                let mut data = vec![0xBB; 6];
+                let payload = [u8::try_from(t / 30).unwrap(); 18];
+                data.extend_from_slice(&payload);
+                aocs.extend_from_slice(&payload);
+                starts.push(stream.len());
+                stream.extend(packet(0x2A, t, &data));
+            }
+            if t % 50 == 0 {
+                starts.push(stream.len());
+                stream.extend(packet(MAX_APID, 0, &[0; 9]));
+            }
+        }
+        (pack(&stream, &starts), hk, aocs)
+    }
+
+    fn config() -> TelemetryConfig {
+        TelemetryConfig::new(FrameFormat::new(FRAME_LEN).asm(true).fecf(true))
+            .skip_secondary_header(6)
+    }
+
+    #[test]
+    fn test_compress_dump() {
+        let (dump, hk, aocs) = dump();
+        let report = compress_dump(&dump, &config()).unwrap();
+        assert_eq!(report.bad_frames, 0);
+        assert_eq!(report.lost_frames, 0);
+        assert_eq!(report.idle_packets, 3);
+
+        let apids: Vec<u16> = report.archives.iter().map(|a| a.apid).collect();
+        assert_eq!(apids, [0x2A, 0x100]);
+        assert_eq!(report.archives[1].file_name(), "apid0256.pkt");
+        for (archive, expected) in report.archives.iter().zip([&aocs, &hk]) {
+            assert_eq!(archive.wrong_length, 0);
+            let segments = decompress_segments(&archive.segment).unwrap();
+            assert_eq!(&segments[0].data, expected);
+        }
+        assert_eq!(report.archives[1].packets, 120);
+
+        let dir = std::env::temp_dir().join(format!("pocketplus-telemetry-{}", std::process::id()));
+        let paths = report.write_files(&dir).unwrap();
+        assert_eq!(
+            std::fs::read(&paths[0]).unwrap(),
+            report.archives[0].segment
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compress_dump_bad_frames() {
+        let (mut dump, hk, _) = dump();
+        let slot = config().format.slot_len();
+        // Corrupt frame 10 and drop frame 20
+        dump[10 * slot + 50] ^= 0xFF;
+        dump.drain(20 * slot..21 * slot);
+
+        let report = compress_dump(&dump, &config()).unwrap();
+        assert_eq!(report.bad_frames, 1);
+        assert_eq!(report.lost_frames, 2);
+        assert!(report.dropped_bytes > 0);
+
+        // Every surviving HK packet is intact and in order
+        let archive = &report.archives[1];
+        assert!(archive.packets < 120 && archive.packets > 100);
+        let data = &decompress_segments(&archive.segment).unwrap()[0].data;
+        let mut rest = &hk[..];
+        for packet in data.chunks(40) {
+            let at = rest.chunks(40).position(|p| p == packet).unwrap();
+            rest = &rest[(at + 1) * 40..];
+        }
+    }
+}
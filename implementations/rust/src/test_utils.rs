@@ -0,0 +1,271 @@
+//! Property-testing helpers (requires the `test-utils` feature).
+//!
+//! Provides [`mod@proptest`] strategies and deterministic packet generators so
+//! downstream crates can property-test their POCKET+ integrations:
+//! - Valid parameter sets (F, R, pt, ft, rt)
+//! - Packet streams with a tunable per-bit change rate
+//! - Corruption mutators for compressed streams
+//!
+//! ```rust,ignore
+//! use pocketplus::test_utils::{stream_with_params, StreamParams};
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     #[test]
+//!     fn round_trips((params, data) in stream_with_params(0.05)) {
+//!         let compressed = params.compress(&data).unwrap();
+//!         prop_assert_eq!(params.decompress(&compressed).unwrap(), data);
+//!     }
+//! }
+//! ```
+
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::cast_sign_loss)]
+
+use crate::compress::compress;
+use crate::decompress::decompress;
+use crate::error::PocketError;
+use crate::synth::XorShift;
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+/// A valid set of compression parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamParams {
+    /// Packet size in bits (F, a multiple of 8).
+    pub packet_size: usize,
+    /// Robustness level (R).
+    pub robustness: usize,
+    /// New mask period.
+    pub pt_limit: usize,
+    /// Full mask period.
+    pub ft_limit: usize,
+    /// Uncompressed packet period.
+    pub rt_limit: usize,
+}
+
+impl StreamParams {
+    /// Packet size in bytes.
+    pub fn packet_bytes(&self) -> usize {
+        self.packet_size / 8
+    }
+
+    /// Compress data with these parameters.
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, PocketError> {
+        compress(
+            data,
+            self.packet_size,
+            self.robustness,
+            self.pt_limit,
+            self.ft_limit,
+            self.rt_limit,
+        )
+    }
+
+    /// Decompress data with these parameters.
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, PocketError> {
+        decompress(data, self.packet_size, self.robustness)
+    }
+}
+
+/// Strategy for valid parameter sets.
+///
+/// Packet sizes range from 1 to 128 bytes; periods from 1 to 64 packets.
+pub fn params() -> impl Strategy<Value = StreamParams> {
+    (
+        1usize..=128,
+        0usize..=7,
+        1usize..=64,
+        1usize..=64,
+        1usize..=64,
+    )
+        .prop_map(
+            |(bytes, robustness, pt_limit, ft_limit, rt_limit)| StreamParams {
+                packet_size: bytes * 8,
+                robustness,
+                pt_limit,
+                ft_limit,
+                rt_limit,
+            },
+        )
+}
+
+/// Uniform value in [0, 1).
+fn next_f64(rng: &mut XorShift) -> f64 {
+    (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Generate a packet stream by randomly flipping bits of a base packet.
+///
+/// Each packet starts from the previous one and flips every bit
+/// independently with probability `change_rate`.
+///
+/// # Arguments
+/// * `base` - First packet
+/// * `num_packets` - Number of packets to generate
+/// * `change_rate` - Per-bit flip probability (0.0-1.0)
+/// * `seed` - Generator seed
+///
+/// # Returns
+/// `num_packets * base.len()` bytes.
+pub fn generate_packets(base: &[u8], num_packets: usize, change_rate: f64, seed: u64) -> Vec<u8> {
+    let mut rng = XorShift::new(seed);
+    let mut packet = base.to_vec();
+    let mut data = Vec::with_capacity(base.len() * num_packets);
+
+    for _ in 0..num_packets {
+        data.extend_from_slice(&packet);
+        for byte in &mut packet {
+            for bit in 0..8 {
+                if next_f64(&mut rng) < change_rate {
+                    *byte ^= 1 << bit;
+                }
+            }
+        }
+    }
+
+    data
+}
+
+/// Strategy for packet streams with a tunable change rate.
+///
+/// # Arguments
+/// * `packet_bytes` - Packet size in bytes
+/// * `max_packets` - Maximum number of packets (at least one is generated)
+/// * `change_rate` - Per-bit flip probability between consecutive packets
+pub fn packet_stream(
+    packet_bytes: usize,
+    max_packets: usize,
+    change_rate: f64,
+) -> impl Strategy<Value = Vec<u8>> {
+    (
+        vec(any::<u8>(), packet_bytes),
+        1..=max_packets.max(1),
+        any::<u64>(),
+    )
+        .prop_map(move |(base, num_packets, seed)| {
+            generate_packets(&base, num_packets, change_rate, seed)
+        })
+}
+
+/// Strategy for a parameter set together with a matching packet stream.
+///
+/// # Arguments
+/// * `change_rate` - Per-bit flip probability between consecutive packets
+pub fn stream_with_params(change_rate: f64) -> impl Strategy<Value = (StreamParams, Vec<u8>)> {
+    params().prop_flat_map(move |p| (Just(p), packet_stream(p.packet_bytes(), 64, change_rate)))
+}
+
+/// A mutation applied to a compressed stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// Flip the bit at this bit offset.
+    FlipBit(usize),
+    /// Keep only this many bytes.
+    Truncate(usize),
+    /// Insert a byte at this offset.
+    InsertByte(usize, u8),
+    /// Remove the byte at this offset.
+    DeleteByte(usize),
+}
+
+impl Corruption {
+    /// Apply the mutation in place.
+    ///
+    /// Offsets are reduced modulo the stream length, so any corruption can
+    /// be applied to any non-empty stream.
+    pub fn apply(&self, data: &mut Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+        match *self {
+            Self::FlipBit(bit) => {
+                let bit = bit % (data.len() * 8);
+                data[bit / 8] ^= 0x80 >> (bit % 8);
+            }
+            Self::Truncate(len) => data.truncate(len % data.len()),
+            Self::InsertByte(offset, value) => data.insert(offset % (data.len() + 1), value),
+            Self::DeleteByte(offset) => {
+                data.remove(offset % data.len());
+            }
+        }
+    }
+}
+
+/// Strategy for arbitrary corruptions.
+pub fn corruption() -> impl Strategy<Value = Corruption> {
+    prop_oneof![
+        any::<usize>().prop_map(Corruption::FlipBit),
+        any::<usize>().prop_map(Corruption::Truncate),
+        (any::<usize>(), any::<u8>()).prop_map(|(o, v)| Corruption::InsertByte(o, v)),
+        any::<usize>().prop_map(Corruption::DeleteByte),
+    ]
+}
+
+/// Strategy for a copy of `stream` with one to `max_corruptions` mutations.
+pub fn corrupted_stream(stream: Vec<u8>, max_corruptions: usize) -> impl Strategy<Value = Vec<u8>> {
+    vec(corruption(), 1..=max_corruptions.max(1)).prop_map(move |corruptions| {
+        let mut data = stream.clone();
+        for c in &corruptions {
+            c.apply(&mut data);
+        }
+        data
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_round_trip((p, data) in stream_with_params(0.05)) {
+            let compressed = p.compress(&data).unwrap();
+            prop_assert_eq!(p.decompress(&compressed).unwrap(), data);
+        }
+
+        #[test]
+        fn prop_corruption_never_panics(
+            c in corrupted_stream(
+                compress(&generate_packets(&[0x5A; 16], 20, 0.02, 1), 128, 1, 10, 20, 50).unwrap(),
+                4,
+            )
+        ) {
+            let _ = decompress(&c, 128, 1);
+        }
+    }
+
+    #[test]
+    fn test_generate_packets_deterministic() {
+        let a = generate_packets(&[0u8; 10], 5, 0.1, 42);
+        let b = generate_packets(&[0u8; 10], 5, 0.1, 42);
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 50);
+        assert_eq!(&a[..10], &[0u8; 10]);
+    }
+
+    #[test]
+    fn test_generate_packets_zero_rate() {
+        let data = generate_packets(&[0xAB; 4], 3, 0.0, 7);
+        assert_eq!(data, vec![0xAB; 12]);
+    }
+
+    #[test]
+    fn test_corruption_apply() {
+        let mut data = vec![0u8; 4];
+        Corruption::FlipBit(9).apply(&mut data);
+        assert_eq!(data, vec![0, 0x40, 0, 0]);
+
+        Corruption::Truncate(2).apply(&mut data);
+        assert_eq!(data.len(), 2);
+
+        Corruption::InsertByte(0, 0xFF).apply(&mut data);
+        assert_eq!(data, vec![0xFF, 0, 0x40]);
+
+        Corruption::DeleteByte(1).apply(&mut data);
+        assert_eq!(data, vec![0xFF, 0x40]);
+    }
+}
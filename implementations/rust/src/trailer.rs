@@ -0,0 +1,164 @@
+//! Whole-stream integrity trailer.
+//!
+//! A corrupted archive usually surfaces as a confusing decode error far
+//! from the damage, or not at all when the damaged bits still decode. An
+//! optional trailer at the end of a compressed stream records a CRC-32 of
+//! the stream, so corruption is detected deterministically before
+//! decoding.
+//!
+//! ## Layout (big-endian)
+//!
+//! | Size | Field                                      |
+//! |------|--------------------------------------------|
+//! | n    | Compressed stream                          |
+//! | 4    | Magic `"PK+T"`                             |
+//! | 4    | CRC-32 of the stream and the magic         |
+//!
+//! [`CompressorBuilder::stream_checksum`](crate::CompressorBuilder::stream_checksum)
+//! makes [`compress_with`](crate::compress_with) and
+//! [`compress_with_progress`](crate::compress_with_progress) append the
+//! trailer. Every whole-stream decoder, from
+//! [`decompress`](crate::decompress) to
+//! [`Decompressor::iter_packets`](crate::Decompressor::iter_packets),
+//! verifies and strips it when the stream ends in the magic, and fails
+//! with a checksum mismatch if the CRC does not match. A stream without
+//! the magic is decoded as before.
+//!
+//! [`DecompressorBuilder::stream_checksum`](crate::DecompressorBuilder::stream_checksum)
+//! overrides the detection: `true` also rejects a stream without a
+//! trailer, `false` decodes every byte as stream data, for the rare plain
+//! stream that happens to end in the magic.
+
+use crate::crc::crc32;
+use crate::error::PocketError;
+
+/// Trailer magic.
+const MAGIC: [u8; 4] = *b"PK+T";
+
+/// Length of the trailer in bytes.
+pub const TRAILER_LEN: usize = 8;
+
+/// Append a trailer to a compressed stream.
+///
+/// # Arguments
+/// * `stream` - Compressed stream; the trailer is appended in place
+pub fn append_trailer(stream: &mut Vec<u8>) {
+    stream.extend_from_slice(&MAGIC);
+    let crc = crc32(stream);
+    stream.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Returns true if `data` ends in a trailer, whether or not its checksum
+/// matches.
+pub fn has_trailer(data: &[u8]) -> bool {
+    data.len() >= TRAILER_LEN && data[data.len() - TRAILER_LEN..][..4] == MAGIC
+}
+
+/// Verify and remove the trailer of a compressed stream.
+///
+/// # Arguments
+/// * `data` - Compressed stream, with or without a trailer
+///
+/// # Returns
+/// The stream without its trailer, or `data` unchanged if it does not end
+/// in the magic. Fails with [`PocketError::InvalidFormat`] if the
+/// checksum does not match.
+pub fn strip_trailer(data: &[u8]) -> Result<&[u8], PocketError> {
+    if has_trailer(data) {
+        verify_trailer(data)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Verify and remove the trailer of a stream that must have one.
+///
+/// # Arguments
+/// * `data` - Compressed stream sealed with [`append_trailer`]
+///
+/// # Returns
+/// The stream without its trailer. Fails with
+/// [`PocketError::InvalidFormat`] if the trailer is missing or its
+/// checksum does not match.
+pub fn verify_trailer(data: &[u8]) -> Result<&[u8], PocketError> {
+    if !has_trailer(data) {
+        return Err(PocketError::InvalidFormat("missing stream checksum".into()));
+    }
+    let (body, crc) = data.split_at(data.len() - 4);
+    if crc32(body).to_be_bytes() != crc {
+        return Err(PocketError::InvalidFormat(
+            "stream checksum mismatch".into(),
+        ));
+    }
+    Ok(&body[..body.len() - MAGIC.len()])
+}
+
+/// Apply a decoder's trailer setting to a whole stream.
+///
+/// # Arguments
+/// * `data` - Compressed stream
+/// * `required` - `Some(true)` to require a trailer, `Some(false)` to keep
+///   every byte, `None` to strip a trailer when present
+#[cfg(feature = "decoder")]
+pub(crate) fn apply_trailer(data: &[u8], required: Option<bool>) -> Result<&[u8], PocketError> {
+    match required {
+        None => strip_trailer(data),
+        Some(true) => verify_trailer(data),
+        Some(false) => Ok(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trailer_round_trip() {
+        let stream = vec![0x12, 0x34, 0x56];
+        let mut sealed = stream.clone();
+        append_trailer(&mut sealed);
+        assert_eq!(sealed.len(), 3 + TRAILER_LEN);
+        assert!(has_trailer(&sealed));
+        assert_eq!(strip_trailer(&sealed).unwrap(), stream.as_slice());
+        assert_eq!(verify_trailer(&sealed).unwrap(), stream.as_slice());
+
+        // No trailer: unchanged
+        assert!(!has_trailer(&stream));
+        assert_eq!(strip_trailer(&stream).unwrap(), stream.as_slice());
+        assert!(verify_trailer(&stream).is_err());
+
+        // Any flipped bit is caught
+        for bit in 0..sealed.len() * 8 {
+            let mut corrupted = sealed.clone();
+            corrupted[bit / 8] ^= 0x80 >> (bit % 8);
+            assert!(verify_trailer(&corrupted).is_err(), "bit {bit}");
+            if has_trailer(&corrupted) {
+                assert!(strip_trailer(&corrupted).is_err(), "bit {bit}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_plain_stream_ending_in_magic() {
+        // Detected as a damaged trailer unless the decoder opts out
+        let stream = [0x12, 0x34, b'P', b'K', b'+', b'T', 0x00, 0x00, 0x00, 0x00];
+        assert!(has_trailer(&stream));
+        let mismatch = Err(PocketError::InvalidFormat(
+            "stream checksum mismatch".into(),
+        ));
+        assert_eq!(strip_trailer(&stream), mismatch);
+        assert_eq!(verify_trailer(&stream), mismatch);
+        #[cfg(feature = "decoder")]
+        {
+            assert_eq!(apply_trailer(&stream, None), mismatch);
+            assert_eq!(
+                apply_trailer(&stream, Some(false)).unwrap(),
+                stream.as_slice()
+            );
+            assert_eq!(
+                apply_trailer(&stream[..4], Some(true)),
+                Err(PocketError::InvalidFormat("missing stream checksum".into()))
+            );
+        }
+    }
+}
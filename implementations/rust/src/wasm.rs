@@ -0,0 +1,161 @@
+//! WebAssembly bindings (`wasm` feature).
+//!
+//! Wrappers for browser-based telemetry tools, exported through
+//! `wasm-bindgen`: byte arrays (`Uint8Array`) in and out, packet sizes in
+//! bits, and errors thrown as JavaScript `Error`s instead of panics.
+//!
+//! ```text
+//! cargo build --lib --release --target wasm32-unknown-unknown --features wasm
+//! wasm-bindgen --target web target/wasm32-unknown-unknown/release/pocketplus.wasm --out-dir pkg
+//! ```
+//!
+//! ```js
+//! import init, { compress, decompress, StreamDecompressor } from "./pkg/pocketplus.js";
+//!
+//! await init();
+//! const packets = decompress(archive, 720, 1);
+//!
+//! // Decode an archive while it downloads
+//! const decoder = new StreamDecompressor(720, 1);
+//! for await (const chunk of response.body) {
+//!     show(decoder.push(chunk));
+//! }
+//! decoder.finish();
+//! ```
+
+use crate::bitvector::MAX_PACKET_LENGTH;
+use crate::decompress::Decompressor;
+use crate::error::PocketError;
+use wasm_bindgen::prelude::*;
+
+/// Convert an error into a JavaScript `Error`.
+fn js_error(err: &PocketError) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// Compress whole packets.
+///
+/// # Arguments
+/// * `data` - Raw packets (a multiple of `packet_size / 8` bytes)
+/// * `packet_size` - Packet size in bits (multiple of 8)
+/// * `robustness` - Robustness level R (0-7)
+/// * `pt_limit`, `ft_limit`, `rt_limit` - Flag periods (0 = never)
+#[cfg(feature = "encoder")]
+#[wasm_bindgen]
+pub fn compress(
+    data: &[u8],
+    packet_size: usize,
+    robustness: usize,
+    pt_limit: usize,
+    ft_limit: usize,
+    rt_limit: usize,
+) -> Result<Vec<u8>, JsError> {
+    crate::compress(data, packet_size, robustness, pt_limit, ft_limit, rt_limit)
+        .map_err(|e| js_error(&e))
+}
+
+/// Decompress a byte-aligned stream.
+///
+/// # Arguments
+/// * `data` - Compressed stream
+/// * `packet_size` - Packet size in bits (multiple of 8)
+/// * `robustness` - Robustness level R used for compression
+#[wasm_bindgen]
+pub fn decompress(data: &[u8], packet_size: usize, robustness: usize) -> Result<Vec<u8>, JsError> {
+    crate::decompress(data, packet_size, robustness).map_err(|e| js_error(&e))
+}
+
+/// Decompressor fed with arbitrary chunks of a byte-aligned stream.
+///
+/// Each [`push`](Self::push) returns the packets completed by the chunk;
+/// bytes of an incomplete packet are kept until the next chunk.
+#[wasm_bindgen]
+pub struct StreamDecompressor {
+    decomp: Decompressor,
+    pending: Vec<u8>,
+    packets: usize,
+}
+
+#[wasm_bindgen]
+impl StreamDecompressor {
+    /// Create a decompressor.
+    ///
+    /// # Arguments
+    /// * `packet_size` - Packet size in bits (multiple of 8)
+    /// * `robustness` - Robustness level R used for compression
+    #[wasm_bindgen(constructor)]
+    pub fn new(packet_size: usize, robustness: usize) -> Result<StreamDecompressor, JsError> {
+        if packet_size == 0 || packet_size > MAX_PACKET_LENGTH || packet_size % 8 != 0 {
+            return Err(js_error(&PocketError::InvalidPacketSize(packet_size)));
+        }
+        let robustness = u8::try_from(robustness)
+            .map_err(|_| js_error(&PocketError::InvalidRobustness(robustness)))?;
+        let decomp = Decompressor::new(packet_size, None, robustness).map_err(|e| js_error(&e))?;
+        Ok(Self {
+            decomp,
+            pending: Vec::new(),
+            packets: 0,
+        })
+    }
+
+    /// Append a chunk and decode every packet it completes.
+    ///
+    /// # Returns
+    /// The decoded packets, concatenated (possibly empty).
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>, JsError> {
+        self.pending.extend_from_slice(chunk);
+        let mut output = Vec::new();
+        let consumed = self
+            .decomp
+            .decompress_available(&self.pending, &mut output)
+            .map_err(|e| js_error(&e))?;
+        self.pending.drain(..consumed);
+        self.packets += output.len() / (self.decomp.packet_size() / 8);
+        Ok(output)
+    }
+
+    /// Check that the stream ended on a packet boundary.
+    pub fn finish(&self) -> Result<(), JsError> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            Err(js_error(&PocketError::UnexpectedEndOfInput))
+        }
+    }
+
+    /// Number of packets decoded so far.
+    #[wasm_bindgen(getter)]
+    pub fn packets(&self) -> usize {
+        self.packets
+    }
+
+    /// Bytes of an incomplete packet waiting for the next chunk.
+    #[wasm_bindgen(getter)]
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_decompressor_chunks() {
+        let data: Vec<u8> = (0..90 * 40)
+            .map(|i| u8::try_from(i % 90 / 3 + i / 900).unwrap())
+            .collect();
+        let stream = compress(&data, 720, 1, 10, 20, 50).ok().unwrap();
+        assert_eq!(decompress(&stream, 720, 1).ok().unwrap(), data);
+
+        let mut decoder = StreamDecompressor::new(720, 1).ok().unwrap();
+        let mut output = Vec::new();
+        for chunk in stream.chunks(7) {
+            output.extend(decoder.push(chunk).ok().unwrap());
+            assert_eq!(output.len(), decoder.packets() * 90);
+        }
+        assert!(decoder.finish().is_ok());
+        assert_eq!(decoder.packets(), 40);
+        assert_eq!(output, data);
+    }
+}
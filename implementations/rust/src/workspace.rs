@@ -0,0 +1,396 @@
+//! Preallocated compressor storage.
+//!
+//! A [`Workspace`] reserves, in one place and up front, every buffer a
+//! [`Compressor`](crate::Compressor) needs: the mask, build and input
+//! vectors, the change and flag histories, the per-packet scratch vectors
+//! and an output buffer sized for the worst-case packet. A compressor
+//! created with [`Compressor::new_in`](crate::Compressor::new_in) takes
+//! this storage instead of allocating its own, and
+//! [`Compressor::release`](crate::Compressor::release) hands it back for
+//! the next pass.
+//!
+//! Together with
+//! [`Compressor::compress_packet_to_slice`](crate::Compressor::compress_packet_to_slice),
+//! which writes into a caller-provided byte slice, steady-state
+//! compression performs no heap allocation, so flight-like software can
+//! budget all codec memory at initialization. Software without a heap at
+//! all can place the storage in `static` buffers with
+//! [`Workspace::from_buffers`], sized by [`Workspace::buffer_words`] and
+//! [`Workspace::buffer_bytes`].
+//!
+//! ```rust
+//! use pocketplus::{Compressor, Workspace};
+//!
+//! // At initialization
+//! let mut workspace = Workspace::new(720, None).unwrap();
+//! let mut comp = Compressor::new_in(&mut workspace, 1, 10, 20, 50).unwrap();
+//! let mut frame = vec![0u8; comp.max_packet_bytes()];
+//!
+//! // Per packet: no allocation
+//! let packet = pocketplus::BitVector::from_bytes(&[0x5A; 90], 720);
+//! let params = comp.next_params();
+//! let len = comp.compress_packet_to_slice(&packet, &params, &mut frame).unwrap();
+//! # assert!(len > 0);
+//!
+//! // End of pass
+//! comp.release(&mut workspace);
+//! assert!(workspace.is_available());
+//! ```
+
+use crate::bitbuffer::BitBuffer;
+use crate::bitvector::{BitVector, MAX_PACKET_LENGTH};
+use crate::compress::{packet_capacity, MAX_HISTORY, MAX_VT_HISTORY};
+use crate::error::PocketError;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Bit vectors held by a compressor besides its histories: mask, previous
+/// mask, build, previous build, previous input, Xₜ and one scratch vector.
+pub(crate) const STATE_VECTORS: usize = 7;
+
+/// Initial mask M₀ of a compressor.
+#[derive(Clone)]
+pub(crate) enum InitialMask {
+    /// Shared between contexts of the same packet type.
+    Shared(Arc<BitVector>),
+    /// Owned by one compressor, possibly in caller-provided storage.
+    Owned(BitVector),
+}
+
+impl InitialMask {
+    /// Mutable access, copying a mask shared with other compressors.
+    pub(crate) fn make_mut(&mut self) -> &mut BitVector {
+        match self {
+            Self::Shared(mask) => Arc::make_mut(mask),
+            Self::Owned(mask) => mask,
+        }
+    }
+
+    /// Heap memory held by the mask, in bytes. A shared mask is only
+    /// counted while there is a single owner.
+    pub(crate) fn heap_size(&self) -> usize {
+        match self {
+            Self::Shared(mask) if Arc::strong_count(mask) > 1 => 0,
+            Self::Shared(mask) => mask.heap_size(),
+            Self::Owned(mask) => mask.heap_size(),
+        }
+    }
+}
+
+impl Deref for InitialMask {
+    type Target = BitVector;
+
+    fn deref(&self) -> &BitVector {
+        match self {
+            Self::Shared(mask) => mask,
+            Self::Owned(mask) => mask,
+        }
+    }
+}
+
+/// Storage taken by a compressor.
+pub(crate) struct Storage {
+    pub(crate) initial_mask: InitialMask,
+    pub(crate) vectors: [BitVector; STATE_VECTORS],
+    pub(crate) change_history: [BitVector; MAX_HISTORY],
+    pub(crate) flag_history: [bool; MAX_VT_HISTORY],
+    pub(crate) output: BitBuffer,
+}
+
+impl Storage {
+    /// Allocate storage for packets of `f` bits.
+    ///
+    /// # Arguments
+    /// * `f` - Packet length in bits (already validated)
+    /// * `initial_mask` - Initial mask M₀, `f` bits long
+    /// * `output` - Output buffer (possibly preallocated)
+    pub(crate) fn new(f: usize, initial_mask: InitialMask, output: BitBuffer) -> Self {
+        Self::with_vectors(initial_mask, || BitVector::new(f), output)
+    }
+
+    /// Assemble storage from the vectors produced by `vector`.
+    fn with_vectors(
+        initial_mask: InitialMask,
+        mut vector: impl FnMut() -> BitVector,
+        output: BitBuffer,
+    ) -> Self {
+        Self {
+            initial_mask,
+            vectors: std::array::from_fn(|_| vector()),
+            change_history: std::array::from_fn(|_| vector()),
+            flag_history: [false; MAX_VT_HISTORY],
+            output,
+        }
+    }
+}
+
+/// Preallocated storage for one compressor.
+pub struct Workspace {
+    /// Packet length in bits (F).
+    f: usize,
+    /// The storage, or `None` while a compressor holds it.
+    pub(crate) storage: Option<Storage>,
+}
+
+impl Workspace {
+    /// Allocate the storage of a compressor for packets of `f` bits.
+    ///
+    /// # Arguments
+    /// * `f` - Packet length in bits (1-65535)
+    /// * `initial_mask` - Initial mask M₀, `f` bits long (all zeros if `None`)
+    pub fn new(f: usize, initial_mask: Option<&BitVector>) -> Result<Self, PocketError> {
+        Self::validate(f, initial_mask)?;
+        let initial = initial_mask.cloned().unwrap_or_else(|| BitVector::new(f));
+        let output = BitBuffer::with_capacity(packet_capacity(f));
+        Ok(Self {
+            f,
+            storage: Some(Storage::new(f, InitialMask::Owned(initial), output)),
+        })
+    }
+
+    /// Lay out the storage of a compressor for packets of `f` bits in
+    /// caller-provided buffers, without allocating.
+    ///
+    /// `words` holds the bit vectors and must have at least
+    /// [`buffer_words(f)`](Self::buffer_words) elements; `output` holds
+    /// the reused output buffer and should have at least
+    /// [`buffer_bytes(f)`](Self::buffer_bytes) bytes. Both are typically
+    /// `static` arrays reserved at initialization. Packets that outgrow a
+    /// shorter `output` fall back to the heap.
+    ///
+    /// # Arguments
+    /// * `f` - Packet length in bits (1-65535)
+    /// * `initial_mask` - Initial mask M₀, `f` bits long (all zeros if `None`)
+    /// * `words` - Storage for the bit vectors (zeroed here)
+    /// * `output` - Storage for the output buffer
+    ///
+    /// # Static Buffers
+    ///
+    /// The buffers are borrowed for the rest of the program. Without a
+    /// heap, lend `static` arrays through a function that hands them out
+    /// only once, which keeps the one `unsafe` block in application code
+    /// and makes a second borrow impossible:
+    ///
+    /// ```rust
+    /// use pocketplus::Workspace;
+    /// use std::ptr::addr_of_mut;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    ///
+    /// const F: usize = 720;
+    ///
+    /// /// Lend the workspace buffers to the first caller, `None` afterwards.
+    /// fn take_buffers() -> Option<(&'static mut [u64], &'static mut [u8])> {
+    ///     static mut WORDS: [u64; Workspace::buffer_words(F)] = [0; Workspace::buffer_words(F)];
+    ///     static mut OUTPUT: [u8; Workspace::buffer_bytes(F)] = [0; Workspace::buffer_bytes(F)];
+    ///     static TAKEN: AtomicBool = AtomicBool::new(false);
+    ///     if TAKEN.swap(true, Ordering::AcqRel) {
+    ///         return None;
+    ///     }
+    ///     // SAFETY: only the first call gets here, so these are the only
+    ///     // references to the arrays for the rest of the program
+    ///     unsafe { Some((&mut *addr_of_mut!(WORDS), &mut *addr_of_mut!(OUTPUT))) }
+    /// }
+    ///
+    /// let (words, output) = take_buffers().unwrap();
+    /// let workspace = Workspace::from_buffers(F, None, words, output).unwrap();
+    /// assert_eq!(workspace.heap_size(), 0);
+    /// assert!(take_buffers().is_none());
+    /// ```
+    ///
+    /// Hosted programs and tests may instead leak heap buffers with
+    /// [`Box::leak`].
+    pub fn from_buffers(
+        f: usize,
+        initial_mask: Option<&BitVector>,
+        words: &'static mut [u64],
+        output: &'static mut [u8],
+    ) -> Result<Self, PocketError> {
+        Self::validate(f, initial_mask)?;
+        let needed = Self::buffer_words(f);
+        if words.len() < needed {
+            return Err(PocketError::InvalidInputLength {
+                expected: needed,
+                actual: words.len(),
+            });
+        }
+
+        // Hand out one packet-sized run of words per vector
+        let vector_words = BitVector::num_words(f);
+        let mut rest = words;
+        let mut vector = move || {
+            let (head, tail) = std::mem::take(&mut rest).split_at_mut(vector_words);
+            rest = tail;
+            BitVector::lent(head, f)
+        };
+        let mut initial = vector();
+        if let Some(mask) = initial_mask {
+            initial.copy_from(mask);
+        }
+        let storage =
+            Storage::with_vectors(InitialMask::Owned(initial), vector, BitBuffer::lent(output));
+        Ok(Self {
+            f,
+            storage: Some(storage),
+        })
+    }
+
+    /// Number of `u64` words [`from_buffers`](Self::from_buffers) needs
+    /// for packets of `f` bits.
+    pub const fn buffer_words(f: usize) -> usize {
+        (STATE_VECTORS + 1 + MAX_HISTORY) * BitVector::num_words(f)
+    }
+
+    /// Number of output bytes [`from_buffers`](Self::from_buffers) needs
+    /// for packets of `f` bits: the largest compressed packet.
+    pub const fn buffer_bytes(f: usize) -> usize {
+        packet_capacity(f)
+    }
+
+    /// Check the packet size and the initial mask length.
+    fn validate(f: usize, initial_mask: Option<&BitVector>) -> Result<(), PocketError> {
+        if f == 0 || f > MAX_PACKET_LENGTH {
+            return Err(PocketError::InvalidPacketSize(f));
+        }
+        match initial_mask {
+            Some(mask) if mask.len() != f => Err(PocketError::InvalidInputLength {
+                expected: f,
+                actual: mask.len(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Wrap storage returned by a compressor.
+    pub(crate) fn from_storage(f: usize, storage: Storage) -> Self {
+        Self {
+            f,
+            storage: Some(storage),
+        }
+    }
+
+    /// Packet length in bits (F).
+    pub fn packet_size(&self) -> usize {
+        self.f
+    }
+
+    /// True unless a compressor currently holds the storage.
+    pub fn is_available(&self) -> bool {
+        self.storage.is_some()
+    }
+
+    /// Heap memory reserved by the workspace, in bytes (0 while a
+    /// compressor holds the storage, and for buffers lent by
+    /// [`from_buffers`](Self::from_buffers)).
+    pub fn heap_size(&self) -> usize {
+        self.storage.as_ref().map_or(0, |storage| {
+            storage.initial_mask.heap_size()
+                + storage
+                    .vectors
+                    .iter()
+                    .chain(&storage.change_history)
+                    .map(BitVector::heap_size)
+                    .sum::<usize>()
+                + storage.output.heap_size()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress_with, Compressor, CompressorBuilder};
+
+    #[test]
+    fn test_workspace_compressor_matches() {
+        let data: Vec<u8> = (0..90 * 60)
+            .map(|i| u8::try_from(i % 90 / 4 + i / 1800).unwrap())
+            .collect();
+        let expected = crate::compress(&data, 720, 2, 10, 20, 50).unwrap();
+
+        let mut workspace = Workspace::new(720, None).unwrap();
+        let reserved = workspace.heap_size();
+        assert!(reserved > 0);
+
+        for _ in 0..2 {
+            let mut comp = Compressor::new_in(&mut workspace, 2, 10, 20, 50).unwrap();
+            assert!(!workspace.is_available());
+            assert!(Compressor::new_in(&mut workspace, 2, 10, 20, 50).is_err());
+
+            let mut frame = vec![0u8; comp.max_packet_bytes()];
+            let mut packet = BitVector::new(720);
+            let mut stream = Vec::new();
+            for chunk in data.chunks(90) {
+                packet.load_bytes(chunk);
+                let params = comp.next_params();
+                let len = comp
+                    .compress_packet_to_slice(&packet, &params, &mut frame)
+                    .unwrap();
+                stream.extend_from_slice(&frame[..len]);
+            }
+            assert_eq!(stream, expected);
+
+            // Nothing grew while compressing
+            assert_eq!(comp.heap_size(), reserved);
+            comp.release(&mut workspace);
+            assert_eq!(workspace.heap_size(), reserved);
+        }
+
+        let mut comp = Compressor::new_in(&mut workspace, 1, 10, 20, 50).unwrap();
+        let params = comp.next_params();
+        let mut short = [0u8; 8];
+        assert!(matches!(
+            comp.compress_packet_to_slice(&BitVector::new(720), &params, &mut short),
+            Err(PocketError::BufferOverflow)
+        ));
+        assert!(Workspace::new(0, None).is_err());
+        assert!(Workspace::new(16, Some(&BitVector::new(8))).is_err());
+    }
+
+    #[test]
+    fn test_builder_reuses_workspace() {
+        let data: Vec<u8> = (0..90 * 40)
+            .map(|i| u8::try_from(i % 90 / 8 + i / 900).unwrap())
+            .collect();
+        let mut mask = BitVector::new(720);
+        mask.set_bit(3, 1);
+
+        let mut workspace = Workspace::new(720, None).unwrap();
+        let reserved = workspace.heap_size();
+
+        // One compressor per pass, each with its own settings
+        for robustness in [0, 3] {
+            let builder = CompressorBuilder::new(720)
+                .robustness(robustness)
+                .initial_mask(&mask)
+                .exclude_ranges(&[16..32, 40..48]);
+            let expected = compress_with(&data, &builder).unwrap();
+
+            let mut comp = builder.build_in(&mut workspace).unwrap();
+            assert_eq!(comp.initial_mask(), &mask);
+            assert!(comp.excluded().is_some());
+
+            let mut frame = vec![0u8; comp.max_packet_bytes()];
+            let mut packet = BitVector::new(720);
+            let mut stream = Vec::new();
+            for chunk in data.chunks(90) {
+                packet.load_bytes(chunk);
+                let params = comp.next_params();
+                let len = comp
+                    .compress_packet_to_slice(&packet, &params, &mut frame)
+                    .unwrap();
+                stream.extend_from_slice(&frame[..len]);
+            }
+            assert_eq!(stream, expected);
+
+            comp.release(&mut workspace);
+            assert_eq!(workspace.heap_size(), reserved);
+        }
+
+        // The workspace must match the packet size
+        assert!(matches!(
+            CompressorBuilder::new(360).build_in(&mut workspace),
+            Err(PocketError::InvalidPacketSize(360))
+        ));
+        assert!(workspace.is_available());
+    }
+}
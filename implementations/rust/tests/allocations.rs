@@ -0,0 +1,244 @@
+//! Heap allocation tests.
+//!
+//! A compressor allocates all its buffers when it is created and must not
+//! touch the heap per packet: the mask, build, change history and scratch
+//! vectors are updated in place, and the reused output buffer is sized for
+//! the largest packet. A counting global allocator checks this for the
+//! packet paths that write into caller-provided memory, and for compressors
+//! taking their storage from a [`Workspace`].
+
+#![cfg(all(feature = "encoder", feature = "decoder"))]
+
+use pocketplus::{
+    compress_with, AdaptivePolicy, BitVector, Compressor, CompressorBuilder, PocketError,
+    RefreshPolicy, Workspace,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ptr::addr_of_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// System allocator counting the allocations of the current thread.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Define `fn $name()` lending `$words` words and `$bytes` bytes of static
+/// storage to its first caller, `None` afterwards (the pattern documented
+/// on [`Workspace::from_buffers`]).
+macro_rules! static_buffers {
+    ($name:ident, $words:expr, $bytes:expr) => {
+        fn $name() -> Option<(&'static mut [u64], &'static mut [u8])> {
+            static mut WORDS: [u64; $words] = [0; $words];
+            static mut OUTPUT: [u8; $bytes] = [0; $bytes];
+            static TAKEN: AtomicBool = AtomicBool::new(false);
+            if TAKEN.swap(true, Ordering::AcqRel) {
+                return None;
+            }
+            // SAFETY: only the first call gets here, so these are the only
+            // references to the arrays for the rest of the program
+            unsafe { Some((&mut *addr_of_mut!(WORDS), &mut *addr_of_mut!(OUTPUT))) }
+        }
+    };
+}
+
+/// Number of allocations made by `f` on this thread.
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Housekeeping-like packets: a counter, slowly drifting values and noise.
+fn packets(count: usize) -> Vec<BitVector> {
+    (0..count)
+        .map(|t| {
+            let bytes: Vec<u8> = (0..90)
+                .map(|i| match i {
+                    0..=3 => (t >> (8 * (3 - i))) as u8,
+                    4..=40 => (t / 16 + i) as u8,
+                    _ => ((t * 31 + i * 17) % 7) as u8 & 0x03,
+                })
+                .collect();
+            BitVector::from_bytes(&bytes, 720)
+        })
+        .collect()
+}
+
+/// Allocations made while compressing `input`, alternating between a
+/// preallocated vector and a slice as the output.
+fn compress_allocations(mut comp: Compressor, input: &[BitVector]) -> usize {
+    let mut out = Vec::with_capacity(input.len() * comp.max_packet_bytes());
+    let mut slice = vec![0u8; comp.max_packet_bytes()];
+
+    count_allocations(|| {
+        for (t, packet) in input.iter().enumerate() {
+            let params = comp.next_params();
+            if t % 2 == 0 {
+                comp.compress_packet_into(packet, &params, &mut out)
+                    .unwrap();
+            } else {
+                comp.compress_packet_to_slice(packet, &params, &mut slice)
+                    .unwrap();
+            }
+        }
+    })
+}
+
+#[test]
+fn test_compress_without_allocation() {
+    let input = packets(300);
+
+    for robustness in [0, 1, 7] {
+        let comp = Compressor::new(720, None, robustness, 10, 20, 50).unwrap();
+        assert_eq!(compress_allocations(comp, &input), 0, "R = {robustness}");
+    }
+
+    // Optional features keep to the retained buffers as well
+    let mut comp = Compressor::new(720, None, 2, 10, 20, 50).unwrap();
+    comp.set_packet_crc(true);
+    comp.set_refresh_policy(RefreshPolicy {
+        max_packets: Some(25),
+        ..RefreshPolicy::default()
+    });
+    comp.set_adaptive_policy(AdaptivePolicy {
+        stale_bits: Some(8),
+        full_mask_on_change: true,
+        ..AdaptivePolicy::default()
+    });
+    let mut excluded = BitVector::new(720);
+    excluded.set_bit(0, 1);
+    comp.set_excluded(Some(&excluded)).unwrap();
+    assert_eq!(compress_allocations(comp, &input), 0);
+}
+
+/// Allocations made by one pass of a compressor created in `workspace`:
+/// creation, compressing `input` into a slice and releasing the storage.
+fn pass_allocations(workspace: &mut Workspace, input: &[BitVector]) -> usize {
+    let mut slice = vec![0u8; Workspace::buffer_bytes(720)];
+    let mut stream = Vec::with_capacity(input.len() * slice.len());
+
+    let count = count_allocations(|| {
+        let mut comp = Compressor::new_in(workspace, 2, 10, 20, 50).unwrap();
+        for packet in input {
+            let params = comp.next_params();
+            let len = comp
+                .compress_packet_to_slice(packet, &params, &mut slice)
+                .unwrap();
+            stream.extend_from_slice(&slice[..len]);
+        }
+        comp.release(workspace);
+    });
+
+    let bytes: Vec<u8> = input.iter().flat_map(BitVector::to_bytes).collect();
+    let expected = pocketplus::compress(&bytes, 720, 2, 10, 20, 50).unwrap();
+    assert_eq!(stream, expected);
+    count
+}
+
+#[test]
+fn test_workspace_without_allocation() {
+    let input = packets(300);
+
+    let mut workspace = Workspace::new(720, None).unwrap();
+    for _ in 0..2 {
+        assert_eq!(pass_allocations(&mut workspace, &input), 0);
+    }
+
+    // Caller-provided buffers: no heap memory at all from setup onwards
+    static_buffers!(
+        take_buffers,
+        Workspace::buffer_words(720),
+        Workspace::buffer_bytes(720)
+    );
+    let (words, output) = take_buffers().unwrap();
+    words.fill(u64::MAX);
+    let mut workspace = None;
+    let setup = count_allocations(|| {
+        workspace = Some(Workspace::from_buffers(720, None, words, output).unwrap());
+    });
+    assert_eq!(setup, 0);
+    let mut workspace = workspace.unwrap();
+    assert_eq!(workspace.heap_size(), 0);
+    for _ in 0..2 {
+        assert_eq!(pass_allocations(&mut workspace, &input), 0);
+    }
+    assert_eq!(workspace.heap_size(), 0);
+}
+
+#[test]
+fn test_workspace_from_buffers() {
+    let data: Vec<u8> = (0..90 * 30)
+        .map(|i| u8::try_from(i % 90 / 8 + i / 900).unwrap())
+        .collect();
+    let mut mask = BitVector::new(720);
+    mask.set_bit(5, 1);
+    let builder = CompressorBuilder::new(720)
+        .robustness(1)
+        .initial_mask(&mask);
+    let expected = compress_with(&data, &builder).unwrap();
+
+    // One static area, split into the buffers of each case below
+    const NEEDED: usize = Workspace::buffer_words(720);
+    static_buffers!(
+        take_buffers,
+        2 * NEEDED - 1 + Workspace::buffer_words(16),
+        Workspace::buffer_bytes(720)
+    );
+    let (words, output) = take_buffers().unwrap();
+    assert!(take_buffers().is_none());
+    words.fill(u64::MAX);
+    output.fill(0xFF);
+    let (words, rest) = words.split_at_mut(NEEDED);
+    let (short, other) = rest.split_at_mut(NEEDED - 1);
+
+    let mut workspace = Workspace::from_buffers(720, Some(&mask), words, output).unwrap();
+    assert_eq!(workspace.heap_size(), 0);
+
+    let mut comp = builder.build_in(&mut workspace).unwrap();
+    assert_eq!(comp.initial_mask(), &mask);
+    let mut stream = Vec::new();
+    let mut state = Vec::new();
+    for (t, chunk) in data.chunks(90).enumerate() {
+        if t == 10 {
+            state = comp.save_state();
+        }
+        let packet = BitVector::from_bytes(chunk, 720);
+        let params = comp.next_params();
+        let packet = comp.compress_packet(&packet, &params).unwrap();
+        stream.extend_from_slice(&packet.to_bytes());
+    }
+    assert_eq!(stream, expected);
+
+    // Restoring copies into the lent vectors
+    comp.restore_state(&state).unwrap();
+    assert_eq!(comp.heap_size(), 0);
+    comp.release(&mut workspace);
+
+    assert!(matches!(
+        Workspace::from_buffers(720, None, short, &mut []),
+        Err(PocketError::InvalidInputLength { .. })
+    ));
+    assert!(Workspace::from_buffers(16, Some(&mask), other, &mut []).is_err());
+}
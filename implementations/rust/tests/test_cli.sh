@@ -80,6 +80,300 @@ test_roundtrip() {
     rm -f "$TEMP_DIR"/*
 }
 
+test_container_concat() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "container-concat"
+
+    cp "$TEST_VECTORS_DIR/simple.bin" "$TEMP_DIR/a.bin"
+    cp "$TEST_VECTORS_DIR/housekeeping.bin" "$TEMP_DIR/b.bin"
+
+    # Compress each pass into its own container segment
+    "$CLI" --container "$TEMP_DIR/a.bin" 90 10 20 50 1 >/dev/null 2>&1
+    "$CLI" --container "$TEMP_DIR/b.bin" 90 20 50 100 2 >/dev/null 2>&1
+
+    # Concatenate segments the way the archive system does
+    cat "$TEMP_DIR/a.bin.pkt" "$TEMP_DIR/b.bin.pkt" > "$TEMP_DIR/all.pkt"
+    cat "$TEMP_DIR/a.bin" "$TEMP_DIR/b.bin" > "$TEMP_DIR/all.bin"
+
+    "$CLI" -d "$TEMP_DIR/all.pkt" 90 1 >/dev/null 2>&1
+
+    if [ "$(compute_hash "$TEMP_DIR/all.bin")" = "$(compute_hash "$TEMP_DIR/all.depkt")" ]; then
+        echo " OK (2 segments)"
+        TESTS_PASSED=$((TESTS_PASSED + 1))
+    else
+        echo " FAIL (hash mismatch)"
+        return 1
+    fi
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_generate() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "generate"
+
+    # Same seed must reproduce the same stream
+    "$CLI" -g "$TEMP_DIR/g1.bin" 500 7 >/dev/null 2>&1
+    "$CLI" -g "$TEMP_DIR/g2.bin" 500 7 >/dev/null 2>&1
+
+    "$CLI" "$TEMP_DIR/g1.bin" 90 10 20 50 1 >/dev/null 2>&1
+    "$CLI" -d "$TEMP_DIR/g1.bin.pkt" 90 1 >/dev/null 2>&1
+
+    if [ "$(compute_hash "$TEMP_DIR/g1.bin")" = "$(compute_hash "$TEMP_DIR/g2.bin")" ] &&
+       [ "$(compute_hash "$TEMP_DIR/g1.bin")" = "$(compute_hash "$TEMP_DIR/g1.bin.depkt")" ]; then
+        echo " OK (500 packets)"
+        TESTS_PASSED=$((TESTS_PASSED + 1))
+    else
+        echo " FAIL (hash mismatch)"
+        return 1
+    fi
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_manifest() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "manifest"
+
+    cp "$TEST_VECTORS_DIR/simple.bin" "$TEMP_DIR/a.bin"
+    cp "$TEST_VECTORS_DIR/housekeeping.bin" "$TEMP_DIR/b.bin"
+
+    # Two packet sizes in one session, the second entry a byte range
+    printf 'a.bin 90 r=1\nb.bin 45 r=2 pt=20 ft=50 rt=100 offset=900 length=4500\n' \
+        > "$TEMP_DIR/session.manifest"
+    "$CLI" -m "$TEMP_DIR/session.manifest" "$TEMP_DIR/session.pkt" >/dev/null 2>&1
+    "$CLI" -d "$TEMP_DIR/session.pkt" 90 1 >/dev/null 2>&1
+
+    cp "$TEMP_DIR/a.bin" "$TEMP_DIR/expected.bin"
+    dd if="$TEMP_DIR/b.bin" bs=900 skip=1 count=5 2>/dev/null >> "$TEMP_DIR/expected.bin"
+
+    if [ "$(compute_hash "$TEMP_DIR/expected.bin")" = "$(compute_hash "$TEMP_DIR/session.depkt")" ]; then
+        echo " OK (2 entries)"
+        TESTS_PASSED=$((TESTS_PASSED + 1))
+    else
+        echo " FAIL (hash mismatch)"
+        return 1
+    fi
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_refresh() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "refresh"
+
+    cp "$TEST_VECTORS_DIR/housekeeping.bin" "$TEMP_DIR/input.bin"
+    "$CLI" "$TEMP_DIR/input.bin" 90 10 20 50 1 >/dev/null 2>&1
+    plain_size=$(wc -c < "$TEMP_DIR/input.bin.pkt")
+    "$CLI" --refresh-packets=7 "$TEMP_DIR/input.bin" 90 10 20 50 1 >/dev/null 2>&1
+    refresh_size=$(wc -c < "$TEMP_DIR/input.bin.pkt")
+    "$CLI" -d "$TEMP_DIR/input.bin.pkt" 90 1 >/dev/null 2>&1
+
+    if [ "$(compute_hash "$TEMP_DIR/input.bin")" != "$(compute_hash "$TEMP_DIR/input.bin.depkt")" ]; then
+        echo " FAIL (hash mismatch)"
+        return 1
+    fi
+    if [ "$refresh_size" -le "$plain_size" ]; then
+        echo " FAIL (no forced refreshes)"
+        return 1
+    fi
+    echo " OK ($plain_size -> $refresh_size bytes)"
+    TESTS_PASSED=$((TESTS_PASSED + 1))
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_crc() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "crc"
+
+    cp "$TEST_VECTORS_DIR/housekeeping.bin" "$TEMP_DIR/input.bin"
+    "$CLI" --crc "$TEMP_DIR/input.bin" 90 10 20 50 1 >/dev/null 2>&1
+    "$CLI" --crc -d "$TEMP_DIR/input.bin.pkt" 90 1 >/dev/null 2>&1
+
+    if [ "$(compute_hash "$TEMP_DIR/input.bin")" != "$(compute_hash "$TEMP_DIR/input.bin.depkt")" ]; then
+        echo " FAIL (hash mismatch)"
+        return 1
+    fi
+
+    # Flip one bit of the last packet's CRC
+    size=$(wc -c < "$TEMP_DIR/input.bin.pkt")
+    last=$(od -An -tu1 -j $((size - 1)) "$TEMP_DIR/input.bin.pkt" | tr -d ' ')
+    head -c $((size - 1)) "$TEMP_DIR/input.bin.pkt" > "$TEMP_DIR/bad.pkt"
+    printf "$(printf '\\%03o' $((last ^ 1)))" >> "$TEMP_DIR/bad.pkt"
+    if "$CLI" --crc -d "$TEMP_DIR/bad.pkt" 90 1 2>&1 | grep -q "CRC mismatch"; then
+        echo " OK"
+        TESTS_PASSED=$((TESTS_PASSED + 1))
+    else
+        echo " FAIL (corruption not detected)"
+        return 1
+    fi
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_bit_changes() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "bit changes"
+
+    "$CLI" -a "$TEST_VECTORS_DIR/housekeeping.bin" 90 "$TEMP_DIR/bits.csv" >/dev/null 2>&1
+
+    # Header plus one row per bit
+    rows=$(wc -l < "$TEMP_DIR/bits.csv" | tr -d ' ')
+    if [ "$(head -n 1 "$TEMP_DIR/bits.csv")" = "bit,byte,changes,frequency" ] &&
+       [ "$rows" -eq 721 ] &&
+       "$CLI" -a "$TEST_VECTORS_DIR/housekeeping.bin" 90 | cmp -s - "$TEMP_DIR/bits.csv"; then
+        echo " OK (720 bits)"
+        TESTS_PASSED=$((TESTS_PASSED + 1))
+    else
+        echo " FAIL (unexpected CSV)"
+        return 1
+    fi
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_json() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "json"
+
+    cp "$TEST_VECTORS_DIR/simple.bin" "$TEMP_DIR/input.bin"
+    "$CLI" --json "$TEMP_DIR/input.bin" 90 10 20 50 1 > "$TEMP_DIR/compress.json"
+    "$CLI" --json --container "$TEMP_DIR/input.bin" 90 10 20 50 1 >/dev/null
+    "$CLI" --json -d "$TEMP_DIR/input.bin.pkt" 90 1 > "$TEMP_DIR/decompress.json"
+
+    if grep -q '^{"mode":"compress","input":".*","input_bytes":[0-9]*,"packets":[0-9]*,' "$TEMP_DIR/compress.json" &&
+       grep -q '"segments":\[{"offset":0,.*"header":{"packet_size":720,"robustness":1,' "$TEMP_DIR/decompress.json" &&
+       [ "$(wc -l < "$TEMP_DIR/decompress.json" | tr -d ' ')" -eq 1 ]; then
+        echo " OK"
+        TESTS_PASSED=$((TESTS_PASSED + 1))
+    else
+        echo " FAIL (unexpected JSON)"
+        return 1
+    fi
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_archive() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "archive"
+
+    cp "$TEST_VECTORS_DIR/housekeeping.bin" "$TEMP_DIR/input.bin"
+    "$CLI" --restart=1 "$TEMP_DIR/input.bin" 90 10 20 50 1 >/dev/null 2>&1
+    "$CLI" -d "$TEMP_DIR/input.bin.pkt" 90 1 >/dev/null 2>&1
+
+    if [ "$(compute_hash "$TEMP_DIR/input.bin")" != "$(compute_hash "$TEMP_DIR/input.bin.depkt")" ]; then
+        echo " FAIL (hash mismatch)"
+        return 1
+    fi
+
+    # Zero-fill a segment of the first chunk, as after a lost transfer segment
+    dd if=/dev/zero of="$TEMP_DIR/input.bin.pkt" bs=1 seek=100 count=300 conv=notrunc 2>/dev/null
+    if "$CLI" -d "$TEMP_DIR/input.bin.pkt" 90 1 2>&1 | grep -q "Missing:     packets 0-"; then
+        echo " OK"
+        TESTS_PASSED=$((TESTS_PASSED + 1))
+    else
+        echo " FAIL (missing packets not reported)"
+        return 1
+    fi
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_time_bits() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "time bits"
+
+    cp "$TEST_VECTORS_DIR/housekeeping.bin" "$TEMP_DIR/input.bin"
+    "$CLI" --time-bits=44 "$TEMP_DIR/input.bin" 90 10 20 50 1 >/dev/null 2>&1
+    "$CLI" --time-bits=44 -d "$TEMP_DIR/input.bin.pkt" 90 1 >/dev/null 2>&1
+
+    if [ "$(compute_hash "$TEMP_DIR/input.bin")" != "$(compute_hash "$TEMP_DIR/input.bin.depkt")" ]; then
+        echo " FAIL (hash mismatch)"
+        return 1
+    fi
+
+    # The time field of the first packet leads the stream verbatim
+    if [ "$(head -c 5 "$TEMP_DIR/input.bin" | od -An -tx1)" = "$(head -c 5 "$TEMP_DIR/input.bin.pkt" | od -An -tx1)" ]; then
+        echo " OK"
+        TESTS_PASSED=$((TESTS_PASSED + 1))
+    else
+        echo " FAIL (time field not passed through)"
+        return 1
+    fi
+
+    rm -f "$TEMP_DIR"/*
+}
+
+test_armor() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "armor"
+
+    cp "$TEST_VECTORS_DIR/housekeeping.bin" "$TEMP_DIR/input.bin"
+    for enc in hex base64; do
+        "$CLI" --armor=$enc "$TEMP_DIR/input.bin" 90 10 20 50 1 >/dev/null 2>&1
+        # Armored output is plain text that survives line ending conversion
+        if LC_ALL=C grep -q '[^0-9A-Za-z+/=]' "$TEMP_DIR/input.bin.pkt"; then
+            echo " FAIL ($enc output is not text)"
+            return 1
+        fi
+        sed 's/$/\r/' "$TEMP_DIR/input.bin.pkt" > "$TEMP_DIR/mangled.pkt"
+        "$CLI" --armor=$enc -d "$TEMP_DIR/mangled.pkt" 90 1 >/dev/null 2>&1
+        if [ "$(compute_hash "$TEMP_DIR/input.bin")" != "$(compute_hash "$TEMP_DIR/mangled.depkt")" ]; then
+            echo " FAIL ($enc hash mismatch)"
+            return 1
+        fi
+        rm -f "$TEMP_DIR/mangled.depkt"
+    done
+
+    echo " OK"
+    TESTS_PASSED=$((TESTS_PASSED + 1))
+    rm -f "$TEMP_DIR"/*
+}
+
+test_index() {
+    TESTS_RUN=$((TESTS_RUN + 1))
+    printf "  %s..." "index"
+
+    cp "$TEST_VECTORS_DIR/housekeeping.bin" "$TEMP_DIR/input.bin"
+    "$CLI" --index "$TEMP_DIR/input.bin" 90 10 20 50 1 >/dev/null 2>&1
+    if [ ! -s "$TEMP_DIR/input.bin.pkt.idx" ]; then
+        echo " FAIL (no index written)"
+        return 1
+    fi
+
+    # Packets 100-149 with and without the index match the input
+    dd if="$TEMP_DIR/input.bin" of="$TEMP_DIR/expected.bin" bs=90 skip=100 count=50 2>/dev/null
+    for opts in "--index --packets=100-149" "--packets=100-149"; do
+        # shellcheck disable=SC2086
+        "$CLI" $opts -d "$TEMP_DIR/input.bin.pkt" 90 1 >/dev/null 2>&1
+        if [ "$(compute_hash "$TEMP_DIR/expected.bin")" != "$(compute_hash "$TEMP_DIR/input.bin.depkt")" ]; then
+            echo " FAIL ($opts hash mismatch)"
+            return 1
+        fi
+        rm -f "$TEMP_DIR/input.bin.depkt"
+    done
+
+    # The whole stream decodes in parallel from the index
+    "$CLI" --index -d "$TEMP_DIR/input.bin.pkt" 90 1 >/dev/null 2>&1
+    if [ "$(compute_hash "$TEMP_DIR/input.bin")" != "$(compute_hash "$TEMP_DIR/input.bin.depkt")" ]; then
+        echo " FAIL (--index hash mismatch)"
+        return 1
+    fi
+
+    # A damaged index is rejected
+    printf 'X' | dd of="$TEMP_DIR/input.bin.pkt.idx" bs=1 seek=30 conv=notrunc 2>/dev/null
+    if "$CLI" --index --packets=100 -d "$TEMP_DIR/input.bin.pkt" 90 1 >/dev/null 2>&1; then
+        echo " FAIL (damaged index accepted)"
+        return 1
+    fi
+
+    echo " OK"
+    TESTS_PASSED=$((TESTS_PASSED + 1))
+    rm -f "$TEMP_DIR"/*
+}
+
 echo ""
 echo "CLI Round-trip Tests"
 echo "===================="
@@ -115,6 +409,23 @@ test_roundtrip "venus-express" \
     "$TEST_VECTORS_DIR/venus-express.ccsds" \
     90 20 50 100 2
 
+# A zero period disables only that flag
+test_roundtrip "housekeeping (rt=0)" \
+    "$TEST_VECTORS_DIR/housekeeping.bin" \
+    90 20 50 0 2
+
+test_container_concat
+test_generate
+test_manifest
+test_refresh
+test_crc
+test_bit_changes
+test_json
+test_archive
+test_time_bits
+test_armor
+test_index
+
 echo ""
 echo "Results: $TESTS_PASSED/$TESTS_RUN tests passed"
 echo ""
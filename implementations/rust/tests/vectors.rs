@@ -3,6 +3,9 @@
 //! These tests verify that the Rust implementation produces byte-identical
 //! output to the C reference implementation for all test vectors.
 
+#![cfg(all(feature = "encoder", feature = "decoder"))]
+
+use pocketplus::spp::{extract_packets, PacketFilter};
 use pocketplus::{compress, decompress};
 use std::fs;
 use std::path::Path;
@@ -279,6 +282,22 @@ fn test_venus_express_round_trip() {
     }
 }
 
+#[test]
+fn test_venus_express_space_packets() {
+    // The capture holds only APID 212 packets of 90 bytes, so extracting
+    // them with their headers reproduces the input
+    if let Some(base_path) = test_vectors_path() {
+        let input = read_file(&format!("{}/input/venus-express.ccsds", base_path));
+        let filter = PacketFilter::new(212, 84).keep_header(true);
+        let (payloads, report) = extract_packets(&input, &filter).unwrap();
+        assert_eq!(report.extracted, 151_200);
+        assert_eq!(report.other_apids + report.wrong_length, 0);
+        assert_eq!(payloads, input);
+    } else {
+        eprintln!("Warning: Test vectors not found, skipping venus-express space packet test");
+    }
+}
+
 #[test]
 fn test_all_vectors_available() {
     // This test just ensures we can find and read all test vectors